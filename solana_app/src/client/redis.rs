@@ -2,6 +2,8 @@ use anyhow::Result;
 use redis::{Commands, Connection};
 
 use crate::models::copy_trade::CopyTradeWallet;
+use crate::models::discovery::DiscoveryReport;
+use crate::models::paper_trade::PaperTrade;
 
 /// Get a connection to Redis
 ///
@@ -59,9 +61,225 @@ pub fn get_copy_trade_wallets(conn: &mut redis::Connection) -> Result<Vec<CopyTr
             account_address: account_address.clone(),
             buy_amount,
             status: status == "active",
+            paper: status == "paper",
         };
         copy_trade_wallets.push(wallet);
     }
 
     Ok(copy_trade_wallets)
 }
+
+/// Store the latest wallet discovery report in Redis
+///
+/// # Parameters
+/// - `conn`: &mut redis::Connection - Mutable reference to the Redis connection
+/// - `report`: &DiscoveryReport - The report to store
+///
+/// # Returns
+/// - `Result<()>`: Ok if successful, or an error
+pub fn set_discovery_report(conn: &mut redis::Connection, report: &DiscoveryReport) -> Result<()> {
+    let report_json = serde_json::to_string(report)?;
+    conn.set::<_, _, ()>("discovery:suggested_wallets", report_json)?;
+    Ok(())
+}
+
+/// Fetch the latest wallet discovery report from Redis
+///
+/// # Parameters
+/// - `conn`: &mut redis::Connection - Mutable reference to the Redis connection
+///
+/// # Returns
+/// - `Result<Option<DiscoveryReport>>`: The report, None if no job has finished yet, or an error
+pub fn get_discovery_report(conn: &mut redis::Connection) -> Result<Option<DiscoveryReport>> {
+    let report_json: Option<String> = conn.get("discovery:suggested_wallets")?;
+    match report_json {
+        Some(report_json) => Ok(Some(serde_json::from_str(&report_json)?)),
+        None => Ok(None),
+    }
+}
+
+/// Store the result of a mint creator lookup in Redis for `ttl_secs` seconds
+///
+/// # Parameters
+/// - `conn`: &mut redis::Connection - Mutable reference to the Redis connection
+/// - `mint`: &str - The token mint
+/// - `creator`: Option<&str> - The wallet that created the mint, None if it couldn't be found
+/// - `ttl_secs`: u64 - How long the result is kept
+///
+/// # Returns
+/// - `Result<()>`: Ok if successful, or an error
+pub fn set_mint_creator(
+    conn: &mut redis::Connection,
+    mint: &str,
+    creator: Option<&str>,
+    ttl_secs: u64,
+) -> Result<()> {
+    conn.set_ex::<_, _, ()>(
+        format!("mint_creator:{mint}"),
+        serde_json::to_string(&creator)?,
+        ttl_secs,
+    )?;
+    Ok(())
+}
+
+/// Fetch the result of the last mint creator lookup from Redis
+///
+/// # Parameters
+/// - `conn`: &mut redis::Connection - Mutable reference to the Redis connection
+/// - `mint`: &str - The token mint
+///
+/// # Returns
+/// - `Result<Option<Option<String>>>`: None if the mint wasn't looked up yet, Some(None) if its creator couldn't be found, or an error
+pub fn get_mint_creator(
+    conn: &mut redis::Connection,
+    mint: &str,
+) -> Result<Option<Option<String>>> {
+    let creator_json: Option<String> = conn.get(format!("mint_creator:{mint}"))?;
+    match creator_json {
+        Some(creator_json) => Ok(Some(serde_json::from_str(&creator_json)?)),
+        None => Ok(None),
+    }
+}
+
+/// Fetch the open paper trade of an account on a token copied from a leader
+///
+/// # Parameters
+/// - `conn`: &mut redis::Connection - Mutable reference to the Redis connection
+/// - `account_address`: &str - The account address
+/// - `copy_trade_address`: &str - The leader wallet address
+/// - `token_ca`: &str - The token's contract address
+///
+/// # Returns
+/// - `Result<Option<PaperTrade>>`: The open paper trade, None if there is none, or an error
+pub fn get_open_paper_trade(
+    conn: &mut redis::Connection,
+    account_address: &str,
+    copy_trade_address: &str,
+    token_ca: &str,
+) -> Result<Option<PaperTrade>> {
+    let key = format!("user:{account_address}:paper_position:{copy_trade_address}:{token_ca}");
+    let trade_json: Option<String> = conn.get(key)?;
+    match trade_json {
+        Some(trade_json) => Ok(Some(serde_json::from_str(&trade_json)?)),
+        None => Ok(None),
+    }
+}
+
+/// Store the open paper trade of an account on a token copied from a leader
+///
+/// # Parameters
+/// - `conn`: &mut redis::Connection - Mutable reference to the Redis connection
+/// - `trade`: &PaperTrade - The open paper trade
+///
+/// # Returns
+/// - `Result<()>`: Ok if successful, or an error
+pub fn set_open_paper_trade(conn: &mut redis::Connection, trade: &PaperTrade) -> Result<()> {
+    let key = format!(
+        "user:{}:paper_position:{}:{}",
+        trade.account_address, trade.copy_trade_address, trade.token_ca
+    );
+    conn.set::<_, _, ()>(key, serde_json::to_string(trade)?)?;
+    Ok(())
+}
+
+/// Fetch the open paper trades of an account
+///
+/// # Parameters
+/// - `conn`: &mut redis::Connection - Mutable reference to the Redis connection
+/// - `account_address`: &str - The account address
+///
+/// # Returns
+/// - `Result<Vec<PaperTrade>>`: The open paper trades or an error
+pub fn get_open_paper_trades(
+    conn: &mut redis::Connection,
+    account_address: &str,
+) -> Result<Vec<PaperTrade>> {
+    let pattern = format!("user:{account_address}:paper_position:*");
+    let mut cursor = 0;
+    let mut keys = Vec::new();
+
+    // Scan Redis for matching keys
+    loop {
+        let (new_cursor, mut result): (i64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(&pattern)
+            .query(conn)?;
+
+        keys.append(&mut result);
+        cursor = new_cursor;
+
+        if cursor == 0 {
+            break;
+        }
+    }
+
+    let mut trades: Vec<PaperTrade> = Vec::new();
+    for key in keys {
+        let trade_json: Option<String> = conn.get(key)?;
+        if let Some(trade_json) = trade_json {
+            trades.push(serde_json::from_str(&trade_json)?);
+        }
+    }
+    Ok(trades)
+}
+
+/// Close a paper trade: remove it from the open ones and append it to the account's history
+///
+/// # Parameters
+/// - `conn`: &mut redis::Connection - Mutable reference to the Redis connection
+/// - `trade`: &PaperTrade - The closed paper trade
+///
+/// # Returns
+/// - `Result<()>`: Ok if successful, or an error
+pub fn close_paper_trade(conn: &mut redis::Connection, trade: &PaperTrade) -> Result<()> {
+    let open_key = format!(
+        "user:{}:paper_position:{}:{}",
+        trade.account_address, trade.copy_trade_address, trade.token_ca
+    );
+    let history_key = format!("user:{}:paper_trades", trade.account_address);
+    conn.del::<_, ()>(open_key)?;
+    conn.rpush::<_, _, ()>(history_key, serde_json::to_string(trade)?)?;
+    Ok(())
+}
+
+/// Append the sold part of a paper trade to the account's history, the rest stays open
+///
+/// # Parameters
+/// - `conn`: &mut redis::Connection - Mutable reference to the Redis connection
+/// - `sold`: &PaperTrade - The closed part of the paper trade
+/// - `remaining`: &PaperTrade - The part of the paper trade still open
+///
+/// # Returns
+/// - `Result<()>`: Ok if successful, or an error
+pub fn partially_close_paper_trade(
+    conn: &mut redis::Connection,
+    sold: &PaperTrade,
+    remaining: &PaperTrade,
+) -> Result<()> {
+    let history_key = format!("user:{}:paper_trades", sold.account_address);
+    set_open_paper_trade(conn, remaining)?;
+    conn.rpush::<_, _, ()>(history_key, serde_json::to_string(sold)?)?;
+    Ok(())
+}
+
+/// Fetch the closed paper trades of an account, oldest first
+///
+/// # Parameters
+/// - `conn`: &mut redis::Connection - Mutable reference to the Redis connection
+/// - `account_address`: &str - The account address
+///
+/// # Returns
+/// - `Result<Vec<PaperTrade>>`: The closed paper trades or an error
+pub fn get_closed_paper_trades(
+    conn: &mut redis::Connection,
+    account_address: &str,
+) -> Result<Vec<PaperTrade>> {
+    let history_key = format!("user:{account_address}:paper_trades");
+    let trades_json: Vec<String> = conn.lrange(history_key, 0, -1)?;
+    let mut trades: Vec<PaperTrade> = Vec::new();
+    for trade_json in trades_json {
+        trades.push(serde_json::from_str(&trade_json)?);
+    }
+    Ok(trades)
+}
@@ -9,7 +9,9 @@ use crate::{
         transfer::handle_transfer_transaction,
     },
     models::{copy_trade::CopyTradeWallet, transaction::LogsNotification},
-    utils::helpers::{decode_signature_get_transaction, get_account_involved_in_transaction},
+    utils::helpers::{
+        decode_signature_get_transaction, get_account_involved_in_transaction, get_sold_fraction,
+    },
 };
 use anyhow::Result;
 use futures::{
@@ -196,11 +198,13 @@ async fn fetch_and_process_transaction(
                     "Raydium Swap" => {
                         let (token_ca, side) =
                             info_raydium_swap(&transaction.transaction, &account_involved)?;
+                        let sold_fraction = get_sold_fraction(&tx, &account_involved, &token_ca);
                         handle_send_copy_trade_swap(
                             client.clone(),
                             token_ca,
                             account_involved,
                             side,
+                            sold_fraction,
                             tx_ws.clone(),
                         )
                         .await?;
@@ -208,11 +212,13 @@ async fn fetch_and_process_transaction(
                     "Jupiter Swap" => {
                         let (token_ca, side) =
                             info_jupiter_swap(&transaction.transaction, &account_involved)?;
+                        let sold_fraction = get_sold_fraction(&tx, &account_involved, &token_ca);
                         handle_send_copy_trade_swap(
                             client.clone(),
                             token_ca,
                             account_involved,
                             side,
+                            sold_fraction,
                             tx_ws.clone(),
                         )
                         .await?;
@@ -220,11 +226,13 @@ async fn fetch_and_process_transaction(
                     "Pump Swap" => {
                         let (token_ca, side) =
                             info_pump_swap(&transaction.transaction, &account_involved)?;
+                        let sold_fraction = get_sold_fraction(&tx, &account_involved, &token_ca);
                         handle_send_copy_trade_swap(
                             client.clone(),
                             token_ca,
                             account_involved,
                             side,
+                            sold_fraction,
                             tx_ws.clone(),
                         )
                         .await?;
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_sdk::{commitment_config::CommitmentConfig, native_token::lamports_to_sol, pubkey::Pubkey};
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, UiTransactionTokenBalance,
+};
+
+use crate::{
+    handlers::{jupiter::is_jupiter_swap, pump::is_pump_swap, raydium::is_raydium_swap},
+    models::discovery::{DiscoveryReport, WalletScore},
+    utils::helpers::{
+        decode_signature_get_transaction, get_account_involved_in_transaction,
+        wallet_token_amount,
+    },
+};
+
+/// Weights used to build the composite wallet score
+const HIT_RATE_WEIGHT: f64 = 0.4;
+const EARLY_ENTRY_WEIGHT: f64 = 0.3;
+const PNL_WEIGHT: f64 = 0.3;
+
+/// A single decoded swap of a wallet on the analysed token
+#[derive(Debug, Clone)]
+struct DecodedTrade {
+    wallet: String,
+    side: String,
+    sol_amount: f64,
+    token_amount: f64,
+}
+
+/// Accumulated activity of a wallet on a single token
+#[derive(Debug, Default, Clone)]
+struct WalletTokenStats {
+    first_buy_rank: Option<usize>,
+    sol_spent: f64,
+    sol_received: f64,
+    tokens_bought: f64,
+    tokens_sold: f64,
+}
+
+impl WalletTokenStats {
+    /// Realised PnL in SOL of the sold part of the position, if the wallet sold anything
+    fn realised_pnl(&self) -> Option<f64> {
+        if self.tokens_bought <= 0.0 || self.tokens_sold <= 0.0 {
+            return None;
+        }
+        let sold_fraction = (self.tokens_sold / self.tokens_bought).min(1.0);
+        Some(self.sol_received - self.sol_spent * sold_fraction)
+    }
+}
+
+/// Run a wallet discovery job over a list of tokens
+///
+/// Only the newest `signatures_limit` signatures of each token are analysed, a single RPC page,
+/// without walking back to the start of its history. On a token with more activity than that,
+/// "early entry" ranks the buyers within that window and not since the token launched, and
+/// buys made before the window are missing from the realised PnL.
+///
+/// # Parameters
+/// - `client`: Arc<RpcClient> - Thread-safe reference to the RPC client
+/// - `mints`: Vec<String> - The token mints to analyse
+/// - `signatures_limit`: usize - Maximum number of signatures to pull per token
+/// - `max_wallets`: usize - Maximum number of wallets to return
+///
+/// # Returns
+/// - `Result<DiscoveryReport>`: The ranked wallets, best first, or an error
+pub fn run_wallet_discovery(
+    client: Arc<RpcClient>,
+    mints: Vec<String>,
+    signatures_limit: usize,
+    max_wallets: usize,
+) -> Result<DiscoveryReport> {
+    println!("@run_wallet_discovery/ analysing {} tokens", mints.len());
+    let mut stats: HashMap<String, HashMap<String, WalletTokenStats>> = HashMap::new();
+    let mut buyers_per_token: HashMap<String, usize> = HashMap::new();
+
+    for mint in mints.iter() {
+        let trades = match get_token_trades(&client, mint, signatures_limit) {
+            Ok(trades) => trades,
+            Err(e) => {
+                eprintln!("@run_wallet_discovery/ failed to get trades for {}: {:?}", mint, e);
+                continue;
+            }
+        };
+        println!("@run_wallet_discovery/ {} decoded trades for {}", trades.len(), mint);
+
+        let mut buyers = 0;
+        for trade in trades {
+            let wallet_stats = stats
+                .entry(trade.wallet.clone())
+                .or_default()
+                .entry(mint.clone())
+                .or_default();
+            if trade.side == "buy" {
+                if wallet_stats.first_buy_rank.is_none() {
+                    wallet_stats.first_buy_rank = Some(buyers);
+                    buyers += 1;
+                }
+                wallet_stats.sol_spent += trade.sol_amount;
+                wallet_stats.tokens_bought += trade.token_amount;
+            } else {
+                wallet_stats.sol_received += trade.sol_amount;
+                wallet_stats.tokens_sold += trade.token_amount;
+            }
+        }
+        buyers_per_token.insert(mint.clone(), buyers);
+    }
+
+    let mut wallets = score_wallets(&stats, &buyers_per_token);
+    wallets.truncate(max_wallets);
+
+    Ok(DiscoveryReport {
+        updated_at: chrono::Utc::now().timestamp(),
+        tokens: mints,
+        wallets,
+    })
+}
+
+/// Score and rank wallets by hit rate, early entry and realised PnL
+///
+/// # Parameters
+/// - `stats`: &HashMap<...> - Per wallet, per token accumulated activity
+/// - `buyers_per_token`: &HashMap<String, usize> - Number of distinct buyers per token
+///
+/// # Returns
+/// - `Vec<WalletScore>`: The wallets with at least one closed trade, best first
+fn score_wallets(
+    stats: &HashMap<String, HashMap<String, WalletTokenStats>>,
+    buyers_per_token: &HashMap<String, usize>,
+) -> Vec<WalletScore> {
+    let mut wallets: Vec<WalletScore> = Vec::new();
+
+    for (wallet, tokens) in stats.iter() {
+        let mut tokens_traded = 0;
+        let mut closed_trades = 0;
+        let mut wins = 0;
+        let mut realised_pnl_sol = 0.0;
+        let mut early_entry_sum = 0.0;
+
+        for (mint, token_stats) in tokens.iter() {
+            if let Some(rank) = token_stats.first_buy_rank {
+                tokens_traded += 1;
+                let buyers = *buyers_per_token.get(mint).unwrap_or(&1);
+                early_entry_sum += if buyers > 1 {
+                    1.0 - rank as f64 / (buyers - 1) as f64
+                } else {
+                    1.0
+                };
+            }
+            if let Some(pnl) = token_stats.realised_pnl() {
+                closed_trades += 1;
+                realised_pnl_sol += pnl;
+                if pnl > 0.0 {
+                    wins += 1;
+                }
+            }
+        }
+
+        // Wallets that never closed a trade in the window can't be judged
+        if closed_trades == 0 {
+            continue;
+        }
+
+        wallets.push(WalletScore {
+            wallet: wallet.clone(),
+            tokens_traded,
+            closed_trades,
+            wins,
+            hit_rate: wins as f64 / closed_trades as f64,
+            realised_pnl_sol,
+            early_entry: early_entry_sum / tokens_traded as f64,
+            score: 0.0,
+        });
+    }
+
+    // Normalise the PnL against the best absolute result so it weighs like the other ratios
+    let max_abs_pnl = wallets
+        .iter()
+        .map(|w| w.realised_pnl_sol.abs())
+        .fold(0.0, f64::max);
+    for wallet in wallets.iter_mut() {
+        let pnl_score = if max_abs_pnl > 0.0 {
+            wallet.realised_pnl_sol / max_abs_pnl
+        } else {
+            0.0
+        };
+        wallet.score = HIT_RATE_WEIGHT * wallet.hit_rate
+            + EARLY_ENTRY_WEIGHT * wallet.early_entry
+            + PNL_WEIGHT * pnl_score;
+    }
+
+    wallets.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    wallets
+}
+
+/// Pull the newest signatures of a token and decode its swaps, oldest first
+///
+/// # Parameters
+/// - `client`: &Arc<RpcClient> - Reference to the thread-safe RPC client
+/// - `mint`: &str - The token mint
+/// - `signatures_limit`: usize - Maximum number of signatures to pull
+///
+/// # Returns
+/// - `Result<Vec<DecodedTrade>>`: The decoded swaps or an error
+fn get_token_trades(
+    client: &Arc<RpcClient>,
+    mint: &str,
+    signatures_limit: usize,
+) -> Result<Vec<DecodedTrade>> {
+    let mint_pubkey = Pubkey::from_str(mint)?;
+    let config = GetConfirmedSignaturesForAddress2Config {
+        before: None,
+        until: None,
+        limit: Some(signatures_limit),
+        commitment: Some(CommitmentConfig::confirmed()),
+    };
+    let signatures = client.get_signatures_for_address_with_config(&mint_pubkey, config)?;
+
+    let mut trades: Vec<DecodedTrade> = Vec::new();
+    // Signatures come newest first
+    for signature in signatures.iter().rev() {
+        if signature.err.is_some() {
+            continue;
+        }
+        let transaction = match decode_signature_get_transaction(&signature.signature, client) {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                eprintln!("@get_token_trades/ failed to fetch {}: {:?}", signature.signature, e);
+                continue;
+            }
+        };
+        if let Some(trade) = decode_trade(&transaction, mint) {
+            trades.push(trade);
+        }
+    }
+
+    Ok(trades)
+}
+
+/// Decode a swap on `mint` from a transaction using the DEX parsers
+///
+/// # Parameters
+/// - `transaction`: &EncodedConfirmedTransactionWithStatusMeta - The transaction
+/// - `mint`: &str - The token mint
+///
+/// # Returns
+/// - `Option<DecodedTrade>`: The swap of the fee payer, or None if it isn't a swap on `mint`
+fn decode_trade(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    mint: &str,
+) -> Option<DecodedTrade> {
+    let meta = transaction.transaction.meta.clone()?;
+    if Option::<Vec<String>>::from(meta.log_messages.clone()).is_none() {
+        return None;
+    }
+
+    let is_swap = is_raydium_swap(&transaction.transaction).unwrap_or(false)
+        || is_jupiter_swap(&transaction.transaction).unwrap_or(false)
+        || is_pump_swap(&transaction.transaction).unwrap_or(false);
+    if !is_swap {
+        return None;
+    }
+
+    let wallet = get_account_involved_in_transaction(transaction)
+        .ok()?
+        .to_string();
+
+    let pre_token_balances: Vec<UiTransactionTokenBalance> =
+        Option::from(meta.pre_token_balances.clone()).unwrap_or_default();
+    let post_token_balances: Vec<UiTransactionTokenBalance> =
+        Option::from(meta.post_token_balances.clone()).unwrap_or_default();
+    let token_delta = wallet_token_amount(&post_token_balances, &wallet, mint)
+        - wallet_token_amount(&pre_token_balances, &wallet, mint);
+
+    // The fee payer is always the first account
+    let lamports_delta = *meta.post_balances.first()? as i64 - *meta.pre_balances.first()? as i64;
+
+    if token_delta > 0.0 && lamports_delta < 0 {
+        Some(DecodedTrade {
+            wallet,
+            side: "buy".to_string(),
+            sol_amount: lamports_to_sol(lamports_delta.unsigned_abs()),
+            token_amount: token_delta,
+        })
+    } else if token_delta < 0.0 && lamports_delta > 0 {
+        Some(DecodedTrade {
+            wallet,
+            side: "sell".to_string(),
+            sol_amount: lamports_to_sol(lamports_delta as u64),
+            token_amount: -token_delta,
+        })
+    } else {
+        None
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_client::RpcClient;
+use solana_transaction_status::UiTransactionTokenBalance;
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+
+use crate::utils::helpers::decode_signature_get_transaction;
+
+const SOL_MINT: &str = "So11111111111111111111111111111111111111112";
+
+/// The balance changes of a confirmed swap for the signing wallet
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SwapFill {
+    /// Mint of the non-SOL side of the swap
+    pub token_mint: String,
+    /// Signed change of the wallet token balance (raw amount)
+    pub token_delta: f64,
+    /// Signed change of the wallet token balance (ui amount)
+    pub token_ui_delta: f64,
+    /// Signed change of the wallet lamports, network fee included
+    pub sol_delta_lamports: i64,
+    /// Network fee paid by the swap transaction
+    pub fee_lamports: u64,
+    /// Jito tip paid by the bundle's tip transaction
+    pub tip_lamports: u64,
+}
+
+/// Fetch a confirmed swap transaction and compute the fill of `wallet`
+///
+/// The blocking RPC calls run on the blocking thread pool, the async runtime only waits on them.
+///
+/// # Parameters
+/// - `rpc_client`: Arc<RpcClient> - The shared RPC client
+/// - `signature`: &str - The swap transaction signature
+/// - `wallet`: &str - The signing wallet
+/// - `input_mint`: &str - The input mint of the swap
+/// - `output_mint`: &str - The output mint of the swap
+/// - `tip_lamports`: u64 - The Jito tip sent alongside the swap
+///
+/// # Returns
+/// - `Result<SwapFill>`: The token and SOL deltas of the wallet
+pub async fn get_swap_fill(
+    rpc_client: Arc<RpcClient>,
+    signature: &str,
+    wallet: &str,
+    input_mint: &str,
+    output_mint: &str,
+    tip_lamports: u64,
+) -> Result<SwapFill> {
+    let token_mint = if input_mint == SOL_MINT {
+        output_mint
+    } else {
+        input_mint
+    };
+
+    // The transaction can take a moment to be served by the RPC after confirmation
+    let max_retries = 5;
+    let retry_delay = Duration::from_secs(2);
+    let mut last_error = anyhow!("Transaction not fetched");
+    for attempt in 1..=max_retries {
+        let client = Arc::clone(&rpc_client);
+        let transaction_signature = signature.to_string();
+        let transaction = tokio::task::spawn_blocking(move || {
+            decode_signature_get_transaction(&transaction_signature, &client)
+        })
+        .await
+        .map_err(|e| anyhow!("Transaction fetch task failed: {}", e))
+        .and_then(|transaction| transaction);
+        match transaction {
+            Ok(transaction) => {
+                let meta = transaction
+                    .transaction
+                    .meta
+                    .ok_or_else(|| anyhow!("Transaction has no meta"))?;
+                let pre_token_balances: Vec<UiTransactionTokenBalance> =
+                    Option::from(meta.pre_token_balances).unwrap_or_default();
+                let post_token_balances: Vec<UiTransactionTokenBalance> =
+                    Option::from(meta.post_token_balances).unwrap_or_default();
+                let (post_amount, post_ui_amount) =
+                    wallet_token_amount(&post_token_balances, wallet, token_mint);
+                let (pre_amount, pre_ui_amount) =
+                    wallet_token_amount(&pre_token_balances, wallet, token_mint);
+
+                // The fee payer is always the first account
+                let pre_lamports = *meta
+                    .pre_balances
+                    .first()
+                    .ok_or_else(|| anyhow!("Missing pre balances"))?;
+                let post_lamports = *meta
+                    .post_balances
+                    .first()
+                    .ok_or_else(|| anyhow!("Missing post balances"))?;
+
+                return Ok(SwapFill {
+                    token_mint: token_mint.to_string(),
+                    token_delta: post_amount - pre_amount,
+                    token_ui_delta: post_ui_amount - pre_ui_amount,
+                    sol_delta_lamports: post_lamports as i64 - pre_lamports as i64,
+                    fee_lamports: meta.fee,
+                    tip_lamports,
+                });
+            }
+            Err(e) => {
+                println!(
+                    "@get_swap_fill/ transaction not available yet (attempt {}/{}): {}",
+                    attempt, max_retries, e
+                );
+                last_error = e;
+            }
+        }
+        if attempt < max_retries {
+            sleep(retry_delay).await;
+        }
+    }
+    Err(last_error)
+}
+
+/// Sum the raw and ui amounts of `mint` held by `wallet` in a list of token balances
+fn wallet_token_amount(
+    balances: &[UiTransactionTokenBalance],
+    wallet: &str,
+    mint: &str,
+) -> (f64, f64) {
+    balances
+        .iter()
+        .filter(|balance| balance.mint == mint)
+        .filter(|balance| {
+            Option::<String>::from(balance.owner.clone()).as_deref() == Some(wallet)
+        })
+        .fold((0.0, 0.0), |(amount, ui_amount), balance| {
+            (
+                amount + balance.ui_token_amount.amount.parse::<f64>().unwrap_or(0.0),
+                ui_amount + balance.ui_token_amount.ui_amount.unwrap_or(0.0),
+            )
+        })
+}
@@ -3,4 +3,8 @@ pub mod pump;
 pub mod raydium;
 pub mod matis;
 pub mod transfer;
-pub mod swap;
\ No newline at end of file
+pub mod swap;
+pub mod discovery;
+pub mod paper;
+pub mod fill;
+pub mod safety;
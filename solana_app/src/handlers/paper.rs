@@ -0,0 +1,173 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use solana_sdk::native_token::{lamports_to_sol, sol_to_lamports};
+use tokio::sync::broadcast;
+
+use crate::{
+    client::redis::{
+        close_paper_trade, get_open_paper_trade, get_redis_connection, partially_close_paper_trade,
+        set_open_paper_trade,
+    },
+    handlers::matis::{get_versioned_quote, SOL_MINT},
+    models::{
+        copy_trade::CopyTradeWallet,
+        paper_trade::{PaperTrade, PaperTradePayload},
+    },
+};
+
+/// A leader selling at least this share of their balance closes the whole paper position
+const FULL_EXIT_FRACTION: f64 = 0.99;
+
+/// Simulate a copy trade without signing or sending anything
+///
+/// # Description
+///
+/// Fetches the quote the live copy trade would have executed and records the simulated fill
+/// from it. On a buy the quoted token amount is added to the open paper trade. On a sell the
+/// same share of the paper position the leader sold is quoted back to SOL and its hypothetical
+/// PnL reported, the position is closed once the leader sold (almost) everything.
+///
+/// # Parameters
+/// - `copy_trade`: &CopyTradeWallet - The copy trade in paper mode
+/// - `token_ca`: &str - The token's contract address
+/// - `side`: &str - The side of the leader's trade (buy/sell)
+/// - `sold_fraction`: f64 - The share of their balance the leader sold, ignored on a buy
+/// - `tx`: &Arc<broadcast::Sender<String>> - Reference to the thread-safe broadcast sender
+///
+/// # Returns
+/// - `Result<()>`: Ok if successful, or an error
+pub async fn handle_paper_copy_trade(
+    copy_trade: &CopyTradeWallet,
+    token_ca: &str,
+    side: &str,
+    sold_fraction: f64,
+    tx: &Arc<broadcast::Sender<String>>,
+) -> Result<()> {
+    let mut con = get_redis_connection();
+    let open_trade = get_open_paper_trade(
+        &mut con,
+        &copy_trade.account_address,
+        &copy_trade.copy_trade_address,
+        token_ca,
+    )?;
+
+    if side == "buy" {
+        let sol_in_lamports = sol_to_lamports(copy_trade.buy_amount);
+        let quote = get_versioned_quote(
+            SOL_MINT.to_string(),
+            token_ca.to_string(),
+            sol_in_lamports.to_string(),
+            0.18,
+        )
+        .await?;
+        let token_amount = quote.out_amount.parse::<u64>()?;
+        println!(
+            "@handle_paper_copy_trade/ paper buy {} SOL -> {} Token({})",
+            copy_trade.buy_amount, token_amount, token_ca
+        );
+
+        let trade = match open_trade {
+            Some(mut trade) => {
+                trade.sol_in_lamports += sol_in_lamports;
+                trade.token_amount += token_amount;
+                trade
+            }
+            None => PaperTrade {
+                account_address: copy_trade.account_address.clone(),
+                copy_trade_address: copy_trade.copy_trade_address.clone(),
+                token_ca: token_ca.to_string(),
+                sol_in_lamports,
+                token_amount,
+                sol_out_lamports: None,
+                pnl_sol: None,
+                opened_at: chrono::Utc::now().timestamp(),
+                closed_at: None,
+            },
+        };
+        set_open_paper_trade(&mut con, &trade)?;
+        send_paper_trade(trade, tx)?;
+    }
+
+    if side == "sell" {
+        let mut trade = match open_trade {
+            Some(trade) => trade,
+            None => {
+                println!("@handle_paper_copy_trade/ no paper position on Token({}) to sell", token_ca);
+                return Ok(());
+            }
+        };
+        let token_amount = if sold_fraction >= FULL_EXIT_FRACTION {
+            trade.token_amount
+        } else {
+            (trade.token_amount as f64 * sold_fraction) as u64
+        };
+        if token_amount == 0 {
+            println!(
+                "@handle_paper_copy_trade/ nothing to sell on Token({})",
+                token_ca
+            );
+            return Ok(());
+        }
+        let quote = get_versioned_quote(
+            token_ca.to_string(),
+            SOL_MINT.to_string(),
+            token_amount.to_string(),
+            0.18,
+        )
+        .await?;
+        let sol_out_lamports = quote.out_amount.parse::<u64>()?;
+        // The sold tokens carry their share of the entry cost
+        let sol_in_lamports = (trade.sol_in_lamports as u128 * token_amount as u128
+            / trade.token_amount as u128) as u64;
+        let pnl_sol = lamports_to_sol(sol_out_lamports) - lamports_to_sol(sol_in_lamports);
+        println!(
+            "@handle_paper_copy_trade/ paper sell {} Token({}) -> {} SOL, PnL {} SOL",
+            token_amount,
+            token_ca,
+            lamports_to_sol(sol_out_lamports),
+            pnl_sol
+        );
+
+        let sold = PaperTrade {
+            sol_in_lamports,
+            token_amount,
+            sol_out_lamports: Some(sol_out_lamports),
+            pnl_sol: Some(pnl_sol),
+            closed_at: Some(chrono::Utc::now().timestamp()),
+            ..trade.clone()
+        };
+        if token_amount == trade.token_amount {
+            close_paper_trade(&mut con, &sold)?;
+        } else {
+            trade.sol_in_lamports -= sol_in_lamports;
+            trade.token_amount -= token_amount;
+            partially_close_paper_trade(&mut con, &sold, &trade)?;
+        }
+        send_paper_trade(sold, tx)?;
+    }
+
+    Ok(())
+}
+
+/// Broadcast a simulated fill to the mini app
+///
+/// Nobody may be listening when the fill is recorded, the trade is already stored in redis so
+/// the fill is only logged then.
+///
+/// # Parameters
+/// - `trade`: PaperTrade - The paper trade after the fill
+/// - `tx`: &Arc<broadcast::Sender<String>> - Reference to the thread-safe broadcast sender
+///
+/// # Returns
+/// - `Result<()>`: Ok if successful, or an error
+fn send_paper_trade(trade: PaperTrade, tx: &Arc<broadcast::Sender<String>>) -> Result<()> {
+    let payload = PaperTradePayload {
+        event_type: "paper_trade".to_string(),
+        data: trade,
+    };
+    if tx.send(serde_json::to_string(&payload)?).is_err() {
+        println!("@send_paper_trade/ no subscriber for the paper trade");
+    }
+    Ok(())
+}
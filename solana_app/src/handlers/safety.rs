@@ -0,0 +1,253 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use lazy_static::lazy_static;
+use serde_json::{json, Value};
+use solana_account_decoder::UiAccountData;
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_client::rpc_request::{RpcRequest, TokenAccountsFilter};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+
+use crate::{
+    client::redis::{get_mint_creator, get_redis_connection, set_mint_creator},
+    models::safety::TokenMintInfo,
+    utils::helpers::{decode_signature_get_transaction, get_account_involved_in_transaction},
+};
+
+/// Signatures pulled per page when looking for the mint creation
+const SIGNATURES_PAGE_SIZE: usize = 1000;
+/// Pages walked back before giving up on the mint creation, busy tokens have too much history
+const MAX_SIGNATURES_PAGES: usize = 5;
+/// The creator of a mint never changes
+const MINT_CREATOR_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+/// A failed lookup is retried after this long
+const MINT_CREATOR_FAILED_TTL_SECS: u64 = 60 * 60;
+
+lazy_static! {
+    /// Mints whose creator lookup is running, a mint is only looked up once at a time
+    static ref PENDING_CREATOR_LOOKUPS: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// Get the on-chain safety facts of a token mint
+///
+/// The dev wallet is read from the creator lookups cached in Redis. Walking the mint history is
+/// too slow to answer in time, so a mint that wasn't looked up yet is returned without its dev
+/// wallet and its lookup starts in the background. Must run within the tokio runtime.
+///
+/// # Parameters
+/// - `client`: Arc<RpcClient> - Thread-safe reference to the RPC client
+/// - `mint`: &str - The token mint
+///
+/// # Returns
+/// - `Result<TokenMintInfo>`: The mint authorities, extensions and dev wallet holdings or an error
+pub fn get_token_mint_info(client: Arc<RpcClient>, mint: &str) -> Result<TokenMintInfo> {
+    let mint_pubkey = Pubkey::from_str(mint)?;
+    // The parsed encoding decodes both token programs, Token-2022 extensions included
+    let response: Value = client.send(
+        RpcRequest::GetAccountInfo,
+        json!([mint, { "encoding": "jsonParsed", "commitment": "confirmed" }]),
+    )?;
+    if response["value"].is_null() {
+        return Err(anyhow::anyhow!("Mint account {} not found", mint));
+    }
+    let parsed = &response["value"]["data"]["parsed"];
+    if parsed["type"].as_str() != Some("mint") {
+        return Err(anyhow::anyhow!("Account {} is not a token mint", mint));
+    }
+    let info = &parsed["info"];
+    let decimals = info["decimals"].as_u64().unwrap_or(0) as u8;
+    let supply = info["supply"]
+        .as_str()
+        .and_then(|supply| supply.parse::<f64>().ok())
+        .unwrap_or(0.0)
+        / 10f64.powi(decimals as i32);
+
+    let extensions: Vec<&Value> = info["extensions"]
+        .as_array()
+        .map(|extensions| extensions.iter().collect())
+        .unwrap_or_default();
+    // Either fee can apply depending on the epoch, the highest one is what a holder risks
+    let transfer_fee_bps = extensions
+        .iter()
+        .filter(|extension| extension["extension"].as_str() == Some("transferFeeConfig"))
+        .flat_map(|extension| {
+            [
+                extension["state"]["olderTransferFee"]["transferFeeBasisPoints"].as_u64(),
+                extension["state"]["newerTransferFee"]["transferFeeBasisPoints"].as_u64(),
+            ]
+        })
+        .flatten()
+        .max()
+        .unwrap_or(0);
+
+    // The dev wallet is best effort, the mint facts are still worth returning without it
+    let mut con = get_redis_connection();
+    let dev_wallet = match get_mint_creator(&mut con, mint) {
+        Ok(Some(dev_wallet)) => dev_wallet,
+        Ok(None) => {
+            spawn_mint_creator_lookup(Arc::clone(&client), mint.to_string());
+            None
+        }
+        Err(e) => {
+            eprintln!(
+                "@get_token_mint_info/ failed to read the creator of {}: {:?}",
+                mint, e
+            );
+            None
+        }
+    };
+    let dev_balance = match dev_wallet.as_ref() {
+        Some(dev_wallet) => match get_wallet_mint_balance(&client, dev_wallet, &mint_pubkey) {
+            Ok(balance) => Some(balance),
+            Err(e) => {
+                eprintln!(
+                    "@get_token_mint_info/ failed to get the dev balance of {}: {:?}",
+                    mint, e
+                );
+                None
+            }
+        },
+        None => None,
+    };
+
+    Ok(TokenMintInfo {
+        mint: mint.to_string(),
+        program: parsed_program(&response),
+        decimals,
+        supply,
+        mint_authority: info["mintAuthority"]
+            .as_str()
+            .map(|authority| authority.to_string()),
+        freeze_authority: info["freezeAuthority"]
+            .as_str()
+            .map(|authority| authority.to_string()),
+        extensions: extensions
+            .iter()
+            .filter_map(|extension| extension["extension"].as_str())
+            .map(|extension| extension.to_string())
+            .collect(),
+        transfer_fee_bps,
+        dev_wallet,
+        dev_balance,
+    })
+}
+
+/// Name of the token program of a parsed account, `spl-token` when missing
+fn parsed_program(response: &Value) -> String {
+    response["value"]["data"]["program"]
+        .as_str()
+        .unwrap_or("spl-token")
+        .to_string()
+}
+
+/// Look up the creator of a mint in the background and cache it in Redis
+///
+/// Failed lookups are cached as not found for `MINT_CREATOR_FAILED_TTL_SECS`.
+///
+/// # Parameters
+/// - `client`: Arc<RpcClient> - Thread-safe reference to the RPC client
+/// - `mint`: String - The token mint
+fn spawn_mint_creator_lookup(client: Arc<RpcClient>, mint: String) {
+    if !PENDING_CREATOR_LOOKUPS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(mint.clone())
+    {
+        return;
+    }
+    tokio::task::spawn_blocking(move || {
+        let creator = Pubkey::from_str(&mint)
+            .map_err(anyhow::Error::from)
+            .and_then(|mint_pubkey| find_mint_creator(&client, &mint_pubkey));
+        let (creator, ttl_secs) = match creator {
+            Ok(Some(creator)) => (Some(creator), MINT_CREATOR_TTL_SECS),
+            Ok(None) => (None, MINT_CREATOR_TTL_SECS),
+            Err(e) => {
+                eprintln!(
+                    "@spawn_mint_creator_lookup/ failed to get the creator of {}: {:?}",
+                    mint, e
+                );
+                (None, MINT_CREATOR_FAILED_TTL_SECS)
+            }
+        };
+        let mut con = get_redis_connection();
+        if let Err(e) = set_mint_creator(&mut con, &mint, creator.as_deref(), ttl_secs) {
+            eprintln!(
+                "@spawn_mint_creator_lookup/ failed to store the creator of {}: {:?}",
+                mint, e
+            );
+        }
+        PENDING_CREATOR_LOOKUPS
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&mint);
+    });
+}
+
+/// Find the wallet that paid for the creation of a mint
+///
+/// # Parameters
+/// - `client`: &Arc<RpcClient> - Reference to the thread-safe RPC client
+/// - `mint`: &Pubkey - The token mint
+///
+/// # Returns
+/// - `Result<Option<String>>`: The fee payer of the oldest mint transaction, None if the history is too long
+fn find_mint_creator(client: &Arc<RpcClient>, mint: &Pubkey) -> Result<Option<String>> {
+    let mut oldest_signature: Option<String> = None;
+    for _ in 0..MAX_SIGNATURES_PAGES {
+        let config = GetConfirmedSignaturesForAddress2Config {
+            before: match oldest_signature.as_ref() {
+                Some(signature) => Some(Signature::from_str(signature)?),
+                None => None,
+            },
+            until: None,
+            limit: Some(SIGNATURES_PAGE_SIZE),
+            commitment: Some(CommitmentConfig::confirmed()),
+        };
+        // Signatures come newest first, a short page reaches the creation
+        let signatures = client.get_signatures_for_address_with_config(mint, config)?;
+        let reached_creation = signatures.len() < SIGNATURES_PAGE_SIZE;
+        if let Some(oldest) = signatures.last() {
+            oldest_signature = Some(oldest.signature.clone());
+        }
+        if reached_creation {
+            let signature = match oldest_signature {
+                Some(signature) => signature,
+                None => return Ok(None),
+            };
+            let transaction = decode_signature_get_transaction(&signature, client)?;
+            let creator = get_account_involved_in_transaction(&transaction)
+                .map_err(|e| anyhow::anyhow!("Failed to get the fee payer: {}", e))?;
+            return Ok(Some(creator.to_string()));
+        }
+    }
+    Ok(None)
+}
+
+/// Get the ui amount of a mint held by a wallet across its token accounts
+///
+/// # Parameters
+/// - `client`: &Arc<RpcClient> - Reference to the thread-safe RPC client
+/// - `wallet`: &str - The wallet address
+/// - `mint`: &Pubkey - The token mint
+///
+/// # Returns
+/// - `Result<f64>`: The ui amount held or an error
+fn get_wallet_mint_balance(client: &Arc<RpcClient>, wallet: &str, mint: &Pubkey) -> Result<f64> {
+    let token_accounts = client.get_token_accounts_by_owner(
+        &Pubkey::from_str(wallet)?,
+        TokenAccountsFilter::Mint(*mint),
+    )?;
+    let balance = token_accounts
+        .iter()
+        .filter_map(|account| match &account.account.data {
+            UiAccountData::Json(parsed_account) => {
+                parsed_account.parsed["info"]["tokenAmount"]["uiAmount"].as_f64()
+            }
+            _ => None,
+        })
+        .sum();
+    Ok(balance)
+}
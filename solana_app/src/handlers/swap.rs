@@ -3,6 +3,7 @@ use std::sync::Arc;
 use crate::client::redis::get_copy_trade_wallets;
 use crate::client::redis::get_redis_connection;
 use crate::handlers::matis::get_swap_versioned_transaction;
+use crate::handlers::paper::handle_paper_copy_trade;
 use crate::models::token::get_tokens_balance;
 use crate::models::transaction::Payload;
 use crate::turnkey::errors::TurnkeyError;
@@ -371,6 +372,7 @@ fn get_bundle_status(status_response: &serde_json::Value) -> Result<BundleStatus
 /// - `token_ca`: String - The token's contract address
 /// - `trader_address`: Pubkey - The trader's public key
 /// - `side`: String - The side of the trade (buy/sell)
+/// - `sold_fraction`: f64 - The share of their balance the trader sold, scales the paper sells
 /// - `tx`: Arc<broadcast::Sender<String>> - Thread-safe reference to the broadcast sender
 ///
 /// # Returns
@@ -380,6 +382,7 @@ pub async fn handle_send_copy_trade_swap(
     token_ca: String,
     trader_address: Pubkey,
     side: String,
+    sold_fraction: f64,
     tx: Arc<broadcast::Sender<String>>,
 ) -> Result<()> {
     let mut con = get_redis_connection();
@@ -416,6 +419,13 @@ pub async fn handle_send_copy_trade_swap(
                         Err(e) => println!("Error sending sell transaction: {:?}", e),
                     }
                 }
+            } else if copy_trade.paper {
+                match handle_paper_copy_trade(&copy_trade, &token_ca, &side, sold_fraction, &tx)
+                    .await
+                {
+                    Ok(_) => println!("Paper {} recorded successfully", side),
+                    Err(e) => println!("Error recording paper {}: {:?}", side, e),
+                }
             }
         }
     }
@@ -458,6 +468,9 @@ async fn get_and_send_buy_transaction(
     let payload = Payload {
         event_type: "copy_trade".to_string(),
         data: transaction,
+        token_ca: token_ca.to_string(),
+        side: "buy".to_string(),
+        sol_amount: buy_amount,
     };
     println!("Payload: {:?}", payload);
     let tx_string = serde_json::to_string(&payload)?;
@@ -1,7 +1,7 @@
 use anyhow::Result;
 use axum::http::StatusCode;
 use axum::{
-    extract::{Path, State as AxumState},
+    extract::{Path, Query, State as AxumState},
     response::IntoResponse,
     routing::{get, post},
     Json, Router,
@@ -11,10 +11,16 @@ use futures::stream::SplitSink;
 use futures_util::stream::StreamExt;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use solana_app::client::redis::{get_copy_trade_wallets, get_redis_connection};
+use solana_app::client::redis::{
+    get_closed_paper_trades, get_copy_trade_wallets, get_discovery_report, get_open_paper_trades,
+    get_redis_connection, set_discovery_report,
+};
 use solana_app::client::rpc::{get_sol_balance, init_rpc_client};
 use solana_app::client::websocket::{handle_incoming_messages, subscribe_to_account_transaction};
+use solana_app::handlers::discovery::run_wallet_discovery;
+use solana_app::handlers::fill::get_swap_fill;
 use solana_app::handlers::matis::get_legacy_swap_transaction;
+use solana_app::handlers::safety::get_token_mint_info;
 use solana_app::handlers::swap::{sign_and_send_swap_transaction, User};
 use solana_app::handlers::transfer::sign_and_send_transaction;
 use solana_app::models::token::get_tokens_balance;
@@ -25,6 +31,7 @@ use solana_sdk::system_instruction;
 use solana_sdk::{message::Message, transaction::Transaction};
 use std::env;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::TcpListener;
@@ -90,8 +97,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 get(get_wallet_sol_balance),
             )
             .route("/get_positions/:address", get(tokens_balance))
+            .route("/token_safety/:mint", get(token_safety))
             .route("/sol/swap", post(sol_swap))
+            .route("/sol/fill/:signature", get(swap_fill))
             .route("/sol/transfer", post(transfer_sol))
+            .route("/discover_wallets", post(discover_wallets))
+            .route("/suggested_wallets", get(suggested_wallets))
+            .route("/copy_trade/paper/:address", get(paper_trades))
             .with_state(state);
 
         let listener = TcpListener::bind("0.0.0.0:3030")
@@ -271,7 +283,7 @@ pub struct SwapRequest {
 ///
 /// A `Result` containing a `Response` or a `tide::Error`
 pub async fn sol_swap(
-    AxumState(_state): AxumState<State>,
+    AxumState(state): AxumState<State>,
     Json(swap_request): Json<SwapRequest>,
 ) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, String)> {
     println!("@sol_swap /sol/swap received request");
@@ -292,8 +304,8 @@ pub async fn sol_swap(
     let swap_transacation = get_legacy_swap_transaction(
         &pubkey,
         priorization_fee_lamports,
-        input_mint,
-        output_mint,
+        input_mint.clone(),
+        output_mint.clone(),
         amount,
         slippage,
     )
@@ -302,17 +314,30 @@ pub async fn sol_swap(
     println!("@sol_swap /sol/swap got transaction");
 
     println!("@sol_swap /sol/swap signing and sending transaction");
+    let wallet = user.public_key.clone();
     match sign_and_send_swap_transaction(swap_transacation, user, jito_tip_amount).await {
         Ok(sig) => {
             println!("@sol_swap /sol/swap transaction sent: {:?}", sig);
-            println!(
-                "@sol_swap /sol/swap response: {:?}",
-                json!({ "transaction": sig.to_string() })
-            );
-            Ok((
-                StatusCode::OK,
-                Json(json!({ "transaction": sig.to_string() })),
-            ))
+            // The fill is best effort, the swap already landed
+            let fill = match get_swap_fill(
+                Arc::clone(&state.client),
+                &sig,
+                &wallet,
+                &input_mint,
+                &output_mint,
+                jito_tip_amount,
+            )
+            .await
+            {
+                Ok(fill) => json!(fill),
+                Err(e) => {
+                    eprintln!("@sol_swap /sol/swap failed to get fill: {:?}", e);
+                    serde_json::Value::Null
+                }
+            };
+            let response = json!({ "transaction": sig.to_string(), "fill": fill });
+            println!("@sol_swap /sol/swap response: {:?}", response);
+            Ok((StatusCode::OK, Json(response)))
         }
         Err(e) => {
             return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
@@ -320,6 +345,54 @@ pub async fn sol_swap(
     }
 }
 
+/// Fill query struct
+///
+/// # Fields
+///
+/// * `wallet` - The wallet that signed the swap
+/// * `input_mint` - The input mint of the swap
+/// * `output_mint` - The output mint of the swap
+#[derive(Debug, Clone, Deserialize)]
+pub struct FillQuery {
+    pub wallet: String,
+    pub input_mint: String,
+    pub output_mint: String,
+}
+
+/// Get the fill of a swap sent outside of the solana app, eg a copy trade signed by the mini app
+///
+/// @GET
+///
+/// @path /sol/fill/{signature}?wallet={wallet}&input_mint={input_mint}&output_mint={output_mint}
+///
+/// # Arguments
+///
+/// * `signature` - The swap transaction signature
+/// * `query` - The wallet and the mints of the swap
+///
+/// # Returns
+///
+/// A `Result` containing the fill or an error
+pub async fn swap_fill(
+    AxumState(state): AxumState<State>,
+    Path(signature): Path<String>,
+    Query(query): Query<FillQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    println!("@swap_fill /sol/fill/{signature} received request");
+    // Copy trades aren't sent as bundles, there is no tip
+    let fill = get_swap_fill(
+        Arc::clone(&state.client),
+        &signature,
+        &query.wallet,
+        &query.input_mint,
+        &query.output_mint,
+        0,
+    )
+    .await
+    .map_err(|e| (StatusCode::NOT_FOUND, e.to_string()))?;
+    Ok(Json(json!(fill)))
+}
+
 /// Get the positions of a wallet
 ///
 /// @GET
@@ -378,6 +451,38 @@ pub async fn tokens_balance(
     Ok(Json(response))
 }
 
+/// Get the on-chain safety facts of a token
+///
+/// @GET
+///
+/// @path /token_safety/{mint}
+///
+/// # Description
+///
+/// Read the mint account (authorities, Token-2022 extensions and transfer fee) and the
+/// holdings of the wallet that created it
+///
+/// # Arguments
+///
+/// * `mint` - The token mint
+///
+/// # Returns
+///
+/// A `Result` containing a `Response` or an error
+pub async fn token_safety(
+    AxumState(state): AxumState<State>,
+    Path(mint): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    println!("@token_safety /token_safety/{mint} received request");
+    let rpc_client = Arc::clone(&state.client);
+    let mint_info = tokio::task::spawn_blocking(move || get_token_mint_info(rpc_client, &mint))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    println!("@token_safety/ mint info: {:?}", mint_info);
+    Ok(Json(json!(mint_info)))
+}
+
 /// Transfer payload struct
 ///
 /// # Fields
@@ -436,3 +541,161 @@ pub async fn transfer_sol(
         Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
+
+/// Set while a wallet discovery job runs, only one job runs at a time
+static DISCOVERY_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Minimum number of seconds between the end of a wallet discovery job and the start of the next
+const DISCOVERY_COOLDOWN_SECS: i64 = 30 * 60;
+
+/// Maximum number of signatures a wallet discovery job pulls per token, one RPC page
+const MAX_DISCOVERY_SIGNATURES: usize = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DiscoverWalletsRequest {
+    tokens: Vec<String>,
+    signatures_limit: Option<usize>,
+    max_wallets: Option<usize>,
+}
+
+/// @discover_wallets /discover_wallets
+///
+/// @POST
+///
+/// @body [DiscoverWalletsRequest]
+///
+/// # Description
+///
+/// Start a wallet discovery job over the given tokens. The job runs in the background
+/// and stores its ranked wallets in Redis, where `/suggested_wallets` picks them up.
+/// Only one job runs at a time (409 otherwise) and a new job can only start
+/// `DISCOVERY_COOLDOWN_SECS` after the last one finished (429 otherwise).
+/// `signatures_limit` is clamped to 1..=1000.
+///
+/// # Arguments
+///
+/// * `req` - The request
+///
+/// # Returns
+///
+/// A `Result` containing a `Response` or an error
+pub async fn discover_wallets(
+    AxumState(state): AxumState<State>,
+    Json(request): Json<DiscoverWalletsRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, String)> {
+    println!("@discover_wallets /discover_wallets request: {:?}", request);
+    if request.tokens.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "No tokens to analyse".to_string()));
+    }
+
+    let mut con = get_redis_connection();
+    if let Ok(Some(report)) = get_discovery_report(&mut con) {
+        if chrono::Utc::now().timestamp() - report.updated_at < DISCOVERY_COOLDOWN_SECS {
+            return Err((
+                StatusCode::TOO_MANY_REQUESTS,
+                "The last wallet discovery job finished too recently".to_string(),
+            ));
+        }
+    }
+    if DISCOVERY_RUNNING.swap(true, Ordering::SeqCst) {
+        return Err((
+            StatusCode::CONFLICT,
+            "A wallet discovery job is already running".to_string(),
+        ));
+    }
+
+    let rpc_client = Arc::clone(&state.client);
+    let tokens = request.tokens.clone();
+    let signatures_limit = request
+        .signatures_limit
+        .unwrap_or(MAX_DISCOVERY_SIGNATURES)
+        .clamp(1, MAX_DISCOVERY_SIGNATURES);
+    let max_wallets = request.max_wallets.unwrap_or(10);
+    tokio::task::spawn_blocking(move || {
+        let result = run_wallet_discovery(rpc_client, tokens, signatures_limit, max_wallets);
+        DISCOVERY_RUNNING.store(false, Ordering::SeqCst);
+        match result {
+            Ok(report) => {
+                println!(
+                    "@discover_wallets/ job finished with {} wallets",
+                    report.wallets.len()
+                );
+                let mut con = get_redis_connection();
+                if let Err(e) = set_discovery_report(&mut con, &report) {
+                    eprintln!("@discover_wallets/ failed to store report: {:?}", e);
+                }
+            }
+            Err(e) => {
+                eprintln!("@discover_wallets/ job failed: {:?}", e);
+            }
+        }
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "status": "started", "tokens": request.tokens.len() })),
+    ))
+}
+
+/// Get the suggested wallets to copy from the latest discovery job
+///
+/// @GET
+///
+/// @path /suggested_wallets
+///
+/// # Returns
+///
+/// A `Result` containing a `Response` or an error
+pub async fn suggested_wallets(
+    AxumState(_state): AxumState<State>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let mut con = get_redis_connection();
+    match get_discovery_report(&mut con) {
+        Ok(Some(report)) => Ok(Json(json!(report))),
+        Ok(None) => Ok(Json(json!({ "updated_at": 0, "tokens": [], "wallets": [] }))),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+/// Get the open paper positions and the hypothetical PnL of the paper copy trades of an account
+///
+/// @GET
+///
+/// @path /copy_trade/paper/{address}
+///
+/// # Description
+///
+/// Each paper sell is a closed trade, a position the leader sold in several steps has one
+/// closed trade per step. The open positions are valued at their entry cost.
+///
+/// # Arguments
+///
+/// * `address` - The account address
+///
+/// # Returns
+///
+/// A `Result` containing a `Response` or an error
+pub async fn paper_trades(
+    AxumState(_state): AxumState<State>,
+    Path(address): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let mut con = get_redis_connection();
+    let trades = get_closed_paper_trades(&mut con, &address)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let positions = get_open_paper_trades(&mut con, &address)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let total_pnl_sol: f64 = trades.iter().filter_map(|trade| trade.pnl_sol).sum();
+    let wins = trades
+        .iter()
+        .filter(|trade| trade.pnl_sol.unwrap_or(0.0) > 0.0)
+        .count();
+
+    Ok(Json(json!({
+        "total_pnl_sol": total_pnl_sol,
+        "wins": wins,
+        "closed_trades": trades.len(),
+        "trades": trades,
+        "positions": positions,
+    })))
+}
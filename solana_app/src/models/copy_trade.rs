@@ -1,8 +1,12 @@
 /// Struct representing a copy trade wallet
+///
+/// `status` is set when the copy trade is live (redis status "active"), `paper` when it only
+/// simulates the trades (redis status "paper").
 #[derive(Debug, serde::Deserialize, Clone)]
 pub struct CopyTradeWallet {
     pub copy_trade_address: String,
     pub account_address: String,
     pub buy_amount: f64,
     pub status: bool,
+    pub paper: bool,
 }
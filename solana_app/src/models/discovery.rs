@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+/// Struct representing a ranked candidate leader wallet
+///
+/// # Fields
+///
+/// * `wallet` - The wallet address
+/// * `tokens_traded` - Number of analysed tokens the wallet bought
+/// * `closed_trades` - Number of tokens the wallet bought and (partially) sold in the window
+/// * `wins` - Number of closed trades with a positive realised PnL
+/// * `hit_rate` - `wins / closed_trades`
+/// * `realised_pnl_sol` - Realised PnL in SOL across all closed trades
+/// * `early_entry` - Average entry earliness within the analysed signatures (1.0 = first buyer, 0.0 = last buyer)
+/// * `score` - Composite ranking score
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletScore {
+    pub wallet: String,
+    pub tokens_traded: usize,
+    pub closed_trades: usize,
+    pub wins: usize,
+    pub hit_rate: f64,
+    pub realised_pnl_sol: f64,
+    pub early_entry: f64,
+    pub score: f64,
+}
+
+/// Struct representing the result of a wallet discovery job
+///
+/// # Fields
+///
+/// * `updated_at` - Unix timestamp of when the job finished
+/// * `tokens` - The token mints that were analysed
+/// * `wallets` - The ranked wallets, best first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveryReport {
+    pub updated_at: i64,
+    pub tokens: Vec<String>,
+    pub wallets: Vec<WalletScore>,
+}
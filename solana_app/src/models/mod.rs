@@ -1,3 +1,6 @@
 pub mod copy_trade;
+pub mod discovery;
+pub mod paper_trade;
+pub mod safety;
 pub mod token;
 pub mod transaction;
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+/// Struct representing a simulated copy trade
+///
+/// A partial sell closes a copy of the trade holding the sold share of the tokens and of the
+/// entry cost, the rest stays open.
+///
+/// # Fields
+///
+/// * `account_address` - The account that would have traded
+/// * `copy_trade_address` - The leader wallet being copied
+/// * `token_ca` - The token's contract address
+/// * `sol_in_lamports` - Lamports that would have been spent on the entry
+/// * `token_amount` - Token amount (raw units) the entry quote returned
+/// * `sol_out_lamports` - Lamports the exit quote returned, None while the trade is open
+/// * `pnl_sol` - Hypothetical PnL in SOL, None while the trade is open
+/// * `opened_at` - Unix timestamp of the simulated entry
+/// * `closed_at` - Unix timestamp of the simulated exit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaperTrade {
+    pub account_address: String,
+    pub copy_trade_address: String,
+    pub token_ca: String,
+    pub sol_in_lamports: u64,
+    pub token_amount: u64,
+    pub sol_out_lamports: Option<u64>,
+    pub pnl_sol: Option<f64>,
+    pub opened_at: i64,
+    pub closed_at: Option<i64>,
+}
+
+/// Struct representing the payload broadcast for simulated copy trades
+#[derive(Debug, Serialize)]
+pub struct PaperTradePayload {
+    pub event_type: String,
+    pub data: PaperTrade,
+}
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+/// Struct representing the on-chain facts used to judge the safety of a token
+///
+/// # Fields
+///
+/// * `mint` - The token mint
+/// * `program` - The token program owning the mint, `spl-token` or `spl-token-2022`
+/// * `decimals` - The token decimals
+/// * `supply` - The token supply in ui amount
+/// * `mint_authority` - The mint authority, None when renounced
+/// * `freeze_authority` - The freeze authority, None when renounced
+/// * `extensions` - The Token-2022 extensions of the mint
+/// * `transfer_fee_bps` - The Token-2022 transfer fee in basis points, 0 without the extension
+/// * `dev_wallet` - The wallet that paid for the mint creation, None if it couldn't be found or wasn't looked up yet
+/// * `dev_balance` - The ui amount of the token held by the dev wallet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenMintInfo {
+    pub mint: String,
+    pub program: String,
+    pub decimals: u8,
+    pub supply: f64,
+    pub mint_authority: Option<String>,
+    pub freeze_authority: Option<String>,
+    pub extensions: Vec<String>,
+    pub transfer_fee_bps: u64,
+    pub dev_wallet: Option<String>,
+    pub dev_balance: Option<f64>,
+}
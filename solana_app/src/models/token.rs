@@ -4,8 +4,12 @@ use serde_json::Value as JsonValue;
 use solana_account_decoder::UiAccountData;
 use solana_client::rpc_client::RpcClient;
 use solana_sdk::{native_token::lamports_to_sol, pubkey::Pubkey};
+use std::str::FromStr;
 use std::sync::Arc;
 
+/// Program of the Token-2022 mints, their accounts aren't owned by spl-token
+const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+
 /// Struct representing a token balance
 #[derive(Debug, Serialize)]
 pub struct TokenBalance {
@@ -31,6 +35,8 @@ impl TokensBalance {
 
 /// Get token balances for a wallet
 ///
+/// The accounts of both the spl-token and the Token-2022 programs are read
+///
 /// # Parameters
 /// - `client`: Arc<RpcClient> - Thread-safe reference to the RPC client
 /// - `wallet_pubkey`: &Pubkey - Reference to the wallet's public key
@@ -38,11 +44,17 @@ impl TokensBalance {
 /// # Returns
 /// - `Result<TokensBalance>`: The token balances or an error
 pub fn get_tokens_balance(client: Arc<RpcClient>, wallet_pubkey: &Pubkey) -> Result<TokensBalance> {
-    // Fetch token accounts for the wallet
-    let token_accounts = client.get_token_accounts_by_owner(
+    // Fetch token accounts for the wallet, a missing program would make its tokens look sold
+    let mut token_accounts = client.get_token_accounts_by_owner(
         wallet_pubkey,
         solana_client::rpc_request::TokenAccountsFilter::ProgramId(spl_token::id()),
     )?;
+    token_accounts.extend(client.get_token_accounts_by_owner(
+        wallet_pubkey,
+        solana_client::rpc_request::TokenAccountsFilter::ProgramId(Pubkey::from_str(
+            TOKEN_2022_PROGRAM_ID,
+        )?),
+    )?);
 
     let mut tokens_balance = TokensBalance {
         token_balance: Vec::new(),
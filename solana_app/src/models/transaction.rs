@@ -2,10 +2,16 @@ use serde::{Deserialize, Serialize};
 
 use crate::handlers::matis::SwapTransaction;
 /// Struct representing the payload for swap transactions
+///
+/// The token, side and SOL amount let the mini app journal the copy trade once it landed.
+/// Only the copy buys are wrapped, the sells are still published as the raw transaction.
 #[derive(Debug, Serialize)]
 pub struct Payload {
     pub event_type: String,
     pub data: SwapTransaction,
+    pub token_ca: String,
+    pub side: String,
+    pub sol_amount: f64, // SOL to spend on the buy
 }
 
 // Struct definitions for parsing log notifications
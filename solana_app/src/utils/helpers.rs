@@ -3,7 +3,7 @@ use solana_sdk::transaction::Transaction;
 use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
 use solana_transaction_status::{
     EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction, TransactionBinaryEncoding,
-    UiMessage, UiTransactionEncoding,
+    UiMessage, UiTransactionEncoding, UiTransactionTokenBalance,
 };
 use std::error::Error;
 use std::fs::File;
@@ -88,4 +88,61 @@ pub fn get_account_involved_in_transaction(
         _ => {}
     }
     Ok(account_involved.expect("Account involved not found"))
-}
\ No newline at end of file
+}
+
+/// Sum the ui amount of `mint` held by `wallet` in a list of token balances
+/// 
+/// # Arguments
+/// 
+/// * `balances` - The pre or post token balances of a transaction
+/// * `wallet` - The wallet address
+/// * `mint` - The token mint
+/// 
+/// # Returns
+/// 
+/// The ui amount held, 0 if the wallet has no account of the mint
+pub fn wallet_token_amount(
+    balances: &[UiTransactionTokenBalance],
+    wallet: &str,
+    mint: &str,
+) -> f64 {
+    balances
+        .iter()
+        .filter(|balance| balance.mint == mint)
+        .filter(|balance| Option::<String>::from(balance.owner.clone()).as_deref() == Some(wallet))
+        .map(|balance| balance.ui_token_amount.ui_amount.unwrap_or(0.0))
+        .sum()
+}
+
+/// Get the share of its `mint` balance a wallet sold in a transaction
+/// 
+/// # Arguments
+/// 
+/// * `transaction` - The transaction
+/// * `wallet` - The wallet that sold
+/// * `mint` - The token mint
+/// 
+/// # Returns
+/// 
+/// The sold fraction between 0 and 1, 1 when the balances are missing
+pub fn get_sold_fraction(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    wallet: &Pubkey,
+    mint: &str,
+) -> f64 {
+    let meta = match transaction.transaction.meta.as_ref() {
+        Some(meta) => meta,
+        None => return 1.0,
+    };
+    let pre_token_balances: Vec<UiTransactionTokenBalance> =
+        Option::from(meta.pre_token_balances.clone()).unwrap_or_default();
+    let post_token_balances: Vec<UiTransactionTokenBalance> =
+        Option::from(meta.post_token_balances.clone()).unwrap_or_default();
+    let wallet = wallet.to_string();
+    let pre_amount = wallet_token_amount(&pre_token_balances, &wallet, mint);
+    let post_amount = wallet_token_amount(&post_token_balances, &wallet, mint);
+    if pre_amount <= 0.0 {
+        return 1.0;
+    }
+    ((pre_amount - post_amount) / pre_amount).clamp(0.0, 1.0)
+}
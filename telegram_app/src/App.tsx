@@ -23,10 +23,12 @@ import Positions from "./components/ui/positions";
 import {
   setCopyTradeWallet,
   getCopyTrades,
+  getPaperTrades,
   decryptPassword,
   encryptPassword,
   getSolBalance as getSolBalance,
   copyTrade,
+  recordCopyTrade,
 } from "./lib/utils";
 import { transferSOL, getSOLPrice } from "./lib/solana";
 import { getETHPrice } from "./lib/eth";
@@ -100,6 +102,9 @@ const App: React.FC = () => {
 
   // Copy trades set by user
   const [copyTrades, setCopyTrades] = useState<any[]>([]);
+
+  // Paper copy trades: open positions and closed trades PnL
+  const [paperTrades, setPaperTrades] = useState<any>(null);
   const [createSessionButtonActive, setCreateSessionButtonActive] =
     useState(true);
 
@@ -156,6 +161,15 @@ const App: React.FC = () => {
     setCopyTrades(updatedCopyTrades);
   }
 
+  async function updatePaperTrades(accountAddress: string) {
+    try {
+      const getPaperTradesResponse = await getPaperTrades(accountAddress);
+      setPaperTrades(getPaperTradesResponse.data);
+    } catch (error) {
+      log(`getPaperTrades: ${error}`, "error");
+    }
+  }
+
   // USER REGISTRATION
   const handleRegister = async () => {
     try {
@@ -731,6 +745,17 @@ const App: React.FC = () => {
                 `Confirmed tx, check:\n https://solscan.io/tx/${result.signature}`,
                 "success"
               );
+              try {
+                await recordCopyTrade(
+                  WebApp.initDataUnsafe.user?.id.toString() ?? "",
+                  data.token_ca,
+                  data.side,
+                  result.signature,
+                  data.sol_amount
+                );
+              } catch (error) {
+                log(`recordCopyTrade: ${error}`, "error");
+              }
             } else {
               log(`Failed to send transaction`, "error");
             }
@@ -739,6 +764,21 @@ const App: React.FC = () => {
           }
         };
         handleCopyTrade();
+      } else if (data.event_type === "paper_trade") {
+        const trade = data.data;
+        updatePaperTrades(trade.account_address);
+        const token = `${trade.token_ca.slice(0, 3)}...${trade.token_ca.slice(-3)}`;
+        if (trade.closed_at) {
+          log(
+            `Paper sell ${token}: ${(trade.sol_out_lamports / 1e9).toFixed(4)} SOL, PnL ${trade.pnl_sol.toFixed(4)} SOL`,
+            trade.pnl_sol >= 0 ? "success" : "error"
+          );
+        } else {
+          log(
+            `Paper buy ${token}: ${(trade.sol_in_lamports / 1e9).toFixed(4)} SOL in position`,
+            "info"
+          );
+        }
       }
 
       setSocket(newSocket);
@@ -756,6 +796,13 @@ const App: React.FC = () => {
     updateBalance();
   }, [userAccounts]);
 
+  // update the paper trades when userAccounts changes
+  useEffect(() => {
+    if (userAccounts.length > 0) {
+      updatePaperTrades(userAccounts[0].address);
+    }
+  }, [userAccounts]);
+
   return (
     <div className="container mx-auto p-4">
       {isAuthenticated ? (
@@ -1177,6 +1224,25 @@ const App: React.FC = () => {
                             ? "Cancel"
                             : "Activate"}
                         </Button>
+                        <Button
+                          onClick={() =>
+                            handleSetCopyTrade(
+                              WebApp.initDataUnsafe.user?.id.toString() ?? "",
+                              walletId,
+                              userAccounts[0].address,
+                              copyTrade.buy_amount,
+                              copyTrade.copy_trade_address,
+                              copyTrade.status === "paper"
+                                ? "inactive"
+                                : "paper"
+                            )
+                          }
+                          className="bg-gray-500 hover:bg-gray-600 text-white rounded-full"
+                        >
+                          {copyTrade.status === "paper"
+                            ? "Stop paper"
+                            : "Paper"}
+                        </Button>
                         <Button
                           className="bg-red-700 hover:bg-red-600 text-white rounded-full"
                           onClick={() => {
@@ -1190,6 +1256,47 @@ const App: React.FC = () => {
                   ))}
                 </div>
               </div>
+              {paperTrades &&
+                (paperTrades.positions.length > 0 ||
+                  paperTrades.closed_trades > 0) && (
+                  <div className="flex flex-col items-center w-full mt-3">
+                    <span>Paper trading</span>
+                    <p
+                      className={`font-medium ${
+                        paperTrades.total_pnl_sol >= 0
+                          ? "text-green-600"
+                          : "text-red-600"
+                      }`}
+                    >
+                      PnL {paperTrades.total_pnl_sol.toFixed(4)} SOL (
+                      {paperTrades.wins}/{paperTrades.closed_trades} winning
+                      sells)
+                    </p>
+                    <div className="w-full">
+                      {paperTrades.positions.map((position: any) => (
+                        <div
+                          key={`${position.copy_trade_address}:${position.token_ca}`}
+                          className="flex flex-row items-center justify-between p-4 bg-gray-100 rounded-lg mb-2 w-full"
+                        >
+                          <div className="flex flex-col">
+                            <p className="font-semibold">
+                              {position.token_ca.slice(0, 3)}...
+                              {position.token_ca.slice(-3)}
+                            </p>
+                            <p className="text-sm text-gray-600">
+                              Copying {position.copy_trade_address.slice(0, 3)}
+                              ...{position.copy_trade_address.slice(-3)}
+                            </p>
+                          </div>
+                          <p className="font-medium">
+                            {(position.sol_in_lamports / 1e9).toFixed(4)} SOL
+                            in
+                          </p>
+                        </div>
+                      ))}
+                    </div>
+                  </div>
+                )}
             </CardContent>
           </Card>
         </>
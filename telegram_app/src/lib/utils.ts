@@ -51,6 +51,7 @@ export function cn(...inputs: ClassValue[]) {
 
 export const BASE_URL_API = "https://srv617785.hstgr.cloud/api";
 export const BOT_API_URL = "https://srv617785.hstgr.cloud/bot_api";
+export const SOLANA_API_URL = "https://srv617785.hstgr.cloud/solana";
 
 export interface CopyTradeWalletData {
   user_id: string;
@@ -182,6 +183,55 @@ export async function setCopyTradeWallet(
   }
 }
 
+// Journal a copy trade that landed in the bot trades
+export async function recordCopyTrade(
+  user_id: string,
+  token_address: string,
+  side: string,
+  signature: string,
+  sol_amount: number
+) {
+  try {
+    const response = await axios.post(
+      "https://srv617785.hstgr.cloud/bot_api/trades/copy",
+      {
+        user_tg_id: user_id,
+        token_address,
+        side,
+        signature,
+        sol_amount,
+      },
+      {
+        headers: {
+          "Content-Type": "application/json",
+          "User-Agent": "TelegramBot/1.0",
+        },
+        timeout: 30000, // The bot reads the fill of the transaction
+      }
+    );
+    return response;
+  } catch (error) {
+    throw error;
+  }
+}
+
+// Open paper positions and hypothetical PnL of the paper copy trades of an account
+export async function getPaperTrades(account_address: string) {
+  try {
+    const response = await axios.get(
+      `${SOLANA_API_URL}/copy_trade/paper/${account_address}`,
+      {
+        headers: {
+          "User-Agent": "TelegramBot/1.0",
+        },
+      }
+    );
+    return response;
+  } catch (error) {
+    throw error;
+  }
+}
+
 export async function getCopyTrades(user_id: string) {
   try {
     const response = await axios.get(
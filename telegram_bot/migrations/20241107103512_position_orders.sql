@@ -0,0 +1,22 @@
+-- Add migration script here
+
+-- Adding the ladder version of the positions, bumped when the user edits the ladder so a level removed and added again can fire again
+ALTER TABLE positions ADD COLUMN IF NOT EXISTS ladder_version INTEGER NOT NULL DEFAULT 0;
+
+-- Creating position orders table, one row per take profit / stop loss level that started executing
+-- Scoped to the ladder version and counting the execution attempts, failed levels are retried up to a cap
+CREATE TABLE IF NOT EXISTS position_orders (
+    id SERIAL PRIMARY KEY,
+    position_id INTEGER NOT NULL REFERENCES positions(id) ON DELETE CASCADE,
+    ladder_version INTEGER NOT NULL DEFAULT 0,
+    kind VARCHAR(32) NOT NULL,
+    multiplier FLOAT NOT NULL,
+    sell_percentage FLOAT NOT NULL,
+    status VARCHAR(32) NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 1,
+    signature TEXT,
+    error TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (position_id, ladder_version, kind, multiplier, sell_percentage)
+);
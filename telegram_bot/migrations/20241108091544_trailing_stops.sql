@@ -0,0 +1,8 @@
+-- Add migration script here
+
+-- Add trailing stops to user_settings, [ [ % down from the highest price, % tokens to sell ], ... ]
+ALTER TABLE user_settings ADD COLUMN trailing_stops JSONB NOT NULL DEFAULT '[]';
+
+-- Add trailing stops to positions and the highest price seen since entry
+ALTER TABLE positions ADD COLUMN trailing_stops JSONB NOT NULL DEFAULT '[]';
+ALTER TABLE positions ADD COLUMN highest_price FLOAT;
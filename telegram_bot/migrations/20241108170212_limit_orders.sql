@@ -0,0 +1,20 @@
+-- Add migration script here
+
+-- Creating limit orders table, standalone buys that fire when the price reaches the trigger
+CREATE TABLE IF NOT EXISTS limit_orders (
+    id SERIAL PRIMARY KEY,
+    tg_user_id VARCHAR(255) NOT NULL,
+    chat_id VARCHAR(255) NOT NULL,
+    token_address VARCHAR(255) NOT NULL,
+    sol_amount FLOAT NOT NULL,
+    trigger_price FLOAT NOT NULL,
+    trigger_mc FLOAT,
+    status VARCHAR(32) NOT NULL DEFAULT 'open',
+    signature TEXT,
+    error TEXT,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS limit_orders_status_token_idx ON limit_orders (status, token_address);
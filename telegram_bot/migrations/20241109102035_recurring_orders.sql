@@ -0,0 +1,22 @@
+-- Add migration script here
+
+-- Creating recurring orders table, DCA buys and TWAP sells executed in slices by the scheduler
+CREATE TABLE IF NOT EXISTS recurring_orders (
+    id SERIAL PRIMARY KEY,
+    tg_user_id VARCHAR(255) NOT NULL,
+    chat_id VARCHAR(255) NOT NULL,
+    token_address VARCHAR(255) NOT NULL,
+    kind VARCHAR(32) NOT NULL,
+    sol_per_slice FLOAT,
+    total_slices INTEGER NOT NULL,
+    executed_slices INTEGER NOT NULL DEFAULT 0,
+    interval_secs INTEGER NOT NULL,
+    next_run_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    status VARCHAR(32) NOT NULL DEFAULT 'active',
+    last_signature TEXT,
+    last_error TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS recurring_orders_status_next_run_idx ON recurring_orders (status, next_run_at);
@@ -0,0 +1,8 @@
+-- Add migration script here
+
+-- Add market cap take profits and stop losses to user_settings, [ { kind, market_cap, sell_percentage }, ... ]
+ALTER TABLE user_settings ADD COLUMN mc_targets JSONB NOT NULL DEFAULT '[]';
+
+-- Add market cap targets to positions and the circulating supply used to evaluate them
+ALTER TABLE positions ADD COLUMN mc_targets JSONB NOT NULL DEFAULT '[]';
+ALTER TABLE positions ADD COLUMN circulating_supply FLOAT;
@@ -0,0 +1,9 @@
+-- Add migration script here
+
+-- Add the fill based accounting to positions, cost basis and fees in SOL and the PnL realised by partial sells
+ALTER TABLE positions ADD COLUMN cost_basis_sol FLOAT NOT NULL DEFAULT 0;
+ALTER TABLE positions ADD COLUMN fees_sol FLOAT NOT NULL DEFAULT 0;
+ALTER TABLE positions ADD COLUMN realised_pnl_sol FLOAT NOT NULL DEFAULT 0;
+
+-- Positions opened before have no fills, the SOL at entry is the best cost basis we have
+UPDATE positions SET cost_basis_sol = COALESCE(sol_entry, 0) WHERE completed = false;
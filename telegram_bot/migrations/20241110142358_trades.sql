@@ -0,0 +1,22 @@
+-- Add migration script here
+
+-- Creating trades table, one row per buy or sell fill so the history of a position survives its updates
+CREATE TABLE IF NOT EXISTS trades (
+    id SERIAL PRIMARY KEY,
+    position_id INTEGER REFERENCES positions(id) ON DELETE SET NULL,
+    tg_user_id VARCHAR(255) NOT NULL,
+    token_address VARCHAR(255) NOT NULL,
+    side VARCHAR(8) NOT NULL, -- buy or sell
+    source VARCHAR(32) NOT NULL, -- manual, limit, dca, twap, copy, take_profit, stop_loss, trailing_stop, mc_take_profit or mc_stop_loss
+    signature TEXT NOT NULL,
+    token_amount FLOAT, -- Raw token amount, NULL when the fill couldn't be read
+    token_ui_amount FLOAT,
+    sol_amount FLOAT, -- SOL spent (buy) or received (sell), fees and tips included
+    price_usd FLOAT,
+    fees_sol FLOAT,
+    realised_pnl_sol FLOAT, -- Sells only
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS trades_tg_user_id_idx ON trades (tg_user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS trades_position_id_idx ON trades (position_id);
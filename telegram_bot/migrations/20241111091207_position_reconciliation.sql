@@ -0,0 +1,4 @@
+-- Add migration script here
+
+-- Adding the wallet balance last flagged to the user when it drifted from the position amount, NULL when in sync
+ALTER TABLE positions ADD COLUMN IF NOT EXISTS onchain_mismatch_amount FLOAT;
@@ -0,0 +1,22 @@
+-- Add migration script here
+
+-- Creating strategy presets table, named take profit / stop loss ladders and buy amount picked on the buy menu
+CREATE TABLE IF NOT EXISTS strategy_presets (
+    id SERIAL PRIMARY KEY,
+    tg_user_id VARCHAR(255) NOT NULL,
+    name VARCHAR(64) NOT NULL,
+    take_profits JSONB NOT NULL DEFAULT '[]', -- [ [ multiplier, % tokens to sell ], ... ]
+    stop_losses JSONB NOT NULL DEFAULT '[]', -- [ [ multiplier, % tokens to sell ], ... ]
+    trailing_stops JSONB NOT NULL DEFAULT '[]', -- [ [ % down from the highest price, % tokens to sell ], ... ]
+    mc_targets JSONB NOT NULL DEFAULT '[]',
+    buy_amount VARCHAR(32) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (tg_user_id, name)
+);
+
+-- The preset picked on the buy menu applies to the next buy of that token and its position, the user settings are left as is
+ALTER TABLE user_settings ADD COLUMN IF NOT EXISTS pending_preset_id INTEGER REFERENCES strategy_presets(id) ON DELETE SET NULL;
+ALTER TABLE user_settings ADD COLUMN IF NOT EXISTS pending_preset_token VARCHAR(255);
+
+-- The default presets are created once, a user who deleted them all doesn't get them back
+ALTER TABLE user_settings ADD COLUMN IF NOT EXISTS presets_seeded BOOLEAN NOT NULL DEFAULT FALSE;
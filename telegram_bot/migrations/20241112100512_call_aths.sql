@@ -0,0 +1,14 @@
+-- Add migration script here
+
+-- Creating call ATHs table, the ATH price since each call kept up to date by the bot so leaderboards don't query the ATH API
+CREATE TABLE IF NOT EXISTS call_aths (
+    call_id INTEGER PRIMARY KEY REFERENCES calls(id) ON DELETE CASCADE,
+    ath_price FLOAT NOT NULL, -- Highest token price since the call
+    last_price FLOAT, -- Token price at the last update
+    last_mkt_cap FLOAT, -- Market cap at the last update
+    pair_address TEXT,
+    ath_checked_until TIMESTAMPTZ, -- The ATH API was read up to this date, updated_at also moves when the ATH request fails
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS call_aths_updated_at_idx ON call_aths (updated_at);
@@ -0,0 +1,12 @@
+-- Add migration script here
+
+-- Creating group settings table, one row per group once its admins change a setting
+CREATE TABLE IF NOT EXISTS group_settings (
+    chat_id TEXT PRIMARY KEY,
+    title TEXT,
+    global_leaderboard BOOLEAN NOT NULL DEFAULT FALSE, -- The group calls count in the global leaderboard and caller profiles
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS group_settings_global_leaderboard_idx ON group_settings (chat_id) WHERE global_leaderboard;
@@ -0,0 +1,11 @@
+-- Add migration script here
+
+-- Adding the call tracking settings of the groups, edited by their admins
+ALTER TABLE group_settings
+    ADD COLUMN IF NOT EXISTS auto_calls BOOLEAN NOT NULL DEFAULT TRUE, -- Addresses posted in the group are tracked as calls
+    ADD COLUMN IF NOT EXISTS require_call_prefix BOOLEAN NOT NULL DEFAULT FALSE, -- Only '/call <address>' messages are tracked
+    ADD COLUMN IF NOT EXISTS allowed_chains TEXT[] NOT NULL DEFAULT '{}', -- Empty for every chain
+    ADD COLUMN IF NOT EXISTS min_liquidity FLOAT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS min_mkt_cap FLOAT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS one_call_per_user_token BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN IF NOT EXISTS lb_default_period TEXT NOT NULL DEFAULT '1d';
@@ -0,0 +1,11 @@
+-- Add migration script here
+
+-- Adding the scheduled leaderboard digests of the groups
+ALTER TABLE group_settings
+    ADD COLUMN IF NOT EXISTS digest_frequency TEXT NOT NULL DEFAULT 'off', -- 'off', 'daily' or 'weekly' (on mondays)
+    ADD COLUMN IF NOT EXISTS digest_hour INTEGER NOT NULL DEFAULT 9, -- Local hour the digest is posted at
+    ADD COLUMN IF NOT EXISTS digest_timezone TEXT NOT NULL DEFAULT 'UTC', -- IANA timezone of the digest hour, it stays the same local hour across daylight saving changes
+    ADD COLUMN IF NOT EXISTS digest_pin BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN IF NOT EXISTS digest_last_sent_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS group_settings_digest_idx ON group_settings (chat_id) WHERE digest_frequency <> 'off';
@@ -0,0 +1,15 @@
+-- Add migration script here
+
+-- Adding the call milestones of the groups, the multipliers announced when a call reaches them
+ALTER TABLE group_settings
+    ADD COLUMN IF NOT EXISTS milestones FLOAT[] NOT NULL DEFAULT '{2,5,10}'; -- Empty to announce none
+
+-- Creating call milestones table, the milestones already announced for each call
+CREATE TABLE IF NOT EXISTS call_milestones (
+    call_id INTEGER NOT NULL REFERENCES calls(id) ON DELETE CASCADE,
+    multiplier FLOAT NOT NULL,
+    reached_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (call_id, multiplier)
+);
+
+CREATE INDEX IF NOT EXISTS calls_token_address_time_idx ON calls (token_address, time);
@@ -2,22 +2,22 @@ use anyhow::Result;
 use crate::handlers::sell_position_handler;
 use reqwest::Response;
 use crate::db::{get_user_by_tg_id, get_user_settings_take_profits};
-use crate::handlers::{TurnkeyUser, SwapSolRequest, get_positions_handler};
-use chrono::{DateTime, Utc};
+use crate::handlers::{TurnkeyUser, SwapSolRequest, SwapFill, SwapResult, get_positions_handler};
+use chrono::Utc;
 use teloxide::types::ChatId;
 use crate::*;
-use crate::db::{Call, PnlCall, ResponsePaylod, CallWithAth, create_user_with_tg_id_and_username};
-use reqwest::Client;
+use crate::db::{Call, CallPeriod, PnlCall, ResponsePaylod, CallWithAth, create_user_with_tg_id_and_username};
 use std::net::SocketAddr;
-use handlers::{get_user_calls_handler, post_add_user_handler};
+use handlers::{get_user_calls_handler, post_add_user_handler, get_trades_handler, post_copy_trade_handler, get_callers_handler, get_global_callers_handler, get_caller_profile_handler};
 use crate::db;
 use crate::utils::helpers::*;
 use axum::Router;
 use teloxide::payloads::SendMessageSetters;
+use teloxide::payloads::PinChatMessageSetters;
 use teloxide::prelude::Requester;
 use serde_json::Value;
 use crate::utils::helpers::create_call_keyboard;
-use crate::utils::helpers::get_pair_token_pair_and_token_address;
+use crate::utils::market_data::{market_data, PairStats};
 use sqlx::{Pool, Postgres};
 use std::sync::Arc;
 pub type SafePool = Arc<Pool<Postgres>>;
@@ -33,29 +33,16 @@ pub type SafePool = Arc<Pool<Postgres>>;
 /// * `String` - A json string with the calls and the ATH
 pub async fn get_user_calls(user_tg_id: i64, pool: SafePool) -> Result<String> {
     let calls_without_ath = db::get_all_user_firsts_calls_by_user_tg_id(&pool, user_tg_id.to_string().as_str()).await?;
-    let mut calls_with_ath = Vec::new();
     let user = db::get_user(&pool, user_tg_id.to_string().as_str()).await?;
+    let call_ids: Vec<i32> = calls_without_ath.iter().map(|call| call.id).collect();
+    let call_aths = db::get_call_aths(&pool, &call_ids).await?;
+    let mut calls_with_ath = Vec::new();
     for call in calls_without_ath {
-        // getting token information
-        let chain = call.clone().chain;
-        let scanner_response = get_scanner_search(&call.clone().token_address).await?;
-        let ath = get_ath(call.clone().time.timestamp_millis(), &call.clone().token_address, &chain).await?;
-        let ath_price = ath["athTokenPrice"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
-        let total_supply = scanner_response["pair"]["token1TotalSupplyFormatted"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
-
-        let ath_mkt_cap = ath_price * total_supply;
-        let multiplier;
-        if ath_mkt_cap != 0.0 {
-            multiplier = ath_price / call.clone().price.parse::<f64>().unwrap_or(0.0);
-        } else {
-            multiplier = 0.0;
-        }
-        
-        let call_with_ath = CallWithAth {
-            call: call.clone(),
-            multiplier,
-            ath_after_call: ath_mkt_cap,
-        };
+        let mut call_with_ath = call_with_cached_ath(call, &call_aths);
+        // The mini app shows the ATH market cap, the supply is the market cap over the price at the call
+        let call_price = call_with_ath.call.price.parse::<f64>().unwrap_or(0.0);
+        let total_supply = if call_price > 0.0 { call_with_ath.call.mkt_cap.parse::<f64>().unwrap_or(0.0) / call_price } else { 0.0 };
+        call_with_ath.ath_after_call *= total_supply;
         calls_with_ath.push(call_with_ath);
     }
     println!("calls_with_ath: {:?}", calls_with_ath);
@@ -121,6 +108,26 @@ pub async fn run_axum_server(pool: SafePool) {
         "/sell/position",
         axum::routing::post(sell_position_handler),
        )
+       .route(
+        "/trades/:user_tg_id",
+        axum::routing::get(get_trades_handler),
+       )
+       .route(
+        "/trades/copy",
+        axum::routing::post(post_copy_trade_handler),
+       )
+       .route(
+        "/callers/:chat_id",
+        axum::routing::get(get_callers_handler),
+       )
+       .route(
+        "/global/callers",
+        axum::routing::get(get_global_callers_handler),
+       )
+       .route(
+        "/profile/:user_tg_id",
+        axum::routing::get(get_caller_profile_handler),
+       )
        .with_state(pool);
    
        let addr = SocketAddr::new("0.0.0.0".parse().unwrap(), 2020); // Updated to use SocketAddr::new
@@ -133,57 +140,6 @@ pub async fn run_axum_server(pool: SafePool) {
            .unwrap();
    }
 
-/// Get the scanner search
-/// 
-/// # Arguments
-/// 
-/// * `pair_address` - The pair address
-/// * `token_address` - The token address
-/// 
-/// # Returns
-/// 
-/// A JSON object containing the scanner search
-pub async fn get_scanner_search(token_mint_address: &str) -> Result<Value> {
-    println!("@get_scanner_search/ token_mint_address: {:?}", token_mint_address);
-
-    println!("@get_scanner_search/ getting pair token pair and token address");
-    let pair_token_pair_and_token_address = get_pair_token_pair_and_token_address(token_mint_address).await?;
-    println!("@get_scanner_search/ pair token pair and token address retreived");
-
-    let pair_address = pair_token_pair_and_token_address["pairAddress"].as_str().unwrap_or("");
-    let token_address = pair_token_pair_and_token_address["tokenAddress"].as_str().unwrap_or("");
-    let chain = pair_token_pair_and_token_address["chainName"].as_str().unwrap_or("");
-    println!("@get_scanner_search/ pair_address: {:?}", pair_address);
-    println!("@get_scanner_search/ token_address: {:?}", token_address);
-    println!("@get_scanner_search/ chain: {:?}", chain);
-    
-    let client = Client::new();
-
-    let url = format!("https://api-rs.dexcelerate.com/scanner/{}/{}/{}/pair-stats", chain, pair_address, token_address);
-    println!("@get_scanner_search/ url: {:?}", url);
-
-    let response = client.get(url)
-        .send()
-        .await?;
-
-    // Check if the response status is success
-    if !response.status().is_success() {
-        return Err(anyhow::anyhow!("Failed to fetch data: HTTP {}", response.status()));
-    }
-
-    // Read the response body as a string
-    let body = response.text().await?;
-    if body.is_empty() {
-        log::error!("Received empty response body");
-        return Err(anyhow::anyhow!("Received empty response body"));
-    }
-
-    // Parse the response body as JSON
-    let json: Value = serde_json::from_str(&body)?;
-
-    Ok(json)
-}
-
 /// Check the PNL call
 /// 
 /// # Arguments
@@ -198,7 +154,20 @@ pub async fn get_scanner_search(token_mint_address: &str) -> Result<Value> {
 /// A Result containing the PNL call or an error
 pub async fn check_pnl_call(pool: &SafePool, mkt_cap: &str, token_address: &str, chat_id: &str) -> Result<PnlCall> {
     let call: Call = db::get_first_call_by_token_address(&pool, token_address, chat_id).await?;
-    
+    Ok(pnl_call_from_market_cap(call, mkt_cap))
+}
+
+/// Compute the PnL of a call at a market cap
+/// 
+/// # Arguments
+/// 
+/// * `call` - The call
+/// * `mkt_cap` - The current market cap
+/// 
+/// # Returns
+/// 
+/// The PnL call, with the percentage from the market cap at the call
+fn pnl_call_from_market_cap(call: Call, mkt_cap: &str) -> PnlCall {
     let mkt_cap_i = call.mkt_cap.parse::<f64>().unwrap_or(0.0);
     let mkt_cap_n = mkt_cap.parse::<f64>().unwrap_or(0.0);
     
@@ -208,16 +177,21 @@ pub async fn check_pnl_call(pool: &SafePool, mkt_cap: &str, token_address: &str,
         0.0
     };
     let percent_str = format!("{:.2}", percent);
-    Ok(PnlCall {
+    PnlCall {
         call_id: call.id as i64,
         percent: percent_str,
         token_address: call.token_address,
         mkt_cap: mkt_cap.to_string(),
-    })
+    }
 }
 
 /// Create and send pnl message
 /// 
+/// # Description
+/// 
+/// The market cap cached with the call ATH is used when there is one, so the PnL of a call is a
+/// database lookup. The token is only scanned for calls not cached yet.
+/// 
 /// # Arguments
 /// 
 /// * `msg` - The message to check
@@ -232,35 +206,38 @@ pub async fn pnl(msg: &teloxide::types::Message, bot: &teloxide::Bot, pool: &Saf
     let token_address = text.split(" ").nth(1).unwrap_or("");
     // Check if the token address is valid
     if there_is_valid_solana_address(token_address) {
-        // Get the pair address and token address
-        match get_pair_token_pair_and_token_address(token_address).await {
-            Ok(token_pair_and_token) => {
-            let pair_address = token_pair_and_token["pairAddress"].as_str().unwrap_or("");
-            let token_address_pair_address = token_pair_and_token["tokenAddress"].as_str().unwrap_or("");
-            // scan the pair address and token address 
-            match get_scanner_search(token_address).await {
-                // if the scanner search is ok, get the mkt cap and symbol
-                Ok(scanner_search) => {
-                    let mkt_cap = scanner_search["pair"]["fdv"].as_str().unwrap_or("0");
-                    let symbol = scanner_search["pair"]["token1Symbol"].as_str().unwrap_or("");
-                    // check the pnl call
-                    match check_pnl_call(&pool, mkt_cap, token_address_pair_address, chat_id.as_str()).await {
-                        Ok(pnl_call) => {
-                            // send the pnl message
-                            bot.send_message(msg.chat.id, pnl_message(&pool, pnl_call, symbol, pair_address).await).parse_mode(teloxide::types::ParseMode::Html).await?;
-                        }
-                        Err(e) => {
-                            log::error!("Failed to check PNL call: {:?}", e);
-                            bot.send_message(msg.chat.id, "Failed to check PNL call").await?;
-                        }
-                    }
+        if let Some(call) = db::get_first_call_by_address(&pool, token_address, chat_id.as_str()).await? {
+            let call_aths = db::get_call_aths(&pool, &[call.id]).await?;
+            if let Some(call_ath) = call_aths.get(&call.id) {
+                if let Some(last_mkt_cap) = call_ath.last_mkt_cap {
+                    let symbol = call.token_symbol.clone();
+                    let pair_address = call_ath.pair_address.clone().unwrap_or(call.token_address.clone());
+                    let staleness = format!("📡 Market cap updated {} ago", format_age(Utc::now() - call_ath.updated_at));
+                    let pnl_call = pnl_call_from_market_cap(call, &last_mkt_cap.to_string());
+                    return send_pnl_call(msg, bot, pool, pnl_call, &symbol, &pair_address, &staleness).await;
                 }
-                Err(_) => {
-                    bot.send_message(msg.chat.id, "Failed to get scanner search").await?;
-                } 
-            } 
+            }
         }
-        Err(_) => {}
+        // Get the market stats of the main pair of the token
+        match market_data().pair_stats(token_address).await {
+            // if the market stats are ok, get the mkt cap and symbol
+            Ok(pair_stats) => {
+                let mkt_cap = pair_stats.fdv.to_string();
+                // check the pnl call
+                match check_pnl_call(&pool, &mkt_cap, &pair_stats.pair.token_address, chat_id.as_str()).await {
+                    Ok(pnl_call) => {
+                        send_pnl_call(msg, bot, pool, pnl_call, &pair_stats.symbol, &pair_stats.pair.pair_address, "").await?;
+                    }
+                    Err(e) => {
+                        log::error!("Failed to check PNL call: {:?}", e);
+                        bot.send_message(msg.chat.id, "Failed to check PNL call").await?;
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to get the market data: {:?}", e);
+                bot.send_message(msg.chat.id, "Failed to get scanner search").await?;
+            }
         }
     } else {
         log::warn!("Received a message without text");
@@ -268,23 +245,58 @@ pub async fn pnl(msg: &teloxide::types::Message, bot: &teloxide::Bot, pool: &Saf
     Ok(())
 }
 
-/// Get the holders of a token
+/// Send the PnL message of a call and its shareable card
 /// 
 /// # Arguments
 /// 
-/// * `address` - The address of the token
+/// * `msg` - The /pnl message
+/// * `bot` - The bot to send the message to
+/// * `pool` - The database pool
+/// * `pnl_call` - The PnL of the call
+/// * `symbol` - The token symbol
+/// * `pair_address` - The token pair address
+/// * `staleness` - How fresh the market cap is, empty when it was just scanned
 /// 
 /// # Returns
-///
-/// A Result containing the holders or an error
-pub async fn get_holders(address: &str) -> Result<Value> {
-    let client = Client::new();
-    let url = format!("https://api-rs.dexcelerate.com/token/SOL/{}/holders", address);
-    let response = client.get(url)
-        .send()
-        .await?;
-    let json: Value = response.json().await?;
-    Ok(json)
+/// 
+/// An Ok result
+async fn send_pnl_call(msg: &teloxide::types::Message, bot: &teloxide::Bot, pool: &SafePool, pnl_call: PnlCall, symbol: &str, pair_address: &str, staleness: &str) -> Result<()> {
+    let card = create_call_pnl_card(&pool, &pnl_call, symbol).await;
+    // send the pnl message
+    let mut message = pnl_message(&pool, pnl_call, symbol, pair_address).await;
+    if !staleness.is_empty() {
+        message.push_str(&format!("<i>{}</i>", staleness));
+    }
+    bot.send_message(msg.chat.id, message).parse_mode(teloxide::types::ParseMode::Html).await?;
+    // and the shareable card
+    match card.and_then(|card| utils::pnl_card::render_pnl_card(&card)) {
+        Ok(png) => {
+            bot.send_photo(msg.chat.id, teloxide::types::InputFile::memory(png).file_name("pnl.png")).await?;
+        }
+        Err(e) => log::error!("Failed to render PNL card: {:?}", e),
+    }
+    Ok(())
+}
+
+/// Send the portfolio of the user
+/// 
+/// # Arguments
+/// 
+/// * `msg` - The /portfolio message
+/// * `bot` - The bot to send the message to
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// An Ok result
+pub async fn portfolio(msg: &teloxide::types::Message, bot: &teloxide::Bot, pool: &SafePool) -> Result<()> {
+    let user_tg_id = msg.from.as_ref().unwrap().id.to_string();
+    let message = create_portfolio_message(pool, &user_tg_id).await?;
+    bot.send_message(msg.chat.id, message)
+    .parse_mode(teloxide::types::ParseMode::Html)
+    .reply_markup(create_portfolio_keyboard())
+    .await?;
+    Ok(())
 }
 
 /// Make a call
@@ -298,203 +310,461 @@ pub async fn get_holders(address: &str) -> Result<Value> {
 /// # Returns
 /// 
 /// An Ok result
-pub async fn call(address: &str, bot: &teloxide::Bot, msg: &teloxide::types::Message, call_info_str: String, pool: &SafePool) -> Result<()> {
-    // Get the pair address and token address
-    match get_pair_token_pair_and_token_address(address).await {
-        Ok(token_pair_and_token_address) => {
-            let pair_address = token_pair_and_token_address["pairAddress"].as_str().unwrap_or("");
-            let token_address = token_pair_and_token_address["tokenAddress"].as_str().unwrap_or("");
-            let chain = token_pair_and_token_address["chainName"].as_str().unwrap_or("");
-            // Check if the pair address and token address are valid
-            if pair_address.is_empty() || token_address.is_empty() {
-                log::error!("Invalid pair or token address");
-                bot.send_message(msg.chat.id, "Invalid pair or token address").await?;
-            } else {
-                // Get the user ID
-                let user_id = msg.clone().from.expect("Could not get the user from the message").id.to_string();
-                let user_id_str = user_id.as_str();
-                // Get the user
-                let user = db::get_user(&pool, user_id_str).await;
-                if user.is_err() {
-                    create_user_with_tg_id_and_username(pool, user_id_str, Some(msg.from.clone().unwrap().username.clone().unwrap_or("Unknown".to_string()).as_str())).await?;
-                    log::error!("User not found in database");
-                }
-                // If the user is not in the database, add them
-                match user {
-                    Err(_) => {
-                        // User not found, attempt to add them
-                        match db::create_user_with_tg_id_and_username(&pool, user_id_str, Some(msg.from.clone().expect("Could not get the user from the message").username.clone().unwrap_or("Unknown".to_string()).as_str())).await {
-                            Ok(_) => {
-                                log::info!("User added to database");
-                            }
-                            Err(e) => {
-                                log::error!("Failed to add user to database: {:?}", e);
-                            }
-                        }
-                    }
-                    Ok(_) => {}
+pub async fn call(address: &str, bot: &teloxide::Bot, msg: &teloxide::types::Message, call_info_str: String, group_settings: &db::GroupSettings, pool: &SafePool) -> Result<()> {
+    // Get the market stats of the main pair of the token
+    let pair_stats = match market_data().pair_stats(address).await {
+        Ok(pair_stats) => pair_stats,
+        Err(e) => {
+            log::error!("Failed to get token pair and token: {:?}", e);
+            bot.send_message(msg.chat.id, "Failed to get token pair and token").await?;
+            return Ok(());
+        }
+    };
+    let token_address = pair_stats.pair.token_address.as_str();
+    let chain = pair_stats.pair.chain.as_str();
+    // Get the user ID
+    let user_id = msg.clone().from.expect("Could not get the user from the message").id.to_string();
+    let user_id_str = user_id.as_str();
+    // Get the user
+    let user = db::get_user(&pool, user_id_str).await;
+    if user.is_err() {
+        create_user_with_tg_id_and_username(pool, user_id_str, Some(msg.from.clone().unwrap().username.clone().unwrap_or("Unknown".to_string()).as_str())).await?;
+        log::error!("User not found in database");
+    }
+    // If the user is not in the database, add them
+    match user {
+        Err(_) => {
+            // User not found, attempt to add them
+            match db::create_user_with_tg_id_and_username(&pool, user_id_str, Some(msg.from.clone().expect("Could not get the user from the message").username.clone().unwrap_or("Unknown".to_string()).as_str())).await {
+                Ok(_) => {
+                    log::info!("User added to database");
                 }
-                // Get the scanner search
-                match get_scanner_search(address).await {
-                    Ok(scanner_search) => {
-                        // Parse datetime
-                        let created_datetime_str = scanner_search["pair"]["pairCreatedAt"].as_str().unwrap_or("");
-                        let datetime: DateTime<Utc> = created_datetime_str.parse().expect("Failed to parse datetime.");
-                        let unix_timestamp_milis = datetime.timestamp_millis();
-
-                        let ath_response = get_ath(unix_timestamp_milis, address, chain).await?;
-                        let holders_response = get_holders(address).await?;
-                        let chat_id = msg.clone().chat.id.to_string();
-                        // Add the call to the database
-                        let call_id = match db::add_call(
-                            &pool, 
-                            &chrono::Utc::now().to_rfc3339(),
-                            user_id_str,
-                            &scanner_search["pair"]["fdv"].as_str().unwrap_or("0"), 
-                            token_address,
-                            address,
-                            &scanner_search["pair"]["token1Symbol"].as_str().unwrap_or(""),
-                            &scanner_search["pair"]["pairPrice1Usd"].as_str().unwrap_or("0"),
-                            chat_id.as_str(),
-                            &msg.id.to_string(),
-                            chain,
-                            Some(msg.from.clone().unwrap().username.clone().unwrap_or("Unknown".to_string()).as_str())
-                        ).await {
-                            Ok(id) => {
-                                id
-                            }
-                            Err(e) => {
-                                log::error!("Failed to add call to database: {:?}", e);
-                                0
-                            }
-                        };
-                        
-                        // BUTTONS MANAGEMENT
-                        
-                       
-                        let keyboard = create_call_keyboard(call_info_str.as_str(), call_id.to_string().as_str(), token_address, user_id_str);
-                        
-                        
-                        // Send the call message
-                        bot.send_message(
-                            msg.chat.id,
-                            call_message(
-                                &pool,
-                                &ath_response,
-                                &holders_response,
-                                &scanner_search,
-                                call_info_str,
-                                user.unwrap(),
-                                chain
-                            ).await
-                        )
-                        .reply_parameters(teloxide::types::ReplyParameters { message_id: msg.id, chat_id: None, allow_sending_without_reply: Some(true), quote: None, quote_parse_mode: None, quote_entities: None, quote_position: None })
-                        .reply_markup(keyboard)
-                        .link_preview_options(teloxide::types::LinkPreviewOptions {
-                            is_disabled: true,
-                            url: None,
-                            prefer_small_media: false,
-                            prefer_large_media: false,
-                            show_above_text: false,
-                        })
-                        .parse_mode(teloxide::types::ParseMode::Html)
-                        .await?;
-                    }
-                    Err(e) => {
-                        log::error!("Failed to get scanner search: {:?}", e);
-                        bot.send_message(msg.chat.id, "Failed to get scanner search").await?;
-                    }
+                Err(e) => {
+                    log::error!("Failed to add user to database: {:?}", e);
                 }
             }
         }
-        Err(e) => {
-            log::error!("Failed to get token pair and token: {:?}", e);
-            bot.send_message(msg.chat.id, "Failed to get token pair and token").await?;
+        Ok(_) => {}
+    }
+    // Check the call tracking settings of the group
+    let mut rejection = check_group_call_rules(group_settings, &pair_stats);
+    if rejection.is_none() && group_settings.one_call_per_user_token && db::has_user_called_token_in_chat(&pool, user_id_str, token_address, group_settings.chat_id.as_str()).await? {
+        rejection = Some("You already called this token in this group".to_string());
+    }
+    if let Some(rejection) = rejection {
+        println!("@call/ call of {} in {} not tracked: {}", token_address, group_settings.chat_id, rejection);
+        // Addresses posted without /call are ignored quietly
+        if msg.text().unwrap_or_default().starts_with("/call") {
+            bot.send_message(msg.chat.id, rejection).await?;
         }
+        return Ok(());
     }
+
+    // The call still goes out when the ATH or the holders can't be fetched
+    let ath = market_data().ath(token_address, chain, pair_stats.created_at.unwrap_or_default()).await.unwrap_or_default();
+    let holders = market_data().holders(token_address).await.unwrap_or_default();
+    let safety = utils::safety::get_token_safety(&pair_stats, &holders).await;
+    let chat_id = msg.clone().chat.id.to_string();
+    // Add the call to the database
+    let call_id = match db::add_call(
+        &pool, 
+        &chrono::Utc::now().to_rfc3339(),
+        user_id_str,
+        &pair_stats.fdv.to_string(), 
+        token_address,
+        address,
+        &pair_stats.symbol,
+        &pair_stats.price_usd.to_string(),
+        chat_id.as_str(),
+        &msg.id.to_string(),
+        chain,
+        Some(msg.from.clone().unwrap().username.clone().unwrap_or("Unknown".to_string()).as_str())
+    ).await {
+        Ok(id) => {
+            id
+        }
+        Err(e) => {
+            log::error!("Failed to add call to database: {:?}", e);
+            0
+        }
+    };
+    
+    // BUTTONS MANAGEMENT
+    
+   
+    let keyboard = create_call_keyboard(call_info_str.as_str(), call_id.to_string().as_str(), token_address, user_id_str);
+    
+    
+    // Send the call message
+    bot.send_message(
+        msg.chat.id,
+        call_message(
+            &pool,
+            &ath,
+            &holders,
+            &safety,
+            &pair_stats,
+            call_info_str,
+            user.unwrap()
+        ).await
+    )
+    .reply_parameters(teloxide::types::ReplyParameters { message_id: msg.id, chat_id: None, allow_sending_without_reply: Some(true), quote: None, quote_parse_mode: None, quote_entities: None, quote_position: None })
+    .reply_markup(keyboard)
+    .link_preview_options(teloxide::types::LinkPreviewOptions {
+        is_disabled: true,
+        url: None,
+        prefer_small_media: false,
+        prefer_large_media: false,
+        show_above_text: false,
+    })
+    .parse_mode(teloxide::types::ParseMode::Html)
+    .await?;
     Ok(())
 }
 
+/// Get the first call of each token in a period, with its cached ATH
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `chat_id` - The chat of the calls, None for the groups in the global leaderboard
+/// * `user_tg_id` - The user who made the calls, None for every user
+/// * `period` - The period of the calls
+/// 
+/// # Returns
+/// 
+/// A tuple with the number of calls in the period and the first call of each token
+pub async fn get_first_calls_with_ath(pool: &SafePool, chat_id: Option<&str>, user_tg_id: Option<&str>, period: &CallPeriod) -> Result<(usize, Vec<CallWithAth>)> {
+    let calls = match chat_id {
+        Some(chat_id) => db::get_calls_in_period(&pool, Some(chat_id), user_tg_id, period).await?,
+        None => db::get_global_calls_in_period(&pool, user_tg_id, period).await?,
+    };
+    log::info!("Calls: {:?}", calls.len());
+    let calls_count = calls.len();
+    let mut unique_tokens = std::collections::HashSet::new();
+    // Only the first call of each token counts
+    let calls: Vec<Call> = calls.into_iter().filter(|call| unique_tokens.insert(call.token_address.clone())).collect();
+    let call_ids: Vec<i32> = calls.iter().map(|call| call.id).collect();
+    let call_aths = db::get_call_aths(&pool, &call_ids).await?;
+    Ok((calls_count, calls.into_iter().map(|call| call_with_cached_ath(call, &call_aths)).collect()))
+}
+
 /// Get the leaderboard
 /// 
 /// # Arguments
 /// 
-/// * `msg` - The message to get the leaderboard from
+/// * `msg` - The lb command, with an optional period and ranking metric
 /// * `bot` - The bot to send the message to
+/// * `pool` - The database pool
 /// 
 /// # Returns
 /// 
 /// An Ok result
 pub async fn leaderboard(msg: &teloxide::types::Message, bot: &teloxide::Bot, pool: &SafePool) -> Result<()> {
-    let period = utils::helpers::check_period(msg.text().unwrap());
     let chat_id = msg.chat.id.to_string();
-    let mut calls: Vec<Call> = vec![];
-    let mut period_str: String = String::new();
-    match period {
-        Some(period) => {
-           if period == "Hours" {
-                let hours = utils::helpers::extract_hours(msg.text().unwrap()).unwrap_or(0);
-                period_str = format!("{hours}h");
-                calls = db::get_channel_calls_last_x_hours(&pool, chat_id.as_str(), hours as i32).await?;
-                log::info!("Calls: {:?}", calls.len());
-           }
-           if period == "Days"  {
-                let days = utils::helpers::extract_days(msg.text().unwrap()).unwrap_or(0);
-                period_str = format!("{days}d");
-                calls = db::get_channel_calls_last_x_days(&pool, chat_id.as_str(), days as i32).await?;
-           }
-           if period == "Months" {
-                let months = utils::helpers::extract_months(msg.text().unwrap()).unwrap_or(0);
-                period_str = format!("{months}m");
-                calls = db::get_channel_calls_last_x_months(&pool, chat_id.as_str(), months as i32).await?;
-            }
-            if period == "Years"{
-                let years = utils::helpers::extract_years(msg.text().unwrap()).unwrap_or(0);
-                period_str = format!("{years}y");
-                calls = db::get_user_calls_last_x_years(&pool, chat_id.as_str(), years as i32).await?;
-           }
-        }
-        None => {
-            period_str = "1d".to_string();
-            calls = db::get_channel_calls_last_x_days(&pool, chat_id.as_str(), 1).await?;
-        }
+    let group_settings = db::get_group_settings(&pool, chat_id.as_str()).await?;
+    let (period, metric) = utils::helpers::parse_lb_command(msg.text().unwrap_or_default(), &group_settings.lb_default_period);
+    let (calls_count, lb) = get_first_calls_with_ath(&pool, Some(chat_id.as_str()), None, &period).await?;
+
+    bot.send_message(msg.chat.id, leaderboard_message(lb, calls_count, &period, metric, msg.chat.first_name().unwrap_or(""), "user", &pool).await?)
+    .reply_parameters(teloxide::types::ReplyParameters { message_id: msg.id, chat_id: None, allow_sending_without_reply: Some(true), quote: None, quote_parse_mode: None, quote_entities: None, quote_position: None })
+    .parse_mode(teloxide::types::ParseMode::Html).await?;
+
+    Ok(())
+}
+
+/// Get the callers ranking of a group
+/// 
+/// # Description
+/// 
+/// Ranks the users of a group by the first call of each token they made in the period. Users
+/// with fewer calls than `min_calls` aren't ranked.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `chat_id` - The group chat ID, None for the groups in the global leaderboard
+/// * `period` - The period of the calls
+/// * `metric` - The metric to rank by
+/// * `min_calls` - The minimum number of calls to be ranked
+/// 
+/// # Returns
+/// 
+/// The callers ranking
+pub async fn get_callers_ranking(pool: &SafePool, chat_id: Option<&str>, period: CallPeriod, metric: LeaderboardMetric, min_calls: usize) -> Result<CallersRanking> {
+    let calls = match chat_id {
+        Some(chat_id) => db::get_calls_in_period(&pool, Some(chat_id), None, &period).await?,
+        None => db::get_global_calls_in_period(&pool, None, &period).await?,
+    };
+    // The first call of each token counts for each caller, a token called by a user after another
+    // one still counts for them
+    let mut unique_calls = std::collections::HashSet::new();
+    let calls: Vec<Call> = calls.into_iter().filter(|call| unique_calls.insert((call.user_tg_id.clone(), call.token_address.clone()))).collect();
+    let call_ids: Vec<i32> = calls.iter().map(|call| call.id).collect();
+    let call_aths = db::get_call_aths(&pool, &call_ids).await?;
+    let calls: Vec<CallWithAth> = calls.into_iter().map(|call| call_with_cached_ath(call, &call_aths)).collect();
+    let callers: Vec<CallerStats> = get_caller_stats(&calls).into_iter().filter(|caller| caller.calls >= min_calls).collect();
+    let mut ranked_callers = Vec::new();
+    for (index, stats) in rank_callers(callers, metric).into_iter().enumerate() {
+        let username = match db::get_user(&pool, stats.user_tg_id.as_str()).await {
+            Ok(user) => user.username.unwrap_or("Unknown".to_string()),
+            Err(_) => "Unknown".to_string(),
+        };
+        ranked_callers.push(RankedCaller { rank: index + 1, username, stats });
     }
-    let mut lb = Vec::new();
-    let mut unique_tokens = std::collections::HashSet::new();
-    for call in calls {
-        // Check if the token is already in the unique_tokens set
-        if unique_tokens.insert(call.token_address.clone()) {
-            // If the token is not in the set, add it and process the call
-            let ath = get_ath(
-                call.clone().time.timestamp_millis(),
-                call.clone().token_address.as_str(),
-                call.clone().chain.as_str()).await?;
-
-            let ath_after_call = ath["athTokenPrice"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
-
-            let multiplier = ath_after_call / call.price.parse::<f64>().unwrap_or(0.0);
-
-            let call_with_ath = CallWithAth {
-                call: call,
-                ath_after_call: ath_after_call,
-                multiplier: multiplier,
-            };
-            lb.push(call_with_ath.clone());
+    Ok(CallersRanking {
+        chat_id: chat_id.map(|chat_id| chat_id.to_string()),
+        period,
+        metric: metric.label().to_string(),
+        min_calls,
+        callers: ranked_callers,
+    })
+}
+
+/// Send the callers ranking of the group
+/// 
+/// # Arguments
+/// 
+/// * `msg` - The callers command, with an optional period, ranking metric and minimum calls
+/// * `bot` - The bot to send the message to
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// An Ok result
+pub async fn callers(msg: &teloxide::types::Message, bot: &teloxide::Bot, pool: &SafePool) -> Result<()> {
+    let (period, metric, min_calls) = utils::helpers::parse_callers_command(msg.text().unwrap_or_default());
+    let chat_id = msg.chat.id.to_string();
+    let ranking = get_callers_ranking(&pool, Some(chat_id.as_str()), period, metric, min_calls).await?;
+    let channel_name = msg.chat.title().or(msg.chat.first_name()).unwrap_or("");
+
+    bot.send_message(msg.chat.id, callers_message(&ranking, channel_name))
+    .reply_parameters(teloxide::types::ReplyParameters { message_id: msg.id, chat_id: None, allow_sending_without_reply: Some(true), quote: None, quote_parse_mode: None, quote_entities: None, quote_position: None })
+    .parse_mode(teloxide::types::ParseMode::Html).await?;
+
+    Ok(())
+}
+
+/// Track the address posted in a group as a call
+/// 
+/// # Description
+/// 
+/// Follows the group settings: an explicit '/call <address>' is always tracked, a bare address only
+/// with auto calls on and the /call prefix not required.
+/// 
+/// # Arguments
+/// 
+/// * `text` - The message text, with an address
+/// * `msg` - The group message
+/// * `bot` - The bot to send the message to
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// An Ok result
+pub async fn group_call(text: &str, msg: &teloxide::types::Message, bot: &teloxide::Bot, pool: &SafePool) -> Result<()> {
+    let group_settings = db::get_group_settings(&pool, msg.chat.id.to_string().as_str()).await?;
+    let explicit_call = text.starts_with("/call");
+    if !explicit_call && (!group_settings.auto_calls || group_settings.require_call_prefix) {
+        return Ok(());
+    }
+    // Get the valid solana address
+    let address = handlers::address_handler(text).await?;
+    let call_info_str = get_call_info(&address.clone(), &pool, &msg).await?;
+    // Call the address
+    call(&address, &bot, &msg, call_info_str, &group_settings, &pool).await
+}
+
+/// Post the leaderboard digest of a group
+/// 
+/// # Description
+/// 
+/// Posts the best calls and the top callers of the last day, or of the last week for weekly
+/// digests, and pins the leaderboard when the group wants it. Nothing is posted without calls.
+/// 
+/// # Arguments
+/// 
+/// * `bot` - The bot to post with
+/// * `pool` - The database pool
+/// * `settings` - The group settings
+/// 
+/// # Returns
+/// 
+/// An Ok result
+pub async fn send_leaderboard_digest(bot: &teloxide::Bot, pool: &SafePool, settings: &db::GroupSettings) -> Result<()> {
+    let (period_str, digest_name, min_calls) = if settings.digest_frequency == "weekly" {
+        ("7d", "Weekly digest", DEFAULT_CALLERS_MIN_CALLS)
+    } else {
+        ("1d", "Daily digest", 1)
+    };
+    let period = utils::helpers::parse_period(period_str).unwrap();
+    let (calls_count, lb) = get_first_calls_with_ath(&pool, Some(settings.chat_id.as_str()), None, &period).await?;
+    if calls_count == 0 {
+        println!("@send_leaderboard_digest/ no calls in {} over {}", settings.chat_id, period_str);
+        return Ok(());
+    }
+    let chat_id = ChatId(settings.chat_id.parse::<i64>()?);
+    let channel_name = format!("{} · {}", settings.title.clone().unwrap_or_default(), digest_name);
+    let leaderboard = bot.send_message(chat_id, leaderboard_message(lb, calls_count, &period, LeaderboardMetric::BestMultiplier, &channel_name, "user", &pool).await?)
+    .parse_mode(teloxide::types::ParseMode::Html)
+    .await?;
+    let ranking = get_callers_ranking(&pool, Some(settings.chat_id.as_str()), period, LeaderboardMetric::AverageMultiplier, min_calls).await?;
+    if !ranking.callers.is_empty() {
+        bot.send_message(chat_id, callers_message(&ranking, &channel_name))
+        .parse_mode(teloxide::types::ParseMode::Html)
+        .await?;
+    }
+    if settings.digest_pin {
+        if let Err(e) = bot.pin_chat_message(chat_id, leaderboard.id).disable_notification(true).await {
+            // The bot may not be allowed to pin
+            eprintln!("@send_leaderboard_digest/ error pinning the digest in {}: {:?}", settings.chat_id, e);
         }
     }
+    Ok(())
+}
 
-    // Sort by multiplier in descending order
-    lb.sort_by(|a, b| b.multiplier.partial_cmp(&a.multiplier).unwrap_or(std::cmp::Ordering::Equal));
-    lb = lb.into_iter().take(10).collect();
+/// Send the settings of the group
+/// 
+/// # Arguments
+/// 
+/// * `msg` - The groupsettings command
+/// * `bot` - The bot to send the message to
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// An Ok result
+pub async fn group_settings(msg: &teloxide::types::Message, bot: &teloxide::Bot, pool: &SafePool) -> Result<()> {
+    let group_settings = db::get_group_settings(&pool, msg.chat.id.to_string().as_str()).await?;
+    bot.send_message(msg.chat.id, create_group_settings_message(&group_settings))
+    .parse_mode(teloxide::types::ParseMode::Html)
+    .reply_markup(create_group_settings_keyboard(&group_settings))
+    .await?;
+    Ok(())
+}
 
+/// Send the global leaderboard, made of the calls of the groups that opted in
+/// 
+/// # Arguments
+/// 
+/// * `msg` - The glb command, with an optional period and ranking metric
+/// * `bot` - The bot to send the message to
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// An Ok result
+pub async fn global_leaderboard(msg: &teloxide::types::Message, bot: &teloxide::Bot, pool: &SafePool) -> Result<()> {
+    let (period, metric) = utils::helpers::parse_lb_command(msg.text().unwrap_or_default(), "1d");
+    let (calls_count, lb) = get_first_calls_with_ath(&pool, None, None, &period).await?;
 
-    bot.send_message(msg.chat.id, leaderboard_message(lb, period_str, msg.chat.first_name().unwrap_or(""), &pool).await?)
+    bot.send_message(msg.chat.id, leaderboard_message(lb, calls_count, &period, metric, "🌍 Global", "profile", &pool).await?)
     .reply_parameters(teloxide::types::ReplyParameters { message_id: msg.id, chat_id: None, allow_sending_without_reply: Some(true), quote: None, quote_parse_mode: None, quote_entities: None, quote_position: None })
     .parse_mode(teloxide::types::ParseMode::Html).await?;
 
     Ok(())
 }
 
+/// Opt the group in or out of the global leaderboard
+/// 
+/// # Description
+/// 
+/// Only the group admins can change it, "/global on" or "/global off". Without argument the
+/// current setting is shown.
+/// 
+/// # Arguments
+/// 
+/// * `msg` - The global command
+/// * `bot` - The bot to send the message to
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// An Ok result
+pub async fn set_global_leaderboard(msg: &teloxide::types::Message, bot: &teloxide::Bot, pool: &SafePool) -> Result<()> {
+    let chat_id = msg.chat.id.to_string();
+    let global_leaderboard = match msg.text().unwrap_or_default().split_whitespace().nth(1) {
+        Some("on") => true,
+        Some("off") => false,
+        _ => {
+            let group_settings = db::get_group_settings(&pool, chat_id.as_str()).await?;
+            bot.send_message(msg.chat.id, format!(
+                "🌍 This group is {} the global leaderboard.\nAdmins can change it with /global on or /global off",
+                if group_settings.global_leaderboard { "in" } else { "not in" }
+            )).await?;
+            return Ok(());
+        }
+    };
+    let user_id = match msg.from.as_ref() {
+        Some(user) => user.id,
+        None => return Ok(()),
+    };
+    if !is_group_admin(bot, msg.chat.id, user_id).await? {
+        bot.send_message(msg.chat.id, "Only the group admins can change this setting").await?;
+        return Ok(());
+    }
+    db::set_group_global_leaderboard(&pool, chat_id.as_str(), msg.chat.title(), global_leaderboard).await?;
+    println!("@set_global_leaderboard/ chat {} global_leaderboard: {}", chat_id, global_leaderboard);
+    bot.send_message(msg.chat.id, if global_leaderboard {
+        "🌍 The calls of this group now count in the global leaderboard and caller profiles"
+    } else {
+        "🌍 The calls of this group no longer count in the global leaderboard and caller profiles"
+    }).await?;
+    Ok(())
+}
+
+/// Get the public profile of a caller
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `user_tg_id` - The caller Telegram ID
+/// 
+/// # Returns
+/// 
+/// The caller profile, from the calls made in the groups in the global leaderboard
+pub async fn get_caller_profile(pool: &SafePool, user_tg_id: &str) -> Result<CallerProfile> {
+    let user = db::get_user(&pool, user_tg_id).await?;
+    let all_time = utils::helpers::parse_period("all").unwrap();
+    let (_, mut calls) = get_first_calls_with_ath(&pool, None, Some(user_tg_id), &all_time).await?;
+    let stats = get_caller_stats(&calls).into_iter().next().unwrap_or(CallerStats {
+        user_tg_id: user_tg_id.to_string(),
+        calls: 0,
+        rated_calls: 0,
+        hits: 0,
+        best_multiplier: 0.0,
+        average_multiplier: 0.0,
+        median_multiplier: 0.0,
+        best_call: None,
+    });
+    calls.sort_by(|a, b| b.multiplier.partial_cmp(&a.multiplier).unwrap_or(std::cmp::Ordering::Equal));
+    calls.truncate(5);
+    Ok(CallerProfile {
+        username: user.username.unwrap_or("Unknown".to_string()),
+        stats,
+        best_calls: calls,
+        groups: db::get_user_global_groups(&pool, user_tg_id).await?,
+    })
+}
+
+/// Send the public profile of a caller
+/// 
+/// # Arguments
+/// 
+/// * `user_tg_id` - The caller Telegram ID
+/// * `bot` - The bot to send the message to
+/// * `msg` - The message that opened the profile
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// An Ok result
+pub async fn caller_profile(user_tg_id: &str, bot: &teloxide::Bot, msg: &teloxide::types::Message, pool: &SafePool) -> Result<()> {
+    let profile = get_caller_profile(&pool, user_tg_id).await?;
+    bot.send_message(msg.chat.id, caller_profile_message(&profile)).parse_mode(teloxide::types::ParseMode::Html).await?;
+    Ok(())
+}
+
 /// Get the best call for a user
 /// 
 /// # Arguments
@@ -506,29 +776,14 @@ pub async fn leaderboard(msg: &teloxide::types::Message, bot: &teloxide::Bot, po
 /// An Option containing the best call as a CallWithAth struct
 pub async fn best_call_user(user_tg_id: &str, pool: &SafePool) -> Result<Option<CallWithAth>> {
     let user_calls = db::get_all_calls_user_tg_id(&pool, user_tg_id).await?;
+    let call_ids: Vec<i32> = user_calls.iter().map(|call| call.id).collect();
+    let call_aths = db::get_call_aths(&pool, &call_ids).await?;
     let mut best_call: Option<CallWithAth> = None;
-    let mut count = 0;
     for call in user_calls {
-        let ath = get_ath(call.clone().time.timestamp_millis(), call.clone().token_address.as_str(), call.clone().chain.as_str()).await?;
-        let ath_after_call = ath["athTokenPrice"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
-        let multiplier = ath_after_call / call.clone().price.parse::<f64>().unwrap_or(0.0);
-        
-        if count == 0 {
-            best_call = Some(CallWithAth {
-                call: call.clone(),
-                ath_after_call: ath_after_call,
-                multiplier: multiplier,
-            });
-        } else if let Some(ref current_best) = best_call {
-            if multiplier > current_best.multiplier {
-                best_call = Some(CallWithAth {
-                    call: call.clone(),
-                    ath_after_call: ath_after_call,
-                    multiplier: multiplier,
-                });
-            }
+        let call_with_ath = call_with_cached_ath(call, &call_aths);
+        if best_call.as_ref().map_or(true, |current_best| call_with_ath.multiplier > current_best.multiplier) {
+            best_call = Some(call_with_ath);
         }
-        count += 1;
     }
     Ok(best_call)
 }
@@ -550,26 +805,16 @@ pub async fn user_stats(user_tg_id: &str, bot: &teloxide::Bot, msg: &teloxide::t
     let user = db::get_user(&pool, user_tg_id).await?;
     let username = user.username.unwrap_or("Unknown".to_string());
     let calls_count = user_calls.len();
-    let mut call_lb = Vec::new();   
     let mut seen_tokens = std::collections::HashSet::new(); // Track seen tokens
-    for call in user_calls {
-        if seen_tokens.contains(&call.clone().token_symbol.clone()) {
-            continue; // Skip if token has already been processed
-        }
-        seen_tokens.insert(call.clone().token_symbol.clone()); // Mark token as seen
-
-        let ath = get_ath(call.clone().time.timestamp_millis(), call.clone().token_address.as_str(), call.clone().chain.as_str()).await?;
-        let ath_after_call = ath["athTokenPrice"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
-        let multiplier = ath_after_call / call.clone().price.parse::<f64>().unwrap_or(0.0);
-        call_lb.push(CallWithAth {
-            call: call.clone(),
-            ath_after_call: ath_after_call,
-            multiplier: multiplier,
-        });
-    }
+    // Skip if token has already been processed
+    let user_calls: Vec<Call> = user_calls.into_iter().filter(|call| seen_tokens.insert(call.token_symbol.clone())).collect();
+    let call_ids: Vec<i32> = user_calls.iter().map(|call| call.id).collect();
+    let call_aths = db::get_call_aths(&pool, &call_ids).await?;
+    let mut call_lb: Vec<CallWithAth> = user_calls.into_iter().map(|call| call_with_cached_ath(call, &call_aths)).collect();
 
     // Sort descending multiplier
     call_lb.sort_by(|a, b| b.multiplier.partial_cmp(&a.multiplier).unwrap_or(std::cmp::Ordering::Equal));
+    let staleness = format_ath_staleness(&call_lb);
 
     let mut learderboard_string = String::new();
     let mut count = 1;
@@ -600,7 +845,7 @@ pub async fn user_stats(user_tg_id: &str, bot: &teloxide::Bot, msg: &teloxide::t
     let multipliers_sum = percent_sum / 100.0;
     let multipliers_avg = percent_sum / 100.0 / count as f64;
 
-    bot.send_message(msg.chat.id,user_stats_message(username, calls_count, multipliers_sum, multipliers_avg, learderboard_string, hit_rate)).parse_mode(teloxide::types::ParseMode::Html).await?;
+    bot.send_message(msg.chat.id,user_stats_message(username, calls_count, multipliers_sum, multipliers_avg, learderboard_string, hit_rate, staleness)).parse_mode(teloxide::types::ParseMode::Html).await?;
     Ok(())
 }
 
@@ -622,14 +867,14 @@ pub async fn sell_token_page(msg: &teloxide::types::Message, bot: &teloxide::Bot
     let token_address = msg.text().unwrap().split("sell_token_").nth(1).unwrap_or("");
     let user_tg_id = msg.from.as_ref().unwrap().id.to_string();
     let user = db::get_user(&pool, &user_tg_id).await?;
-    let scanner_response = get_scanner_search(token_address).await?;
-    let token_symbol = scanner_response["pair"]["token1Symbol"].as_str().unwrap_or("");
-    let token_price = scanner_response["pair"]["pairPrice1Usd"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
+    let pair_stats = market_data().pair_stats(token_address).await?;
+    let token_symbol = pair_stats.symbol.as_str();
+    let token_price = pair_stats.price_usd;
     let token_amount = get_token_amount(&user.solana_address.clone().unwrap_or("".to_string()), token_address).await?;
-    let token_name = scanner_response["pair"]["token1Name"].as_str().unwrap_or("");
+    let token_name = pair_stats.name.as_str();
     let keyboard = create_sol_sell_swap_keyboard(&pool, user_tg_id.as_str(), token_address).await?;
     let sol_balance = get_wallet_sol_balance(&user.solana_address.clone().unwrap_or("".to_string())).await?.parse::<f64>().unwrap_or(0.0);
-    let fdv = format_number(scanner_response["pair"]["fdv"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0));
+    let fdv = format_number(pair_stats.fdv);
     
     bot.send_message(
         msg.chat.id, 
@@ -658,8 +903,8 @@ pub async fn sell_token_page(msg: &teloxide::types::Message, bot: &teloxide::Bot
 /// 
 /// # Returns
 /// 
-/// A Response
-pub async fn execute_swap(pool: &SafePool, input_token: &str, output_token: &str, user_tg_id: String, chat_id: &str) -> Result<Response> {
+/// The swap result, with the fill booked on the position
+pub async fn execute_swap(pool: &SafePool, input_token: &str, output_token: &str, user_tg_id: String, chat_id: &str) -> Result<SwapResult> {
     println!("@execute_swap: Starting execution");
     println!("@execute_swap: User Telegram ID: {}", user_tg_id);
     println!("@execute_swap: Input token: {}", input_token);
@@ -687,9 +932,17 @@ pub async fn execute_swap(pool: &SafePool, input_token: &str, output_token: &str
         }
     };
 
+    // The preset picked on the buy menu for this token sets the amount and the ladders of the buy
+    let pending_preset = if input_token == "So11111111111111111111111111111111111111112" {
+        db::get_pending_strategy_preset(pool, &user_tg_id, output_token).await?
+    } else {
+        None
+    };
     let input_token_amount: f64;
     if input_token == "So11111111111111111111111111111111111111112" {
-        input_token_amount = user_settings.buy_amount.parse::<f64>().unwrap_or(0.2);
+        input_token_amount = pending_preset.as_ref()
+            .and_then(|preset| preset.buy_amount.parse::<f64>().ok())
+            .unwrap_or(user_settings.buy_amount.parse::<f64>().unwrap_or(0.2));
     } else {
         match get_token_amount(&user.solana_address.clone().unwrap_or("".to_string()), input_token).await {
             Ok(amount) => {
@@ -747,7 +1000,7 @@ pub async fn execute_swap(pool: &SafePool, input_token: &str, output_token: &str
     let url = "http://solana_app:3030/sol/swap";
     println!("@execute_swap: Sending request to url: {:?}", url);
 
-    // response.data = {transaction: "tx_hash"}
+    // response.data = {transaction: "tx_hash", fill: {...}}
     let response = match client.post(url).json(&request).send().await {
         Ok(res) => {
             println!("@execute_swap: Response received successfully");
@@ -762,32 +1015,22 @@ pub async fn execute_swap(pool: &SafePool, input_token: &str, output_token: &str
         db::set_position_completed(pool, input_token, &user_tg_id).await?;
     }
     println!("@execute_swap: input_token: {:?}", input_token);
-    if response.status().is_success() { 
-        // If the input token is SOL = buy
-        if input_token == "So11111111111111111111111111111111111111112" {
-            println!("@execute_swap: input_token is SOL, creating position");
-            let take_profits = get_user_settings_take_profits(pool, &user_tg_id).await?;
-            println!("@execute_swap: take_profits: {:?}", take_profits);
-
-            let stop_losses = db::get_user_settings_stop_losses(pool, &user_tg_id).await?;
-            println!("@execute_swap: stop_losses: {:?}", stop_losses);
-
-            println!("@execute_swap: getting scanner response");
-            let scanner_response = get_scanner_search(output_token).await?;
-
-            let token_price = scanner_response["pair"]["pairPrice1Usd"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
-            println!("@execute_swap: token_price: {:?}", token_price);
-            let fdv = scanner_response["pair"]["fdv"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
-
-            let (token_amount_in_wallet, token_ui_amount_in_wallet) = get_token_amount_in_wallet(&user.solana_address.clone().unwrap_or("".to_string()), output_token).await?;
-            println!("@execute_swap: token_amount_in_wallet: {:?}", token_amount_in_wallet);
-
-            println!("@execute_swap: inserting position");
-            db::insert_position(pool, &user_tg_id, output_token, take_profits, stop_losses, token_amount_in_wallet, fdv, token_price, chat_id, input_token_amount, &token_ui_amount_in_wallet).await?;
-            println!("@execute_swap: position inserted");
-        } 
+    let mut swap_result = read_swap_response(response).await?;
+    // If the input token is SOL = buy
+    if input_token == "So11111111111111111111111111111111111111112" {
+        println!("@execute_swap: input_token is SOL, adding the buy to the position");
+        if let Err(e) = apply_buy_to_position(pool, &user_tg_id, output_token, chat_id, input_token_amount, &swap_result, "manual", pending_preset.as_ref()).await {
+            eprintln!("@execute_swap: error adding the buy {} to the position: {:?}", swap_result.signature, e);
+        }
+        if pending_preset.is_some() {
+            if let Err(e) = db::set_pending_strategy_preset(pool, &user_tg_id, None, None).await {
+                eprintln!("@execute_swap: error clearing the preset of the buy {}: {:?}", swap_result.signature, e);
+            }
+        }
+    } else {
+        swap_result.realised_pnl_sol = book_sell(pool, &user_tg_id, input_token, &swap_result, "manual").await;
     }
-    Ok(response)
+    Ok(swap_result)
 }
 
 /// Execute a swap without chat interaction
@@ -798,11 +1041,14 @@ pub async fn execute_swap(pool: &SafePool, input_token: &str, output_token: &str
 /// * `input_token` - The input token
 /// * `output_token` - The output token
 /// * `user_tg_id` - The user Telegram ID
+/// * `sell_percentage` - The % of the balance to sell, for sells
+/// * `buy_amount` - The SOL to spend, for buys, defaults to the user buy amount
+/// * `source` - What triggered the swap, recorded on the trade
 /// 
 /// # Returns
 /// 
-/// A Response
-pub async fn execute_swap_no_chat(pool: &SafePool, input_token: &str, output_token: &str, user_tg_id: String, sell_percentage: f64) -> Result<Response> {
+/// The swap result, sells are booked on the position, buys are left to the caller
+pub async fn execute_swap_no_chat(pool: &SafePool, input_token: &str, output_token: &str, user_tg_id: String, sell_percentage: f64, buy_amount: Option<f64>, source: &str) -> Result<SwapResult> {
     println!("@execute_swap_no_chat: Starting execution");
     println!("@execute_swap_no_chat: User Telegram ID: {}", user_tg_id);
     println!("@execute_swap_no_chat: Input token: {}", input_token);
@@ -832,7 +1078,7 @@ pub async fn execute_swap_no_chat(pool: &SafePool, input_token: &str, output_tok
 
     let input_token_amount: f64;
     if input_token == "So11111111111111111111111111111111111111112" {
-        input_token_amount = user_settings.buy_amount.parse::<f64>().unwrap_or(0.2);
+        input_token_amount = buy_amount.unwrap_or(user_settings.buy_amount.parse::<f64>().unwrap_or(0.2));
     } else {
         match get_token_amount(&user.solana_address.clone().unwrap_or("".to_string()), input_token).await {
             Ok(amount) => {
@@ -903,8 +1149,11 @@ pub async fn execute_swap_no_chat(pool: &SafePool, input_token: &str, output_tok
         }
     };
     println!("@execute_swap_no_chat: input_token: {:?}", input_token);
-
-    Ok(response)
+    let mut swap_result = read_swap_response(response).await?;
+    if input_token != "So11111111111111111111111111111111111111112" {
+        swap_result.realised_pnl_sol = book_sell(pool, &user_tg_id, input_token, &swap_result, source).await;
+    }
+    Ok(swap_result)
 }
 
 /// Execute a swap take profit
@@ -916,11 +1165,12 @@ pub async fn execute_swap_no_chat(pool: &SafePool, input_token: &str, output_tok
 /// * `take_profit` - The take profit
 /// * `input_token` - The input token
 /// * `output_token` - The output token
+/// * `source` - The take profit / stop loss kind, recorded on the trade
 /// 
 /// # Returns
 /// 
-/// A Result indicating whether the swap take profit was executed successfully
-pub async fn execute_swap_take_profit(pool: &SafePool, user_tg_id: String, take_profit: (f64, f64), input_token: &str, output_token: &str) -> Result<Response> {
+/// The swap result, with the sell booked on the position
+pub async fn execute_swap_take_profit(pool: &SafePool, user_tg_id: String, take_profit: (f64, f64), input_token: &str, output_token: &str, source: &str) -> Result<SwapResult> {
     println!("@execute_swap_take_profit: Starting execution");
     println!("@execute_swap_take_profit: User Telegram ID: {}", user_tg_id);
     println!("@execute_swap_take_profit: Input token: {}", input_token);
@@ -987,12 +1237,27 @@ pub async fn execute_swap_take_profit(pool: &SafePool, user_tg_id: String, take_
     let url = "http://solana_app:3030/sol/swap";
     let response = client.post(url).json(&request).send().await?;
     println!("@execute_swap_take_profit: Response received: {:?}", response);
-
-    Ok(response)
+    let mut swap_result = read_swap_response(response).await?;
+    swap_result.realised_pnl_sol = book_sell(pool, &user_tg_id, input_token, &swap_result, source).await;
+    Ok(swap_result)
 }
 
 
-pub async fn execute_swap_stop_loss(pool: &SafePool, user_tg_id: String, stop_loss: (f64, f64), input_token: &str, output_token: &str) -> Result<()> {
+/// Execute a swap stop loss
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `user_tg_id` - The user Telegram ID
+/// * `stop_loss` - The stop loss
+/// * `input_token` - The input token
+/// * `output_token` - The output token
+/// * `source` - The take profit / stop loss kind, recorded on the trade
+/// 
+/// # Returns
+/// 
+/// The swap result, with the sell booked on the position
+pub async fn execute_swap_stop_loss(pool: &SafePool, user_tg_id: String, stop_loss: (f64, f64), input_token: &str, output_token: &str, source: &str) -> Result<SwapResult> {
     println!("@execute_swap_stop_loss: Sending request");
     let user = get_user_by_tg_id(pool, &user_tg_id).await?;
 
@@ -1043,10 +1308,315 @@ pub async fn execute_swap_stop_loss(pool: &SafePool, user_tg_id: String, stop_lo
     let url = "http://solana_app:3030/sol/swap";
     let response = client.post(url).json(&request).send().await?;
     println!("@bot/commands/execute_swap_stop_losses/ response: {:?}", response);
+    let mut swap_result = read_swap_response(response).await?;
+    swap_result.realised_pnl_sol = book_sell(pool, &user_tg_id, input_token, &swap_result, source).await;
+    Ok(swap_result)
+}
+
+/// Execute a limit buy order
+/// 
+/// # Description
+/// 
+/// Buys the order SOL amount of the token through the solana app with the user gas, tip and
+/// slippage settings. The fill is left to the caller, to book once the order is marked filled.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `order` - The limit order
+/// 
+/// # Returns
+/// 
+/// The swap result
+pub async fn execute_swap_limit_buy(pool: &SafePool, order: &db::LimitOrder) -> Result<SwapResult> {
+    println!("@execute_swap_limit_buy: order: {:?}", order);
+    let sol_mint = "So11111111111111111111111111111111111111112";
+
+    let user = db::get_user(&pool, &order.tg_user_id).await?;
+    let user_settings = db::get_or_create_user_settings(pool, &order.tg_user_id).await?;
+    let slippage = user_settings.slippage_tolerance.parse::<f64>().unwrap_or(0.5);
+
+    let turnkey_user = TurnkeyUser {
+        api_public_key: user.turnkey_info.api_public_key.clone().expect("API public key not found"),
+        api_private_key: user.turnkey_info.api_private_key.clone().expect("API private key not found"),
+        organization_id: user.turnkey_info.suborg_id.clone().expect("Suborg ID not found"),
+        public_key: user.solana_address.clone().expect("Solana address not found").to_string(),
+    };
+    let request = SwapSolRequest {
+        user: turnkey_user,
+        user_public_key: user.solana_address.clone().expect("Solana address not found").to_string(),
+        priorization_fee_lamports: user_settings.gas_lamports as u64,
+        jito_tip_amount: user_settings.jito_tip_amount as u64,
+        output_mint: order.token_address.clone(),
+        input_mint: sol_mint.to_string(),
+        amount: sol_to_lamports_u64(order.sol_amount),
+        slippage: slippage * 100.0,
+    };
+
+    let client = reqwest::Client::new();
+    let url = "http://solana_app:3030/sol/swap";
+    let response = client.post(url).json(&request).send().await?;
+    println!("@execute_swap_limit_buy: response: {:?}", response);
+
+    read_swap_response(response).await
+}
+
+/// Read the solana app response of a swap
+/// 
+/// # Arguments
+/// 
+/// * `response` - The solana app response
+/// 
+/// # Returns
+/// 
+/// The swap result, an error if the swap failed or the signature is missing
+async fn read_swap_response(response: Response) -> Result<SwapResult> {
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!("solana app returned {}: {}", response.status(), response.text().await.unwrap_or_default()));
+    }
+    let json_response = response.json::<Value>().await?;
+    let signature = match json_response["transaction"].as_str() {
+        Some(signature) => signature.to_string(),
+        None => return Err(anyhow::anyhow!("Transaction signature not found in solana app response")),
+    };
+    let fill = serde_json::from_value::<SwapFill>(json_response["fill"].clone()).ok();
+    if fill.is_none() {
+        println!("@read_swap_response: no fill for transaction {}", signature);
+    }
+    Ok(SwapResult { signature, fill, realised_pnl_sol: None })
+}
 
+/// Get the fill of a swap the solana app didn't send, eg a copy trade signed by the mini app
+/// 
+/// # Arguments
+/// 
+/// * `signature` - The swap transaction signature
+/// * `wallet` - The wallet that signed the swap
+/// * `input_mint` - The input mint of the swap
+/// * `output_mint` - The output mint of the swap
+/// 
+/// # Returns
+/// 
+/// The fill, None if the solana app could not read the transaction
+async fn get_swap_fill(signature: &str, wallet: &str, input_mint: &str, output_mint: &str) -> Option<SwapFill> {
+    let client = reqwest::Client::new();
+    let url = format!("http://solana_app:3030/sol/fill/{signature}");
+    let response = client.get(url)
+        .query(&[("wallet", wallet), ("input_mint", input_mint), ("output_mint", output_mint)])
+        .send()
+        .await;
+    match response {
+        Ok(response) if response.status().is_success() => response.json::<SwapFill>().await.ok(),
+        Ok(response) => {
+            eprintln!("@get_swap_fill: no fill for transaction {}: {}", signature, response.text().await.unwrap_or_default());
+            None
+        }
+        Err(e) => {
+            eprintln!("@get_swap_fill: error fetching the fill of transaction {}: {:?}", signature, e);
+            None
+        }
+    }
+}
+
+/// Book a copy trade the mini app signed and sent
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `user_tg_id` - The user Telegram ID
+/// * `token_address` - The token bought or sold
+/// * `side` - "buy" or "sell"
+/// * `signature` - The swap transaction signature
+/// * `sol_amount` - The SOL swapped by a buy, used when there is no fill
+/// 
+/// # Returns
+/// 
+/// An Ok result if the trade was booked
+pub async fn book_copy_trade(pool: &SafePool, user_tg_id: &str, token_address: &str, side: &str, signature: &str, sol_amount: f64) -> Result<()> {
+    let sol_mint = "So11111111111111111111111111111111111111112";
+    let (input_mint, output_mint) = match side {
+        "buy" => (sol_mint, token_address),
+        "sell" => (token_address, sol_mint),
+        _ => return Err(anyhow::anyhow!("Unknown side {}", side)),
+    };
+    let user = db::get_user(pool, user_tg_id).await?;
+    let wallet = user.solana_address.unwrap_or_default();
+    let fill = get_swap_fill(signature, &wallet, input_mint, output_mint).await;
+    let swap_result = SwapResult { signature: signature.to_string(), fill, realised_pnl_sol: None };
+    if side == "buy" {
+        apply_buy_to_position(pool, user_tg_id, token_address, user_tg_id, sol_amount, &swap_result, "copy", None).await
+    } else {
+        apply_sell_to_position(pool, user_tg_id, token_address, &swap_result, "copy").await.map(|_| ())
+    }
+}
+
+/// Add a buy to the user position on the token and record the trade
+/// 
+/// # Description
+/// 
+/// With a fill the tokens received and the SOL spent, fees and tips included, are added to the
+/// open position, averaging the entry price, or open a new one with the user take profits, stop losses,
+/// trailing stops and market cap targets. Without a fill a new position is opened from the market
+/// price and the wallet balance, and an open position is left as is.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `user_tg_id` - The user Telegram ID
+/// * `token_address` - The token bought
+/// * `chat_id` - The chat to notify about the position
+/// * `sol_amount` - The SOL swapped, used when there is no fill
+/// * `swap_result` - The result of the buy
+/// * `source` - What triggered the buy, manual, limit, dca or copy
+/// * `preset` - The strategy preset picked for the buy, its ladders replace the user ones on the position
+/// 
+/// # Returns
+/// 
+/// An Ok result if the buy was added
+pub async fn apply_buy_to_position(pool: &SafePool, user_tg_id: &str, token_address: &str, chat_id: &str, sol_amount: f64, swap_result: &SwapResult, source: &str, preset: Option<&db::StrategyPreset>) -> Result<()> {
+    let active_positions = db::get_active_positions(pool, user_tg_id).await?;
+    let open_position = active_positions.iter().find(|position| position.token_address == token_address);
+
+    let pair_stats = match market_data().pair_stats(token_address).await {
+        Ok(pair_stats) => pair_stats,
+        Err(e) => {
+            eprintln!("@apply_buy_to_position: error fetching the market data of {}: {:?}", token_address, e);
+            PairStats::default()
+        }
+    };
+    let token_price = pair_stats.price_usd;
+    let fdv = pair_stats.fdv;
+    // Market cap targets are evaluated with the circulating supply, left unknown until a provider has it
+    let circulating_supply = market_data().circulating_supply(token_address).await.ok();
+
+    let (amount, ui_amount, sol_cost, fees_sol, entry_price) = match swap_result.fill.as_ref() {
+        Some(fill) if fill.token_delta > 0.0 && fill.token_ui_delta > 0.0 => {
+            // The tip is paid by another transaction of the bundle, it isn't in the lamports delta
+            let sol_cost = ((-fill.sol_delta_lamports).max(0) as u64 + fill.tip_lamports) as f64 / 1_000_000_000.0;
+            let fees_sol = (fill.fee_lamports + fill.tip_lamports) as f64 / 1_000_000_000.0;
+            // Without the SOL price the market price of the token stands in
+            let cost_usd = sol_to_usd(sol_cost).await.unwrap_or(0.0);
+            let entry_price = if cost_usd > 0.0 { cost_usd / fill.token_ui_delta } else { token_price };
+            (fill.token_delta, fill.token_ui_delta, sol_cost, fees_sol, entry_price)
+        }
+        _ => {
+            if let Some(position) = open_position {
+                println!("@apply_buy_to_position: no fill, position {} on {} left as is", position.id, token_address);
+                db::insert_trade(pool, Some(position.id), user_tg_id, token_address, "buy", source, &swap_result.signature, None, None, Some(sol_amount), Some(token_price).filter(|token_price| *token_price > 0.0), None, None).await?;
+                return Ok(());
+            }
+            let user = db::get_user(&pool, user_tg_id).await?;
+            let (token_amount_in_wallet, token_ui_amount_in_wallet) = get_token_amount_in_wallet(&user.solana_address.clone().unwrap_or("".to_string()), token_address).await?;
+            (token_amount_in_wallet, token_ui_amount_in_wallet.parse::<f64>().unwrap_or(0.0), sol_amount, 0.0, token_price)
+        }
+    };
+    println!("@apply_buy_to_position: amount: {:?}, sol_cost: {:?}, fees_sol: {:?}, entry_price: {:?}", amount, sol_cost, fees_sol, entry_price);
+    if !(entry_price > 0.0 && entry_price.is_finite()) {
+        // A 0 entry price would fire every target, the buy is only journaled and the position
+        // is left to the wallet reconciliation
+        db::insert_trade(pool, open_position.map(|position| position.id), user_tg_id, token_address, "buy", source, &swap_result.signature, Some(amount), Some(ui_amount), Some(sol_cost), None, Some(fees_sol), None).await?;
+        return Err(anyhow::anyhow!("No price to book the buy {} at", swap_result.signature));
+    }
+
+    let position_id = match open_position {
+        Some(position) => {
+            println!("@apply_buy_to_position: adding the buy to position {}", position.id);
+            db::add_buy_fill_to_position(pool, position.id, amount, ui_amount, sol_cost, fees_sol, entry_price * ui_amount).await?;
+            if let Some(preset) = preset {
+                db::set_position_ladders(pool, position.id, &preset.take_profits, &preset.stop_losses, &preset.trailing_stops, &preset.mc_targets).await?;
+            }
+            position.id
+        }
+        None => {
+            let (take_profits, stop_losses, trailing_stops, mc_targets) = match preset {
+                Some(preset) => (Some(preset.take_profits.clone()), Some(preset.stop_losses.clone()), Some(preset.trailing_stops.clone()), preset.mc_targets.clone()),
+                None => (
+                    get_user_settings_take_profits(pool, user_tg_id).await?,
+                    db::get_user_settings_stop_losses(pool, user_tg_id).await?,
+                    db::get_user_settings_trailing_stops(pool, user_tg_id).await?,
+                    db::get_user_settings_mc_targets(pool, user_tg_id).await?,
+                ),
+            };
+            let mc_entry = circulating_supply.map(|circulating_supply| entry_price * circulating_supply).unwrap_or(fdv);
+
+            println!("@apply_buy_to_position: inserting position");
+            db::insert_position(pool, user_tg_id, token_address, take_profits, stop_losses, trailing_stops, mc_targets, circulating_supply, amount, mc_entry, entry_price, chat_id, sol_cost, &ui_amount.to_string(), fees_sol).await?
+        }
+    };
+    db::insert_trade(pool, Some(position_id), user_tg_id, token_address, "buy", source, &swap_result.signature, Some(amount), Some(ui_amount), Some(sol_cost), Some(entry_price), Some(fees_sol), None).await?;
     Ok(())
 }
 
+/// Book a sell once its swap landed
+/// 
+/// # Description
+/// 
+/// A booking error is only logged, the swap is done and its signature must reach the user.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `user_tg_id` - The user Telegram ID
+/// * `token_address` - The token sold
+/// * `swap_result` - The result of the sell
+/// * `source` - What triggered the sell
+/// 
+/// # Returns
+/// 
+/// The PnL in SOL realised by the sell, None if it couldn't be booked
+async fn book_sell(pool: &SafePool, user_tg_id: &str, token_address: &str, swap_result: &SwapResult, source: &str) -> Option<f64> {
+    match apply_sell_to_position(pool, user_tg_id, token_address, swap_result, source).await {
+        Ok(realised_pnl_sol) => realised_pnl_sol,
+        Err(e) => {
+            eprintln!("@book_sell: error booking the sell {} on the position: {:?}", swap_result.signature, e);
+            None
+        }
+    }
+}
+
+/// Book a sell on the user position on the token and record the trade
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `user_tg_id` - The user Telegram ID
+/// * `token_address` - The token sold
+/// * `swap_result` - The result of the sell
+/// * `source` - What triggered the sell, manual, twap, copy or the take profit / stop loss kind
+/// 
+/// # Returns
+/// 
+/// The PnL in SOL realised by the sell, None without a fill or a position
+pub async fn apply_sell_to_position(pool: &SafePool, user_tg_id: &str, token_address: &str, swap_result: &SwapResult, source: &str) -> Result<Option<f64>> {
+    // Look in all the positions, a 100% sell marks the position completed before the fill is read
+    let positions = db::get_positions_by_user_tg_id(pool, user_tg_id).await?;
+    let position = positions.iter().filter(|position| position.token_address == token_address).max_by_key(|position| position.created_at);
+
+    let fill = match swap_result.fill.as_ref() {
+        Some(fill) if fill.token_delta < 0.0 => fill,
+        _ => {
+            db::insert_trade(pool, position.map(|position| position.id), user_tg_id, token_address, "sell", source, &swap_result.signature, None, None, None, None, None, None).await?;
+            return Ok(None);
+        }
+    };
+    let sol_received = (fill.sol_delta_lamports - fill.tip_lamports as i64) as f64 / 1_000_000_000.0;
+    let fees_sol = (fill.fee_lamports + fill.tip_lamports) as f64 / 1_000_000_000.0;
+    // The price is only informative, the sell is booked without it when SOL has no price
+    let price_usd = sol_to_usd(sol_received + fees_sol).await.ok()
+        .filter(|value_usd| *value_usd > 0.0)
+        .map(|value_usd| value_usd / -fill.token_ui_delta);
+    let realised_pnl_sol = match position {
+        Some(position) => {
+            let realised_pnl_sol = db::add_sell_fill_to_position(pool, position.id, -fill.token_delta, -fill.token_ui_delta, sol_received, fees_sol).await?;
+            println!("@apply_sell_to_position: position {} realised {:?} SOL", position.id, realised_pnl_sol);
+            Some(realised_pnl_sol)
+        }
+        None => None,
+    };
+    db::insert_trade(pool, position.map(|position| position.id), user_tg_id, token_address, "sell", source, &swap_result.signature, Some(-fill.token_delta), Some(-fill.token_ui_delta), Some(sol_received), price_usd, Some(fees_sol), realised_pnl_sol).await?;
+    Ok(realised_pnl_sol)
+}
+
 /// Add a refferal if the user is new
 /// 
 /// # Arguments
@@ -1073,4 +1643,40 @@ pub async fn add_referral_if_user_is_new(pool: &SafePool, uuid: &str, user_tg_id
         println!("@add_referral_if_user_is_new/ referral users referred updated");
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Check if the user is an admin of a group
+/// 
+/// # Arguments
+/// 
+/// * `bot` - The bot
+/// * `chat_id` - The group chat ID
+/// * `user_id` - The user ID
+/// 
+/// # Returns
+/// 
+/// A boolean indicating if the user is the owner or an administrator of the group
+pub async fn is_group_admin(bot: &teloxide::Bot, chat_id: teloxide::types::ChatId, user_id: teloxide::types::UserId) -> Result<bool> {
+    let member = bot.get_chat_member(chat_id, user_id).await?;
+    Ok(member.is_privileged())
+}
+
+/// Check if the user is an admin of the bot
+/// 
+/// # Description
+/// 
+/// The bot admins are listed in the BOT_ADMINS env var, a comma separated list of Telegram user IDs.
+/// Nobody is an admin when it isn't set.
+/// 
+/// # Arguments
+/// 
+/// * `user_id` - The user ID
+/// 
+/// # Returns
+/// 
+/// A boolean indicating if the user is a bot admin
+pub fn is_bot_admin(user_id: teloxide::types::UserId) -> bool {
+    std::env::var("BOT_ADMINS").unwrap_or_default()
+        .split(",")
+        .any(|admin| admin.trim() == user_id.to_string())
+}
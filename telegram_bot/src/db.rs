@@ -4,7 +4,7 @@ use serde_json::json;
 use sqlx::{Encode, Pool};
 use sqlx::Postgres;
 use crate::handlers::PostUserRequest;
-use crate::utils::helpers::check_period_for_leaderboard;
+use crate::utils::ladder::MarketCapTarget;
 use serde::Serialize;
 use sqlx::Row;
 use anyhow::Result;
@@ -23,6 +23,8 @@ pub struct UserSettings {
     pub anti_mev: bool,
     pub take_profits: Vec<(f64, f64)>,
     pub stop_losses: Vec<(f64, f64)>,
+    pub trailing_stops: Vec<(f64, f64)>,
+    pub mc_targets: Vec<MarketCapTarget>,
     pub jito_tip_amount: i32,
     pub active_complete_positions: String,
     pub withdraw_sol_amount: String,
@@ -37,11 +39,111 @@ pub struct UserSettings {
 /// * `call` - The call
 /// * `ath_after_call` - The ATH after the call
 /// * `multiplier` - The multiplier
+/// * `ath_updated_at` - When the cached ATH was last updated, None while it isn't computed yet
 #[derive(Debug, Clone, Serialize)]
 pub struct CallWithAth {
     pub call: Call,
     pub ath_after_call: f64,
     pub multiplier: f64,
+    pub ath_updated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Struct to hold a period of calls
+/// 
+/// # Fields
+/// 
+/// * `start` - The first instant of the period, None for no lower bound
+/// * `end` - The end of the period, excluded, None for no upper bound
+/// * `label` - How the period is shown, eg "7d" or "All time"
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CallPeriod {
+    pub start: Option<chrono::DateTime<chrono::Utc>>,
+    pub end: Option<chrono::DateTime<chrono::Utc>>,
+    pub label: String,
+}
+
+/// Struct to hold the settings of a group
+/// 
+/// # Fields
+/// 
+/// * `chat_id` - The group chat ID
+/// * `title` - The group title when the settings were last changed
+/// * `global_leaderboard` - Whether the group calls count in the global leaderboard and caller profiles
+/// * `auto_calls` - Whether the addresses posted in the group are tracked as calls
+/// * `require_call_prefix` - Whether only '/call <address>' messages are tracked
+/// * `allowed_chains` - The chains tracked, empty for every chain
+/// * `min_liquidity` - The minimum liquidity in USD for a call to count
+/// * `min_mkt_cap` - The minimum market cap in USD for a call to count
+/// * `one_call_per_user_token` - Whether a user can call a token only once
+/// * `lb_default_period` - The period of /lb without a period
+/// * `digest_frequency` - How often the leaderboard digest is posted, "off", "daily" or "weekly"
+/// * `digest_hour` - The local hour the digest is posted at
+/// * `digest_timezone` - The IANA timezone of the digest hour, eg "Europe/Paris"
+/// * `digest_pin` - Whether the digest is pinned
+/// * `digest_last_sent_at` - When the last digest was posted
+/// * `milestones` - The multipliers announced when a call reaches them, empty for none
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupSettings {
+    pub chat_id: String,
+    pub title: Option<String>,
+    pub global_leaderboard: bool,
+    pub auto_calls: bool,
+    pub require_call_prefix: bool,
+    pub allowed_chains: Vec<String>,
+    pub min_liquidity: f64,
+    pub min_mkt_cap: f64,
+    pub one_call_per_user_token: bool,
+    pub lb_default_period: String,
+    pub digest_frequency: String,
+    pub digest_hour: i32,
+    pub digest_timezone: String,
+    pub digest_pin: bool,
+    pub digest_last_sent_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub milestones: Vec<f64>,
+}
+
+impl GroupSettings {
+    /// The settings of a group that never changed them
+    pub fn default_for(chat_id: &str) -> GroupSettings {
+        GroupSettings {
+            chat_id: chat_id.to_string(),
+            title: None,
+            global_leaderboard: false,
+            auto_calls: true,
+            require_call_prefix: false,
+            allowed_chains: Vec::new(),
+            min_liquidity: 0.0,
+            min_mkt_cap: 0.0,
+            one_call_per_user_token: false,
+            lb_default_period: "1d".to_string(),
+            digest_frequency: "off".to_string(),
+            digest_hour: 9,
+            digest_timezone: "UTC".to_string(),
+            digest_pin: false,
+            digest_last_sent_at: None,
+            milestones: vec![2.0, 5.0, 10.0],
+        }
+    }
+}
+
+/// Struct to hold the number of calls of a user in a group
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupActivity {
+    pub chat_id: String,
+    pub title: Option<String>,
+    pub calls: i64,
+}
+
+/// Struct to hold the cached ATH of a call and the token price at its last update
+#[derive(Debug, Clone, Serialize)]
+pub struct CallAth {
+    pub call_id: i32,
+    pub ath_price: f64, // Highest token price since the call
+    pub last_price: Option<f64>,
+    pub last_mkt_cap: Option<f64>,
+    pub pair_address: Option<String>,
+    pub ath_checked_until: Option<chrono::DateTime<chrono::Utc>>, // The ATH API was read up to this date
+    pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Encode)]
@@ -96,6 +198,14 @@ pub struct Position {
     pub token_address: String, // Token address
     pub take_profits: Vec<(f64, f64)>, // Array of arrays with the take profit [ [ +% price limit to sell, % tokens to sell], ... ]
     pub stop_losses: Vec<(f64, f64)>, // Array of arrays with the stop [ [ -% price limit to sell, % tokens to sell], ... ]
+    pub trailing_stops: Vec<(f64, f64)>, // Array of arrays with the trailing stop [ [ -% from the highest price to sell, % tokens to sell], ... ]
+    pub highest_price: Option<f64>, // Highest price seen since entry, None until the watcher sees a price
+    pub mc_targets: Vec<MarketCapTarget>, // Take profits and stop losses at absolute market caps
+    pub circulating_supply: Option<f64>, // Circulating supply at entry, to turn prices into market caps, None when unknown
+    pub cost_basis_sol: f64, // SOL cost of the tokens still held, fees and tips included
+    pub fees_sol: f64, // Network fees and tips paid on the position buys and sells
+    pub realised_pnl_sol: f64, // SOL realised by the sells minus the cost basis of the tokens sold
+    pub onchain_mismatch_amount: Option<f64>, // Wallet balance last flagged as drifting from the amount, None when in sync
     pub amount: f64, // Amount of tokens bought
     pub mc_entry: f64, // Market cap at entry
     pub entry_price: f64, // Price at entry
@@ -107,6 +217,74 @@ pub struct Position {
 }
 
 
+/// Struct to hold a trade, a buy or sell fill of a position
+#[derive(Debug, Clone, Serialize)]
+pub struct Trade {
+    pub id: i32,
+    pub position_id: Option<i32>,
+    pub tg_user_id: String,
+    pub token_address: String,
+    pub side: String, // buy or sell
+    pub source: String, // manual, limit, dca, twap, copy or the take profit / stop loss kind that triggered it
+    pub signature: String,
+    pub token_amount: Option<f64>, // Raw token amount, None when the fill couldn't be read
+    pub token_ui_amount: Option<f64>,
+    pub sol_amount: Option<f64>, // SOL spent (buy), fees and tips included, or received (sell), net of fees and tips
+    pub price_usd: Option<f64>,
+    pub fees_sol: Option<f64>,
+    pub realised_pnl_sol: Option<f64>, // Sells only
+    pub created_at: NaiveDateTime,
+}
+
+/// Struct to hold a limit buy order
+#[derive(Debug, Clone, Serialize)]
+pub struct LimitOrder {
+    pub id: i32,
+    pub tg_user_id: String,
+    pub chat_id: String,
+    pub token_address: String,
+    pub sol_amount: f64, // SOL to spend
+    pub trigger_price: f64, // Buy when the price is at or below this price
+    pub trigger_mc: Option<f64>, // Market cap the user asked for, the trigger price is derived from it
+    pub status: String, // open, executing, filled, failed, cancelled or expired
+    pub signature: Option<String>,
+    pub error: Option<String>,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+/// Struct to hold a recurring order, a DCA buy or a TWAP sell executed in slices
+#[derive(Debug, Clone, Serialize)]
+pub struct RecurringOrder {
+    pub id: i32,
+    pub tg_user_id: String,
+    pub chat_id: String,
+    pub token_address: String,
+    pub kind: String, // dca (buy) or twap (sell)
+    pub sol_per_slice: Option<f64>, // SOL spent by each DCA slice, TWAP slices sell an equal share of the balance left
+    pub total_slices: i32,
+    pub executed_slices: i32,
+    pub interval_secs: i32,
+    pub next_run_at: NaiveDateTime,
+    pub status: String, // active, paused, completed or cancelled
+    pub last_signature: Option<String>,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// Struct to hold a strategy preset, named ladders and buy amount used by the next buy of a token when picked
+#[derive(Debug, Clone, Serialize)]
+pub struct StrategyPreset {
+    pub id: i32,
+    pub tg_user_id: String,
+    pub name: String,
+    pub take_profits: Vec<(f64, f64)>,
+    pub stop_losses: Vec<(f64, f64)>,
+    pub trailing_stops: Vec<(f64, f64)>,
+    pub mc_targets: Vec<MarketCapTarget>,
+    pub buy_amount: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct Refferal {
     pub id: i32,
@@ -332,223 +510,6 @@ pub async fn get_all_calls_chat_id(pool: &PgPool, chat_id: &str) -> Result<Vec<C
     Ok(calls_vec)
 }
 
-/// Retrieves all calls made in a channel in the last `x` days.
-pub async fn get_channel_calls_last_x_days(pool: &PgPool, chat_id: &str, days: i32) -> Result<Vec<Call>> {
-    let q = "SELECT id, time, mkt_cap, token_address, token_mint, token_symbol, price, user_tg_id, chat_id, message_id, chain
-        FROM calls
-        WHERE time::timestamp >= NOW() - INTERVAL '$1 days' AND chat_id = $2
-        ORDER BY time ASC";
-
-    let calls = sqlx::query(q)
-    .bind(days)
-    .bind(chat_id)
-    .fetch_all(pool)
-    .await?;
-    let mut calls_vec: Vec<Call> = Vec::new();
-    for call in calls {
-        calls_vec.push(Call {
-            id: call.get("id"),
-            time: call.get("time"),
-            mkt_cap: call.get("mkt_cap"),
-            token_address: call.get("token_address"),
-            token_mint: call.get("token_mint"),
-            token_symbol: call.get("token_symbol"),
-            price: call.get("price"),
-            user_tg_id: call.get("user_tg_id"),
-            chat_id: call.get("chat_id"),
-            message_id: call.get("message_id"),
-            chain: call.get("chain"),
-        });
-    }           
-    Ok(calls_vec)
-}
-
-/// Retrieves all calls made in a channel in the last `x` hours.
-pub async fn get_channel_calls_last_x_hours(pool: &PgPool, chat_id: &str, hours: i32) -> Result<Vec<Call>> {
-    let q = "SELECT id, time, mkt_cap, token_address, token_mint, token_symbol, price, user_tg_id, chat_id, message_id, chain
-        FROM calls
-        WHERE time >= NOW() - INTERVAL '$1 hours' AND chat_id = $2
-        ORDER BY time ASC";
-
-    let calls = sqlx::query(q)
-    .bind(hours)
-    .bind(chat_id)
-    .fetch_all(pool)
-    .await?;
-    let mut calls_vec: Vec<Call> = Vec::new();
-    for call in calls {
-        calls_vec.push(Call {
-            id: call.get("id"),
-            time: call.get("time"),
-            mkt_cap: call.get("mkt_cap"),
-            token_address: call.get("token_address"),
-            token_mint: call.get("token_mint"),             
-            token_symbol: call.get("token_symbol"),
-            price: call.get("price"),
-            user_tg_id: call.get("user_tg_id"),
-            chat_id: call.get("chat_id"),
-            message_id: call.get("message_id"),
-            chain: call.get("chain"),
-        });
-    }   
-    Ok(calls_vec)
-}
-
-/// Retrieves all calls made in a channel in the last `x` months.
-pub async fn get_channel_calls_last_x_months(pool: &PgPool, chat_id: &str, months: i32) -> Result<Vec<Call>> {
-   let q = " SELECT id, time, mkt_cap, token_address, token_mint, token_symbol, price, user_tg_id, chat_id, message_id, chain
-        FROM calls
-        WHERE time >= NOW() - INTERVAL '$1 months' AND chat_id = $2
-        ORDER BY time ASC";
-
-    let channels = sqlx::query(q)
-    .bind(months)
-    .bind(chat_id)
-    .fetch_all(pool)
-    .await?;
-    let mut calls_vec: Vec<Call> = Vec::new();
-    for call in channels {
-        calls_vec.push(Call {
-            id: call.get("id"),
-            time: call.get("time"),
-            mkt_cap: call.get("mkt_cap"),
-            token_address: call.get("token_address"),
-            token_mint: call.get("token_mint"),
-            token_symbol: call.get("token_symbol"),
-            price: call.get("price"),
-            user_tg_id: call.get("user_tg_id"),
-            chat_id: call.get("chat_id"),
-            message_id: call.get("message_id"),
-            chain: call.get("chain"),
-        });
-    }   
-    Ok(calls_vec)
-}
-
-/// Retrieves all calls made by a specific user in the last `x` years.
-/// 
-/// # Arguments
-/// 
-/// * `pool` - The PostgreSQL connection pool.
-/// * `tg_id` - The user's Telegram ID.
-/// * `days` - The number of days.
-///
-/// # Returns
-///
-/// A vector of calls.
-pub async fn get_user_calls_last_x_years(pool: &PgPool, tg_id: &str, years: i32) -> Result<Vec<Call>> {
-    let q = "SELECT id, time, mkt_cap, token_address, token_mint, token_symbol, price, user_tg_id, chat_id, message_id, chain
-        FROM calls
-        WHERE user_tg_id = $1 AND time >= NOW() - INTERVAL '$2 years'
-        ORDER BY time ASC";
-
-    let calls = sqlx::query(q)
-    .bind(tg_id)
-    .bind(years)
-    .fetch_all(pool)
-    .await?;
-    let mut calls_vec: Vec<Call> = Vec::new();
-    for call in calls {
-        calls_vec.push(Call {
-            id: call.get("id"),
-            time: call.get("time"),
-            mkt_cap: call.get("mkt_cap"),
-            token_address: call.get("token_address"),
-            token_mint: call.get("token_mint"),
-            token_symbol: call.get("token_symbol"),
-            price: call.get("price"),
-            user_tg_id: call.get("user_tg_id"),
-            chat_id: call.get("chat_id"),
-            message_id: call.get("message_id"),
-            chain: call.get("chain"),
-        });
-    }   
-    Ok(calls_vec)
-}
-
-/// Retrieves all calls made by a specific user in the last `x` hours.
-/// 
-/// # Arguments
-/// 
-/// * `pool` - The PostgreSQL connection pool.
-/// * `tg_id` - The user's Telegram ID.
-/// * `hours` - The number of hours.
-///
-/// # Returns
-///
-/// A vector of calls.
-pub async fn get_user_calls_last_x_hours(pool: &PgPool, tg_id: &str, hours: i32) -> Result<Vec<Call>> {
-   let q = " SELECT id, time, mkt_cap, token_address, token_mint, token_symbol, price, user_tg_id, chat_id, message_id, chain
-        FROM calls
-        WHERE user_tg_id = $1 AND time >= NOW() - INTERVAL '$2 hours'
-        ORDER BY time ASC";
-
-    let calls = sqlx::query(q)
-    .bind(tg_id)
-    .bind(hours)
-    .fetch_all(pool)
-    .await?;
-
-    let mut calls_vec: Vec<Call> = Vec::new();
-    for call in calls {
-        calls_vec.push(Call {
-            id: call.get("id"),
-            time: call.get("time"),
-            mkt_cap: call.get("mkt_cap"),
-            token_address: call.get("token_address"),
-            token_mint: call.get("token_mint"),     
-            token_symbol: call.get("token_symbol"),
-            price: call.get("price"),
-            user_tg_id: call.get("user_tg_id"),
-            chat_id: call.get("chat_id"),
-            message_id: call.get("message_id"),
-            chain: call.get("chain"),
-        });
-    }   
-    Ok(calls_vec)
-}
-
-/// Retrieves all calls made by a specific user in the last `x` months.
-/// 
-/// # Arguments
-/// 
-/// * `pool` - The PostgreSQL connection pool.
-/// * `tg_id` - The user's Telegram ID.
-/// * `months` - The number of months.
-///
-/// # Returns
-///
-/// A vector of calls.
-pub async fn get_user_calls_last_x_months(pool: &PgPool, tg_id: &str, months: i32) -> Result<Vec<Call>> {
-    let q = " SELECT id, time, mkt_cap, token_address, token_mint, token_symbol, price, user_tg_id, chat_id, message_id, chain
-        FROM calls
-        WHERE user_tg_id = $1 AND time >= NOW() - INTERVAL '$2 months'
-        ORDER BY time ASC";
-
-    let calls = sqlx::query(q)
-    .bind(tg_id)
-    .bind(months)
-    .fetch_all(pool)
-    .await?;
-    let mut calls_vec: Vec<Call> = Vec::new();
-    for call in calls {
-        calls_vec.push(Call {
-            id: call.get("id"),
-            time: call.get("time"),
-            mkt_cap: call.get("mkt_cap"),
-            token_address: call.get("token_address"),
-            token_mint: call.get("token_mint"),
-            token_symbol: call.get("token_symbol"),
-            price: call.get("price"),
-            user_tg_id: call.get("user_tg_id"),
-            chat_id: call.get("chat_id"),
-            message_id: call.get("message_id"),
-            chain: call.get("chain"),
-        });
-    }   
-    Ok(calls_vec)
-}
-
 /// Retrieves the first call for each token addressed by a user.
 pub async fn get_all_user_firsts_calls_by_user_tg_id(pool: &PgPool, user_id: &str) -> Result<Vec<Call>> {
     let q = "SELECT DISTINCT ON (token_address)
@@ -678,6 +639,36 @@ pub async fn get_total_calls_in_chat(pool: &PgPool, chat_id: &str, period: &str)
     Ok(count)
 }
 
+/// Retrieves the most called solana tokens of the last hours, most called first.
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool.
+/// * `period` - The period string (e.g., "24 hours").
+/// * `limit` - The maximum number of tokens to return.
+///
+/// # Returns
+///
+/// The token addresses.
+pub async fn get_most_called_solana_tokens(pool: &PgPool, period: &str, limit: i64) -> Result<Vec<String>> {
+    let q = "
+        SELECT token_address
+        FROM calls
+        WHERE LOWER(chain) = 'solana'
+          AND time >= NOW() - $1::interval
+        GROUP BY token_address
+        ORDER BY COUNT(*) DESC
+        LIMIT $2
+    ";
+    let tokens: Vec<String> = sqlx::query_scalar(q)
+        .bind(period)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(tokens)
+}
+
 /// Retrieves the number of calls a user has made in the last 24 hours.
 pub async fn get_qtd_calls_user_made_in_24hrs(pool: &PgPool, user_tg_id: &str) -> Result<i64> {
     let q = "
@@ -776,101 +767,7 @@ pub async fn get_first_call_token_chat(
     })
 }
 
-/// Get the user call count for a user
-/// 
-/// # Arguments
-/// 
-/// * `pool` - The PostgreSQL connection pool
-/// * `user_tg_id` - The user's Telegram ID
-/// * `chat_id` - The chat ID
-/// * `period` - The period to get the call count
-/// 
-/// # Returns
-/// 
-/// The number of calls made by the user in the last period
-pub async fn get_user_call_count_for_user_chat_with_period(
-    pool: &PgPool,
-    user_tg_id: &str,
-    chat_id: &str,
-    period: &str,
-) -> Result<i64> {
-    let (number, unit) = match check_period_for_leaderboard(period) {
-        Some(p) => p,
-        None => return Ok(0), // Invalid period
-    };
-
-    let interval = match unit {
-        "h" => format!("{} hours", number),
-        "d" => format!("{} days", number),
-        "w" => format!("{} weeks", number),
-        "y" => format!("{} years", number),
-        _ => return Ok(0), // Invalid unit
-    };
-
-    let count: i64 = sqlx::query_scalar(
-        "
-        SELECT COUNT(DISTINCT token_symbol) as count
-        FROM calls
-        WHERE user_tg_id = $1
-          AND chat_id = $2
-          AND time::timestamp >= NOW() - $3::interval
-        "
-    )
-    .bind(user_tg_id)
-    .bind(chat_id)
-    .bind(interval)
-    .fetch_one(pool)
-    .await?;
-
-    Ok(count)
-}
-
-/// Get the number of calls in a chat in the last period
-/// 
-/// # Arguments
-/// 
-/// * `pool` - The PostgreSQL connection pool
-/// * `chat_id` - The chat ID
-/// * `period` - The period to get the call count
-/// 
-/// # Returns
-/// 
-/// The number of calls made in the last period
-pub async fn get_chat_call_count_with_period(
-    pool: &PgPool,
-    chat_id: &str,
-    period: &str,
-) -> Result<i64> {
-    let (number, unit) = match check_period_for_leaderboard(period) {
-        Some(p) => p,
-        None => return Ok(0), // Invalid period
-    };
-
-    let interval = match unit {
-        "h" => format!("{} hours", number),
-        "d" => format!("{} days", number),
-        "w" => format!("{} weeks", number),
-        "y" => format!("{} years", number),
-        _ => return Ok(0), // Invalid unit
-    };
-
-    let count: i64 = sqlx::query_scalar(
-        "
-        SELECT COUNT(*) as count
-        FROM calls
-        WHERE chat_id = $1
-          AND time >= NOW() - INTERVAL $2
-        ",
-    )
-    .bind(chat_id)
-    .bind(interval)
-    .fetch_one(pool)
-    .await?;
-
-    Ok(count)
-}
-
-/// Gets all calls made by a user
+/// Gets all calls made by a user
 /// 
 /// # Arguments
 /// 
@@ -988,6 +885,36 @@ pub async fn get_user_by_tg_id(pool: &PgPool, tg_id: &str) -> Result<User> {
     })
 }
 
+/// Gets the users with a Solana wallet
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// 
+/// # Returns
+/// 
+/// A Vec<User> with the users that have a Solana address
+pub async fn get_users_with_solana_address(pool: &PgPool) -> Result<Vec<User>> {
+    let rows = sqlx::query("SELECT * FROM users WHERE solana_address IS NOT NULL AND solana_address <> ''")
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(|row| User {
+        id: row.get("id"),
+        tg_id: row.get("tg_id"),
+        username: row.get("username"),
+        turnkey_info: TurnkeyInfo {
+            api_public_key: row.get("api_public_key"),
+            api_private_key: row.get("api_private_key"),
+            suborg_id: row.get("suborg_id"),
+            wallet_id: row.get("wallet_id"),
+        },
+        solana_address: row.get("solana_address"),
+        eth_address: row.get("eth_address"),
+        referral_id: row.try_get("referral_id").ok(),
+    }).collect())
+}
+
 
 /// Gets the user ID by the user's Telegram ID
 /// 
@@ -1126,6 +1053,14 @@ pub async fn get_user_settings(pool: &PgPool, user_tg_id: &str) -> Result<UserSe
         Ok(Some(stop_losses)) => serde_json::from_value(stop_losses).unwrap_or_default(),
         _ => vec![],
     };
+    let trailing_stops: Vec<(f64, f64)> = match user_settings.try_get("trailing_stops") {
+        Ok(Some(trailing_stops)) => serde_json::from_value(trailing_stops).unwrap_or_default(),
+        _ => vec![],
+    };
+    let mc_targets: Vec<MarketCapTarget> = match user_settings.try_get("mc_targets") {
+        Ok(Some(mc_targets)) => serde_json::from_value(mc_targets).unwrap_or_default(),
+        _ => vec![],
+    };
     Ok(UserSettings {
         tg_id: user_tg_id.to_string(),
         slippage_tolerance: user_settings.get("slippage_tolerance"),
@@ -1136,6 +1071,8 @@ pub async fn get_user_settings(pool: &PgPool, user_tg_id: &str) -> Result<UserSe
         anti_mev: user_settings.get("anti_mev"),
         take_profits,
         stop_losses,
+        trailing_stops,
+        mc_targets,
         jito_tip_amount: user_settings.get("jito_tip_amount"),
         active_complete_positions: user_settings.get("active_complete_positions"),
         withdraw_sol_amount: user_settings.get("withdraw_sol_amount"),
@@ -1348,7 +1285,7 @@ pub async fn get_or_create_user_settings(pool: &PgPool, user_tg_id: &str) -> Res
     }
 }
 
-pub async fn insert_position(pool: &PgPool, tg_user_id: &str, token_address: &str, take_profits: Option<Vec<(f64, f64)>>, stop_losses: Option<Vec<(f64, f64)>>, amount: f64, mc_entry: f64, token_price: f64, chat_id: &str, sol_entry: f64, ui_amount: &str) -> Result<()> {
+pub async fn insert_position(pool: &PgPool, tg_user_id: &str, token_address: &str, take_profits: Option<Vec<(f64, f64)>>, stop_losses: Option<Vec<(f64, f64)>>, trailing_stops: Option<Vec<(f64, f64)>>, mc_targets: Vec<MarketCapTarget>, circulating_supply: Option<f64>, amount: f64, mc_entry: f64, token_price: f64, chat_id: &str, sol_entry: f64, ui_amount: &str, fees_sol: f64) -> Result<i32> {
     let take_profits_json = if take_profits.is_some() {
         Some(serde_json::to_value(take_profits).unwrap())
     } else {
@@ -1361,11 +1298,18 @@ pub async fn insert_position(pool: &PgPool, tg_user_id: &str, token_address: &st
         None
     };
 
-    sqlx::query("INSERT INTO positions (tg_user_id, token_address, take_profits, stop_losses, amount, mc_entry, entry_price, chat_id, sol_entry, ui_amount, completed) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)")
+    let trailing_stops_json = serde_json::to_value(trailing_stops.unwrap_or_default()).unwrap();
+    let mc_targets_json = serde_json::to_value(mc_targets).unwrap();
+
+    // The whole SOL spent on the buy is the cost basis of the tokens
+    let row = sqlx::query("INSERT INTO positions (tg_user_id, token_address, take_profits, stop_losses, trailing_stops, mc_targets, circulating_supply, amount, mc_entry, entry_price, chat_id, sol_entry, ui_amount, completed, cost_basis_sol, fees_sol) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $12, $15) RETURNING id")
     .bind(tg_user_id)
     .bind(token_address)
     .bind(take_profits_json)
     .bind(stop_losses_json)
+    .bind(trailing_stops_json)
+    .bind(mc_targets_json)
+    .bind(circulating_supply)
     .bind(amount)
     .bind(mc_entry)
     .bind(token_price)
@@ -1373,9 +1317,10 @@ pub async fn insert_position(pool: &PgPool, tg_user_id: &str, token_address: &st
     .bind(sol_entry)
     .bind(ui_amount)
     .bind(false)
-    .execute(pool)
+    .bind(fees_sol)
+    .fetch_one(pool)
     .await?;
-    Ok(())
+    Ok(row.get("id"))
 }
 
 
@@ -1427,6 +1372,30 @@ pub async fn get_user_settings_stop_losses(pool: &PgPool, user_tg_id: &str) -> R
     }
 }
 
+/// Gets the user settings trailing stops
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `user_tg_id` - The user's Telegram ID
+/// 
+/// # Returns
+/// 
+/// A Vec<(f64, f64)> representing the trailing stops
+pub async fn get_user_settings_trailing_stops(pool: &PgPool, user_tg_id: &str) -> Result<Option<Vec<(f64, f64)>>> {
+    let trailing_stops: Option<serde_json::Value> = sqlx::query_scalar(
+        "SELECT trailing_stops FROM user_settings WHERE tg_id = $1"
+    )
+    .bind(user_tg_id)
+    .fetch_optional(pool)
+    .await?;
+
+    match trailing_stops {
+        Some(json) => Ok(Some(serde_json::from_value(json).unwrap_or_default())),
+        None => Ok(None)
+    }
+}
+
 /// Sets the user settings take profits
 /// 
 /// # Arguments
@@ -1518,6 +1487,29 @@ pub async fn delete_user_settings_stop_loss(pool: &PgPool, stop_loss: (f64, f64)
     Ok(())
 }
 
+/// Deletes a user trailing stop from user settings
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `trailing_stop` - The trailing stop
+/// * `user_tg_id` - The user's Telegram ID
+/// 
+/// # Returns
+/// 
+/// A result indicating whether the user settings trailing stop was deleted
+pub async fn delete_user_settings_trailing_stop(pool: &PgPool, trailing_stop: (f64, f64), user_tg_id: &str) -> Result<()> {
+    println!("@delete_user_settings_trailing_stop/ trailing_stop: {:?}", trailing_stop);
+
+    if let Some(mut user_trailing_stops) = get_user_settings_trailing_stops(pool, user_tg_id).await? {
+        user_trailing_stops.retain(|&ts| ts != trailing_stop);
+        println!("@delete_user_settings_trailing_stop/ user_trailing_stops after retaining: {:?}", user_trailing_stops);
+        set_user_settings_trailing_stops(pool, user_tg_id, user_trailing_stops).await?;
+    }
+
+    Ok(())
+}
+
 /// Get all positions
 /// 
 /// # Arguments
@@ -1546,6 +1538,15 @@ pub async fn get_all_positions(pool: &PgPool) -> Result<Vec<Position>> {
             None => Vec::new(),
         };
 
+        let trailing_stops: Vec<(f64, f64)> = match position.get::<Option<serde_json::Value>, _>("trailing_stops") {
+            Some(v) => serde_json::from_value(v).unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let mc_targets: Vec<MarketCapTarget> = match position.get::<Option<serde_json::Value>, _>("mc_targets") {
+            Some(v) => serde_json::from_value(v).unwrap_or_default(),
+            None => Vec::new(),
+        };
+
         positions_vec.push(Position {
             id: position.get("id"),
             tg_user_id: position.get("tg_user_id"),
@@ -1559,23 +1560,50 @@ pub async fn get_all_positions(pool: &PgPool) -> Result<Vec<Position>> {
             ui_amount: position.get("ui_amount"),
             take_profits,
             stop_losses,
+            trailing_stops,
+            highest_price: position.get("highest_price"),
+            mc_targets,
+            circulating_supply: position.get("circulating_supply"),
+            cost_basis_sol: position.get("cost_basis_sol"),
+            fees_sol: position.get("fees_sol"),
+            realised_pnl_sol: position.get("realised_pnl_sol"),
+            onchain_mismatch_amount: position.get("onchain_mismatch_amount"),
             completed: position.get("completed"),
         });
     }
     Ok(positions_vec)
 }
 
-/// Get all open positions
+
+/// Get the distinct tokens of all open positions
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// 
+/// # Returns
+/// 
+/// A Vec<String> with the token addresses
+pub async fn get_open_positions_tokens(pool: &PgPool) -> Result<Vec<String>> {
+    let tokens: Vec<String> = sqlx::query_scalar("SELECT DISTINCT token_address FROM positions WHERE completed = false")
+    .fetch_all(pool)
+    .await?;
+    Ok(tokens)
+}
+
+/// Get the open positions of a token
 /// 
 /// # Arguments
 /// 
 /// * `pool` - The PostgreSQL connection pool
+/// * `token_address` - The token address
 /// 
 /// # Returns
 /// 
-/// A Vec<Position> representing all open positions
-pub async fn get_all_open_positions(pool: &PgPool) -> Result<Vec<Position>> {
-    let positions = sqlx::query("SELECT * FROM positions WHERE completed = false")
+/// A Vec<Position> representing the open positions of the token
+pub async fn get_open_positions_by_token_address(pool: &PgPool, token_address: &str) -> Result<Vec<Position>> {
+    let positions = sqlx::query("SELECT * FROM positions WHERE completed = false AND token_address = $1")
+    .bind(token_address)
     .fetch_all(pool)
     .await?;
     let mut positions_vec: Vec<Position> = Vec::new();
@@ -1593,6 +1621,15 @@ pub async fn get_all_open_positions(pool: &PgPool) -> Result<Vec<Position>> {
             None => Vec::new(),
         };
 
+        let trailing_stops: Vec<(f64, f64)> = match position.get::<Option<serde_json::Value>, _>("trailing_stops") {
+            Some(v) => serde_json::from_value(v).unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let mc_targets: Vec<MarketCapTarget> = match position.get::<Option<serde_json::Value>, _>("mc_targets") {
+            Some(v) => serde_json::from_value(v).unwrap_or_default(),
+            None => Vec::new(),
+        };
+
         positions_vec.push(Position {
             id: position.get("id"),
             tg_user_id: position.get("tg_user_id"),
@@ -1606,6 +1643,14 @@ pub async fn get_all_open_positions(pool: &PgPool) -> Result<Vec<Position>> {
             ui_amount: position.get("ui_amount"),
             take_profits,
             stop_losses,
+            trailing_stops,
+            highest_price: position.get("highest_price"),
+            mc_targets,
+            circulating_supply: position.get("circulating_supply"),
+            cost_basis_sol: position.get("cost_basis_sol"),
+            fees_sol: position.get("fees_sol"),
+            realised_pnl_sol: position.get("realised_pnl_sol"),
+            onchain_mismatch_amount: position.get("onchain_mismatch_amount"),
             completed: position.get("completed"),
         });
     }
@@ -1779,6 +1824,135 @@ pub async fn set_user_settings_stop_losses(pool: &PgPool, user_tg_id: &str, stop
     Ok(())
 }
 
+/// Adds a user trailing stop in user settings
+/// 
+/// # Arguments
+/// 
+/// * `user_tg_id` - The user's Telegram ID
+/// * `trailing_stop` - The trailing stop as (% down from the highest price, % to sell)
+/// * `pool` - The PostgreSQL connection pool
+/// 
+/// # Returns
+/// 
+/// A result indicating whether the user trailing stop was added
+pub async fn add_user_trailing_stop_user_settings(user_tg_id: &str, trailing_stop: (f64, f64), pool: &SafePool) -> Result<()> {
+    println!("@add_user_trailing_stop_user_settings/ trailing_stop: {:?}", trailing_stop);
+
+    let mut user_trailing_stops = get_user_settings_trailing_stops(pool, user_tg_id).await?.unwrap_or_default();
+    if !user_trailing_stops.contains(&trailing_stop) {
+        user_trailing_stops.push(trailing_stop);
+    }
+    println!("@add_user_trailing_stop_user_settings/ user_trailing_stops after adding: {:?}", user_trailing_stops);
+    set_user_settings_trailing_stops(pool, user_tg_id, user_trailing_stops).await?;
+    Ok(())
+}
+
+/// Sets the user settings trailing stops
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `user_tg_id` - The user's Telegram ID
+/// * `trailing_stops` - The trailing stops
+/// 
+/// # Returns
+/// 
+/// A result indicating whether the user settings trailing stops were set
+pub async fn set_user_settings_trailing_stops(pool: &PgPool, user_tg_id: &str, trailing_stops: Vec<(f64, f64)>) -> Result<()> {
+    let trailing_stops_json = serde_json::to_value(trailing_stops).unwrap_or(json!([]));
+    sqlx::query("UPDATE user_settings SET trailing_stops = $1 WHERE tg_id = $2")
+    .bind(trailing_stops_json)
+    .bind(user_tg_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Gets the user settings market cap targets
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `user_tg_id` - The user's Telegram ID
+/// 
+/// # Returns
+/// 
+/// A Vec<MarketCapTarget> with the market cap take profits and stop losses
+pub async fn get_user_settings_mc_targets(pool: &PgPool, user_tg_id: &str) -> Result<Vec<MarketCapTarget>> {
+    let mc_targets: Option<serde_json::Value> = sqlx::query_scalar(
+        "SELECT mc_targets FROM user_settings WHERE tg_id = $1"
+    )
+    .bind(user_tg_id)
+    .fetch_optional(pool)
+    .await?;
+
+    match mc_targets {
+        Some(json) => Ok(serde_json::from_value(json).unwrap_or_default()),
+        None => Ok(Vec::new())
+    }
+}
+
+/// Sets the user settings market cap targets
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `user_tg_id` - The user's Telegram ID
+/// * `mc_targets` - The market cap targets
+/// 
+/// # Returns
+/// 
+/// A result indicating whether the user settings market cap targets were set
+pub async fn set_user_settings_mc_targets(pool: &PgPool, user_tg_id: &str, mc_targets: Vec<MarketCapTarget>) -> Result<()> {
+    let mc_targets_json = serde_json::to_value(mc_targets).unwrap_or(json!([]));
+    sqlx::query("UPDATE user_settings SET mc_targets = $1 WHERE tg_id = $2")
+    .bind(mc_targets_json)
+    .bind(user_tg_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Adds a market cap target in user settings
+/// 
+/// # Arguments
+/// 
+/// * `user_tg_id` - The user's Telegram ID
+/// * `mc_target` - The market cap target
+/// * `pool` - The PostgreSQL connection pool
+/// 
+/// # Returns
+/// 
+/// A result indicating whether the market cap target was added
+pub async fn add_user_mc_target_user_settings(user_tg_id: &str, mc_target: MarketCapTarget, pool: &SafePool) -> Result<()> {
+    println!("@add_user_mc_target_user_settings/ mc_target: {:?}", mc_target);
+    let mut user_mc_targets = get_user_settings_mc_targets(pool, user_tg_id).await?;
+    if !user_mc_targets.contains(&mc_target) {
+        user_mc_targets.push(mc_target);
+    }
+    set_user_settings_mc_targets(pool, user_tg_id, user_mc_targets).await?;
+    Ok(())
+}
+
+/// Deletes a market cap target from user settings
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `mc_target` - The market cap target
+/// * `user_tg_id` - The user's Telegram ID
+/// 
+/// # Returns
+/// 
+/// A result indicating whether the market cap target was deleted
+pub async fn delete_user_settings_mc_target(pool: &PgPool, mc_target: &MarketCapTarget, user_tg_id: &str) -> Result<()> {
+    println!("@delete_user_settings_mc_target/ mc_target: {:?}", mc_target);
+    let mut user_mc_targets = get_user_settings_mc_targets(pool, user_tg_id).await?;
+    user_mc_targets.retain(|target| target != mc_target);
+    set_user_settings_mc_targets(pool, user_tg_id, user_mc_targets).await?;
+    Ok(())
+}
+
 /// Removes a take profit from a position and sort it by multiplier
 /// 
 /// # Arguments
@@ -1864,7 +2038,7 @@ pub async fn set_position_stop_losses(pool: &PgPool, token_address: &str, user_t
 
     /// Adding to db
     let stop_losses_json = serde_json::to_value(sorted_stop_losses).unwrap();
-    sqlx::query("UPDATE positions SET take_profits = $1 WHERE token_address = $2 AND tg_user_id = $3")
+    sqlx::query("UPDATE positions SET stop_losses = $1 WHERE token_address = $2 AND tg_user_id = $3")
     .bind(stop_losses_json)
     .bind(token_address)
     .bind(user_tg_id)
@@ -1895,71 +2069,261 @@ pub async fn remove_stop_loss_from_position(pool: &PgPool, token_address: &str,
     Ok(())
 }
 
-/// Deletes a position
+/// Removes a trailing stop from a position
 /// 
 /// # Arguments
 /// 
 /// * `pool` - The PostgreSQL connection pool
-/// * `token_address` - The token address
-/// * `user_tg_id` - The user's Telegram ID
+/// * `position_id` - The position id
+/// * `trailing_stop` - The trailing stop to remove
 /// 
 /// # Returns
 /// 
-/// A result indicating whether the position was deleted
-pub async fn delete_position(pool: &PgPool, token_address: &str, user_tg_id: &str) -> Result<()> {
-    sqlx::query("DELETE FROM positions WHERE token_address = $1 AND tg_user_id = $2")
-    .bind(token_address)
-    .bind(user_tg_id)
+/// A result indicating whether the trailing stop was removed
+pub async fn remove_trailing_stop_from_position(pool: &PgPool, position_id: i32, trailing_stop: (f64, f64)) -> Result<()> {
+    println!("@remove_trailing_stop_from_position/ trailing_stop to remove: {:?}", trailing_stop);
+    let trailing_stops: serde_json::Value = sqlx::query_scalar("SELECT trailing_stops FROM positions WHERE id = $1")
+    .bind(position_id)
+    .fetch_one(pool)
+    .await?;
+    let mut position_trailing_stops: Vec<(f64, f64)> = serde_json::from_value(trailing_stops).unwrap_or_default();
+    position_trailing_stops.retain(|&ts| ts != trailing_stop);
+
+    sqlx::query("UPDATE positions SET trailing_stops = $1 WHERE id = $2")
+    .bind(serde_json::to_value(position_trailing_stops).unwrap_or(json!([])))
+    .bind(position_id)
     .execute(pool)
     .await?;
     Ok(())
 }
 
-
-/// Get a position
+/// Removes a market cap target from a position
 /// 
 /// # Arguments
 /// 
 /// * `pool` - The PostgreSQL connection pool
-/// * `token_address` - The token address
-/// * `user_tg_id` - The user's Telegram ID
+/// * `position_id` - The position id
+/// * `mc_target` - The market cap target to remove
 /// 
 /// # Returns
 /// 
-/// A result indicating whether the position was deleted
-pub async fn get_position(pool: &PgPool, token_address: &str, user_tg_id: &str) -> Result<Position> {
-    let query_result = sqlx::query("
-    GET * from position WHERE token_address = $1 AND tg_user_id $2")
-    .bind(token_address)
-    .bind(user_tg_id)
+/// A result indicating whether the market cap target was removed
+pub async fn remove_mc_target_from_position(pool: &PgPool, position_id: i32, mc_target: &MarketCapTarget) -> Result<()> {
+    println!("@remove_mc_target_from_position/ mc_target to remove: {:?}", mc_target);
+    let mc_targets: serde_json::Value = sqlx::query_scalar("SELECT mc_targets FROM positions WHERE id = $1")
+    .bind(position_id)
     .fetch_one(pool)
     .await?;
-    let take_profits_json: serde_json::Value = query_result.get("take_profits");
-    let take_profits = serde_json::from_value(take_profits_json).unwrap_or_default();
-    let stop_losses_json: serde_json::Value = query_result.get("stop_losses");
-    let stop_losses = serde_json::from_value(stop_losses_json).unwrap_or_default();
+    let mut position_mc_targets: Vec<MarketCapTarget> = serde_json::from_value(mc_targets).unwrap_or_default();
+    position_mc_targets.retain(|target| target != mc_target);
 
-    Ok(
-        Position { id: query_result.get("id"), tg_user_id: query_result.get("tg_user_id"), token_address: query_result.get("token_address"), amount: query_result.get("amount"), mc_entry: query_result.get("mc_entry"), entry_price: query_result.get("entry_price"), created_at: query_result.get("created_at"), chat_id: query_result.get("chat_id"), sol_entry: query_result.get("sol_entry"), ui_amount: query_result.get("ui_amount"),
-        take_profits,
-        stop_losses,
-        completed: query_result.get("completed")
-        }
-    )
+    sqlx::query("UPDATE positions SET mc_targets = $1 WHERE id = $2")
+    .bind(serde_json::to_value(position_mc_targets).unwrap_or(json!([])))
+    .bind(position_id)
+    .execute(pool)
+    .await?;
+    Ok(())
 }
 
-
-/// Get all positions for a user
+/// Raise the highest price seen for a position
+/// 
+/// # Description
+/// 
+/// The running high only moves up and starts at the entry price, so the trailing stops of a
+/// position keep their reference across restarts of the bot.
 /// 
 /// # Arguments
 /// 
 /// * `pool` - The PostgreSQL connection pool
-/// * `user_tg_id` - The user's Telegram ID
+/// * `position_id` - The position id
+/// * `price` - The current price
 /// 
 /// # Returns
 /// 
-/// A Vec<Position> representing the positions
-pub async fn get_positions_by_user_tg_id(pool: &PgPool, user_tg_id: &str) -> Result<Vec<Position>> {
+/// The highest price of the position after the update
+pub async fn update_position_highest_price(pool: &PgPool, position_id: i32, price: f64) -> Result<f64> {
+    let highest_price: f64 = sqlx::query_scalar(
+        "UPDATE positions SET highest_price = GREATEST(COALESCE(highest_price, entry_price, $1), $1) WHERE id = $2 RETURNING highest_price"
+    )
+    .bind(price)
+    .bind(position_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(highest_price)
+}
+
+/// Add a buy fill to an open position
+/// 
+/// # Description
+/// 
+/// The entry price becomes the average price paid weighted by the tokens bought, so repeated buys
+/// average in. The SOL cost, fees and tips included, is added to the cost basis and the SOL at entry.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `position_id` - The position id
+/// * `amount` - The raw token amount bought
+/// * `ui_amount` - The ui token amount bought
+/// * `sol_cost` - The SOL spent, fees and tips included
+/// * `fees_sol` - The network fee and tip of the buy
+/// * `cost_usd` - The USD value of `sol_cost`
+/// 
+/// # Returns
+/// 
+/// A result indicating whether the fill was added
+pub async fn add_buy_fill_to_position(pool: &PgPool, position_id: i32, amount: f64, ui_amount: f64, sol_cost: f64, fees_sol: f64, cost_usd: f64) -> Result<()> {
+    // Every expression reads the row before the update
+    sqlx::query(
+        "UPDATE positions SET
+            entry_price = (COALESCE(entry_price, 0) * COALESCE(NULLIF(ui_amount, '')::FLOAT, 0) + $5) / (COALESCE(NULLIF(ui_amount, '')::FLOAT, 0) + $3),
+            mc_entry = CASE WHEN COALESCE(circulating_supply, 0) > 0
+                THEN (COALESCE(entry_price, 0) * COALESCE(NULLIF(ui_amount, '')::FLOAT, 0) + $5) / (COALESCE(NULLIF(ui_amount, '')::FLOAT, 0) + $3) * circulating_supply
+                ELSE mc_entry END,
+            amount = amount + $2,
+            ui_amount = (COALESCE(NULLIF(ui_amount, '')::FLOAT, 0) + $3)::TEXT,
+            sol_entry = COALESCE(sol_entry, 0) + $4,
+            cost_basis_sol = cost_basis_sol + $4,
+            fees_sol = fees_sol + $6
+        WHERE id = $1"
+    )
+    .bind(position_id)
+    .bind(amount)
+    .bind(ui_amount)
+    .bind(sol_cost)
+    .bind(cost_usd)
+    .bind(fees_sol)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Add a sell fill to an open position
+/// 
+/// # Description
+/// 
+/// The tokens sold take their share of the cost basis at the average cost, the SOL received
+/// minus that share is added to the realised PnL. The position is completed once no token is left.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `position_id` - The position id
+/// * `amount` - The raw token amount sold
+/// * `ui_amount` - The ui token amount sold
+/// * `sol_received` - The SOL received, fees and tips deducted
+/// * `fees_sol` - The network fee and tip of the sell
+/// 
+/// # Returns
+/// 
+/// The PnL in SOL realised by the sell
+pub async fn add_sell_fill_to_position(pool: &PgPool, position_id: i32, amount: f64, ui_amount: f64, sol_received: f64, fees_sol: f64) -> Result<f64> {
+    // Every expression reads the row before the update, tokens bought outside the bot have no cost
+    let realised_pnl_sol: f64 = sqlx::query_scalar(
+        "WITH sold AS (
+            SELECT id,
+                CASE WHEN amount > 0 THEN cost_basis_sol * LEAST($2, amount) / amount ELSE 0 END AS cost_sold
+            FROM positions WHERE id = $1
+            FOR UPDATE
+        )
+        UPDATE positions SET
+            realised_pnl_sol = realised_pnl_sol + $4 - sold.cost_sold,
+            cost_basis_sol = GREATEST(cost_basis_sol - sold.cost_sold, 0),
+            amount = GREATEST(amount - $2, 0),
+            ui_amount = GREATEST(COALESCE(NULLIF(ui_amount, '')::FLOAT, 0) - $3, 0)::TEXT,
+            fees_sol = fees_sol + $5,
+            completed = completed OR amount - $2 <= 0
+        FROM sold
+        WHERE positions.id = sold.id
+        RETURNING $4 - sold.cost_sold"
+    )
+    .bind(position_id)
+    .bind(amount)
+    .bind(ui_amount)
+    .bind(sol_received)
+    .bind(fees_sol)
+    .fetch_one(pool)
+    .await?;
+    Ok(realised_pnl_sol)
+}
+
+/// Deletes a position
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `token_address` - The token address
+/// * `user_tg_id` - The user's Telegram ID
+/// 
+/// # Returns
+/// 
+/// A result indicating whether the position was deleted
+pub async fn delete_position(pool: &PgPool, token_address: &str, user_tg_id: &str) -> Result<()> {
+    sqlx::query("DELETE FROM positions WHERE token_address = $1 AND tg_user_id = $2")
+    .bind(token_address)
+    .bind(user_tg_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+
+/// Get a position
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `token_address` - The token address
+/// * `user_tg_id` - The user's Telegram ID
+/// 
+/// # Returns
+/// 
+/// A result indicating whether the position was deleted
+pub async fn get_position(pool: &PgPool, token_address: &str, user_tg_id: &str) -> Result<Position> {
+    let query_result = sqlx::query("
+    GET * from position WHERE token_address = $1 AND tg_user_id $2")
+    .bind(token_address)
+    .bind(user_tg_id)
+    .fetch_one(pool)
+    .await?;
+    let take_profits_json: serde_json::Value = query_result.get("take_profits");
+    let take_profits = serde_json::from_value(take_profits_json).unwrap_or_default();
+    let stop_losses_json: serde_json::Value = query_result.get("stop_losses");
+    let stop_losses = serde_json::from_value(stop_losses_json).unwrap_or_default();
+    let trailing_stops_json: serde_json::Value = query_result.get("trailing_stops");
+    let trailing_stops = serde_json::from_value(trailing_stops_json).unwrap_or_default();
+    let mc_targets_json: serde_json::Value = query_result.get("mc_targets");
+    let mc_targets = serde_json::from_value(mc_targets_json).unwrap_or_default();
+
+    Ok(
+        Position { id: query_result.get("id"), tg_user_id: query_result.get("tg_user_id"), token_address: query_result.get("token_address"), amount: query_result.get("amount"), mc_entry: query_result.get("mc_entry"), entry_price: query_result.get("entry_price"), created_at: query_result.get("created_at"), chat_id: query_result.get("chat_id"), sol_entry: query_result.get("sol_entry"), ui_amount: query_result.get("ui_amount"),
+        take_profits,
+        stop_losses,
+        trailing_stops,
+        highest_price: query_result.get("highest_price"),
+        mc_targets,
+        circulating_supply: query_result.get("circulating_supply"),
+        cost_basis_sol: query_result.get("cost_basis_sol"),
+        fees_sol: query_result.get("fees_sol"),
+        realised_pnl_sol: query_result.get("realised_pnl_sol"),
+        onchain_mismatch_amount: query_result.get("onchain_mismatch_amount"),
+        completed: query_result.get("completed")
+        }
+    )
+}
+
+
+/// Get all positions for a user
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `user_tg_id` - The user's Telegram ID
+/// 
+/// # Returns
+/// 
+/// A Vec<Position> representing the positions
+pub async fn get_positions_by_user_tg_id(pool: &PgPool, user_tg_id: &str) -> Result<Vec<Position>> {
     let positions = sqlx::query("SELECT * FROM positions WHERE tg_user_id = $1")
         .bind(user_tg_id)
         .fetch_all(pool)
@@ -1972,6 +2336,15 @@ pub async fn get_positions_by_user_tg_id(pool: &PgPool, user_tg_id: &str) -> Res
         let stop_losses: Vec<(f64, f64)> = position.get::<Option<serde_json::Value>, _>("stop_losses")
             .map_or_else(Vec::new, |v| serde_json::from_value(v).unwrap_or_default());
 
+        let trailing_stops: Vec<(f64, f64)> = match position.get::<Option<serde_json::Value>, _>("trailing_stops") {
+            Some(v) => serde_json::from_value(v).unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let mc_targets: Vec<MarketCapTarget> = match position.get::<Option<serde_json::Value>, _>("mc_targets") {
+            Some(v) => serde_json::from_value(v).unwrap_or_default(),
+            None => Vec::new(),
+        };
+
         positions_vec.push(Position {
             id: position.get("id"),
             tg_user_id: position.get("tg_user_id"),
@@ -1985,6 +2358,14 @@ pub async fn get_positions_by_user_tg_id(pool: &PgPool, user_tg_id: &str) -> Res
             ui_amount: position.get("ui_amount"),
             take_profits,
             stop_losses,
+            trailing_stops,
+            highest_price: position.get("highest_price"),
+            mc_targets,
+            circulating_supply: position.get("circulating_supply"),
+            cost_basis_sol: position.get("cost_basis_sol"),
+            fees_sol: position.get("fees_sol"),
+            realised_pnl_sol: position.get("realised_pnl_sol"),
+            onchain_mismatch_amount: position.get("onchain_mismatch_amount"),
             completed: position.get("completed")
         });
     }
@@ -2084,6 +2465,15 @@ pub async fn get_active_positions(pool: &PgPool, user_tg_id: &str) -> Result<Vec
             Some(v) => serde_json::from_value(v).unwrap_or_default(),
             None => Vec::new(),
         };
+        let trailing_stops: Vec<(f64, f64)> = match position.get::<Option<serde_json::Value>, _>("trailing_stops") {
+            Some(v) => serde_json::from_value(v).unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let mc_targets: Vec<MarketCapTarget> = match position.get::<Option<serde_json::Value>, _>("mc_targets") {
+            Some(v) => serde_json::from_value(v).unwrap_or_default(),
+            None => Vec::new(),
+        };
+
         positions_vec.push(Position {
             id: position.get("id"),
             tg_user_id: position.get("tg_user_id"),
@@ -2097,6 +2487,14 @@ pub async fn get_active_positions(pool: &PgPool, user_tg_id: &str) -> Result<Vec
             ui_amount: position.get("ui_amount"),
             take_profits,
             stop_losses,
+            trailing_stops,
+            highest_price: position.get("highest_price"),
+            mc_targets,
+            circulating_supply: position.get("circulating_supply"),
+            cost_basis_sol: position.get("cost_basis_sol"),
+            fees_sol: position.get("fees_sol"),
+            realised_pnl_sol: position.get("realised_pnl_sol"),
+            onchain_mismatch_amount: position.get("onchain_mismatch_amount"),
             completed: false
         });
     }
@@ -2132,6 +2530,15 @@ pub async fn get_complete_positions(pool: &PgPool, user_tg_id: &str) -> Result<V
             Some(v) => serde_json::from_value(v).unwrap_or_default(),
             None => Vec::new(),
         };
+        let trailing_stops: Vec<(f64, f64)> = match position.get::<Option<serde_json::Value>, _>("trailing_stops") {
+            Some(v) => serde_json::from_value(v).unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let mc_targets: Vec<MarketCapTarget> = match position.get::<Option<serde_json::Value>, _>("mc_targets") {
+            Some(v) => serde_json::from_value(v).unwrap_or_default(),
+            None => Vec::new(),
+        };
+
         positions_vec.push(Position {
             id: position.get("id"),
             tg_user_id: position.get("tg_user_id"),
@@ -2145,6 +2552,14 @@ pub async fn get_complete_positions(pool: &PgPool, user_tg_id: &str) -> Result<V
             ui_amount: position.get("ui_amount"),
             take_profits,
             stop_losses,
+            trailing_stops,
+            highest_price: position.get("highest_price"),
+            mc_targets,
+            circulating_supply: position.get("circulating_supply"),
+            cost_basis_sol: position.get("cost_basis_sol"),
+            fees_sol: position.get("fees_sol"),
+            realised_pnl_sol: position.get("realised_pnl_sol"),
+            onchain_mismatch_amount: position.get("onchain_mismatch_amount"),
             completed: false
         });
     }
@@ -2343,6 +2758,46 @@ pub async fn set_position_completed(pool: &PgPool, token_address: &str, user_tg_
     Ok(())
 }
 
+/// Set the circulating supply of a position opened while it was unknown
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `position_id` - The position id
+/// * `circulating_supply` - The token circulating supply
+/// 
+/// # Returns
+/// 
+/// A result indicating whether the supply was set
+pub async fn set_position_circulating_supply(pool: &PgPool, position_id: i32, circulating_supply: f64) -> Result<()> {
+    sqlx::query("UPDATE positions SET circulating_supply = $1 WHERE id = $2 AND circulating_supply IS NULL")
+    .bind(circulating_supply)
+    .bind(position_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Set the wallet balance flagged as drifting from a position amount
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `position_id` - The position id
+/// * `onchain_mismatch_amount` - The raw wallet balance flagged to the user, None once back in sync
+/// 
+/// # Returns
+/// 
+/// A result indicating whether the mismatch was set
+pub async fn set_position_onchain_mismatch(pool: &PgPool, position_id: i32, onchain_mismatch_amount: Option<f64>) -> Result<()> {
+    sqlx::query("UPDATE positions SET onchain_mismatch_amount = $1 WHERE id = $2")
+    .bind(onchain_mismatch_amount)
+    .bind(position_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
 /// Set the user withdraw sol amount
 /// 
 /// # Arguments
@@ -2381,4 +2836,1263 @@ pub async fn set_user_withdraw_sol_address(pool: &PgPool, user_tg_id: &str, addr
     .execute(pool)
     .await?;
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Claim a position order level for execution
+/// 
+/// # Description
+/// 
+/// Atomically moves the level to executing. Only one caller can claim a level. A failed level
+/// can be claimed again until it reached `max_attempts`, and so can a level stuck executing for
+/// `stale_minutes`, when the bot stopped before recording the outcome. The claim is scoped to the
+/// current ladder version of the position, so a level the user removed and added again is new.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `position_id` - The position ID
+/// * `kind` - The order kind (take_profit or stop_loss)
+/// * `multiplier` - The level multiplier
+/// * `sell_percentage` - The % of the token balance to sell
+/// * `max_attempts` - The attempts after which a level isn't claimed anymore
+/// * `stale_minutes` - The minutes after which an executing level can be claimed again
+/// 
+/// # Returns
+/// 
+/// The order ID if the level was claimed, None otherwise
+pub async fn claim_position_order(pool: &PgPool, position_id: i32, kind: &str, multiplier: f64, sell_percentage: f64, max_attempts: i32, stale_minutes: i32) -> Result<Option<i32>> {
+    let q = "
+        INSERT INTO position_orders (position_id, ladder_version, kind, multiplier, sell_percentage, status)
+        SELECT id, ladder_version, $2, $3, $4, 'executing' FROM positions WHERE id = $1
+        ON CONFLICT (position_id, ladder_version, kind, multiplier, sell_percentage)
+        DO UPDATE SET status = 'executing', attempts = position_orders.attempts + 1, error = NULL, updated_at = CURRENT_TIMESTAMP
+        WHERE position_orders.attempts < $5
+        AND (
+            position_orders.status = 'failed'
+            OR (position_orders.status = 'executing' AND position_orders.updated_at < CURRENT_TIMESTAMP - make_interval(mins => $6))
+        )
+        RETURNING id
+    ";
+    let order_id: Option<i32> = sqlx::query_scalar(q)
+    .bind(position_id)
+    .bind(kind)
+    .bind(multiplier)
+    .bind(sell_percentage)
+    .bind(max_attempts)
+    .bind(stale_minutes)
+    .fetch_optional(pool)
+    .await?;
+    Ok(order_id)
+}
+
+/// Set the status of a position order
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `order_id` - The order ID
+/// * `status` - The new status
+/// * `signature` - The swap transaction signature, if any
+/// * `error` - The error, if any
+/// 
+/// # Returns
+/// 
+/// A result indicating whether the status was set
+pub async fn set_position_order_status(pool: &PgPool, order_id: i32, status: &str, signature: Option<&str>, error: Option<&str>) -> Result<()> {
+    sqlx::query("UPDATE position_orders SET status = $1, signature = $2, error = $3, updated_at = CURRENT_TIMESTAMP WHERE id = $4")
+    .bind(status)
+    .bind(signature)
+    .bind(error)
+    .bind(order_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+fn limit_order_from_row(row: &sqlx::postgres::PgRow) -> LimitOrder {
+    LimitOrder {
+        id: row.get("id"),
+        tg_user_id: row.get("tg_user_id"),
+        chat_id: row.get("chat_id"),
+        token_address: row.get("token_address"),
+        sol_amount: row.get("sol_amount"),
+        trigger_price: row.get("trigger_price"),
+        trigger_mc: row.get("trigger_mc"),
+        status: row.get("status"),
+        signature: row.get("signature"),
+        error: row.get("error"),
+        expires_at: row.get("expires_at"),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// Insert a limit buy order
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `tg_user_id` - The user's Telegram ID
+/// * `chat_id` - The chat to notify
+/// * `token_address` - The token to buy
+/// * `sol_amount` - The SOL to spend
+/// * `trigger_price` - The price at or below which to buy
+/// * `trigger_mc` - The market cap the trigger price was derived from, if any
+/// * `expires_in_hours` - Hours until the order stops being evaluated
+/// 
+/// # Returns
+/// 
+/// The ID of the new order
+pub async fn insert_limit_order(pool: &PgPool, tg_user_id: &str, chat_id: &str, token_address: &str, sol_amount: f64, trigger_price: f64, trigger_mc: Option<f64>, expires_in_hours: f64) -> Result<i32> {
+    let order_id: i32 = sqlx::query_scalar("INSERT INTO limit_orders (tg_user_id, chat_id, token_address, sol_amount, trigger_price, trigger_mc, expires_at) VALUES ($1, $2, $3, $4, $5, $6, NOW() + $7 * INTERVAL '1 hour') RETURNING id")
+    .bind(tg_user_id)
+    .bind(chat_id)
+    .bind(token_address)
+    .bind(sol_amount)
+    .bind(trigger_price)
+    .bind(trigger_mc)
+    .bind(expires_in_hours)
+    .fetch_one(pool)
+    .await?;
+    Ok(order_id)
+}
+
+/// Get the tokens with open limit orders
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// 
+/// # Returns
+/// 
+/// A Vec<String> with the token addresses
+pub async fn get_open_limit_orders_tokens(pool: &PgPool) -> Result<Vec<String>> {
+    let tokens: Vec<String> = sqlx::query_scalar("SELECT DISTINCT token_address FROM limit_orders WHERE status = 'open' AND expires_at > NOW()")
+    .fetch_all(pool)
+    .await?;
+    Ok(tokens)
+}
+
+/// Get the open limit orders of a token
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `token_address` - The token address
+/// 
+/// # Returns
+/// 
+/// A Vec<LimitOrder> with the open orders, oldest first
+pub async fn get_open_limit_orders_by_token_address(pool: &PgPool, token_address: &str) -> Result<Vec<LimitOrder>> {
+    let rows = sqlx::query("SELECT * FROM limit_orders WHERE token_address = $1 AND status = 'open' AND expires_at > NOW() ORDER BY id ASC")
+    .bind(token_address)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(limit_order_from_row).collect())
+}
+
+/// Get the open limit orders of a user
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `tg_user_id` - The user's Telegram ID
+/// 
+/// # Returns
+/// 
+/// A Vec<LimitOrder> with the open orders, oldest first
+pub async fn get_user_open_limit_orders(pool: &PgPool, tg_user_id: &str) -> Result<Vec<LimitOrder>> {
+    let rows = sqlx::query("SELECT * FROM limit_orders WHERE tg_user_id = $1 AND status = 'open' AND expires_at > NOW() ORDER BY id ASC")
+    .bind(tg_user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(limit_order_from_row).collect())
+}
+
+/// Claim an open limit order for execution
+/// 
+/// # Description
+/// 
+/// Moves the order from open to executing in a single statement so only one caller can
+/// execute it, a cancelled or expired order can't be claimed.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `order_id` - The order ID
+/// 
+/// # Returns
+/// 
+/// true if the order was claimed
+pub async fn claim_limit_order(pool: &PgPool, order_id: i32) -> Result<bool> {
+    let claimed: Option<i32> = sqlx::query_scalar("UPDATE limit_orders SET status = 'executing', updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND status = 'open' AND expires_at > NOW() RETURNING id")
+    .bind(order_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(claimed.is_some())
+}
+
+/// Set the status of a limit order
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `order_id` - The order ID
+/// * `status` - The new status
+/// * `signature` - The swap transaction signature, if any
+/// * `error` - The execution error, if any
+/// 
+/// # Returns
+/// 
+/// A result indicating whether the status was set
+pub async fn set_limit_order_status(pool: &PgPool, order_id: i32, status: &str, signature: Option<&str>, error: Option<&str>) -> Result<()> {
+    sqlx::query("UPDATE limit_orders SET status = $1, signature = $2, error = $3, updated_at = CURRENT_TIMESTAMP WHERE id = $4")
+    .bind(status)
+    .bind(signature)
+    .bind(error)
+    .bind(order_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Cancel an open limit order of a user
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `order_id` - The order ID
+/// * `tg_user_id` - The user's Telegram ID, only the owner can cancel
+/// 
+/// # Returns
+/// 
+/// true if the order was open and is now cancelled
+pub async fn cancel_limit_order(pool: &PgPool, order_id: i32, tg_user_id: &str) -> Result<bool> {
+    let cancelled: Option<i32> = sqlx::query_scalar("UPDATE limit_orders SET status = 'cancelled', updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND tg_user_id = $2 AND status = 'open' RETURNING id")
+    .bind(order_id)
+    .bind(tg_user_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(cancelled.is_some())
+}
+
+/// Expire the open limit orders past their expiry
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// 
+/// # Returns
+/// 
+/// A Vec<LimitOrder> with the orders that just expired
+pub async fn expire_limit_orders(pool: &PgPool) -> Result<Vec<LimitOrder>> {
+    let rows = sqlx::query("UPDATE limit_orders SET status = 'expired', updated_at = CURRENT_TIMESTAMP WHERE status = 'open' AND expires_at <= NOW() RETURNING *")
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(limit_order_from_row).collect())
+}
+
+fn recurring_order_from_row(row: &sqlx::postgres::PgRow) -> RecurringOrder {
+    RecurringOrder {
+        id: row.get("id"),
+        tg_user_id: row.get("tg_user_id"),
+        chat_id: row.get("chat_id"),
+        token_address: row.get("token_address"),
+        kind: row.get("kind"),
+        sol_per_slice: row.get("sol_per_slice"),
+        total_slices: row.get("total_slices"),
+        executed_slices: row.get("executed_slices"),
+        interval_secs: row.get("interval_secs"),
+        next_run_at: row.get("next_run_at"),
+        status: row.get("status"),
+        last_signature: row.get("last_signature"),
+        last_error: row.get("last_error"),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// Insert a recurring order, its first slice runs right away
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `tg_user_id` - The user's Telegram ID
+/// * `chat_id` - The chat to notify
+/// * `token_address` - The token address
+/// * `kind` - dca or twap
+/// * `sol_per_slice` - The SOL spent by each slice of a DCA
+/// * `total_slices` - The number of slices
+/// * `interval_secs` - The seconds between slices
+/// 
+/// # Returns
+/// 
+/// The ID of the new order
+pub async fn insert_recurring_order(pool: &PgPool, tg_user_id: &str, chat_id: &str, token_address: &str, kind: &str, sol_per_slice: Option<f64>, total_slices: i32, interval_secs: i32) -> Result<i32> {
+    let order_id: i32 = sqlx::query_scalar("INSERT INTO recurring_orders (tg_user_id, chat_id, token_address, kind, sol_per_slice, total_slices, interval_secs) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id")
+    .bind(tg_user_id)
+    .bind(chat_id)
+    .bind(token_address)
+    .bind(kind)
+    .bind(sol_per_slice)
+    .bind(total_slices)
+    .bind(interval_secs)
+    .fetch_one(pool)
+    .await?;
+    Ok(order_id)
+}
+
+/// Get the active and paused recurring orders of a user
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `tg_user_id` - The user's Telegram ID
+/// 
+/// # Returns
+/// 
+/// A Vec<RecurringOrder> with the orders, oldest first
+pub async fn get_user_recurring_orders(pool: &PgPool, tg_user_id: &str) -> Result<Vec<RecurringOrder>> {
+    let rows = sqlx::query("SELECT * FROM recurring_orders WHERE tg_user_id = $1 AND status IN ('active', 'paused') ORDER BY id ASC")
+    .bind(tg_user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(recurring_order_from_row).collect())
+}
+
+/// Claim the recurring orders with a slice due
+/// 
+/// # Description
+/// 
+/// Pushes the next run of the due orders one interval ahead in the same statement that selects
+/// them, so a slice is only picked once even if the scheduler runs again before it finishes.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// 
+/// # Returns
+/// 
+/// A Vec<RecurringOrder> with the claimed orders
+pub async fn claim_due_recurring_orders(pool: &PgPool) -> Result<Vec<RecurringOrder>> {
+    let q = "
+        UPDATE recurring_orders
+        SET next_run_at = NOW() + interval_secs * INTERVAL '1 second', updated_at = CURRENT_TIMESTAMP
+        WHERE id IN (
+            SELECT id FROM recurring_orders
+            WHERE status = 'active' AND next_run_at <= NOW()
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING *
+    ";
+    let rows = sqlx::query(q)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(recurring_order_from_row).collect())
+}
+
+/// Record an executed slice of a recurring order
+/// 
+/// # Description
+/// 
+/// Failed slices count too, the order completes after its last slice either way.
+/// Only active orders record slices, an order paused or cancelled while its slice ran is left unchanged.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `order_id` - The order ID
+/// * `signature` - The slice transaction signature, if it succeeded
+/// * `error` - The slice error, if it failed
+/// 
+/// # Returns
+/// 
+/// The order after the slice, None if the order isn't active anymore
+pub async fn record_recurring_order_slice(pool: &PgPool, order_id: i32, signature: Option<&str>, error: Option<&str>) -> Result<Option<RecurringOrder>> {
+    let q = "
+        UPDATE recurring_orders
+        SET executed_slices = executed_slices + 1,
+            last_signature = COALESCE($2, last_signature),
+            last_error = $3,
+            status = CASE WHEN executed_slices + 1 >= total_slices THEN 'completed' ELSE status END,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = $1 AND status = 'active'
+        RETURNING *
+    ";
+    let row = sqlx::query(q)
+    .bind(order_id)
+    .bind(signature)
+    .bind(error)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.as_ref().map(recurring_order_from_row))
+}
+
+/// Pause an active recurring order of a user
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `order_id` - The order ID
+/// * `tg_user_id` - The user's Telegram ID
+/// 
+/// # Returns
+/// 
+/// true if the order was paused
+pub async fn pause_recurring_order(pool: &PgPool, order_id: i32, tg_user_id: &str) -> Result<bool> {
+    let paused: Option<i32> = sqlx::query_scalar("UPDATE recurring_orders SET status = 'paused', updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND tg_user_id = $2 AND status = 'active' RETURNING id")
+    .bind(order_id)
+    .bind(tg_user_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(paused.is_some())
+}
+
+/// Resume a paused recurring order of a user, its next slice runs right away
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `order_id` - The order ID
+/// * `tg_user_id` - The user's Telegram ID
+/// 
+/// # Returns
+/// 
+/// true if the order was resumed
+pub async fn resume_recurring_order(pool: &PgPool, order_id: i32, tg_user_id: &str) -> Result<bool> {
+    let resumed: Option<i32> = sqlx::query_scalar("UPDATE recurring_orders SET status = 'active', next_run_at = NOW(), updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND tg_user_id = $2 AND status = 'paused' RETURNING id")
+    .bind(order_id)
+    .bind(tg_user_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(resumed.is_some())
+}
+
+/// Cancel an active or paused recurring order of a user
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `order_id` - The order ID
+/// * `tg_user_id` - The user's Telegram ID
+/// 
+/// # Returns
+/// 
+/// true if the order was cancelled
+pub async fn cancel_recurring_order(pool: &PgPool, order_id: i32, tg_user_id: &str) -> Result<bool> {
+    let cancelled: Option<i32> = sqlx::query_scalar("UPDATE recurring_orders SET status = 'cancelled', updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND tg_user_id = $2 AND status IN ('active', 'paused') RETURNING id")
+    .bind(order_id)
+    .bind(tg_user_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(cancelled.is_some())
+}
+
+fn trade_from_row(row: &sqlx::postgres::PgRow) -> Trade {
+    Trade {
+        id: row.get("id"),
+        position_id: row.get("position_id"),
+        tg_user_id: row.get("tg_user_id"),
+        token_address: row.get("token_address"),
+        side: row.get("side"),
+        source: row.get("source"),
+        signature: row.get("signature"),
+        token_amount: row.get("token_amount"),
+        token_ui_amount: row.get("token_ui_amount"),
+        sol_amount: row.get("sol_amount"),
+        price_usd: row.get("price_usd"),
+        fees_sol: row.get("fees_sol"),
+        realised_pnl_sol: row.get("realised_pnl_sol"),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// Insert a trade
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `position_id` - The position the fill was booked on, if any
+/// * `tg_user_id` - The user's Telegram ID
+/// * `token_address` - The token traded
+/// * `side` - buy or sell
+/// * `source` - What triggered the trade
+/// * `signature` - The swap transaction signature
+/// * `token_amount` - The raw token amount
+/// * `token_ui_amount` - The ui token amount
+/// * `sol_amount` - The SOL spent, fees and tips included, or received, net of fees and tips
+/// * `price_usd` - The USD price of a token
+/// * `fees_sol` - The network fee and tip
+/// * `realised_pnl_sol` - The PnL realised by a sell
+/// 
+/// # Returns
+/// 
+/// The id of the trade
+pub async fn insert_trade(pool: &PgPool, position_id: Option<i32>, tg_user_id: &str, token_address: &str, side: &str, source: &str, signature: &str, token_amount: Option<f64>, token_ui_amount: Option<f64>, sol_amount: Option<f64>, price_usd: Option<f64>, fees_sol: Option<f64>, realised_pnl_sol: Option<f64>) -> Result<i32> {
+    let id: i32 = sqlx::query_scalar(
+        "INSERT INTO trades (position_id, tg_user_id, token_address, side, source, signature, token_amount, token_ui_amount, sol_amount, price_usd, fees_sol, realised_pnl_sol)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        RETURNING id"
+    )
+    .bind(position_id)
+    .bind(tg_user_id)
+    .bind(token_address)
+    .bind(side)
+    .bind(source)
+    .bind(signature)
+    .bind(token_amount)
+    .bind(token_ui_amount)
+    .bind(sol_amount)
+    .bind(price_usd)
+    .bind(fees_sol)
+    .bind(realised_pnl_sol)
+    .fetch_one(pool)
+    .await?;
+    Ok(id)
+}
+
+/// Get the latest trades of a user
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `tg_user_id` - The user's Telegram ID
+/// * `limit` - The maximum number of trades
+/// 
+/// # Returns
+/// 
+/// A Vec<Trade> with the trades, newest first
+pub async fn get_user_trades(pool: &PgPool, tg_user_id: &str, limit: i64) -> Result<Vec<Trade>> {
+    let rows = sqlx::query("SELECT * FROM trades WHERE tg_user_id = $1 ORDER BY created_at DESC, id DESC LIMIT $2")
+    .bind(tg_user_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(trade_from_row).collect())
+}
+
+/// Get the trades of a position
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `position_id` - The position id
+/// 
+/// # Returns
+/// 
+/// A Vec<Trade> with the trades, oldest first
+pub async fn get_position_trades(pool: &PgPool, position_id: i32) -> Result<Vec<Trade>> {
+    let rows = sqlx::query("SELECT * FROM trades WHERE position_id = $1 ORDER BY created_at ASC, id ASC")
+    .bind(position_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(trade_from_row).collect())
+}
+
+/// Get the PnL realised by the sells of a user over the last 24 hours, 7 days and 30 days
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `tg_user_id` - The user's Telegram ID
+/// 
+/// # Returns
+/// 
+/// A tuple with the realised PnL in SOL over 24h, 7d and 30d
+pub async fn get_user_realised_pnl_by_period(pool: &PgPool, tg_user_id: &str) -> Result<(f64, f64, f64)> {
+    let row = sqlx::query(
+        "SELECT
+            COALESCE(SUM(realised_pnl_sol) FILTER (WHERE created_at > NOW() - INTERVAL '1 day'), 0) AS pnl_24h,
+            COALESCE(SUM(realised_pnl_sol) FILTER (WHERE created_at > NOW() - INTERVAL '7 days'), 0) AS pnl_7d,
+            COALESCE(SUM(realised_pnl_sol) FILTER (WHERE created_at > NOW() - INTERVAL '30 days'), 0) AS pnl_30d
+        FROM trades
+        WHERE tg_user_id = $1 AND side = 'sell'"
+    )
+    .bind(tg_user_id)
+    .fetch_one(pool)
+    .await?;
+    Ok((row.get("pnl_24h"), row.get("pnl_7d"), row.get("pnl_30d")))
+}
+
+/// Build a strategy preset from a strategy_presets row
+fn strategy_preset_from_row(row: &sqlx::postgres::PgRow) -> StrategyPreset {
+    StrategyPreset {
+        id: row.get("id"),
+        tg_user_id: row.get("tg_user_id"),
+        name: row.get("name"),
+        take_profits: serde_json::from_value(row.get("take_profits")).unwrap_or_default(),
+        stop_losses: serde_json::from_value(row.get("stop_losses")).unwrap_or_default(),
+        trailing_stops: serde_json::from_value(row.get("trailing_stops")).unwrap_or_default(),
+        mc_targets: serde_json::from_value(row.get("mc_targets")).unwrap_or_default(),
+        buy_amount: row.get("buy_amount"),
+    }
+}
+
+/// Insert a strategy preset, replacing the user preset with the same name
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `tg_user_id` - The user's Telegram ID
+/// * `name` - The preset name
+/// * `take_profits` - The take profits, [ (multiplier, % to sell), ... ]
+/// * `stop_losses` - The stop losses, [ (multiplier, % to sell), ... ]
+/// * `trailing_stops` - The trailing stops, [ (% down from the highest price, % to sell), ... ]
+/// * `mc_targets` - The market cap targets
+/// * `buy_amount` - The SOL to buy
+/// 
+/// # Returns
+/// 
+/// The preset id
+pub async fn insert_strategy_preset(pool: &PgPool, tg_user_id: &str, name: &str, take_profits: &[(f64, f64)], stop_losses: &[(f64, f64)], trailing_stops: &[(f64, f64)], mc_targets: &[MarketCapTarget], buy_amount: &str) -> Result<i32> {
+    let row = sqlx::query(
+        "INSERT INTO strategy_presets (tg_user_id, name, take_profits, stop_losses, trailing_stops, mc_targets, buy_amount)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (tg_user_id, name) DO UPDATE SET
+            take_profits = EXCLUDED.take_profits,
+            stop_losses = EXCLUDED.stop_losses,
+            trailing_stops = EXCLUDED.trailing_stops,
+            mc_targets = EXCLUDED.mc_targets,
+            buy_amount = EXCLUDED.buy_amount
+        RETURNING id"
+    )
+    .bind(tg_user_id)
+    .bind(name)
+    .bind(serde_json::to_value(take_profits)?)
+    .bind(serde_json::to_value(stop_losses)?)
+    .bind(serde_json::to_value(trailing_stops)?)
+    .bind(serde_json::to_value(mc_targets)?)
+    .bind(buy_amount)
+    .fetch_one(pool)
+    .await?;
+    Ok(row.get("id"))
+}
+
+/// Get the strategy presets of a user, creating the default ones the first time
+/// 
+/// # Description
+/// 
+/// The defaults are "Scalp", quick take profits behind a tight stop and trailing stop, and
+/// "Moon bag", take profits up to 10x that keep a bag running behind a wide stop. They are only
+/// created once per user, a user who deleted them doesn't get them back.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `tg_user_id` - The user's Telegram ID
+/// 
+/// # Returns
+/// 
+/// A Vec<StrategyPreset> with the user presets, oldest first
+pub async fn get_or_create_strategy_presets(pool: &PgPool, tg_user_id: &str) -> Result<Vec<StrategyPreset>> {
+    // Flipping the flag claims the seeding, concurrent calls can't create the defaults twice
+    let seed = sqlx::query("UPDATE user_settings SET presets_seeded = TRUE WHERE tg_id = $1 AND NOT presets_seeded")
+    .bind(tg_user_id)
+    .execute(pool)
+    .await?
+    .rows_affected() > 0;
+    if seed {
+        insert_strategy_preset(pool, tg_user_id, "Scalp", &[(1.5, 50.0), (2.0, 100.0)], &[(0.8, 100.0)], &[(15.0, 100.0)], &[], "0.5").await?;
+        insert_strategy_preset(pool, tg_user_id, "Moon bag", &[(2.0, 50.0), (5.0, 25.0), (10.0, 15.0)], &[(0.5, 100.0)], &[], &[], "0.2").await?;
+    }
+    let rows = sqlx::query("SELECT * FROM strategy_presets WHERE tg_user_id = $1 ORDER BY id")
+    .bind(tg_user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(strategy_preset_from_row).collect())
+}
+
+/// Get a strategy preset of a user
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `preset_id` - The preset id
+/// * `tg_user_id` - The user's Telegram ID, the preset must be theirs
+/// 
+/// # Returns
+/// 
+/// The preset, None if the user has no preset with this id
+pub async fn get_strategy_preset(pool: &PgPool, preset_id: i32, tg_user_id: &str) -> Result<Option<StrategyPreset>> {
+    let row = sqlx::query("SELECT * FROM strategy_presets WHERE id = $1 AND tg_user_id = $2")
+    .bind(preset_id)
+    .bind(tg_user_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.as_ref().map(strategy_preset_from_row))
+}
+
+/// Delete a strategy preset of a user
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `preset_id` - The preset id
+/// * `tg_user_id` - The user's Telegram ID, the preset must be theirs
+/// 
+/// # Returns
+/// 
+/// Whether a preset was deleted
+pub async fn delete_strategy_preset(pool: &PgPool, preset_id: i32, tg_user_id: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM strategy_presets WHERE id = $1 AND tg_user_id = $2")
+    .bind(preset_id)
+    .bind(tg_user_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Set the strategy preset of the next buy of a token
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `tg_user_id` - The user's Telegram ID
+/// * `preset_id` - The preset, None to clear it
+/// * `token_address` - The token the preset applies to, None to clear it
+/// 
+/// # Returns
+/// 
+/// A result indicating whether the preset was set
+pub async fn set_pending_strategy_preset(pool: &PgPool, tg_user_id: &str, preset_id: Option<i32>, token_address: Option<&str>) -> Result<()> {
+    sqlx::query("UPDATE user_settings SET pending_preset_id = $1, pending_preset_token = $2 WHERE tg_id = $3")
+    .bind(preset_id)
+    .bind(token_address)
+    .bind(tg_user_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Get the strategy preset of the next buy of a token
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `tg_user_id` - The user's Telegram ID
+/// * `token_address` - The token bought
+/// 
+/// # Returns
+/// 
+/// The preset, None if none was picked for this token
+pub async fn get_pending_strategy_preset(pool: &PgPool, tg_user_id: &str, token_address: &str) -> Result<Option<StrategyPreset>> {
+    let row = sqlx::query(
+        "SELECT strategy_presets.* FROM user_settings
+        JOIN strategy_presets ON strategy_presets.id = user_settings.pending_preset_id
+        WHERE user_settings.tg_id = $1 AND user_settings.pending_preset_token = $2"
+    )
+    .bind(tg_user_id)
+    .bind(token_address)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.as_ref().map(strategy_preset_from_row))
+}
+
+/// Set the take profits, stop losses, trailing stops and market cap targets of a position
+/// 
+/// # Description
+/// 
+/// Bumps the ladder version of the position, the levels set can fire even if an identical
+/// level already fired before.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `position_id` - The position id
+/// * `take_profits` - The take profits
+/// * `stop_losses` - The stop losses
+/// * `trailing_stops` - The trailing stops
+/// * `mc_targets` - The market cap targets
+/// 
+/// # Returns
+/// 
+/// A result indicating whether the ladders were set
+pub async fn set_position_ladders(pool: &PgPool, position_id: i32, take_profits: &[(f64, f64)], stop_losses: &[(f64, f64)], trailing_stops: &[(f64, f64)], mc_targets: &[MarketCapTarget]) -> Result<()> {
+    sqlx::query("UPDATE positions SET take_profits = $1, stop_losses = $2, trailing_stops = $3, mc_targets = $4, ladder_version = ladder_version + 1 WHERE id = $5")
+    .bind(serde_json::to_value(take_profits)?)
+    .bind(serde_json::to_value(stop_losses)?)
+    .bind(serde_json::to_value(trailing_stops)?)
+    .bind(serde_json::to_value(mc_targets)?)
+    .bind(position_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Build a call from a calls row
+fn call_from_row(row: &sqlx::postgres::PgRow) -> Call {
+    Call {
+        id: row.get("id"),
+        time: row.get("time"),
+        mkt_cap: row.get("mkt_cap"),
+        token_address: row.get("token_address"),
+        token_mint: row.get("token_mint"),
+        token_symbol: row.get("token_symbol"),
+        price: row.get("price"),
+        user_tg_id: row.get("user_tg_id"),
+        chat_id: row.get("chat_id"),
+        message_id: row.get("message_id"),
+        chain: row.get("chain"),
+    }
+}
+
+/// Get the calls whose cached ATH is missing or due for an update
+/// 
+/// # Description
+/// 
+/// Calls of the last 7 days are refreshed every 10 minutes, older calls every 6 hours. Calls
+/// without an ATH come first, then the least recently updated.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `limit` - The maximum number of calls
+/// 
+/// # Returns
+/// 
+/// A Vec<Call> with the calls to update
+pub async fn get_calls_due_for_ath_update(pool: &PgPool, limit: i64) -> Result<Vec<Call>> {
+    let rows = sqlx::query(
+        "SELECT calls.* FROM calls
+        LEFT JOIN call_aths ON call_aths.call_id = calls.id
+        WHERE call_aths.call_id IS NULL
+            OR call_aths.updated_at < NOW() - CASE WHEN calls.time > NOW() - INTERVAL '7 days' THEN INTERVAL '10 minutes' ELSE INTERVAL '6 hours' END
+        ORDER BY call_aths.updated_at ASC NULLS FIRST, calls.id ASC
+        LIMIT $1"
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(call_from_row).collect())
+}
+
+/// Get the cached ATHs of calls
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `call_ids` - The call ids
+/// 
+/// # Returns
+/// 
+/// A HashMap from call id to its cached ATH, calls without one are missing
+pub async fn get_call_aths(pool: &PgPool, call_ids: &[i32]) -> Result<std::collections::HashMap<i32, CallAth>> {
+    let rows = sqlx::query("SELECT * FROM call_aths WHERE call_id = ANY($1)")
+    .bind(call_ids)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(|row| {
+        let call_ath = CallAth {
+            call_id: row.get("call_id"),
+            ath_price: row.get("ath_price"),
+            last_price: row.get("last_price"),
+            last_mkt_cap: row.get("last_mkt_cap"),
+            pair_address: row.get("pair_address"),
+            ath_checked_until: row.get("ath_checked_until"),
+            updated_at: row.get("updated_at"),
+        };
+        (call_ath.call_id, call_ath)
+    }).collect())
+}
+
+/// Insert or update the cached ATH of a call
+/// 
+/// # Description
+/// 
+/// The ATH only goes up, an update with a lower ATH price keeps the stored one. The pair address
+/// is kept when the update has none, and the date the ATH API was read up to when it wasn't read.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `call_id` - The call id
+/// * `ath_price` - The highest price seen since the last update
+/// * `last_price` - The current token price
+/// * `last_mkt_cap` - The current market cap
+/// * `pair_address` - The token pair address
+/// * `ath_checked_until` - The date the ATH API was read up to, None if it couldn't be read
+/// 
+/// # Returns
+/// 
+/// A result indicating whether the ATH was saved
+pub async fn upsert_call_ath(pool: &PgPool, call_id: i32, ath_price: f64, last_price: Option<f64>, last_mkt_cap: Option<f64>, pair_address: Option<&str>, ath_checked_until: Option<chrono::DateTime<chrono::Utc>>) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO call_aths (call_id, ath_price, last_price, last_mkt_cap, pair_address, ath_checked_until, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, NOW())
+        ON CONFLICT (call_id) DO UPDATE SET
+            ath_price = GREATEST(call_aths.ath_price, EXCLUDED.ath_price),
+            last_price = COALESCE(EXCLUDED.last_price, call_aths.last_price),
+            last_mkt_cap = COALESCE(EXCLUDED.last_mkt_cap, call_aths.last_mkt_cap),
+            pair_address = COALESCE(EXCLUDED.pair_address, call_aths.pair_address),
+            ath_checked_until = COALESCE(EXCLUDED.ath_checked_until, call_aths.ath_checked_until),
+            updated_at = NOW()"
+    )
+    .bind(call_id)
+    .bind(ath_price)
+    .bind(last_price)
+    .bind(last_mkt_cap)
+    .bind(pair_address)
+    .bind(ath_checked_until)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Get the first call of a token in a chat by its token address or the address it was called with
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `address` - The token address or the called address
+/// * `chat_id` - The chat id
+/// 
+/// # Returns
+/// 
+/// The first call, None if the token wasn't called in the chat
+pub async fn get_first_call_by_address(pool: &PgPool, address: &str, chat_id: &str) -> Result<Option<Call>> {
+    let row = sqlx::query("SELECT * FROM calls WHERE (token_address = $1 OR token_mint = $1) AND chat_id = $2 ORDER BY time ASC LIMIT 1")
+    .bind(address)
+    .bind(chat_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.as_ref().map(call_from_row))
+}
+
+/// Get the calls made in a period
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `chat_id` - The chat of the calls, None for every chat
+/// * `user_tg_id` - The user who made the calls, None for every user
+/// * `period` - The period of the calls
+/// 
+/// # Returns
+/// 
+/// A Vec<Call> with the calls, oldest first
+pub async fn get_calls_in_period(pool: &PgPool, chat_id: Option<&str>, user_tg_id: Option<&str>, period: &CallPeriod) -> Result<Vec<Call>> {
+    let rows = sqlx::query(
+        "SELECT * FROM calls
+        WHERE ($1::TEXT IS NULL OR chat_id = $1)
+            AND ($2::TEXT IS NULL OR user_tg_id = $2)
+            AND ($3::TIMESTAMPTZ IS NULL OR time >= $3)
+            AND ($4::TIMESTAMPTZ IS NULL OR time < $4)
+        ORDER BY time ASC, id ASC"
+    )
+    .bind(chat_id)
+    .bind(user_tg_id)
+    .bind(period.start)
+    .bind(period.end)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(call_from_row).collect())
+}
+
+/// Get the settings of a group
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `chat_id` - The group chat ID
+/// 
+/// # Returns
+/// 
+/// The group settings, the defaults when the group never changed them
+pub async fn get_group_settings(pool: &PgPool, chat_id: &str) -> Result<GroupSettings> {
+    let row = sqlx::query("SELECT * FROM group_settings WHERE chat_id = $1")
+        .bind(chat_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(match row {
+        Some(row) => group_settings_from_row(&row),
+        None => GroupSettings::default_for(chat_id),
+    })
+}
+
+/// Build the settings of a group from a group_settings row
+fn group_settings_from_row(row: &sqlx::postgres::PgRow) -> GroupSettings {
+    GroupSettings {
+        chat_id: row.get("chat_id"),
+        title: row.get("title"),
+        global_leaderboard: row.get("global_leaderboard"),
+        auto_calls: row.get("auto_calls"),
+        require_call_prefix: row.get("require_call_prefix"),
+        allowed_chains: row.get("allowed_chains"),
+        min_liquidity: row.get("min_liquidity"),
+        min_mkt_cap: row.get("min_mkt_cap"),
+        one_call_per_user_token: row.get("one_call_per_user_token"),
+        lb_default_period: row.get("lb_default_period"),
+        digest_frequency: row.get("digest_frequency"),
+        digest_hour: row.get("digest_hour"),
+        digest_timezone: row.get("digest_timezone"),
+        digest_pin: row.get("digest_pin"),
+        digest_last_sent_at: row.get("digest_last_sent_at"),
+        milestones: row.get("milestones"),
+    }
+}
+
+/// Save the settings of a group
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `settings` - The group settings
+/// 
+/// # Returns
+/// 
+/// An empty result
+pub async fn upsert_group_settings(pool: &PgPool, settings: &GroupSettings) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO group_settings (chat_id, title, global_leaderboard, auto_calls, require_call_prefix, allowed_chains, min_liquidity, min_mkt_cap, one_call_per_user_token, lb_default_period, digest_frequency, digest_hour, digest_timezone, digest_pin, milestones)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+        ON CONFLICT (chat_id) DO UPDATE
+        SET title = COALESCE(EXCLUDED.title, group_settings.title),
+            global_leaderboard = EXCLUDED.global_leaderboard,
+            auto_calls = EXCLUDED.auto_calls,
+            require_call_prefix = EXCLUDED.require_call_prefix,
+            allowed_chains = EXCLUDED.allowed_chains,
+            min_liquidity = EXCLUDED.min_liquidity,
+            min_mkt_cap = EXCLUDED.min_mkt_cap,
+            one_call_per_user_token = EXCLUDED.one_call_per_user_token,
+            lb_default_period = EXCLUDED.lb_default_period,
+            digest_frequency = EXCLUDED.digest_frequency,
+            digest_hour = EXCLUDED.digest_hour,
+            digest_timezone = EXCLUDED.digest_timezone,
+            digest_pin = EXCLUDED.digest_pin,
+            milestones = EXCLUDED.milestones,
+            updated_at = NOW()"
+    )
+    .bind(&settings.chat_id)
+    .bind(&settings.title)
+    .bind(settings.global_leaderboard)
+    .bind(settings.auto_calls)
+    .bind(settings.require_call_prefix)
+    .bind(&settings.allowed_chains)
+    .bind(settings.min_liquidity)
+    .bind(settings.min_mkt_cap)
+    .bind(settings.one_call_per_user_token)
+    .bind(&settings.lb_default_period)
+    .bind(&settings.digest_frequency)
+    .bind(settings.digest_hour)
+    .bind(&settings.digest_timezone)
+    .bind(settings.digest_pin)
+    .bind(&settings.milestones)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Check if a user already called a token in a chat
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `user_tg_id` - The user's Telegram ID
+/// * `token_address` - The token address
+/// * `chat_id` - The chat ID
+/// 
+/// # Returns
+/// 
+/// A boolean indicating if the user already called the token in the chat
+pub async fn has_user_called_token_in_chat(pool: &PgPool, user_tg_id: &str, token_address: &str, chat_id: &str) -> Result<bool> {
+    let called: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM calls WHERE user_tg_id = $1 AND token_address = $2 AND chat_id = $3)")
+        .bind(user_tg_id)
+        .bind(token_address)
+        .bind(chat_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(called)
+}
+
+/// Opt a group in or out of the global leaderboard
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `chat_id` - The group chat ID
+/// * `title` - The group title
+/// * `global_leaderboard` - Whether the group calls count in the global leaderboard
+/// 
+/// # Returns
+/// 
+/// An empty result
+pub async fn set_group_global_leaderboard(pool: &PgPool, chat_id: &str, title: Option<&str>, global_leaderboard: bool) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO group_settings (chat_id, title, global_leaderboard)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (chat_id) DO UPDATE
+        SET title = COALESCE(EXCLUDED.title, group_settings.title), global_leaderboard = EXCLUDED.global_leaderboard, updated_at = NOW()"
+    )
+    .bind(chat_id)
+    .bind(title)
+    .bind(global_leaderboard)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Get the calls made in a period in the groups that opted in to the global leaderboard
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `user_tg_id` - The user who made the calls, None for every user
+/// * `period` - The period of the calls
+/// 
+/// # Returns
+/// 
+/// A Vec<Call> with the calls, oldest first
+pub async fn get_global_calls_in_period(pool: &PgPool, user_tg_id: Option<&str>, period: &CallPeriod) -> Result<Vec<Call>> {
+    let rows = sqlx::query(
+        "SELECT calls.* FROM calls
+        JOIN group_settings ON group_settings.chat_id = calls.chat_id AND group_settings.global_leaderboard
+        WHERE ($1::TEXT IS NULL OR calls.user_tg_id = $1)
+            AND ($2::TIMESTAMPTZ IS NULL OR calls.time >= $2)
+            AND ($3::TIMESTAMPTZ IS NULL OR calls.time < $3)
+        ORDER BY calls.time ASC, calls.id ASC"
+    )
+    .bind(user_tg_id)
+    .bind(period.start)
+    .bind(period.end)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(call_from_row).collect())
+}
+
+/// Get the groups opted in to the global leaderboard where a user made calls
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `user_tg_id` - The user's Telegram ID
+/// 
+/// # Returns
+/// 
+/// A Vec<GroupActivity> with the number of calls of the user in each group, most calls first
+pub async fn get_user_global_groups(pool: &PgPool, user_tg_id: &str) -> Result<Vec<GroupActivity>> {
+    let rows = sqlx::query(
+        "SELECT group_settings.chat_id, group_settings.title, COUNT(*) AS calls FROM calls
+        JOIN group_settings ON group_settings.chat_id = calls.chat_id AND group_settings.global_leaderboard
+        WHERE calls.user_tg_id = $1
+        GROUP BY group_settings.chat_id, group_settings.title
+        ORDER BY calls DESC"
+    )
+    .bind(user_tg_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(|row| GroupActivity {
+        chat_id: row.get("chat_id"),
+        title: row.get("title"),
+        calls: row.get("calls"),
+    }).collect())
+}
+
+/// Get the groups with a leaderboard digest
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// 
+/// # Returns
+/// 
+/// A Vec<GroupSettings> with the settings of the groups whose digest isn't off
+pub async fn get_digest_groups(pool: &PgPool) -> Result<Vec<GroupSettings>> {
+    let rows = sqlx::query("SELECT * FROM group_settings WHERE digest_frequency <> 'off'")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.iter().map(group_settings_from_row).collect())
+}
+
+/// Set when the last leaderboard digest of a group was posted
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `chat_id` - The group chat ID
+/// * `sent_at` - When the digest was posted
+/// 
+/// # Returns
+/// 
+/// An empty result
+pub async fn set_group_digest_sent(pool: &PgPool, chat_id: &str, sent_at: chrono::DateTime<chrono::Utc>) -> Result<()> {
+    sqlx::query("UPDATE group_settings SET digest_last_sent_at = $1 WHERE chat_id = $2")
+        .bind(sent_at)
+        .bind(chat_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Get the tokens of the recent calls
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `days` - How many days back the calls are recent
+/// * `limit` - The maximum number of tokens, the most recently called first
+/// 
+/// # Returns
+/// 
+/// A Vec<String> with the Solana token addresses of the calls
+pub async fn get_recent_calls_tokens(pool: &PgPool, days: i32, limit: i64) -> Result<Vec<String>> {
+    let tokens: Vec<String> = sqlx::query_scalar(
+        "SELECT token_address FROM calls
+        WHERE time >= NOW() - make_interval(days => $1) AND token_address IS NOT NULL AND token_address NOT LIKE '0x%'
+        GROUP BY token_address
+        ORDER BY MAX(time) DESC
+        LIMIT $2"
+    )
+    .bind(days)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(tokens)
+}
+
+/// Get the recent first call of a token in each chat
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `token_address` - The token address
+/// * `days` - How many days back the calls are recent
+/// 
+/// # Returns
+/// 
+/// A Vec<Call> with the first call of the token in each chat, when it is recent
+pub async fn get_recent_first_calls_by_token(pool: &PgPool, token_address: &str, days: i32) -> Result<Vec<Call>> {
+    let rows = sqlx::query(
+        "SELECT * FROM (
+            SELECT DISTINCT ON (chat_id) * FROM calls WHERE token_address = $1 ORDER BY chat_id, time ASC
+        ) first_calls
+        WHERE time >= NOW() - make_interval(days => $2)"
+    )
+    .bind(token_address)
+    .bind(days)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(call_from_row).collect())
+}
+
+/// Record the milestones reached by a call
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `call_id` - The call id
+/// * `multipliers` - The milestones reached
+/// 
+/// # Returns
+/// 
+/// A Vec<f64> with the milestones that weren't recorded yet, the others were already announced
+pub async fn insert_call_milestones(pool: &PgPool, call_id: i32, multipliers: &[f64]) -> Result<Vec<f64>> {
+    let inserted: Vec<f64> = sqlx::query_scalar(
+        "INSERT INTO call_milestones (call_id, multiplier)
+        SELECT $1, UNNEST($2::FLOAT[])
+        ON CONFLICT (call_id, multiplier) DO NOTHING
+        RETURNING multiplier"
+    )
+    .bind(call_id)
+    .bind(multipliers)
+    .fetch_all(pool)
+    .await?;
+    Ok(inserted)
+}
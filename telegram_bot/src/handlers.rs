@@ -15,15 +15,16 @@ use teloxide::Bot;
 use serde::{Serialize, Deserialize};
 use crate::db::*;
 use crate::utils::helpers::*;
+use crate::utils::market_data::market_data;
 use axum::extract::State;
 use crate::*;
 use crate::commands::*;
 use teloxide::payloads::AnswerCallbackQuerySetters;
 use teloxide::payloads::EditMessageTextSetters;
 use teloxide::prelude::Requester;
-use chrono::{DateTime, Utc};
 use regex::Regex;
 use axum::extract::Path;
+use axum::extract::Query;
 use std::sync::Arc;
 use sqlx::Pool;
 use sqlx::Postgres;
@@ -44,19 +45,12 @@ pub async fn handle_callback_clear_call( bot: &teloxide::Bot, query: &teloxide::
 pub async fn handle_callback_refresh_call(data: String, bot: &teloxide::Bot, query: &teloxide::types::CallbackQuery, pool: SafePool) -> Result<()> {
     let call_id = data.strip_prefix("refresh:").unwrap_or_default();
     let call = crate::db::get_call_by_id(&pool, call_id.parse::<i64>().expect("Could not parse call id, maybe the value is not a number or to big.")).await?;
-    let token_pair_token_address = get_pair_token_pair_and_token_address(&call.token_mint).await?;
-    let token_address = token_pair_token_address["tokenAddress"].as_str().unwrap_or("");
-    let chain = token_pair_token_address["chainName"].as_str().unwrap_or("");
-    let scanner_response = get_scanner_search(
-&call.token_mint).await?;
-
-    let created_datetime_str = scanner_response["pair"]["pairCreatedAt"].as_str().unwrap_or("");
-    let datetime: DateTime<Utc> = created_datetime_str.parse().expect("Failed to parse datetime.");
-    let unix_timestamp_milis = datetime.timestamp_millis();
-
-    let ath_response = get_ath(unix_timestamp_milis, &call.token_mint, &call.chain).await?;
-    log::info!("ath_response: {:?}", ath_response);
-    let holders_response = get_holders(token_address).await?;
+    let pair_stats = market_data().pair_stats(&call.token_mint).await?;
+    // The call is still refreshed when the ATH or the holders can't be fetched
+    let ath = market_data().ath(&pair_stats.pair.token_address, &pair_stats.pair.chain, pair_stats.created_at.unwrap_or_default()).await.unwrap_or_default();
+    log::info!("ath: {:?}", ath);
+    let holders = market_data().holders(&pair_stats.pair.token_address).await.unwrap_or_default();
+    let safety = utils::safety::get_token_safety(&pair_stats, &holders).await;
     let user = get_user(&pool, call.user_tg_id.as_str()).await?;
     if let Some(ref message) = query.message {
         match message {
@@ -64,12 +58,12 @@ pub async fn handle_callback_refresh_call(data: String, bot: &teloxide::Bot, que
                 let call_info_str = utils::helpers::get_call_info(&call.token_address.clone(), &pool, msg).await?;
                 let call_message = call_message(
                     &pool,
-                    &ath_response,
-                    &holders_response,
-                    &scanner_response,
+                    &ath,
+                    &holders,
+                    &safety,
+                    &pair_stats,
                     call_info_str,
-                    user,
-                    chain
+                    user
                 ).await;
                 let keyboard = create_call_keyboard_after_just_scanning(call_id, call.token_address.as_str());
                 bot.edit_message_text(msg.chat.id, msg.id, call_message)
@@ -238,6 +232,9 @@ pub async fn handle_message(
                     bot.send_message(msg.chat.id, "Invalid SOL address").await?;
                 }
             }
+            else if GROUP_SETTING_PROMPTS.iter().any(|(_, prompt)| reply_to_message.text().unwrap_or_default().starts_with(prompt)) {
+                handle_group_setting_reply(text, reply_to_message.text().unwrap_or_default(), &bot, &msg, &pool).await?;
+            }
             else if reply_to_message.text().unwrap_or_default().starts_with("Enter the amount of SOL to withdraw") {
                 if let Ok(amount) = text.parse::<f64>() {
                     set_user_withdraw_sol_amount(&pool, msg.from.as_ref().unwrap().id.to_string().as_str(), amount.to_string().as_str()).await?;
@@ -309,6 +306,20 @@ pub async fn handle_message(
             }
             else if reply_to_message.text().unwrap_or_default().starts_with("Send '<multiplier>,<%_token_position_amount_to_sell>' (eg: '1.5,100' that means if the price goes up 1.5x, sell 100% of the position)") {
                 println!("@handle_message/ text: {:?}", text);
+                if text.trim_start().starts_with('$') {
+                    let mc_target = match parse_market_cap_target_message(text, "take_profit") {
+                        Ok(mc_target) => mc_target,
+                        Err(_) => {
+                            bot.send_message(msg.chat.id, "Invalid market cap take profit format. Please use format: '$<market_cap>,<%_token_position_amount_to_sell>'").await?;
+                            return Ok(());
+                        }
+                    };
+                    add_user_mc_target_user_settings(msg.clone().from.unwrap().id.to_string().as_str(), mc_target, &pool).await?;
+                    bot.send_message(msg.chat.id, "Market cap take profit set").await?;
+                    let last_token = get_user_last_sent_token(&pool, msg.from.as_ref().unwrap().id.to_string().as_str()).await?;
+                    token_address_buy_info_handler(last_token.as_str(), &bot, &msg, &pool).await?;
+                    return Ok(());
+                }
                 let take_profits = match parse_take_profit_message(text) {
                     Ok(tp) => {
                         tp
@@ -331,6 +342,20 @@ pub async fn handle_message(
             }
             else if reply_to_message.text().unwrap_or_default().starts_with("Send '<%down>,<%_token_position_amount_to_sell>' (eg: '10,100' that means if the price goes down 10%, sell 100% of the position)") {
                 println!("@handle_message/ text: {:?}", text);
+                if text.trim_start().starts_with('$') {
+                    let mc_target = match parse_market_cap_target_message(text, "stop_loss") {
+                        Ok(mc_target) => mc_target,
+                        Err(_) => {
+                            bot.send_message(msg.chat.id, "Invalid market cap stop loss format. Please use format: '$<market_cap>,<%_token_position_amount_to_sell>'").await?;
+                            return Ok(());
+                        }
+                    };
+                    add_user_mc_target_user_settings(msg.clone().from.unwrap().id.to_string().as_str(), mc_target, &pool).await?;
+                    bot.send_message(msg.chat.id, "Market cap stop loss set").await?;
+                    let last_token = get_user_last_sent_token(&pool, msg.from.as_ref().unwrap().id.to_string().as_str()).await?;
+                    token_address_buy_info_handler(last_token.as_str(), &bot, &msg, &pool).await?;
+                    return Ok(());
+                }
                 let stop_loss = match parse_stop_loss_message(text) {
                     Ok(sl) => sl,
                     Err(e) => {
@@ -347,6 +372,156 @@ pub async fn handle_message(
                     Err(e) => log::error!("Failed to open buy menu for token address: {:?}", e),
                 }   
             }
+            else if reply_to_message.text().unwrap_or_default().starts_with("Send '<sol_per_buy>,<buys>,<interval_minutes>'") {
+                println!("@handle_message/ text: {:?}", text);
+                let (sol_per_buy, buys, interval_minutes) = match parse_dca_message(text) {
+                    Ok(dca) => dca,
+                    Err(_) => {
+                        bot.send_message(msg.chat.id, "Invalid DCA format. Please use format: '<sol_per_buy>,<buys>,<interval_minutes>'").await?;
+                        return Ok(());
+                    }
+                };
+                let user_tg_id = msg.from.as_ref().unwrap().id.to_string();
+                let token_address = get_user_last_sent_token(&pool, &user_tg_id).await?;
+                let order_id = db::insert_recurring_order(&pool, &user_tg_id, &msg.chat.id.to_string(), &token_address, "dca", Some(sol_per_buy), buys, interval_minutes * 60).await?;
+                println!("@handle_message/ DCA order {} created", order_id);
+                bot.send_message(msg.chat.id, format!("🔁 DCA #{} started: buying {} SOL every {} minutes, {} times", order_id, sol_per_buy, interval_minutes, buys)).await?;
+            }
+            else if reply_to_message.text().unwrap_or_default().starts_with("Send '<slices>,<window_minutes>'") {
+                println!("@handle_message/ text: {:?}", text);
+                let (slices, window_minutes) = match parse_twap_message(text) {
+                    Ok(twap) => twap,
+                    Err(_) => {
+                        bot.send_message(msg.chat.id, "Invalid TWAP format. Please use format: '<slices>,<window_minutes>'").await?;
+                        return Ok(());
+                    }
+                };
+                // The token is at the end of the prompt
+                let token_address = reply_to_message.text().unwrap_or_default().split("token: ").nth(1).unwrap_or_default().trim().to_string();
+                if token_address.is_empty() {
+                    bot.send_message(msg.chat.id, "Token not found, open the sell menu again").await?;
+                    return Ok(());
+                }
+                let user_tg_id = msg.from.as_ref().unwrap().id.to_string();
+                // Slices are spread evenly, the first one runs right away
+                let interval_secs = if slices > 1 { window_minutes * 60 / (slices - 1) } else { window_minutes * 60 };
+                let order_id = db::insert_recurring_order(&pool, &user_tg_id, &msg.chat.id.to_string(), &token_address, "twap", None, slices, interval_secs).await?;
+                println!("@handle_message/ TWAP order {} created", order_id);
+                bot.send_message(msg.chat.id, format!("🔁 TWAP #{} started: selling the position in {} slices over {} minutes", order_id, slices, window_minutes)).await?;
+            }
+            else if reply_to_message.text().unwrap_or_default().starts_with("Send '<mc|price>,<value>,<hours_to_expire>'") {
+                println!("@handle_message/ text: {:?}", text);
+                let (trigger_kind, trigger_value, expires_in_hours) = match parse_limit_buy_message(text) {
+                    Ok(limit_buy) => limit_buy,
+                    Err(_) => {
+                        bot.send_message(msg.chat.id, "Invalid limit buy format. Please use format: '<mc|price>,<value>,<hours_to_expire>'").await?;
+                        return Ok(());
+                    }
+                };
+                let user_tg_id = msg.from.as_ref().unwrap().id.to_string();
+                let token_address = get_user_last_sent_token(&pool, &user_tg_id).await?;
+                let user_settings = get_user_settings(&pool, &user_tg_id).await?;
+                let sol_amount = user_settings.buy_amount.parse::<f64>().unwrap_or(0.2);
+
+                // The price engine tracks prices, a market cap target is turned into the price at that market cap
+                let (trigger_price, trigger_mc) = if trigger_kind == "mc" {
+                    let circulating_supply = match market_data().circulating_supply(&token_address).await {
+                        Ok(circulating_supply) => circulating_supply,
+                        Err(_) => {
+                            bot.send_message(msg.chat.id, "Market cap not available for this token, set a price limit instead").await?;
+                            return Ok(());
+                        }
+                    };
+                    (trigger_value / circulating_supply, Some(trigger_value))
+                } else {
+                    (trigger_value, None)
+                };
+
+                let order_id = db::insert_limit_order(&pool, &user_tg_id, &msg.chat.id.to_string(), &token_address, sol_amount, trigger_price, trigger_mc, expires_in_hours).await?;
+                println!("@handle_message/ limit order {} created, trigger_price: {}", order_id, trigger_price);
+                bot.send_message(msg.chat.id, format!("Limit buy #{} set: {} SOL when {} ≤ ${}, expires in {} hours", order_id, sol_amount, if trigger_kind == "mc" { "MC" } else { "price" }, trigger_value, expires_in_hours)).await?;
+            }
+            else if reply_to_message.text().unwrap_or_default().starts_with("Send the preset name") {
+                let name = text.trim();
+                if name.is_empty() || name.chars().count() > 32 {
+                    bot.send_message(msg.chat.id, "Invalid preset name, use 1 to 32 characters").await?;
+                    return Ok(());
+                }
+                let user_tg_id = msg.from.as_ref().unwrap().id.to_string();
+                let user_settings = get_user_settings(&pool, &user_tg_id).await?;
+                let preset_id = db::insert_strategy_preset(&pool, &user_tg_id, name, &user_settings.take_profits, &user_settings.stop_losses, &user_settings.trailing_stops, &user_settings.mc_targets, &user_settings.buy_amount).await?;
+                println!("@handle_message/ strategy preset {} saved as {:?}", preset_id, name);
+                bot.send_message(msg.chat.id, format!("Preset {} saved", name)).await?;
+                let last_token = get_user_last_sent_token(&pool, &user_tg_id).await?;
+                match token_address_buy_info_handler(last_token.as_str(), &bot, &msg, &pool).await {
+                    Ok(_) => (),
+                    Err(e) => log::error!("Failed to open buy menu for token address: {:?}", e),
+                }
+            }
+            else if reply_to_message.text().unwrap_or_default().starts_with("Send '<multiplier>,<%_to_sell>' to add a take profit") || reply_to_message.text().unwrap_or_default().starts_with("Send '<%down>,<%_to_sell>' to add a stop loss") {
+                let prompt = reply_to_message.text().unwrap_or_default();
+                let is_take_profit = prompt.contains("take profit");
+                // The position is at the end of the prompt
+                let position_id = prompt.split("position: ").nth(1).unwrap_or_default().trim().parse::<i32>().unwrap_or(0);
+                let user_tg_id = msg.from.as_ref().unwrap().id.to_string();
+                let mut position = match get_active_positions(&pool, &user_tg_id).await?.into_iter().find(|position| position.id == position_id) {
+                    Some(position) => position,
+                    None => {
+                        bot.send_message(msg.chat.id, "Position not found, it may be completed").await?;
+                        return Ok(());
+                    }
+                };
+                if text.trim_start().starts_with('$') {
+                    match parse_market_cap_target_message(text, if is_take_profit { "take_profit" } else { "stop_loss" }) {
+                        Ok(mc_target) => position.mc_targets.push(mc_target),
+                        Err(_) => {
+                            bot.send_message(msg.chat.id, "Invalid market cap format. Please use format: '$<market_cap>,<%_to_sell>'").await?;
+                            return Ok(());
+                        }
+                    }
+                } else if is_take_profit {
+                    match parse_take_profit_message(text) {
+                        Ok(take_profit) => position.take_profits.push(take_profit),
+                        Err(_) => {
+                            bot.send_message(msg.chat.id, "Invalid take profit format. Please use format: '<multiplier>,<%_to_sell>'").await?;
+                            return Ok(());
+                        }
+                    }
+                } else {
+                    match parse_stop_loss_message(text) {
+                        Ok(stop_loss) => position.stop_losses.push(stop_loss),
+                        Err(_) => {
+                            bot.send_message(msg.chat.id, "Invalid stop loss format. Please use format: '<%_down>,<%_to_sell>'").await?;
+                            return Ok(());
+                        }
+                    }
+                }
+                db::set_position_ladders(&pool, position.id, &position.take_profits, &position.stop_losses, &position.trailing_stops, &position.mc_targets).await?;
+                println!("@handle_message/ position {} targets updated", position.id);
+                let presets = db::get_or_create_strategy_presets(&pool, &user_tg_id).await?;
+                bot.send_message(msg.chat.id, create_position_targets_message(&position))
+                .parse_mode(teloxide::types::ParseMode::Html)
+                .reply_markup(create_position_targets_keyboard(&position, &presets))
+                .await?;
+            }
+            else if reply_to_message.text().unwrap_or_default().starts_with("Send '<%trail>,<%_token_position_amount_to_sell>' (eg: '20,100' that means if the price falls 20% from the highest price since entry, sell 100% of the position)") {
+                println!("@handle_message/ text: {:?}", text);
+                let trailing_stop = match parse_trailing_stop_message(text) {
+                    Ok(ts) => ts,
+                    Err(_) => {
+                        bot.send_message(msg.chat.id, "Invalid trailing stop format. Please use format: '<%_trail>,<%_token_position_amount_to_sell>' with a trail and a percentage to sell between 0 and 100").await?;
+                        return Ok(());
+                    }
+                };
+                println!("@handle_message/ trailing_stop: {:?}", trailing_stop);
+                add_user_trailing_stop_user_settings(msg.clone().from.unwrap().id.to_string().as_str(), trailing_stop, &pool).await?;
+                bot.send_message(msg.chat.id, "Trailing stop set").await?;
+                let last_token = get_user_last_sent_token(&pool, msg.from.as_ref().unwrap().id.to_string().as_str()).await.unwrap();
+                match token_address_buy_info_handler(last_token.as_str(), &bot, &msg, &pool).await {
+                    Ok(_) => (),
+                    Err(e) => log::error!("Failed to open buy menu for token address: {:?}", e),
+                }
+            }
         }
         if is_pnl_command(text) {
             log::info!("Message is a pnl command");
@@ -361,6 +536,24 @@ pub async fn handle_message(
                 Err(e) => log::error!("Failed to leaderboard: {:?}", e),
             }
         }
+        else if utils::helpers::is_callers_command(text) {
+            match callers(&msg, &bot, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to callers: {:?}", e),
+            }
+        }
+        else if text.starts_with("/glb") {
+            match global_leaderboard(&msg, &bot, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to global leaderboard: {:?}", e),
+            }
+        }
+        else if text.starts_with("/global") && !msg.chat.is_private() {
+            match set_global_leaderboard(&msg, &bot, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to set global leaderboard: {:?}", e),
+            }
+        }
         else if msg.chat.is_private() {
                 if text.starts_with("/start user_") {
                     // get the user id
@@ -372,6 +565,14 @@ pub async fn handle_message(
                         }
                     }
                 }
+                else if text.starts_with("/start profile_") {
+                    if let Some(user_id) = text.strip_prefix("/start profile_") {
+                        match caller_profile(user_id, &bot, &msg, &pool).await {
+                            Ok(_) => (),
+                            Err(e) => log::error!("Failed to caller profile: {:?}", e),
+                        }
+                    }
+                }
                 else if text.starts_with("/start r-") {
                     let uuid = text.strip_prefix("/start r-").unwrap();
                     println!("@handle_message/ uuid: {:?}", uuid);
@@ -386,6 +587,12 @@ pub async fn handle_message(
                         Err(e) => log::error!("Failed to sell token: {:?}", e),
                     }
                 }
+                else if is_portfolio_command(text) {
+                    match portfolio(&msg, &bot, &pool).await {
+                        Ok(_) => (),
+                        Err(e) => log::error!("Failed to portfolio: {:?}", e),
+                    }
+                }
                 else if text.starts_with("/start") {
                     let user_tg_id = msg.from.as_ref().unwrap().id.to_string();
                     let username = msg.from.as_ref().unwrap().username.clone().unwrap_or("Unknown username".to_string());
@@ -402,13 +609,15 @@ pub async fn handle_message(
                     }
                 }
         }
+        else if text.starts_with("/groupsettings") {
+            match group_settings(&msg, &bot, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to group settings: {:?}", e),
+            }
+        }
         // Check if there's a valid solana address in the message
         else if there_is_valid_solana_address(text) || there_is_valid_eth_address(text) {
-            // Get the valid solana address
-            let address = address_handler(text).await?;
-            let call_info_str = get_call_info(&address.clone(), &pool, &msg).await?;
-            // Call the address
-            match call(&address, &bot, &msg, call_info_str, &pool).await {
+            match group_call(text, &msg, &bot, &pool).await {
                 Ok(_) => (),
                 Err(e) => log::error!("Failed to call: {:?}", e),
             }
@@ -495,6 +704,48 @@ pub async fn handle_callback_query(
                 Err(e) => log::error!("Failed to delete take profit: {:?}", e),
             }
         }
+        else if data == "trades_journal" {
+            match handle_trades_journal_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to handle trades journal callback: {:?}", e),
+            }
+        }
+        else if data == "recurring_orders" {
+            match handle_recurring_orders_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to handle recurring orders callback: {:?}", e),
+            }
+        }
+        else if data.starts_with("pause_recurring_order:") || data.starts_with("resume_recurring_order:") || data.starts_with("cancel_recurring_order:") {
+            match handle_update_recurring_order_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to update recurring order: {:?}", e),
+            }
+        }
+        else if data == "dca_buy" {
+            match handle_dca_buy_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to handle DCA buy callback: {:?}", e),
+            }
+        }
+        else if data.starts_with("twap_sell:") {
+            match handle_twap_sell_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to handle TWAP sell callback: {:?}", e),
+            }
+        }
+        else if data == "limit_buy" {
+            match handle_limit_buy_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to handle limit buy callback: {:?}", e),
+            }
+        }
+        else if data.starts_with("cancel_limit_order:") {
+            match handle_cancel_limit_order_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to cancel limit order: {:?}", e),
+            }
+        }
         else if data.starts_with("limit_orders") {
             match handle_limit_orders_callback(data.to_string(), &bot, &query, &pool).await {
                 Ok(_) => (),
@@ -507,6 +758,18 @@ pub async fn handle_callback_query(
                 Err(e) => log::error!("Failed to delete stop loss: {:?}", e),
             }
         }
+        else if data.starts_with("delete_mc_target:") {
+            match handle_delete_mc_target_user_settings_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to delete market cap target: {:?}", e),
+            }
+        }
+        else if data.starts_with("delete_trailing_stop:") {
+            match handle_delete_trailing_stop_user_settings_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to delete trailing stop: {:?}", e),
+            }
+        }
         else if data == "back" {
             let user_tg_id = query.from.id.to_string();
             let user = get_user(&pool, &user_tg_id).await?;
@@ -524,6 +787,54 @@ pub async fn handle_callback_query(
                 Err(e) => log::error!("Failed to set custom gas: {:?}", e),
             }
         }
+        else if data == "portfolio" || data == "refresh_portfolio" {
+            match handle_portfolio_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to handle portfolio callback: {:?}", e),
+            }
+        }
+        else if data.starts_with("group_toggle:") || data.starts_with("group_set:") {
+            match handle_group_settings_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to handle group settings: {:?}", e),
+            }
+        }
+        else if data.starts_with("pnl_card:") {
+            match handle_pnl_card_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to send pnl card: {:?}", e),
+            }
+        }
+        else if data.starts_with("preset:") {
+            match handle_select_strategy_preset_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to select strategy preset: {:?}", e),
+            }
+        }
+        else if data == "save_preset" {
+            match handle_save_strategy_preset_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to save strategy preset: {:?}", e),
+            }
+        }
+        else if data == "strategy_presets" || data.starts_with("delete_preset:") {
+            match handle_strategy_presets_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to handle strategy presets: {:?}", e),
+            }
+        }
+        else if data.starts_with("position_targets:") || data.starts_with("apply_preset:") || data.starts_with("del_pos_level:") {
+            match handle_position_targets_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to handle position targets: {:?}", e),
+            }
+        }
+        else if data.starts_with("pos_add_take_profit:") || data.starts_with("pos_add_stop_loss:") {
+            match handle_position_add_level_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to add position level: {:?}", e),
+            }
+        }
         else if data == "positions" {
             match handle_positions_callback(data.to_string(), &bot, &query, &pool).await {
                 Ok(_) => (),
@@ -572,6 +883,12 @@ pub async fn handle_callback_query(
                 Err(e) => log::error!("Failed to add stop loss: {:?}", e),
             }
         }
+        else if data == "add_trailing_stop" {
+            match handle_add_trailing_stop_user_settings_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to add trailing stop: {:?}", e),
+            }
+        }
         else if data.starts_with("set_jito_tip_amount") {
             match handle_set_jito_tip_amount_callback(data.to_string(), &bot, &query, &pool).await {
                 Ok(_) => (),
@@ -620,6 +937,18 @@ pub async fn handle_callback_query(
                 Err(e) => log::error!("Failed to set withdraw sol address: {:?}", e),
             }
         }
+        else if data == "copy_trade" {
+            match handle_copy_trade_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to handle copy trade callback: {:?}", e),
+            }
+        }
+        else if data == "discover_wallets" {
+            match handle_discover_wallets_callback(data.to_string(), &bot, &query, &pool).await {
+                Ok(_) => (),
+                Err(e) => log::error!("Failed to handle discover wallets callback: {:?}", e),
+            }
+        }
         else if data.starts_with("wallet") {
             match handle_wallet_callback(data.to_string(), &bot, &query, &pool).await {
                 Ok(_) => (),
@@ -760,7 +1089,7 @@ pub async fn sell_position_handler(
 ) -> impl IntoResponse {
     println!("@sell_position_handler/ payload: {:?}", payload);
 
-    execute_swap_no_chat(&pool, &payload.token_address, "So11111111111111111111111111111111111111112", payload.user_tg_id, payload.sell_percentage).await.expect("Could not execute swap");
+    execute_swap_no_chat(&pool, &payload.token_address, "So11111111111111111111111111111111111111112", payload.user_tg_id, payload.sell_percentage, None, "manual").await.expect("Could not execute swap");
 
     (StatusCode::OK, "sold").into_response()
 }   
@@ -783,6 +1112,181 @@ pub async fn get_positions_handler(
     (StatusCode::OK, Json(positions)).into_response()
 }
 
+/// Get trades handler
+/// 
+/// # Arguments
+/// 
+/// * `user_tg_id` - The user's Telegram ID
+/// * `pool` - The PostgreSQL connection pool
+/// 
+/// # Returns
+/// 
+/// A JSON response with the latest 100 trades of the user, newest first
+pub async fn get_trades_handler(
+    Path(user_tg_id): Path<String>, 
+    State(pool): State<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    match get_user_trades(&pool, &user_tg_id, 100).await {
+        Ok(trades) => (StatusCode::OK, Json(trades)).into_response(),
+        Err(e) => {
+            log::error!("Failed to get trades: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Could not get trades").into_response()
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct CopyTradePayload {
+    pub user_tg_id: String,
+    pub token_address: String,
+    pub side: String,
+    pub signature: String,
+    pub sol_amount: f64,
+}
+
+/// Post copy trade handler
+/// 
+/// # Description
+/// 
+/// The mini app signs and sends the copy trades, it posts each one that landed to journal it
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The PostgreSQL connection pool
+/// * `payload` - The copy trade
+/// 
+/// # Returns
+/// 
+/// A response saying if the trade was booked
+pub async fn post_copy_trade_handler(
+    State(pool): State<Arc<Pool<Postgres>>>,
+    Json(payload): Json<CopyTradePayload>,
+) -> impl IntoResponse {
+    println!("@post_copy_trade_handler/ payload: {:?}", payload);
+    match book_copy_trade(&pool, &payload.user_tg_id, &payload.token_address, &payload.side, &payload.signature, payload.sol_amount).await {
+        Ok(_) => (StatusCode::OK, "booked").into_response(),
+        Err(e) => {
+            log::error!("Failed to book copy trade {}: {:?}", payload.signature, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Could not book copy trade").into_response()
+        }
+    }
+}
+
+/// Query parameters of the callers ranking
+/// 
+/// # Fields
+/// 
+/// * `period` - The period, as in the callers command, eg "30d" or "all"
+/// * `metric` - The ranking metric, as in the callers command, eg "avg" or "hits"
+/// * `min_calls` - The minimum number of calls to be ranked
+#[derive(Debug, Deserialize)]
+pub struct CallersQuery {
+    pub period: Option<String>,
+    pub metric: Option<String>,
+    pub min_calls: Option<usize>,
+}
+
+/// Parse the query parameters of a callers ranking
+/// 
+/// # Arguments
+/// 
+/// * `query` - The query parameters
+/// 
+/// # Returns
+/// 
+/// The period, metric and minimum calls, the /callers defaults for the missing ones, or the invalid parameter
+fn parse_callers_query(query: &CallersQuery) -> Result<(CallPeriod, LeaderboardMetric, usize), &'static str> {
+    let (default_period, default_metric, default_min_calls) = parse_callers_command("/callers");
+    let period = match query.period.as_deref() {
+        Some(period) => parse_period(period).ok_or("Invalid period")?,
+        None => default_period,
+    };
+    let metric = match query.metric.as_deref() {
+        Some(metric) => LeaderboardMetric::parse(metric).ok_or("Invalid metric")?,
+        None => default_metric,
+    };
+    Ok((period, metric, query.min_calls.unwrap_or(default_min_calls)))
+}
+
+/// Get callers handler
+/// 
+/// # Arguments
+/// 
+/// * `chat_id` - The group chat ID
+/// * `query` - The period, metric and minimum calls of the ranking
+/// * `pool` - The PostgreSQL connection pool
+/// 
+/// # Returns
+/// 
+/// A JSON response with the callers ranking of the group, defaulting to the /callers defaults
+pub async fn get_callers_handler(
+    Path(chat_id): Path<String>,
+    Query(query): Query<CallersQuery>,
+    State(pool): State<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    let (period, metric, min_calls) = match parse_callers_query(&query) {
+        Ok(params) => params,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    match get_callers_ranking(&pool, Some(chat_id.as_str()), period, metric, min_calls).await {
+        Ok(ranking) => (StatusCode::OK, Json(ranking)).into_response(),
+        Err(e) => {
+            log::error!("Failed to get callers: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Could not get callers").into_response()
+        }
+    }
+}
+
+/// Get global callers handler
+/// 
+/// # Arguments
+/// 
+/// * `query` - The period, metric and minimum calls of the ranking
+/// * `pool` - The PostgreSQL connection pool
+/// 
+/// # Returns
+/// 
+/// A JSON response with the callers ranking of the groups in the global leaderboard
+pub async fn get_global_callers_handler(
+    Query(query): Query<CallersQuery>,
+    State(pool): State<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    let (period, metric, min_calls) = match parse_callers_query(&query) {
+        Ok(params) => params,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    match get_callers_ranking(&pool, None, period, metric, min_calls).await {
+        Ok(ranking) => (StatusCode::OK, Json(ranking)).into_response(),
+        Err(e) => {
+            log::error!("Failed to get global callers: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Could not get callers").into_response()
+        }
+    }
+}
+
+/// Get caller profile handler
+/// 
+/// # Arguments
+/// 
+/// * `user_tg_id` - The caller Telegram ID
+/// * `pool` - The PostgreSQL connection pool
+/// 
+/// # Returns
+/// 
+/// A JSON response with the public profile of the caller
+pub async fn get_caller_profile_handler(
+    Path(user_tg_id): Path<String>,
+    State(pool): State<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    match get_caller_profile(&pool, &user_tg_id).await {
+        Ok(profile) => (StatusCode::OK, Json(profile)).into_response(),
+        Err(e) => {
+            log::error!("Failed to get caller profile: {:?}", e);
+            (StatusCode::NOT_FOUND, "Could not get caller profile").into_response()
+        }
+    }
+}
+
 /// Handle buy callback
 /// 
 /// # Description
@@ -832,6 +1336,25 @@ pub struct SwapSolRequest {
     pub slippage: f64,
 }
 
+/// The wallet balance changes of a confirmed swap, as returned by the solana app
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SwapFill {
+    pub token_mint: String,
+    pub token_delta: f64, // Raw token amount received (buy) or sent (sell, negative)
+    pub token_ui_delta: f64, // Same change in ui amount
+    pub sol_delta_lamports: i64, // Lamports change of the wallet, network fee included
+    pub fee_lamports: u64, // Network fee of the swap transaction
+    pub tip_lamports: u64, // Jito tip of the bundle, paid by a separate transaction
+}
+
+/// The result of a swap sent to the solana app
+#[derive(Debug, Clone)]
+pub struct SwapResult {
+    pub signature: String,
+    pub fill: Option<SwapFill>, // None if the solana app could not read the confirmed transaction
+    pub realised_pnl_sol: Option<f64>, // PnL realised by a sell, when it was booked against a position
+}
+
 /// Handle execute buy sol callback
 /// 
 /// # Description
@@ -854,7 +1377,7 @@ pub async fn handle_execute_buy_sol_callback(data: String, bot: &teloxide::Bot,
     let user_id = q.from.id.to_string();
     println!("@handle_execute_buy_sol_callback/ user_id: {:?}", user_id);
 
-    let response = match execute_swap(pool, "So11111111111111111111111111111111111111112", token_address.as_str(), user_id, q.chat_id().expect("Chat ID not found").to_string().as_str()).await {
+    let swap_result = match execute_swap(pool, "So11111111111111111111111111111111111111112", token_address.as_str(), user_id, q.chat_id().expect("Chat ID not found").to_string().as_str()).await {
         Ok(r) => r,
         Err(e) => {
             println!("@handle_execute_buy_sol_callback/ error executing swap: {:?}", e);
@@ -863,23 +1386,8 @@ pub async fn handle_execute_buy_sol_callback(data: String, bot: &teloxide::Bot,
         }
     };
 
-    println!("@handle_execute_buy_sol_callback/ checking if response is success");
-    if response.status().is_success() {
-        println!("@handle_execute_buy_sol_callback/ response is success");
-        let json_response = response.json::<serde_json::Value>().await?;
-        println!("@handle_execute_buy_sol_callback/ json_response: {:?}", json_response);
-        if let Some(transaction) = json_response["transaction"].as_str() {
-            println!("@handle_execute_buy_sol_callback/ transaction signature found on response: {:?}", transaction);
-            bot.send_message(q.message.as_ref().unwrap().chat().id, format!("https://solscan.io/tx/{}", transaction)).await?;
-        } else {
-            println!("@handle_execute_buy_sol_callback/ transaction signature not found on response");
-            bot.send_message(q.message.as_ref().unwrap().chat().id, "Transaction ID not found in solana app response.".to_string()).await?;
-        }
-    } else {
-        println!("@handle_execute_buy_sol_callback/ response is not success");
-        bot.send_message(q.message.as_ref().unwrap().chat().id, format!("Failed to buy: {}", response.text().await?)).await?;
-        println!("@handle_execute_buy_sol_callback/ response is not success");
-    }   
+    println!("@handle_execute_buy_sol_callback/ transaction signature found on response: {:?}", swap_result.signature);
+    bot.send_message(q.message.as_ref().unwrap().chat().id, format!("https://solscan.io/tx/{}", swap_result.signature)).await?;
     println!("@handle_execute_buy_sol_callback/ done");
     Ok(())
 }
@@ -912,18 +1420,20 @@ pub async fn token_address_buy_info_handler(text: &str, bot: &teloxide::Bot, msg
     println!("@buy_sol_token_address_handler/ creating keyboard");
     let keyboard = create_sol_buy_swap_keyboard(&pool, user.tg_id.to_string().as_str()).await;
     println!("@buy_sol_token_address_handler/ keyboard created");
-    println!("@buy_sol_token_address_handler/ sending scanner request");
-    let scanner_response = get_scanner_search(token_address.as_str()).await?;
-    println!("@buy_sol_token_address_handler/ received response");
+    println!("@buy_sol_token_address_handler/ fetching the market data");
+    let pair_stats = market_data().pair_stats(token_address.as_str()).await?;
+    println!("@buy_sol_token_address_handler/ received the market data");
 
     // token info
-    let token_symbol = scanner_response["pair"]["token1Symbol"].as_str().unwrap_or("N/A").to_uppercase();
-    let token_name = scanner_response["pair"]["token1Name"].as_str().unwrap_or("N/A");
-    let token_usd_price = format!("{:.8}", scanner_response["pair"]["pairPrice1Usd"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0)).parse::<f64>().unwrap_or(0.0);
-    let mkt_cap: String = format_number(scanner_response["pair"]["token1TotalSupplyFormatted"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0) * scanner_response["pair"]["pairPrice1Usd"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0));
-    let lp = scanner_response["pair"]["burnedAmount"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
-    let burnt = if scanner_response["pair"]["burnedAmount"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0) == scanner_response["pair"]["burnedSupply"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0) { "✓" } else { "x" };
-    let renounced = if scanner_response["pair"]["renounced"].as_bool().unwrap_or(false) { "✓" } else { "x" };
+    let token_symbol = pair_stats.symbol.to_uppercase();
+    let token_name = pair_stats.name.as_str();
+    let token_usd_price = format!("{:.8}", pair_stats.price_usd).parse::<f64>().unwrap_or(0.0);
+    let mkt_cap: String = format_number(pair_stats.market_cap());
+    let lp = format_number(pair_stats.liquidity_usd);
+    // The buy screen still shows up when the holders can't be fetched, the scan just has less to go on
+    let holders = market_data().holders(token_address.as_str()).await.unwrap_or_default();
+    let safety = utils::safety::get_token_safety(&pair_stats, &holders).await;
+    let safety_str = utils::safety::safety_message(&safety);
 
     bot.send_message(
         msg.chat.id, 
@@ -932,7 +1442,7 @@ pub async fn token_address_buy_info_handler(text: &str, bot: &teloxide::Bot, msg
             <code> {token_address}</code> (Tap to copy)\n\
             • SOL Balance: {sol_balance} (${sol_balance_usd}) [TransferSOL]\n\
             • Price: <b>${token_usd_price}</b> LP: <b>${lp}</b> MC: <b>${mkt_cap}</b>\n\
-            • Renounced: {renounced} Burnt: {burnt}
+            {safety_str}
             "
         )
     )
@@ -1122,11 +1632,15 @@ async fn handle_positions_callback(data: String, bot: &teloxide::Bot, q: &teloxi
     Ok(())
 }
 
-/// Handle sell callback
+/// Handle portfolio callback
+/// 
+/// # Description
+/// 
+/// Show the portfolio of the user on the tg bot, a refresh updates the message in place
 /// 
 /// # Arguments
 /// 
-/// * `data` - The callback data
+/// * `data` - The callback data, "portfolio" or "refresh_portfolio"
 /// * `bot` - The Telegram bot
 /// * `q` - The callback query
 /// * `pool` - The database pool
@@ -1134,13 +1648,469 @@ async fn handle_positions_callback(data: String, bot: &teloxide::Bot, q: &teloxi
 /// # Returns
 /// 
 /// A result indicating the success of the operation
-async fn handle_execute_sell_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
-    println!("@handle_execute_sell_callback/ data: {:?}", data);
-    let token_address = data.split(":").nth(1).unwrap_or("N/A").to_string();
-    println!("@handle_execute_sell_callback/ token_address: {:?}", token_address);
+async fn handle_portfolio_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    let message = create_portfolio_message(pool, &q.from.id.to_string()).await?;
+    let keyboard = create_portfolio_keyboard();
+    match q.message.as_ref() {
+        Some(portfolio_message) if data == "refresh_portfolio" => {
+            let edited = bot.edit_message_text(portfolio_message.chat().id, portfolio_message.id(), message)
+            .parse_mode(teloxide::types::ParseMode::Html)
+            .reply_markup(keyboard)
+            .await;
+            match edited {
+                // Nothing changed since the last refresh
+                Ok(_) | Err(teloxide::RequestError::Api(teloxide::ApiError::MessageNotModified)) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        _ => {
+            bot.send_message(q.message.as_ref().unwrap().chat().id, message)
+            .parse_mode(teloxide::types::ParseMode::Html)
+            .reply_markup(keyboard)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Handle select strategy preset callback
+/// 
+/// # Description
+/// 
+/// Pick the preset for the next buy of the token on the buy menu, picking it again clears it, and
+/// show the buy menu again. The buy uses the preset amount and its position the preset ladders, the
+/// user settings are left as is.
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data, "preset:<preset_id>"
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_select_strategy_preset_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    let preset_id = data.split(":").nth(1).unwrap_or("0").parse::<i32>().unwrap_or(0);
+    let user_tg_id = q.from.id.to_string();
+    let preset = match db::get_strategy_preset(pool, preset_id, &user_tg_id).await? {
+        Some(preset) => preset,
+        None => {
+            bot.answer_callback_query(q.id.clone()).text("Preset not found").await?;
+            return Ok(());
+        }
+    };
+    let last_token_address = get_user_last_sent_token(pool, &user_tg_id).await?;
+    let pending_preset = db::get_pending_strategy_preset(pool, &user_tg_id, &last_token_address).await?;
+    if pending_preset.map(|pending_preset| pending_preset.id) == Some(preset.id) {
+        db::set_pending_strategy_preset(pool, &user_tg_id, None, None).await?;
+        println!("@handle_select_strategy_preset_callback/ preset {} cleared for {}", preset.id, user_tg_id);
+        bot.answer_callback_query(q.id.clone()).text(format!("{} preset cleared", preset.name)).await?;
+    } else {
+        db::set_pending_strategy_preset(pool, &user_tg_id, Some(preset.id), Some(&last_token_address)).await?;
+        println!("@handle_select_strategy_preset_callback/ preset {} picked by {} for {}", preset.id, user_tg_id, last_token_address);
+        bot.answer_callback_query(q.id.clone()).text(format!("{} preset selected for this buy", preset.name)).await?;
+    }
+
+    if let Some(teloxide::types::MaybeInaccessibleMessage::Regular(msg)) = q.message.as_ref() {
+        token_address_buy_info_handler(last_token_address.as_str(), bot, msg, pool).await?;
+    }
+    Ok(())
+}
+
+/// Handle save strategy preset callback
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_save_strategy_preset_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    bot.send_message(q.message.as_ref().unwrap().chat().id, "Send the preset name to save the current take profits, stop losses, trailing stops and buy amount under (eg: 'Scalp'), an existing preset with this name is replaced")
+    .reply_markup(teloxide::types::ForceReply{force_reply: teloxide::types::True, input_field_placeholder: Some("Send the preset name".to_string()), selective: false})
+    .await?;
+    Ok(())
+}
+
+/// Handle strategy presets callback
+/// 
+/// # Description
+/// 
+/// Show the user presets, deleting one updates the message in place
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data, "strategy_presets" or "delete_preset:<preset_id>"
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_strategy_presets_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    let user_tg_id = q.from.id.to_string();
+    if let Some(preset_id) = data.strip_prefix("delete_preset:") {
+        let preset_id = preset_id.parse::<i32>().unwrap_or(0);
+        if db::delete_strategy_preset(pool, preset_id, &user_tg_id).await? {
+            bot.answer_callback_query(q.id.clone()).text("Preset deleted").await?;
+        }
+    }
+    let presets = db::get_or_create_strategy_presets(pool, &user_tg_id).await?;
+    let message = create_strategy_presets_message(&presets);
+    let keyboard = create_strategy_presets_keyboard(&presets);
+    match q.message.as_ref() {
+        Some(presets_message) if data.starts_with("delete_preset:") => {
+            bot.edit_message_text(presets_message.chat().id, presets_message.id(), message)
+            .parse_mode(teloxide::types::ParseMode::Html)
+            .reply_markup(keyboard)
+            .await?;
+        }
+        _ => {
+            bot.send_message(q.message.as_ref().unwrap().chat().id, message)
+            .parse_mode(teloxide::types::ParseMode::Html)
+            .reply_markup(keyboard)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Handle position targets callback
+/// 
+/// # Description
+/// 
+/// Show the take profits, stop losses, trailing stops and market cap targets of an open position.
+/// Applying a preset replaces them with the preset ladders, deleting a level removes it, both
+/// update the message in place.
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data, "position_targets:<position_id>", "apply_preset:<position_id>:<preset_id>"
+///   or "del_pos_level:<position_id>:<tp|sl|ts|mctp|mcsl>:<value>_<percentage_to_sell>"
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_position_targets_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    let parts: Vec<&str> = data.split(":").collect();
+    let position_id = parts.get(1).unwrap_or(&"0").parse::<i32>().unwrap_or(0);
+    let user_tg_id = q.from.id.to_string();
+    // Only the open positions of the user can be edited
+    let mut position = match get_active_positions(pool, &user_tg_id).await?.into_iter().find(|position| position.id == position_id) {
+        Some(position) => position,
+        None => {
+            bot.answer_callback_query(q.id.clone()).text("Position not found, it may be completed").await?;
+            return Ok(());
+        }
+    };
+
+    if data.starts_with("apply_preset:") {
+        let preset_id = parts.get(2).unwrap_or(&"0").parse::<i32>().unwrap_or(0);
+        let preset = match db::get_strategy_preset(pool, preset_id, &user_tg_id).await? {
+            Some(preset) => preset,
+            None => {
+                bot.answer_callback_query(q.id.clone()).text("Preset not found").await?;
+                return Ok(());
+            }
+        };
+        position.take_profits = preset.take_profits;
+        position.stop_losses = preset.stop_losses;
+        position.trailing_stops = preset.trailing_stops;
+        position.mc_targets = preset.mc_targets;
+        db::set_position_ladders(pool, position.id, &position.take_profits, &position.stop_losses, &position.trailing_stops, &position.mc_targets).await?;
+        println!("@handle_position_targets_callback/ preset {} applied to position {}", preset_id, position.id);
+        bot.answer_callback_query(q.id.clone()).text(format!("{} applied", preset.name)).await?;
+    }
+    else if data.starts_with("del_pos_level:") {
+        let kind = parts.get(2).unwrap_or(&"");
+        let mut values = parts.get(3).unwrap_or(&"").split("_").map(|value| value.parse::<f64>().unwrap_or(0.0));
+        let level = (values.next().unwrap_or(0.0), values.next().unwrap_or(0.0));
+        match *kind {
+            "tp" => position.take_profits.retain(|take_profit| *take_profit != level),
+            "sl" => position.stop_losses.retain(|stop_loss| *stop_loss != level),
+            "ts" => position.trailing_stops.retain(|trailing_stop| *trailing_stop != level),
+            "mctp" | "mcsl" => position.mc_targets.retain(|mc_target| {
+                mc_target.is_take_profit() != (*kind == "mcsl") || mc_target.market_cap != level.0 || mc_target.sell_percentage != level.1
+            }),
+            _ => (),
+        }
+        db::set_position_ladders(pool, position.id, &position.take_profits, &position.stop_losses, &position.trailing_stops, &position.mc_targets).await?;
+        println!("@handle_position_targets_callback/ {} level {:?} removed from position {}", kind, level, position.id);
+    }
+
+    let presets = db::get_or_create_strategy_presets(pool, &user_tg_id).await?;
+    let message = create_position_targets_message(&position);
+    let keyboard = create_position_targets_keyboard(&position, &presets);
+    match q.message.as_ref() {
+        Some(targets_message) if !data.starts_with("position_targets:") => {
+            bot.edit_message_text(targets_message.chat().id, targets_message.id(), message)
+            .parse_mode(teloxide::types::ParseMode::Html)
+            .reply_markup(keyboard)
+            .await?;
+        }
+        _ => {
+            bot.send_message(q.message.as_ref().unwrap().chat().id, message)
+            .parse_mode(teloxide::types::ParseMode::Html)
+            .reply_markup(keyboard)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// ForceReply prompts of the group settings, by setting
+const GROUP_SETTING_PROMPTS: [(&str, &str); 6] = [
+    ("milestones", "Send the call milestones of this group, comma separated multipliers (eg: '2,5,10'), or 'off'"),
+    ("allowed_chains", "Send the chains tracked in this group, comma separated (eg: 'solana,ethereum'), or 'all'"),
+    ("digest_time", "Send the leaderboard digest time of this group as '<hour>' or '<hour> <timezone>' (eg: '9', '18 Europe/Paris' or '8 America/New_York')"),
+    ("lb_default_period", "Send the default leaderboard period of this group (eg: '1d', '12h', '7d' or 'all')"),
+    ("min_liquidity", "Send the minimum liquidity in USD to track a call in this group (eg: '10000', '0' for none)"),
+    ("min_mkt_cap", "Send the minimum market cap in USD to track a call in this group (eg: '50000', '0' for none)"),
+];
+
+/// Handle group settings callback
+/// 
+/// # Description
+/// 
+/// Toggles a switch of the group settings and edits the settings message, or asks for a new
+/// value with a ForceReply. Only the group admins can change the settings.
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_group_settings_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    let settings_message = match q.message.as_ref() {
+        Some(settings_message) => settings_message,
+        None => return Ok(()),
+    };
+    let chat_id = settings_message.chat().id;
+    if !is_group_admin(bot, chat_id, q.from.id).await? {
+        bot.answer_callback_query(q.id.clone()).text("Only the group admins can change the settings").await?;
+        return Ok(());
+    }
+    let setting = data.split(":").nth(1).unwrap_or("");
+
+    if data.starts_with("group_set:") {
+        if let Some((_, prompt)) = GROUP_SETTING_PROMPTS.iter().find(|(name, _)| *name == setting) {
+            bot.answer_callback_query(q.id.clone()).await?;
+            bot.send_message(chat_id, *prompt)
+            .reply_markup(teloxide::types::ForceReply{force_reply: teloxide::types::True, input_field_placeholder: None, selective: false})
+            .await?;
+        }
+        return Ok(());
+    }
+
+    let mut settings = db::get_group_settings(pool, &chat_id.to_string()).await?;
+    settings.title = settings_message.chat().title().map(|title| title.to_string());
+    match setting {
+        "auto_calls" => settings.auto_calls = !settings.auto_calls,
+        "require_call_prefix" => settings.require_call_prefix = !settings.require_call_prefix,
+        "one_call_per_user_token" => settings.one_call_per_user_token = !settings.one_call_per_user_token,
+        "global_leaderboard" => settings.global_leaderboard = !settings.global_leaderboard,
+        "digest_pin" => settings.digest_pin = !settings.digest_pin,
+        // Cycles off -> daily -> weekly -> off
+        "digest_frequency" => {
+            settings.digest_frequency = match settings.digest_frequency.as_str() {
+                "off" => "daily",
+                "daily" => "weekly",
+                _ => "off",
+            }.to_string();
+        }
+        _ => return Ok(()),
+    }
+    db::upsert_group_settings(pool, &settings).await?;
+    println!("@handle_group_settings_callback/ chat {} {} toggled by {}", chat_id, setting, q.from.id);
+    bot.answer_callback_query(q.id.clone()).await?;
+    bot.edit_message_text(chat_id, settings_message.id(), create_group_settings_message(&settings))
+    .parse_mode(teloxide::types::ParseMode::Html)
+    .reply_markup(create_group_settings_keyboard(&settings))
+    .await?;
+    Ok(())
+}
+
+/// Handle the reply to a group setting prompt
+/// 
+/// # Arguments
+/// 
+/// * `text` - The reply text
+/// * `prompt` - The prompt replied to
+/// * `bot` - The Telegram bot
+/// * `msg` - The reply message
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_group_setting_reply(text: &str, prompt: &str, bot: &teloxide::Bot, msg: &teloxide::types::Message, pool: &SafePool) -> Result<()> {
+    let user_id = match msg.from.as_ref() {
+        Some(user) => user.id,
+        None => return Ok(()),
+    };
+    if msg.chat.is_private() || !is_group_admin(bot, msg.chat.id, user_id).await? {
+        bot.send_message(msg.chat.id, "Only the group admins can change the settings").await?;
+        return Ok(());
+    }
+    let setting = GROUP_SETTING_PROMPTS.iter().find(|(_, setting_prompt)| prompt.starts_with(setting_prompt)).map(|(name, _)| *name).unwrap_or("");
+    let mut settings = db::get_group_settings(pool, &msg.chat.id.to_string()).await?;
+    settings.title = msg.chat.title().map(|title| title.to_string());
+    let text = text.trim();
+    match setting {
+        "allowed_chains" => {
+            settings.allowed_chains = if text.eq_ignore_ascii_case("all") {
+                Vec::new()
+            } else {
+                text.split(",").map(|chain| chain.trim().to_lowercase()).filter(|chain| !chain.is_empty()).collect()
+            };
+        }
+        "digest_time" => {
+            match parse_digest_time(text) {
+                Some((hour, timezone)) => {
+                    settings.digest_hour = hour;
+                    settings.digest_timezone = timezone.name().to_string();
+                }
+                None => {
+                    bot.send_message(msg.chat.id, "Invalid time. Please use eg '9', '18 Europe/Paris' or '8 America/New_York'").await?;
+                    return Ok(());
+                }
+            }
+        }
+        "milestones" => {
+            match parse_milestones(text) {
+                Some(milestones) => settings.milestones = milestones,
+                None => {
+                    bot.send_message(msg.chat.id, "Invalid milestones. Please use multipliers above 1, eg '2,5,10', or 'off'").await?;
+                    return Ok(());
+                }
+            }
+        }
+        "lb_default_period" => {
+            if parse_period(text).is_none() {
+                bot.send_message(msg.chat.id, "Invalid period. Please use eg '1d', '12h', '7d', '3m' or 'all'").await?;
+                return Ok(());
+            }
+            settings.lb_default_period = text.to_lowercase();
+        }
+        "min_liquidity" | "min_mkt_cap" => {
+            let value = match text.trim_start_matches("$").replace(",", "").parse::<f64>() {
+                Ok(value) if value >= 0.0 => value,
+                _ => {
+                    bot.send_message(msg.chat.id, "Invalid amount. Please send a number of USD, eg '10000'").await?;
+                    return Ok(());
+                }
+            };
+            if setting == "min_liquidity" {
+                settings.min_liquidity = value;
+            } else {
+                settings.min_mkt_cap = value;
+            }
+        }
+        _ => return Ok(()),
+    }
+    db::upsert_group_settings(pool, &settings).await?;
+    println!("@handle_group_setting_reply/ chat {} {} set by {}", msg.chat.id, setting, user_id);
+    bot.send_message(msg.chat.id, create_group_settings_message(&settings))
+    .parse_mode(teloxide::types::ParseMode::Html)
+    .reply_markup(create_group_settings_keyboard(&settings))
+    .await?;
+    Ok(())
+}
+
+/// Handle position add level callback
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data, "pos_add_take_profit:<position_id>" or "pos_add_stop_loss:<position_id>"
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_position_add_level_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    let position_id = data.split(":").nth(1).unwrap_or("0").parse::<i32>().unwrap_or(0);
+    let prompt = if data.starts_with("pos_add_take_profit:") {
+        format!("Send '<multiplier>,<%_to_sell>' to add a take profit (eg: '2,50' that means if the price goes up 2x, sell 50%) or '$<market_cap>,<%_to_sell>' for a market cap, to position: {}", position_id)
+    } else {
+        format!("Send '<%down>,<%_to_sell>' to add a stop loss (eg: '20,100' that means if the price goes down 20%, sell 100%) or '$<market_cap>,<%_to_sell>' for a market cap, to position: {}", position_id)
+    };
+    bot.send_message(q.message.as_ref().unwrap().chat().id, prompt)
+    .reply_markup(teloxide::types::ForceReply{force_reply: teloxide::types::True, input_field_placeholder: Some("Send <value>,<% to sell>".to_string()), selective: false})
+    .await?;
+    Ok(())
+}
+
+/// Handle PnL card callback
+/// 
+/// # Description
+/// 
+/// Render the PnL card of one of the user positions and send it as a photo
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data, "pnl_card:<position_id>"
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_pnl_card_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    let position_id = data.split(":").nth(1).unwrap_or("0").parse::<i32>().unwrap_or(0);
+    let user_tg_id = q.from.id.to_string();
+    // Only the positions of the user can be shared
+    let positions = get_positions_by_user_tg_id(pool, &user_tg_id).await?;
+    let position = match positions.iter().find(|position| position.id == position_id) {
+        Some(position) => position,
+        None => {
+            bot.answer_callback_query(q.id.clone()).text("Position not found").await?;
+            return Ok(());
+        }
+    };
+    let username = q.from.username.clone().unwrap_or("Unknown".to_string());
+    let card = create_position_pnl_card(pool, position, &username).await?;
+    let png = utils::pnl_card::render_pnl_card(&card)?;
+    bot.send_photo(q.message.as_ref().unwrap().chat().id, teloxide::types::InputFile::memory(png).file_name("pnl.png")).await?;
+    Ok(())
+}
+
+/// Handle sell callback
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_execute_sell_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    println!("@handle_execute_sell_callback/ data: {:?}", data);
+    let token_address = data.split(":").nth(1).unwrap_or("N/A").to_string();
+    println!("@handle_execute_sell_callback/ token_address: {:?}", token_address);
     let user_id = q.from.id.to_string();
     println!("@handle_execute_sell_callback/ user_id: {:?}", user_id);
-    let response = match execute_swap(&pool, &token_address, "So11111111111111111111111111111111111111112", user_id, q.chat_id().expect("Chat ID not found").to_string().as_str()).await {
+    let swap_result = match execute_swap(&pool, &token_address, "So11111111111111111111111111111111111111112", user_id, q.chat_id().expect("Chat ID not found").to_string().as_str()).await {
         Ok(r) => r,
         Err(e) => {
             println!("@handle_execute_sell_callback/ error executing swap: {:?}", e);
@@ -1148,15 +2118,11 @@ async fn handle_execute_sell_callback(data: String, bot: &teloxide::Bot, q: &tel
             return Ok(());
         }
     };
-    if response.status().is_success() {
-        println!("@handle_execute_sell_callback/ response is success");
-        let json_response = response.json::<serde_json::Value>().await?;
-        bot.send_message(q.message.as_ref().unwrap().chat().id, format!("https://solscan.io/tx/{}", json_response["transaction"].as_str().unwrap_or("N/A"))).await?;
-    } else {
-        let error_text = response.text().await?;
-        println!("@handle_execute_sell_callback/ response is not success: {}", error_text);
-        bot.send_message(q.message.as_ref().unwrap().chat().id, format!("Failed to sell: {}", error_text)).await?;
-    }   
+    let mut message = format!("https://solscan.io/tx/{}", swap_result.signature);
+    if let Some(realised_pnl_sol) = swap_result.realised_pnl_sol {
+        message.push_str(&format!("\nRealised PnL: {:+.4} SOL", realised_pnl_sol));
+    }
+    bot.send_message(q.message.as_ref().unwrap().chat().id, message).await?;
     Ok(())
 }
 
@@ -1256,7 +2222,7 @@ async fn handle_set_custom_gas_callback(_data: String, bot: &teloxide::Bot, q: &
 /// 
 /// A result indicating the success of the operation    
 async fn handle_add_take_profit_user_settings_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
-    bot.send_message(q.message.as_ref().unwrap().chat().id, "Send '<multiplier>,<%_token_position_amount_to_sell>' (eg: '1.5,100' that means if the price goes up 1.5x, sell 100% of the position)")
+    bot.send_message(q.message.as_ref().unwrap().chat().id, "Send '<multiplier>,<%_token_position_amount_to_sell>' (eg: '1.5,100' that means if the price goes up 1.5x, sell 100% of the position). Send '$<market_cap>,<%_to_sell>' to sell at a market cap instead (eg: '$1M,50' that means at 1M market cap, sell 50% of the position)")
     .reply_markup(teloxide::types::ForceReply{force_reply: teloxide::types::True, input_field_placeholder: Some("Send <multiplier to leave>, <% to sell> ".to_string()), selective: false})
     .await?;
     Ok(())
@@ -1275,13 +2241,33 @@ async fn handle_add_take_profit_user_settings_callback(data: String, bot: &telox
 /// 
 /// A result indicating the success of the operation
 async fn handle_add_stop_loss_user_settings_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
-    bot.send_message(q.message.as_ref().unwrap().chat().id, "Send '<%down>,<%_token_position_amount_to_sell>' (eg: '10,100' that means if the price goes down 10%, sell 100% of the position)")
+    bot.send_message(q.message.as_ref().unwrap().chat().id, "Send '<%down>,<%_token_position_amount_to_sell>' (eg: '10,100' that means if the price goes down 10%, sell 100% of the position). Send '$<market_cap>,<%_to_sell>' to sell at a market cap instead (eg: '$200K,100' that means at 200K market cap, sell 100% of the position)")
     .reply_markup(teloxide::types::ForceReply{force_reply: teloxide::types::True, input_field_placeholder: Some("Send <% down>,<% to sell> ".to_string()), selective: false})
     .await?;
     Ok(())
 }
 
 
+/// handle add trailing stop callback
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_add_trailing_stop_user_settings_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    bot.send_message(q.message.as_ref().unwrap().chat().id, "Send '<%trail>,<%_token_position_amount_to_sell>' (eg: '20,100' that means if the price falls 20% from the highest price since entry, sell 100% of the position)")
+    .reply_markup(teloxide::types::ForceReply{force_reply: teloxide::types::True, input_field_placeholder: Some("Send <% trail>,<% to sell> ".to_string()), selective: false})
+    .await?;
+    Ok(())
+}
+
+
 /// Handle delete take profit user settings callback
 /// 
 /// # Arguments
@@ -1344,6 +2330,61 @@ async fn handle_delete_stop_loss_user_settings_callback(data: String, bot: &telo
     Ok(())
 }
 
+/// Handle delete market cap target user settings callback
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data, "delete_mc_target:<kind>_<market_cap>_<percentage_to_sell>"
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_delete_mc_target_user_settings_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    let user_tg_id = q.from.id.to_string();
+    let target = data.strip_prefix("delete_mc_target:").unwrap_or_default();
+    // The kind has an underscore too, the numbers are the last two parts
+    let mut parts = target.rsplitn(3, '_');
+    let sell_percentage = parts.next().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
+    let market_cap = parts.next().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
+    let kind = parts.next().unwrap_or_default().to_string();
+    let mc_target = utils::ladder::MarketCapTarget { kind, market_cap, sell_percentage };
+    db::delete_user_settings_mc_target(&pool, &mc_target, &user_tg_id).await?;
+    let last_token_address = get_user_last_sent_token(&pool, &user_tg_id).await?;
+    if let Some(teloxide::types::MaybeInaccessibleMessage::Regular(msg)) = q.message.as_ref() {
+        token_address_buy_info_handler(last_token_address.as_str(), bot, msg, pool).await?;
+    }
+    Ok(())
+}
+
+/// Handle delete trailing stop user settings callback
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_delete_trailing_stop_user_settings_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    let user_tg_id = q.from.id.to_string();
+    // data = "delete_trailing_stop:<trail>_<percentage_to_sell>"
+    let trail_and_percentage_to_sell = data.split(":").nth(1).unwrap_or("N/A");
+    let trail = trail_and_percentage_to_sell.split("_").next().unwrap_or("N/A").parse::<f64>().unwrap_or(0.0);
+    let percentage_to_sell = trail_and_percentage_to_sell.split("_").nth(1).unwrap_or("N/A").parse::<f64>().unwrap_or(0.0);
+    db::delete_user_settings_trailing_stop(&pool, (trail, percentage_to_sell), &user_tg_id).await?;
+    let last_token_address = get_user_last_sent_token(&pool, &user_tg_id).await?;
+    if let Some(teloxide::types::MaybeInaccessibleMessage::Regular(msg)) = q.message.as_ref() {
+        token_address_buy_info_handler(last_token_address.as_str(), bot, msg, pool).await?;
+    }
+    Ok(())
+}
+
 /// Handle set jito tip amount callback
 /// 
 /// # Arguments
@@ -1501,6 +2542,49 @@ async fn handle_refferal_callback(data: String, bot: &teloxide::Bot, q: &teloxid
 /// 
 /// A result indicating the success of the operation
 async fn handle_copy_trade_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    let report = get_suggested_wallets().await?;
+    let message = create_copy_trade_message(&report);
+    let keyboard = create_copy_trade_keyboard(is_bot_admin(q.from.id));
+    bot.send_message(q.message.as_ref().unwrap().chat().id, message)
+    .reply_markup(keyboard)
+    .parse_mode(teloxide::types::ParseMode::Html)
+    .await?;
+    Ok(())
+}
+
+/// Handle discover wallets callback
+/// 
+/// # Description
+/// 
+/// Start a wallet discovery job in the solana_app over the most called tokens of the last 24 hours.
+/// Only the bot admins can start a job.
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_discover_wallets_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    if !is_bot_admin(q.from.id) {
+        bot.answer_callback_query(q.id.clone()).text("Only the bot admins can start a wallet discovery").await?;
+        return Ok(());
+    }
+    let tokens = get_most_called_solana_tokens(pool, "24 hours", 10).await?;
+    println!("@handle_discover_wallets_callback/ tokens: {:?}", tokens);
+    if tokens.is_empty() {
+        bot.send_message(q.message.as_ref().unwrap().chat().id, "No tokens were called in the last 24 hours, nothing to analyse.").await?;
+        return Ok(());
+    }
+    if !request_wallet_discovery(tokens.clone()).await? {
+        bot.send_message(q.message.as_ref().unwrap().chat().id, "A wallet discovery job is already running or finished recently, open Copy Trade again to see the suggested wallets.").await?;
+        return Ok(());
+    }
+    bot.send_message(q.message.as_ref().unwrap().chat().id, format!("🔎 Analysing the traders of the {} most called tokens of the last 24 hours. Open Copy Trade again in a few minutes to see the suggested wallets.", tokens.len())).await?;
     Ok(())
 }
 
@@ -1689,8 +2773,210 @@ pub async fn handle_withdraw_callback(data: String, bot: &teloxide::Bot, q: &tel
  /// A result indicating the success of the operation
  pub async fn handle_limit_orders_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
     let message = create_limit_orders_message(pool, &q.from.id.to_string()).await?;
+    let keyboard = limit_orders_keyboard(pool, &q.from.id.to_string()).await?;
+    bot.send_message(q.message.as_ref().unwrap().chat().id, message)
+    .parse_mode(teloxide::types::ParseMode::Html)
+    .reply_markup(keyboard)
+    .await?;
+    Ok(())
+ }
+
+/// Handle recurring orders callback
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_recurring_orders_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    let user_tg_id = q.from.id.to_string();
+    let message = create_recurring_orders_message(pool, &user_tg_id).await?;
+    let keyboard = create_recurring_orders_keyboard(pool, &user_tg_id).await?;
+    bot.send_message(q.message.as_ref().unwrap().chat().id, message)
+    .parse_mode(teloxide::types::ParseMode::Html)
+    .reply_markup(keyboard)
+    .await?;
+    Ok(())
+}
+
+/// Handle trades journal callback
+/// 
+/// # Description
+/// 
+/// Show the latest trades of the user on the tg bot
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_trades_journal_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    let user_tg_id = q.from.id.to_string();
+    let message = create_trades_journal_message(pool, &user_tg_id).await?;
+    let keyboard = create_trades_journal_keyboard();
     bot.send_message(q.message.as_ref().unwrap().chat().id, message)
     .parse_mode(teloxide::types::ParseMode::Html)
+    .link_preview_options(teloxide::types::LinkPreviewOptions {
+        is_disabled: true,
+        url: None,
+        prefer_small_media: false,
+        prefer_large_media: false,
+        show_above_text: false,
+    })
+    .reply_markup(keyboard)
+    .await?;
+    Ok(())
+}
+
+/// Handle pause, resume and cancel recurring order callbacks
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data, "<pause|resume|cancel>_recurring_order:<order_id>"
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_update_recurring_order_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    let user_tg_id = q.from.id.to_string();
+    let (action, order_id) = data.split_once("_recurring_order:").unwrap_or(("", "0"));
+    let order_id = order_id.parse::<i32>().unwrap_or(0);
+    let updated = match action {
+        "pause" => db::pause_recurring_order(pool, order_id, &user_tg_id).await?,
+        "resume" => db::resume_recurring_order(pool, order_id, &user_tg_id).await?,
+        "cancel" => db::cancel_recurring_order(pool, order_id, &user_tg_id).await?,
+        _ => false,
+    };
+    let text = if updated {
+        format!("Order #{} updated", order_id)
+    } else {
+        format!("Order #{} can't be updated", order_id)
+    };
+    bot.answer_callback_query(q.id.clone()).text(text).await?;
+
+    if let Some(message) = q.message.as_ref() {
+        let recurring_orders_message = create_recurring_orders_message(pool, &user_tg_id).await?;
+        let keyboard = create_recurring_orders_keyboard(pool, &user_tg_id).await?;
+        bot.edit_message_text(message.chat().id, message.id(), recurring_orders_message)
+        .parse_mode(teloxide::types::ParseMode::Html)
+        .reply_markup(keyboard)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Handle DCA buy callback
+/// 
+/// # Description
+/// 
+/// Asks for the DCA of the last sent token
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_dca_buy_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    bot.send_message(q.message.as_ref().unwrap().chat().id, "Send '<sol_per_buy>,<buys>,<interval_minutes>' (eg: '0.1,5,60' that means buy 0.1 SOL every 60 minutes, 5 times)")
+    .reply_markup(teloxide::types::ForceReply{force_reply: teloxide::types::True, input_field_placeholder: Some("Send <sol>,<buys>,<minutes> ".to_string()), selective: false})
+    .await?;
+    Ok(())
+}
+
+/// Handle TWAP sell callback
+/// 
+/// # Description
+/// 
+/// Asks for the TWAP of a token, the token goes in the prompt so the reply can find it
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data, "twap_sell:<token_address>"
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_twap_sell_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    let token_address = data.strip_prefix("twap_sell:").unwrap_or_default();
+    bot.send_message(q.message.as_ref().unwrap().chat().id, format!("Send '<slices>,<window_minutes>' (eg: '4,60' that means sell the position in 4 slices over 60 minutes) for token: {}", token_address))
+    .reply_markup(teloxide::types::ForceReply{force_reply: teloxide::types::True, input_field_placeholder: Some("Send <slices>,<minutes> ".to_string()), selective: false})
+    .await?;
+    Ok(())
+}
+
+/// Handle limit buy callback
+/// 
+/// # Description
+/// 
+/// Asks for the trigger of a limit buy of the user buy amount on the last sent token
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_limit_buy_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    bot.send_message(q.message.as_ref().unwrap().chat().id, "Send '<mc|price>,<value>,<hours_to_expire>' (eg: 'mc,50000,24' that means buy when the market cap is at or below $50000, the order expires in 24 hours)")
+    .reply_markup(teloxide::types::ForceReply{force_reply: teloxide::types::True, input_field_placeholder: Some("Send <mc|price>,<value>,<hours> ".to_string()), selective: false})
     .await?;
     Ok(())
- }
\ No newline at end of file
+}
+
+/// Handle cancel limit order callback
+/// 
+/// # Arguments
+/// 
+/// * `data` - The callback data, "cancel_limit_order:<order_id>"
+/// * `bot` - The Telegram bot
+/// * `q` - The callback query
+/// * `pool` - The database pool
+/// 
+/// # Returns
+/// 
+/// A result indicating the success of the operation
+async fn handle_cancel_limit_order_callback(data: String, bot: &teloxide::Bot, q: &teloxide::types::CallbackQuery, pool: &SafePool) -> Result<()> {
+    let user_tg_id = q.from.id.to_string();
+    let order_id = data.strip_prefix("cancel_limit_order:").unwrap_or("0").parse::<i32>().unwrap_or(0);
+    let text = if db::cancel_limit_order(pool, order_id, &user_tg_id).await? {
+        format!("Limit buy #{} cancelled", order_id)
+    } else {
+        format!("Limit buy #{} is no longer open", order_id)
+    };
+    bot.answer_callback_query(q.id.clone()).text(text).await?;
+
+    if let Some(message) = q.message.as_ref() {
+        let limit_orders_message = create_limit_orders_message(pool, &user_tg_id).await?;
+        let keyboard = limit_orders_keyboard(pool, &user_tg_id).await?;
+        bot.edit_message_text(message.chat().id, message.id(), limit_orders_message)
+        .parse_mode(teloxide::types::ParseMode::Html)
+        .reply_markup(keyboard)
+        .await?;
+    }
+    Ok(())
+}
\ No newline at end of file
@@ -1,13 +1,13 @@
-use commands::{execute_swap, execute_swap_take_profit, execute_swap_stop_loss};
+use commands::{execute_swap, execute_swap_no_chat, execute_swap_take_profit, execute_swap_stop_loss, execute_swap_limit_buy, apply_buy_to_position};
 use db::get_user_by_tg_id;
 use teloxide::prelude::*;
-use tungstenite::Message as WsMessage;
-use futures_util::stream::StreamExt;
-use futures_util::SinkExt;
-use tokio_tungstenite::connect_async;
 use teloxide::{dispatching::UpdateFilterExt, Bot};
-use utils::helpers::check_raydiums_tokens;
-use utils::helpers::get_token_amount_in_wallet;
+use utils::helpers::{format_number, get_token_amount_in_wallet, get_positions_balance, sol_to_usd};
+use utils::ladder::{triggered_levels, triggered_market_cap_targets, triggered_trailing_stops, LadderLevel, MarketCapTarget, OrderKind, OrderStatus, MAX_ORDER_ATTEMPTS, STALE_ORDER_MINUTES};
+use utils::market_data::market_data;
+use utils::market_data::cache::in_background;
+use utils::price_service::{PriceService, PriceUpdate};
+use std::collections::HashMap;
 use std::sync::Arc;
 use sqlx::Pool;
 use sqlx::Postgres;
@@ -50,6 +50,45 @@ async fn main() {
         positions_watcher(positions_pool, &bot_clone).await;
     });
 
+    // Expire limit orders
+    let limit_orders_pool = shared_pool.clone();
+    let bot_clone = bot.clone();
+    tokio::spawn(async move {
+        println!("@main/ running limit_orders_expirer");
+        limit_orders_expirer(limit_orders_pool, &bot_clone).await;
+    });
+
+    // DCA and TWAP slices
+    let recurring_orders_pool = shared_pool.clone();
+    let bot_clone = bot.clone();
+    tokio::spawn(async move {
+        println!("@main/ running recurring_orders_scheduler");
+        recurring_orders_scheduler(recurring_orders_pool, &bot_clone).await;
+    });
+
+    // Cached call ATHs, their market data requests give way to the users ones
+    let call_aths_pool = shared_pool.clone();
+    tokio::spawn(async move {
+        println!("@main/ running call_aths_updater");
+        in_background(call_aths_updater(call_aths_pool)).await;
+    });
+
+    // Reconcile positions with the wallets, in the background too
+    let reconciler_pool = shared_pool.clone();
+    let bot_clone = bot.clone();
+    tokio::spawn(async move {
+        println!("@main/ running positions_reconciler");
+        in_background(positions_reconciler(reconciler_pool, &bot_clone)).await;
+    });
+
+
+    // Leaderboard digests
+    let digests_pool = shared_pool.clone();
+    let bot_clone = bot.clone();
+    tokio::spawn(async move {
+        println!("@main/ running leaderboard_digests_scheduler");
+        leaderboard_digests_scheduler(digests_pool, &bot_clone).await;
+    });
 
     let handler = dptree::entry()
         .branch(Update::filter_message().endpoint(handle_message))
@@ -65,194 +104,723 @@ async fn main() {
 
 
 
-#[derive(Debug, serde::Serialize)]
-pub struct PumpPayload {
-    method: String,
-    keys: Vec<String>,
-}
-
 /// Watch open positions
 /// 
+/// # Description
+/// 
+/// Evaluates the take profits and stop losses of the open positions of a token, and its open
+/// limit buy orders, each time the price service pushes a new price for it. The prices are passed
+/// on to the call milestones watcher, which runs in its own task so announcing milestones never
+/// delays a take profit or a stop loss.
 async fn positions_watcher(pool: SafePool, bot: &Bot) {
-    let url = "wss://pumpportal.fun/api/data";
-    let (ws_stream, _) = connect_async(url).await.expect("Failed to connect to pumpportal");
-    let (mut pump_write, mut pump_read) = ws_stream.split();
-
-    // Spawn WebSocket listener
-    let _pump_read_handle = tokio::spawn(async move {
-        while let Some(msg) = pump_read.next().await {
-            match msg {
-                Ok(WsMessage::Text(text)) => {
-                    // Handle the message (implement your price checking logic here)
-                    println!("Message received: {}", text);
-                },
-                Err(e) => eprintln!("Error receiving message: {:?}", e),
-                _ => {}
+    let (_price_service, mut price_updates) = PriceService::start(pool.clone()).await;
+
+    let (milestone_updates, milestone_updates_rx) = tokio::sync::mpsc::unbounded_channel::<PriceUpdate>();
+    let milestones_pool = pool.clone();
+    let milestones_bot = bot.clone();
+    tokio::spawn(async move {
+        call_milestones_watcher(milestones_pool, &milestones_bot, milestone_updates_rx).await;
+    });
+
+    while let Some(update) = price_updates.recv().await {
+        // Keep only the latest price of each token if updates piled up while executing swaps
+        let mut latest_prices: HashMap<String, f64> = HashMap::new();
+        latest_prices.insert(update.token_address, update.price);
+        while let Ok(update) = price_updates.try_recv() {
+            latest_prices.insert(update.token_address, update.price);
+        }
+
+        for (token_address, current_price) in latest_prices {
+            if let Err(e) = milestone_updates.send(PriceUpdate { token_address: token_address.clone(), price: current_price }) {
+                eprintln!("@positions_watcher/ error passing the price of {} to the milestones watcher: {:?}", token_address, e);
+            }
+            check_limit_orders(&pool, bot, &token_address, current_price).await;
+
+            let positions = match db::get_open_positions_by_token_address(&pool, &token_address).await {
+                Ok(positions) => positions,
+                Err(e) => {
+                    eprintln!("Error fetching positions: {:?}", e);
+                    continue;
+                }
+            };
+            println!("@positions_watcher/ {} price changed to {}, {} positions open", token_address, current_price, positions.len());
+            let mut count: usize = 0;
+            for position in &positions {
+                count += 1;
+                check_position_targets(&pool, bot, position, current_price, count).await;
             }
         }
-    });
+    }
+}
 
-    loop {
-        // Fetch all positions from database
-        let all_positions = match db::get_all_open_positions(&pool).await {
-            Ok(positions) => positions,
+/// Check a position against its take profits, stop losses, trailing stops and market cap targets and execute the ones reached
+/// 
+/// # Description
+/// 
+/// All the levels reached at this price are executed in order. Each level is claimed in the
+/// database before executing so it can only fire once, even if the position update lags behind
+/// the next price tick. The highest price of the position is raised before checking the
+/// trailing stops. Market cap targets are checked against the price times the circulating supply.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `bot` - The Telegram bot
+/// * `position` - The position to check
+/// * `current_price_float` - The current token price in USD
+/// * `count` - The position number, for logging
+async fn check_position_targets(pool: &SafePool, bot: &Bot, position: &db::Position, current_price_float: f64, count: usize) {
+    let percentage_change = ((current_price_float - position.entry_price) / position.entry_price) * 100.0;
+    println!("@bot/main/positions_watcher/\n\nPosition:{}\n\nposition: {:?}\ncurrent_price: {:?}\nentry_price: {:?}\ntake_profit: {:?}\nstop_loss: {:?}\npercentage_change: {:.2}% \n\n\n\n", count, position, current_price_float, position.entry_price, position.take_profits, position.stop_losses, percentage_change);
+
+    let mut levels = triggered_levels(&position.take_profits, &position.stop_losses, position.entry_price, current_price_float);
+    if !position.trailing_stops.is_empty() {
+        match db::update_position_highest_price(pool, position.id, current_price_float).await {
+            Ok(highest_price) => {
+                println!("@positions_watcher/ highest_price: {:?}, trailing_stops: {:?}", highest_price, position.trailing_stops);
+                levels.extend(triggered_trailing_stops(&position.trailing_stops, highest_price, current_price_float));
+            }
+            Err(e) => eprintln!("@positions_watcher/ error updating highest price: {:?}", e),
+        }
+    }
+    if !position.mc_targets.is_empty() {
+        // Positions opened while the circulating supply was unknown get it once a provider has it,
+        // until then the targets are skipped
+        let circulating_supply = match position.circulating_supply {
+            Some(circulating_supply) => circulating_supply,
+            None => match market_data().circulating_supply(&position.token_address).await {
+                Ok(circulating_supply) => {
+                    if let Err(e) = db::set_position_circulating_supply(pool, position.id, circulating_supply).await {
+                        eprintln!("@positions_watcher/ error saving the circulating supply of position {}: {:?}", position.id, e);
+                    }
+                    circulating_supply
+                }
+                Err(e) => {
+                    eprintln!("@positions_watcher/ market cap targets of position {} skipped: {:?}", position.id, e);
+                    0.0
+                }
+            },
+        };
+        levels.extend(triggered_market_cap_targets(&position.mc_targets, circulating_supply, current_price_float));
+    }
+    for level in levels {
+        let order_id = match db::claim_position_order(pool, position.id, level.kind.as_str(), level.multiplier, level.sell_percentage, MAX_ORDER_ATTEMPTS, STALE_ORDER_MINUTES).await {
+            Ok(Some(order_id)) => order_id,
+            Ok(None) => {
+                println!("@positions_watcher/ {} {:?} already claimed for position: {}", level.kind.as_str(), level, count);
+                continue;
+            }
             Err(e) => {
-                eprintln!("Error fetching positions: {:?}", e);
-                tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+                eprintln!("@positions_watcher/ error claiming {}: {:?}", level.kind.as_str(), e);
                 continue;
             }
         };
-        if !all_positions.is_empty() {
-            println!("@check_positions/ found {} positions open", all_positions.len());
-        } else {
-            println!("@check_positions/ no positions open");
-        }
+        println!("@bot/main/positions_watcher/ {} reached for position: {}", level.kind.as_str(), count);
 
-        let tokens: Vec<String> = all_positions.iter()
-            .map(|p| p.token_address.clone())
-            .collect();
+        match execute_ladder_level(pool, position, &level).await {
+            Ok(signature) => {
+                if let Err(e) = db::set_position_order_status(pool, order_id, OrderStatus::Filled.as_str(), Some(&signature), None).await {
+                    eprintln!("@positions_watcher/ error marking order {} as filled: {:?}", order_id, e);
+                }
+                let message = match level.kind {
+                    OrderKind::TakeProfit => format!("🟢 Take profit executed sold at {}x 📈 {}% of token balance\nhttps://solscan.io/tx/{}", level.multiplier, level.sell_percentage, signature),
+                    OrderKind::StopLoss => format!("🔴 Stop loss executed sold at {}x 📉 {}% of token balance\nhttps://solscan.io/tx/{}", level.multiplier, level.sell_percentage, signature),
+                    OrderKind::TrailingStop => format!("🟠 Trailing stop executed sold at -{}% from the high 📉 {}% of token balance\nhttps://solscan.io/tx/{}", level.multiplier, level.sell_percentage, signature),
+                    OrderKind::MarketCapTakeProfit => format!("🟢 Take profit executed sold at ${} MC 📈 {}% of token balance\nhttps://solscan.io/tx/{}", format_number(level.multiplier), level.sell_percentage, signature),
+                    OrderKind::MarketCapStopLoss => format!("🔴 Stop loss executed sold at ${} MC 📉 {}% of token balance\nhttps://solscan.io/tx/{}", format_number(level.multiplier), level.sell_percentage, signature),
+                };
+                if let Err(e) = bot.send_message(position.chat_id.clone(), message).await {
+                    eprintln!("@positions_watcher/ error sending message: {:?}", e);
+                }
+
+                // Remove the executed level from the position
+                let removed = match level.kind {
+                    OrderKind::TakeProfit => db::remove_take_profit_from_position(pool, &position.token_address, &position.tg_user_id, (level.multiplier, level.sell_percentage)).await,
+                    OrderKind::StopLoss => db::remove_stop_loss_from_position(pool, &position.token_address, &position.tg_user_id, (level.multiplier, level.sell_percentage)).await,
+                    OrderKind::TrailingStop => db::remove_trailing_stop_from_position(pool, position.id, (level.multiplier, level.sell_percentage)).await,
+                    OrderKind::MarketCapTakeProfit | OrderKind::MarketCapStopLoss => {
+                        let mc_target = MarketCapTarget {
+                            kind: if level.kind == OrderKind::MarketCapTakeProfit { "take_profit" } else { "stop_loss" }.to_string(),
+                            market_cap: level.multiplier,
+                            sell_percentage: level.sell_percentage,
+                        };
+                        db::remove_mc_target_from_position(pool, position.id, &mc_target).await
+                    }
+                };
+                if let Err(e) = removed {
+                    eprintln!("@positions_watcher/ error removing {} from position: {:?}", level.kind.as_str(), e);
+                }
+
+                // Nothing left to sell, the remaining levels can't fire
+                if let Ok(user) = get_user_by_tg_id(pool, &position.tg_user_id).await {
+                    if let Some(solana_address) = user.solana_address {
+                        match get_token_amount_in_wallet(&solana_address, &position.token_address).await {
+                            Ok((user_token_amount, _)) => {
+                                println!("@positions_watcher/ user_token_amount: {:?}", user_token_amount);
+                                if user_token_amount <= 0.0 {
+                                    println!("@positions_watcher/ user has no token in wallet, completing position");
+                                    if let Err(e) = db::mark_position_completed(pool, &position.token_address, &position.tg_user_id).await {
+                                        eprintln!("@bot/main/positions_watcher/ error marking position as completed: {}", e);
+                                    }
+                                    break;
+                                }
+                            }
+                            Err(e) => eprintln!("Error getting token amount: {:?}", e),
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("@positions_watcher/ error executing {}: {:?}", level.kind.as_str(), e);
+                if let Err(e) = db::set_position_order_status(pool, order_id, OrderStatus::Failed.as_str(), None, Some(&e.to_string())).await {
+                    eprintln!("@positions_watcher/ error marking order {} as failed: {:?}", order_id, e);
+                }
+                let level_str = match level.kind {
+                    OrderKind::TakeProfit => format!("Take profit at {}x", level.multiplier),
+                    OrderKind::StopLoss => format!("Stop loss at {}x", level.multiplier),
+                    OrderKind::TrailingStop => format!("Trailing stop at -{}% from the high", level.multiplier),
+                    OrderKind::MarketCapTakeProfit => format!("Take profit at ${} MC", format_number(level.multiplier)),
+                    OrderKind::MarketCapStopLoss => format!("Stop loss at ${} MC", format_number(level.multiplier)),
+                };
+                if let Err(e) = bot.send_message(position.chat_id.clone(), format!("⚠️ {} failed: {}", level_str, e)).await {
+                    eprintln!("@positions_watcher/ error sending message: {:?}", e);
+                }
+            }
+        }
+    }
+}
 
-        // Get updated Raydium tokens
-        let raydium_tokens = match check_raydiums_tokens(tokens.clone()).await {
-            Ok(tokens) => tokens.into_iter().collect::<std::collections::HashSet<_>>(),
+/// Check the open limit buy orders of a token and execute the ones reached
+/// 
+/// # Description
+/// 
+/// An order is reached when the price is at or below its trigger price. Each order is claimed
+/// before executing so it only fires once and can't fire after being cancelled.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `bot` - The Telegram bot
+/// * `token_address` - The token address
+/// * `current_price` - The current token price in USD
+async fn check_limit_orders(pool: &SafePool, bot: &Bot, token_address: &str, current_price: f64) {
+    let orders = match db::get_open_limit_orders_by_token_address(pool, token_address).await {
+        Ok(orders) => orders,
+        Err(e) => {
+            eprintln!("@check_limit_orders/ error fetching limit orders: {:?}", e);
+            return;
+        }
+    };
+    for order in orders.iter().filter(|order| current_price <= order.trigger_price) {
+        match db::claim_limit_order(pool, order.id).await {
+            Ok(true) => (),
+            Ok(false) => {
+                println!("@check_limit_orders/ limit order {} already claimed", order.id);
+                continue;
+            }
             Err(e) => {
-                eprintln!("Error checking Raydium tokens: {:?}", e);
+                eprintln!("@check_limit_orders/ error claiming limit order {}: {:?}", order.id, e);
                 continue;
             }
+        }
+        println!("@check_limit_orders/ limit order {} reached at price {}", order.id, current_price);
+
+        let message = match execute_swap_limit_buy(pool, order).await {
+            Ok(swap_result) => {
+                if let Err(e) = db::set_limit_order_status(pool, order.id, "filled", Some(&swap_result.signature), None).await {
+                    eprintln!("@check_limit_orders/ error marking limit order {} as filled: {:?}", order.id, e);
+                }
+                // The SOL is spent, a booking error must not make the order look failed
+                if let Err(e) = apply_buy_to_position(pool, &order.tg_user_id, &order.token_address, &order.chat_id, order.sol_amount, &swap_result, "limit", None).await {
+                    eprintln!("@check_limit_orders/ error adding the buy of limit order {} to the position: {:?}", order.id, e);
+                }
+                format!("🟢 Limit buy executed, bought {} SOL at ${}\nhttps://solscan.io/tx/{}", order.sol_amount, current_price, swap_result.signature)
+            }
+            Err(e) => {
+                eprintln!("@check_limit_orders/ error executing limit order {}: {:?}", order.id, e);
+                if let Err(e) = db::set_limit_order_status(pool, order.id, "failed", None, Some(&e.to_string())).await {
+                    eprintln!("@check_limit_orders/ error marking limit order {} as failed: {:?}", order.id, e);
+                }
+                format!("⚠️ Limit buy of {} SOL failed: {}", order.sol_amount, e)
+            }
         };
-        println!("@main/positions_watcher/ raydium tokens: {:?}", raydium_tokens);
-
-        let raydium_positions = all_positions.iter()
-            .filter(|p| raydium_tokens.contains(&p.token_address))
-            .collect::<Vec<_>>();
-        println!("@positions_watcher/ raydium positions: {:?}", raydium_positions);
-
-        let pumpfun_positions = all_positions.iter()
-            .filter(|p| !raydium_tokens.contains(&p.token_address))
-            .collect::<Vec<_>>();
-        println!("@positions_watcher/ pumpfun positions: {:?}", pumpfun_positions);
-
-        // Calculate new PumpFun tokens
-        let pumpfun_tokens: std::collections::HashSet<String> = tokens.into_iter()
-            .filter(|token| !raydium_tokens.contains(token))
-            .collect();
-
-        // If there are new tokens to subscribe
-        if !pumpfun_tokens.is_empty() {
-            let pump_payload = PumpPayload {
-                method: "subscribeTokenTrade".to_string(),
-                keys: pumpfun_tokens.iter().cloned().collect(),
-            };
-            if let Ok(payload_json) = serde_json::to_string(&pump_payload) {
-                if let Err(e) = pump_write.send(WsMessage::Text(payload_json)).await {
-                    eprintln!("Error sending subscription: {:?}", e);
+        if let Err(e) = bot.send_message(order.chat_id.clone(), message).await {
+            eprintln!("@check_limit_orders/ error sending message: {:?}", e);
+        }
+    }
+}
+
+/// Expire the limit orders past their expiry and notify their users, every minute
+async fn limit_orders_expirer(pool: SafePool, bot: &Bot) {
+    loop {
+        match db::expire_limit_orders(&pool).await {
+            Ok(orders) => {
+                for order in orders {
+                    println!("@limit_orders_expirer/ limit order {} expired", order.id);
+                    if let Err(e) = bot.send_message(order.chat_id.clone(), format!("⌛ Limit buy of {} SOL on <code>{}</code> expired", order.sol_amount, order.token_address))
+                        .parse_mode(teloxide::types::ParseMode::Html)
+                        .await {
+                        eprintln!("@limit_orders_expirer/ error sending message: {:?}", e);
+                    }
                 }
             }
+            Err(e) => eprintln!("@limit_orders_expirer/ error expiring limit orders: {:?}", e),
         }
+        tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+    }
+}
 
-        // TODO: Remove the tokens that doesnt have take_profit and stop_losses because why track their price if the application is not in realtime.
+/// Keep the cached ATHs of the calls up to date, every minute
+/// 
+/// # Description
+/// 
+/// Takes the calls whose ATH is missing or due for an update and raises it with the ATH since the
+/// last update, or since the call for a new one, so the leaderboards, user stats and /pnl never
+/// query the ATH API themselves.
+async fn call_aths_updater(pool: SafePool) {
+    loop {
+        match db::get_calls_due_for_ath_update(&pool, 100).await {
+            Ok(calls) => update_call_aths(&pool, calls).await,
+            Err(e) => eprintln!("@call_aths_updater/ error fetching calls: {:?}", e),
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+    }
+}
 
-        // Check Raydium prices
-        if let Ok(current_prices) = crate::utils::helpers::check_tokens_prices(
-            raydium_tokens.iter().cloned().collect()
-        ).await {
-            println!("@positions_watcher/ current_prices: {:?}", current_prices);
-            let mut count: usize = 0;
-            for position in &raydium_positions {
-                count += 1;
-                if let Some(current_price) = current_prices.get(&position.token_address) {
-                    let current_price_str = current_price.trim_matches('"');
-                    let current_price_float = current_price_str.parse::<f64>().unwrap_or_default();
-                    let percentage_change = ((current_price_float - position.entry_price) / position.entry_price) * 100.0;
-                    println!("@bot/main/positions_watcher/\n\nPosition:{}\n\nposition: {:?}\ncurrent_price: {:?}\nentry_price: {:?}\ntake_profit: {:?}\nstop_loss: {:?}\npercentage_change: {:.2}% \n\n\n\n", count, position, current_price_float, position.entry_price, position.take_profits, position.stop_losses, percentage_change);
-                    if position.take_profits.len() > 0 {
-                        if current_price_float >= (position.take_profits[0].0 * position.entry_price) {
-                            println!("@bot/main/positions_watcher/ Take profit reached for position: {}", count);
-                            // Execute take profit
-                            match execute_swap_take_profit(
-                                &pool,
-                                position.tg_user_id.clone(),
-                                (position.take_profits[0].0, position.take_profits[0].1),
-                                &position.token_address,
-                                "So11111111111111111111111111111111111111112"
-                            ).await {
-                                // TODO: Send message to user with the signature in the res
-                                Ok(_res) => {
-                                    println!("@positions_watcher/ take profit executed for position: {:?}", position);
-                                    bot.send_message(position.chat_id.clone(), format!("🟢 Take profit executed sold at {}x 📈 {}% of token balance", &position.take_profits[0].0, &position.take_profits[0].1)).await.expect("Could not send message");
-                                    // Get updated token amount after swap
-                                    if let Ok(user) = get_user_by_tg_id(&pool, &position.tg_user_id).await {
-                                        if let Some(solana_address) = user.solana_address {
-                                            match get_token_amount_in_wallet(&solana_address, &position.token_address).await {
-                                                Ok((user_token_amount, _)) => {
-                                                    println!("@positions_watcher/ user_token_amount: {:?}", user_token_amount);
-                                                    if user_token_amount > 0.0 {
-                                                        // If user still has tokens, just remove the take profit
-                                                        if let Err(e) = db::remove_take_profit_from_position(
-                                                            &pool,
-                                                            &position.token_address,
-                                                            &position.tg_user_id,
-                                                            (position.take_profits[0].0, position.take_profits[0].1)
-                                                        ).await {
-                                                            eprintln!("Error removing take profit: {:?}", e);
-                                                        }
-                                                    }
-                                                }
-                                                Err(e) => eprintln!("Error getting token amount: {:?}", e),
-                                            }
-                                        }
-                                    }
-                                }
-                                Err(e) => eprintln!("Error executing swap: {:?}", e),
-                            }
-                        } 
+/// Update the cached ATH of calls
+/// 
+/// # Description
+/// 
+/// Each token is scanned once for its current price, market cap and pair. The ATH of a call never
+/// goes below the price at the call or the current price, a failed ATH request falls back to them
+/// so the call isn't retried ahead of the others forever.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `calls` - The calls to update
+async fn update_call_aths(pool: &SafePool, calls: Vec<db::Call>) {
+    let call_ids: Vec<i32> = calls.iter().map(|call| call.id).collect();
+    let call_aths = match db::get_call_aths(pool, &call_ids).await {
+        Ok(call_aths) => call_aths,
+        Err(e) => {
+            eprintln!("@update_call_aths/ error fetching cached ATHs: {:?}", e);
+            return;
+        }
+    };
+
+    // (price, market cap, pair address) of each token
+    let mut markets: HashMap<String, Option<(f64, f64, String)>> = HashMap::new();
+    for call in calls {
+        if !markets.contains_key(&call.token_address) {
+            let market = match market_data().pair_stats(&call.token_address).await {
+                Ok(pair_stats) => Some((pair_stats.price_usd, pair_stats.fdv, pair_stats.pair.pair_address)),
+                Err(e) => {
+                    eprintln!("@update_call_aths/ error scanning {}: {:?}", call.token_address, e);
+                    None
+                }
+            };
+            markets.insert(call.token_address.clone(), market);
+        }
+        let market = markets.get(&call.token_address).cloned().flatten();
+
+        // Only a successful ATH request moves the date the next one starts from, a failed one
+        // would otherwise skip the highs in between
+        let since = call_aths.get(&call.id).and_then(|call_ath| call_ath.ath_checked_until).unwrap_or(call.time);
+        let checked_at = chrono::Utc::now();
+        let (ath_price, ath_checked_until) = match market_data().ath(&call.token_address, &call.chain, since).await {
+            Ok(ath) => (ath.price, Some(checked_at)),
+            Err(e) => {
+                eprintln!("@update_call_aths/ error fetching the ATH of call {}: {:?}", call.id, e);
+                (0.0, None)
+            }
+        };
+        let ath_price = ath_price
+            .max(call.price.parse::<f64>().unwrap_or(0.0))
+            .max(market.as_ref().map(|market| market.0).unwrap_or(0.0));
+
+        let result = db::upsert_call_ath(
+            pool,
+            call.id,
+            ath_price,
+            market.as_ref().map(|market| market.0),
+            market.as_ref().map(|market| market.1),
+            market.as_ref().map(|market| market.2.as_str()).filter(|pair_address| !pair_address.is_empty()),
+            ath_checked_until,
+        ).await;
+        if let Err(e) = result {
+            eprintln!("@update_call_aths/ error saving the ATH of call {}: {:?}", call.id, e);
+        }
+    }
+}
+
+/// Watch the recent calls for their milestones
+/// 
+/// # Description
+/// 
+/// Checks the milestones of the recent calls of a token each time the positions watcher passes
+/// on a new price for it, only the latest price of each token is checked when they piled up.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `bot` - The bot to post with
+/// * `price_updates` - The prices passed on by the positions watcher
+async fn call_milestones_watcher(pool: SafePool, bot: &Bot, mut price_updates: tokio::sync::mpsc::UnboundedReceiver<PriceUpdate>) {
+    while let Some(update) = price_updates.recv().await {
+        let mut latest_prices: HashMap<String, f64> = HashMap::new();
+        latest_prices.insert(update.token_address, update.price);
+        while let Ok(update) = price_updates.try_recv() {
+            latest_prices.insert(update.token_address, update.price);
+        }
+        for (token_address, current_price) in latest_prices {
+            check_call_milestones(&pool, bot, &token_address, current_price).await;
+        }
+    }
+}
+
+/// Announce the milestones reached by the recent calls of a token
+/// 
+/// # Description
+/// 
+/// Each milestone of the group is announced once per call, as a reply to the call message. When
+/// the price jumps over several milestones at once only the highest is announced, the lower ones
+/// are recorded so they aren't announced afterwards.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `bot` - The bot to post with
+/// * `token_address` - The token address
+/// * `current_price` - The current token price
+async fn check_call_milestones(pool: &SafePool, bot: &Bot, token_address: &str, current_price: f64) {
+    let calls = match db::get_recent_first_calls_by_token(pool, token_address, utils::helpers::CALL_MILESTONES_DAYS).await {
+        Ok(calls) => calls,
+        Err(e) => {
+            eprintln!("@check_call_milestones/ error fetching the calls of {}: {:?}", token_address, e);
+            return;
+        }
+    };
+    for call in calls {
+        let call_price = call.price.parse::<f64>().unwrap_or(0.0);
+        if call_price <= 0.0 {
+            continue;
+        }
+        let multiplier = current_price / call_price;
+        let group_settings = match db::get_group_settings(pool, &call.chat_id).await {
+            Ok(group_settings) => group_settings,
+            Err(e) => {
+                eprintln!("@check_call_milestones/ error fetching the settings of {}: {:?}", call.chat_id, e);
+                continue;
+            }
+        };
+        let reached: Vec<f64> = group_settings.milestones.iter().copied().filter(|milestone| multiplier >= *milestone).collect();
+        if reached.is_empty() {
+            continue;
+        }
+        let new_milestones = match db::insert_call_milestones(pool, call.id, &reached).await {
+            Ok(new_milestones) => new_milestones,
+            Err(e) => {
+                eprintln!("@check_call_milestones/ error recording the milestones of call {}: {:?}", call.id, e);
+                continue;
+            }
+        };
+        let milestone = match new_milestones.iter().copied().fold(None, |highest: Option<f64>, milestone| Some(highest.map_or(milestone, |highest| highest.max(milestone)))) {
+            Some(milestone) => milestone,
+            None => continue,
+        };
+        let username = match db::get_user(pool, &call.user_tg_id).await {
+            Ok(user) => user.username.unwrap_or("Unknown".to_string()),
+            Err(_) => "Unknown".to_string(),
+        };
+        println!("@check_call_milestones/ call {} reached {}x in {}", call.id, milestone, call.chat_id);
+        let chat_id = match call.chat_id.parse::<i64>() {
+            Ok(chat_id) => ChatId(chat_id),
+            Err(_) => continue,
+        };
+        let mut message = bot.send_message(chat_id, utils::helpers::call_milestone_message(&call, milestone, multiplier, &username))
+            .parse_mode(teloxide::types::ParseMode::Html);
+        if let Ok(message_id) = call.message_id.parse::<i32>() {
+            message = message.reply_parameters(teloxide::types::ReplyParameters { message_id: teloxide::types::MessageId(message_id), chat_id: None, allow_sending_without_reply: Some(true), quote: None, quote_parse_mode: None, quote_entities: None, quote_position: None });
+        }
+        if let Err(e) = message.await {
+            eprintln!("@check_call_milestones/ error announcing the milestone of call {}: {:?}", call.id, e);
+        }
+    }
+}
+
+/// Post the scheduled leaderboard digests of the groups, checked every minute
+/// 
+/// # Description
+/// 
+/// A digest is marked as sent before it is posted, so a group the bot can't post in anymore
+/// isn't retried every minute.
+async fn leaderboard_digests_scheduler(pool: SafePool, bot: &Bot) {
+    loop {
+        match db::get_digest_groups(&pool).await {
+            Ok(groups) => {
+                let now = chrono::Utc::now();
+                for settings in groups.iter().filter(|settings| utils::helpers::is_digest_due(settings, now)) {
+                    if let Err(e) = db::set_group_digest_sent(&pool, &settings.chat_id, now).await {
+                        eprintln!("@leaderboard_digests_scheduler/ error marking the digest of {}: {:?}", settings.chat_id, e);
+                        continue;
                     }
-                    if position.stop_losses.len() > 0 {
-                        if current_price_float <= (position.stop_losses[0].0 * position.entry_price) {
-                            println!("@bot/main/positions_watcher/ Stop loss reached for position: {}", count);
-                            if let Err(e) = execute_swap_stop_loss(
-                                &pool,
-                                position.tg_user_id.clone(),
-                                (position.stop_losses[0].0, position.stop_losses[0].1),
-                                &position.token_address,
-                                "So11111111111111111111111111111111111111112"
-                            ).await {
-                                eprintln!("Error executing swap: {:?}", e);
-                            } else {
-                                println!("@bot/main/positions_watcher/ Stop realized");
-                                println!("@positions_watcher/ deleting position stop loss");
-                                bot.send_message(position.chat_id.clone(), format!("🔴 Stop loss executed sold at {}x 📉 {}% of token balance", &position.stop_losses[0].0, &position.stop_losses[0].1)).await.expect("Could not send message");
-                                let user = get_user_by_tg_id(&pool, &position.tg_user_id).await.expect("Could not get user");
-                                let (user_token_amount, _) = get_token_amount_in_wallet(&user.solana_address.unwrap(), &position.token_address).await.expect("Could not get token amount in wallet.");
-                                if user_token_amount > 0.0 {
-                                    match db::remove_stop_loss_from_position(&pool, &position.token_address, &position.tg_user_id, (position.stop_losses[0].0, position.stop_losses[1].1)).await {
-                                        Ok(_) => {}
-                                        Err(e) => {
-                                            eprintln!("@bot/main/positions_watcher/ error removing stop loss from position error: {}", e);
-                                        }
-                                    }
-                                } else {
-                                    println!("@positions_watcher/ user has no token in wallet, deleting position");
-                                    match db::mark_position_completed(&pool, &position.token_address, &position.tg_user_id).await {
-                                        Ok(_) => {},
-                                        Err(e) => {
-                                            eprintln!("@bot/main/positions_watcher/ error marking position as completed: {}", e);
-                                        }
-                                    }
-                                }
-                                // Remove stop loss from position
-                                match db::remove_stop_loss_from_position(&pool, &position.token_address, &position.tg_user_id, (position.stop_losses[0].0, position.stop_losses[0].1)).await {
-                                    Ok(_) => {},
-                                    Err(e) => {
-                                        eprintln!("@bot/main/watcher_position/ error removing stop loss from position: {}", e);
-                                    }
-                                }
-                            }
-                        }
+                    println!("@leaderboard_digests_scheduler/ posting the {} digest of {}", settings.digest_frequency, settings.chat_id);
+                    if let Err(e) = commands::send_leaderboard_digest(bot, &pool, settings).await {
+                        eprintln!("@leaderboard_digests_scheduler/ error posting the digest of {}: {:?}", settings.chat_id, e);
                     }
                 }
             }
+            Err(e) => eprintln!("@leaderboard_digests_scheduler/ error fetching groups: {:?}", e),
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+    }
+}
+
+/// Reconcile the stored positions with the wallets, every 15 minutes
+/// 
+/// # Description
+/// 
+/// Positions drift from the wallets when the user trades from another wallet app or a swap fails
+/// after the position was saved. The open positions of every user with a wallet are compared with
+/// the token balances returned by the solana app.
+async fn positions_reconciler(pool: SafePool, bot: &Bot) {
+    loop {
+        match db::get_users_with_solana_address(&pool).await {
+            Ok(users) => {
+                for user in users {
+                    if let Err(e) = reconcile_user_positions(&pool, bot, &user).await {
+                        eprintln!("@positions_reconciler/ error reconciling the positions of {}: {:?}", user.tg_id, e);
+                    }
+                }
+            }
+            Err(e) => eprintln!("@positions_reconciler/ error fetching users: {:?}", e),
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(15 * 60)).await;
+    }
+}
+
+/// Reconcile the open positions of a user with the token balances of their wallet
+/// 
+/// # Description
+/// 
+/// A position whose token is gone from the wallet is marked completed, a token worth more than $1
+/// held without a position gets one opened at the current price, and a balance more than 1% off
+/// the position amount is flagged to the user once per balance. Positions opened and tokens traded
+/// in the last 10 minutes are skipped, their swap may still be landing.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `bot` - The Telegram bot
+/// * `user` - The user to reconcile
+/// 
+/// # Returns
+/// 
+/// An Ok result if the positions were reconciled
+async fn reconcile_user_positions(pool: &SafePool, bot: &Bot, user: &db::User) -> anyhow::Result<()> {
+    let sol_mint = "So11111111111111111111111111111111111111112";
+    let wallet_address = user.solana_address.clone().unwrap_or_default();
+
+    // A failed balance request must stop here, reading it as an empty wallet would complete every position
+    let balances = get_positions_balance(&wallet_address).await?;
+    let tokens = balances["tokens"].as_array().ok_or_else(|| anyhow::anyhow!("no tokens in the balance of {}", wallet_address))?;
+    let mut wallet_amounts: HashMap<String, (f64, f64)> = HashMap::new();
+    for token in tokens {
+        let mint = token["mint"].as_str().unwrap_or_default().to_string();
+        let entry = wallet_amounts.entry(mint).or_insert((0.0, 0.0));
+        entry.0 += token["token_amount"].as_f64().unwrap_or(0.0);
+        entry.1 += token["token_ui_amount"].as_f64().unwrap_or(0.0);
+    }
+
+    let settle_cutoff = chrono::Utc::now().naive_utc() - chrono::Duration::minutes(10);
+    let recently_traded: Vec<String> = db::get_user_trades(pool, &user.tg_id, 50).await?
+        .into_iter()
+        .filter(|trade| trade.created_at > settle_cutoff)
+        .map(|trade| trade.token_address)
+        .collect();
+    let positions = db::get_active_positions(pool, &user.tg_id).await?;
+
+    for position in &positions {
+        if position.created_at > settle_cutoff || recently_traded.contains(&position.token_address) {
+            continue;
+        }
+        let (wallet_amount, wallet_ui_amount) = wallet_amounts.get(&position.token_address).copied().unwrap_or((0.0, 0.0));
+
+        if wallet_amount <= 0.0 {
+            println!("@reconcile_user_positions/ position {} on {} has no balance left, marking it completed", position.id, position.token_address);
+            db::set_position_completed(pool, &position.token_address, &user.tg_id).await?;
+            let message = format!("🔄 Your wallet holds no more <code>{}</code>, the position was marked completed", position.token_address);
+            if let Err(e) = bot.send_message(position.chat_id.clone(), message).parse_mode(teloxide::types::ParseMode::Html).await {
+                eprintln!("@reconcile_user_positions/ error sending message: {:?}", e);
+            }
+            continue;
+        }
+
+        let drift = (wallet_amount - position.amount).abs() / position.amount.max(1.0);
+        if drift > 0.01 {
+            if position.onchain_mismatch_amount == Some(wallet_amount) {
+                continue;
+            }
+            println!("@reconcile_user_positions/ position {} tracks {} tokens, the wallet holds {}", position.id, position.amount, wallet_amount);
+            db::set_position_onchain_mismatch(pool, position.id, Some(wallet_amount)).await?;
+            let message = format!(
+                "⚠️ Your wallet holds {} <code>{}</code> but the position tracks {}\n\nThe tokens were traded outside the bot or a swap failed, the position PnL may be off",
+                format_number(wallet_ui_amount), position.token_address, format_number(position.ui_amount.parse::<f64>().unwrap_or(0.0))
+            );
+            if let Err(e) = bot.send_message(position.chat_id.clone(), message).parse_mode(teloxide::types::ParseMode::Html).await {
+                eprintln!("@reconcile_user_positions/ error sending message: {:?}", e);
+            }
+        } else if position.onchain_mismatch_amount.is_some() {
+            db::set_position_onchain_mismatch(pool, position.id, None).await?;
+        }
+    }
+
+    for (token_address, (amount, ui_amount)) in &wallet_amounts {
+        if token_address == sol_mint || *amount <= 0.0 || recently_traded.contains(token_address) || positions.iter().any(|position| &position.token_address == token_address) {
+            continue;
+        }
+        let pair_stats = match market_data().pair_stats(token_address).await {
+            Ok(pair_stats) => pair_stats,
+            Err(e) => {
+                eprintln!("@reconcile_user_positions/ error fetching {}: {:?}", token_address, e);
+                continue;
+            }
+        };
+        let token_price = pair_stats.price_usd;
+        // Dust and airdropped spam tokens aren't worth a position
+        if token_price * ui_amount < 1.0 {
+            continue;
+        }
+        let circulating_supply = market_data().circulating_supply(token_address).await.ok();
+        let mc_entry = circulating_supply.map(|circulating_supply| token_price * circulating_supply).unwrap_or(pair_stats.fdv);
+        // The cost of tokens bought elsewhere is unknown, they are tracked from their current value
+        let sol_price = match sol_to_usd(1.0).await {
+            Ok(sol_price) if sol_price > 0.0 => sol_price,
+            sol_price => {
+                eprintln!("@reconcile_user_positions/ no SOL price to value {}: {:?}", token_address, sol_price);
+                continue;
+            }
+        };
+        let sol_value = token_price * ui_amount / sol_price;
+
+        let position_id = db::insert_position(pool, &user.tg_id, token_address, Some(Vec::new()), Some(Vec::new()), None, Vec::new(), circulating_supply, *amount, mc_entry, token_price, &user.tg_id, sol_value, &ui_amount.to_string(), 0.0).await?;
+        println!("@reconcile_user_positions/ opened position {} for untracked {} of {}", position_id, token_address, user.tg_id);
+        let message = format!(
+            "🔄 Found {} <code>{}</code> in your wallet without a position, it is now tracked from the current price ${}",
+            format_number(*ui_amount), token_address, token_price
+        );
+        if let Err(e) = bot.send_message(user.tg_id.clone(), message).parse_mode(teloxide::types::ParseMode::Html).await {
+            eprintln!("@reconcile_user_positions/ error sending message: {:?}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Run the due slices of the recurring orders
+/// 
+/// # Description
+/// 
+/// Every 10 seconds claims the DCA and TWAP orders with a slice due and runs them. The schedule
+/// lives in the database so the orders carry on after a restart.
+async fn recurring_orders_scheduler(pool: SafePool, bot: &Bot) {
+    loop {
+        match db::claim_due_recurring_orders(&pool).await {
+            Ok(orders) => {
+                for order in orders {
+                    run_recurring_order_slice(&pool, bot, &order).await;
+                }
+            }
+            Err(e) => eprintln!("@recurring_orders_scheduler/ error claiming recurring orders: {:?}", e),
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
+    }
+}
+
+/// Run a slice of a recurring order and notify the user
+/// 
+/// # Description
+/// 
+/// A DCA slice buys the order SOL per slice. A TWAP slice sells an equal share of the balance
+/// left for the remaining slices, so the last one sells everything.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `bot` - The Telegram bot
+/// * `order` - The recurring order
+async fn run_recurring_order_slice(pool: &SafePool, bot: &Bot, order: &db::RecurringOrder) {
+    let sol_mint = "So11111111111111111111111111111111111111112";
+    let slice = order.executed_slices + 1;
+    println!("@run_recurring_order_slice/ order {} slice {}/{}", order.id, slice, order.total_slices);
+
+    let result = if order.kind == "dca" {
+        execute_swap_no_chat(pool, sol_mint, &order.token_address, order.tg_user_id.clone(), 0.0, order.sol_per_slice, "dca").await
+    } else {
+        let remaining_slices = (order.total_slices - order.executed_slices).max(1);
+        execute_swap_no_chat(pool, &order.token_address, sol_mint, order.tg_user_id.clone(), 100.0 / remaining_slices as f64, None, "twap").await
+    };
+
+    let kind = if order.kind == "dca" { "DCA" } else { "TWAP" };
+    let mut message = match &result {
+        Ok(swap_result) => format!("🔁 {} #{} slice {}/{} executed\nhttps://solscan.io/tx/{}", kind, order.id, slice, order.total_slices, swap_result.signature),
+        Err(e) => format!("⚠️ {} #{} slice {}/{} failed: {}", kind, order.id, slice, order.total_slices, e),
+    };
+    let recorded = match &result {
+        Ok(swap_result) => db::record_recurring_order_slice(pool, order.id, Some(&swap_result.signature), None).await,
+        Err(e) => db::record_recurring_order_slice(pool, order.id, None, Some(&e.to_string())).await,
+    };
+    match recorded {
+        Ok(Some(order)) if order.status == "completed" => message.push_str(&format!("\n✅ {} #{} completed", kind, order.id)),
+        Ok(Some(_)) => (),
+        Ok(None) => println!("@run_recurring_order_slice/ order {} isn't active anymore, slice not recorded", order.id),
+        Err(e) => eprintln!("@run_recurring_order_slice/ error recording slice of order {}: {:?}", order.id, e),
+    }
+
+    // Each DCA buy averages into the position, the first one opens it so the user TP/SL apply to it
+    if order.kind == "dca" {
+        if let Ok(swap_result) = &result {
+            if let Err(e) = apply_buy_to_position(pool, &order.tg_user_id, &order.token_address, &order.chat_id, order.sol_per_slice.unwrap_or(0.0), swap_result, "dca", None).await {
+                eprintln!("@run_recurring_order_slice/ error adding the buy to the position: {:?}", e);
+            }
         }
-        tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
     }
+
+    if let Err(e) = bot.send_message(order.chat_id.clone(), message).await {
+        eprintln!("@run_recurring_order_slice/ error sending message: {:?}", e);
+    }
+}
+
+/// Execute the swap of a take profit / stop loss / trailing stop level
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `position` - The position
+/// * `level` - The level to execute
+/// 
+/// # Returns
+/// 
+/// The swap transaction signature
+async fn execute_ladder_level(pool: &SafePool, position: &db::Position, level: &LadderLevel) -> anyhow::Result<String> {
+    let response = match level.kind {
+        OrderKind::TakeProfit | OrderKind::MarketCapTakeProfit => execute_swap_take_profit(
+            pool,
+            position.tg_user_id.clone(),
+            (level.multiplier, level.sell_percentage),
+            &position.token_address,
+            "So11111111111111111111111111111111111111112",
+            level.kind.as_str()
+        ).await?,
+        // Trailing stops and market cap stop losses sell like a stop loss, only the trigger differs
+        OrderKind::StopLoss | OrderKind::TrailingStop | OrderKind::MarketCapStopLoss => execute_swap_stop_loss(
+            pool,
+            position.tg_user_id.clone(),
+            (level.multiplier, level.sell_percentage),
+            &position.token_address,
+            "So11111111111111111111111111111111111111112",
+            level.kind.as_str()
+        ).await?,
+    };
+    Ok(response.signature)
 }
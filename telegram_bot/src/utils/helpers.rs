@@ -8,12 +8,17 @@ use crate::db::*;
 use regex::Regex;
 use chrono::Duration;
 use teloxide::types::{InlineKeyboardMarkup, InlineKeyboardButton};
-use reqwest::Client;
+use teloxide::utils::html;
 use sqlx::PgPool;
 use anyhow::Result;
-use crate::commands::get_scanner_search;
 use teloxide::types::Message;
 use chrono::{NaiveDateTime, Utc, DateTime};
+use chrono_tz::Tz;
+use crate::utils::ladder::{trailing_stop_trigger_price, MarketCapTarget};
+use crate::utils::pnl_card::PnlCard;
+use crate::utils::market_data::{market_data, Ath, Holders, PairStats};
+pub use crate::utils::market_data::raydium::check_raydiums_tokens;
+use crate::utils::safety::{safety_message, TokenSafety};
 
 
 /// Convert lamports to SOL
@@ -29,127 +34,199 @@ pub fn lamports_to_sol(lamports: i32) -> f64 {
     lamports as f64 / 1_000_000_000.0
 }
 
-/// Check the period from a lb command
+/// Parse a period of calls
+/// 
+/// # Description
+/// 
+/// Accepts "all", a duration back from now made of a number and a unit, one of h (hours),
+/// d (days), w (weeks), m (months) or y (years), eg "12h" or "7d", and date ranges with both
+/// dates included: "2024-10-01..2024-10-31", "2024-10-01.." (until now), "..2024-10-31" or a
+/// single day "2024-10-01".
 /// 
 /// # Arguments
 /// 
-/// * `text` - The command text to check
+/// * `text` - The period to parse
 /// 
 /// # Returns
 /// 
-/// An Option containing the period or None if no period is found
-pub fn check_period(text: &str) -> Option<String> {
-    if text.ends_with("d") || text.ends_with("D") {
-        Some("Days".to_string())
-    } else if text.ends_with("w") || text.ends_with("W") {
-        Some("Weeks".to_string())
-    } else if text.ends_with("m") || text.ends_with("M") {
-        Some("Months".to_string())
-    } else if text.ends_with("y") || text.ends_with("Y") {
-        Some("Years".to_string())
-    } else if text.ends_with("h") || text.ends_with("H") {
-        Some("Hours".to_string())
-    } else {
-        None
+/// An Option containing the period or None if the text isn't a period
+pub fn parse_period(text: &str) -> Option<CallPeriod> {
+    let text = text.trim().to_lowercase();
+    if text == "all" {
+        return Some(CallPeriod { start: None, end: None, label: "All time".to_string() });
+    }
+
+    let duration_re = Regex::new(r"^(\d+)([hdwmy])$").unwrap();
+    if let Some(cap) = duration_re.captures(&text) {
+        let number = cap[1].parse::<u32>().ok()?;
+        let now = Utc::now();
+        let start = match &cap[2] {
+            "h" => now.checked_sub_signed(Duration::try_hours(number as i64)?),
+            "d" => now.checked_sub_signed(Duration::try_days(number as i64)?),
+            "w" => now.checked_sub_signed(Duration::try_weeks(number as i64)?),
+            "m" => now.checked_sub_months(chrono::Months::new(number)),
+            _ => now.checked_sub_months(chrono::Months::new(number.checked_mul(12)?)),
+        }?;
+        return Some(CallPeriod { start: Some(start), end: None, label: text });
+    }
+
+    let range_re = Regex::new(r"^(\d{4}-\d{2}-\d{2})?(\.\.)?(\d{4}-\d{2}-\d{2})?$").unwrap();
+    let cap = range_re.captures(&text)?;
+    let parse_day = |day: Option<regex::Match>| -> Option<Option<DateTime<Utc>>> {
+        match day {
+            Some(day) => Some(Some(chrono::NaiveDate::parse_from_str(day.as_str(), "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0)?.and_utc())),
+            None => Some(None),
+        }
+    };
+    let first_day = parse_day(cap.get(1))?;
+    let last_day = parse_day(cap.get(3))?;
+    let (start, last_day, label) = match (cap.get(2).is_some(), first_day, last_day) {
+        // A single day
+        (false, Some(day), None) => (Some(day), Some(day), cap[1].to_string()),
+        (true, Some(first_day), Some(last_day)) if first_day <= last_day => (Some(first_day), Some(last_day), format!("{} → {}", &cap[1], &cap[3])),
+        (true, Some(first_day), None) => (Some(first_day), None, format!("Since {}", &cap[1])),
+        (true, None, Some(last_day)) => (None, Some(last_day), format!("Until {}", &cap[3])),
+        _ => return None,
+    };
+    // The last day is included
+    let end = match last_day {
+        Some(last_day) => Some(last_day.checked_add_signed(Duration::days(1))?),
+        None => None,
+    };
+    Some(CallPeriod { start, end, label })
+}
+
+/// Metric used to rank a leaderboard
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LeaderboardMetric {
+    BestMultiplier, // The best calls, one row per call
+    AverageMultiplier, // The callers by average multiplier of their calls
+    MedianMultiplier, // The callers by median multiplier of their calls
+    HitRate, // The callers by share of calls that reached 2x
+    TotalCalls, // The callers by number of calls
+}
+
+impl LeaderboardMetric {
+    /// Parse a metric from its name in a lb command
+    pub fn parse(text: &str) -> Option<LeaderboardMetric> {
+        match text.trim().to_lowercase().as_str() {
+            "best" | "x" => Some(LeaderboardMetric::BestMultiplier),
+            "avg" | "average" => Some(LeaderboardMetric::AverageMultiplier),
+            "median" | "med" => Some(LeaderboardMetric::MedianMultiplier),
+            "hits" | "hitrate" | "hit" => Some(LeaderboardMetric::HitRate),
+            "calls" | "total" => Some(LeaderboardMetric::TotalCalls),
+            _ => None,
+        }
+    }
+
+    /// Name shown on the leaderboard
+    pub fn label(&self) -> &'static str {
+        match self {
+            LeaderboardMetric::BestMultiplier => "Best call",
+            LeaderboardMetric::AverageMultiplier => "Average x",
+            LeaderboardMetric::MedianMultiplier => "Median x",
+            LeaderboardMetric::HitRate => "Hit rate (≥2x)",
+            LeaderboardMetric::TotalCalls => "Total calls",
+        }
     }
 }
 
-/// Check the period for a leaderboard command
+/// Parse the period, metric and minimum number of calls of a leaderboard command
 /// 
 /// # Arguments
 /// 
-/// * `text` - The command text to check
+/// * `text` - The command text
 /// 
 /// # Returns
 /// 
-/// An Option containing a tuple of the number and unit
-pub fn check_period_for_leaderboard(text: &str) -> Option<(u32, &str)> {
-    let re = regex::Regex::new(r"(\d+)([hdwy])$").unwrap();
-    re.captures(text).and_then(|cap| {
-        let number = cap.get(1)?.as_str().parse::<u32>().ok()?;
-        let unit = cap.get(2)?.as_str();
-        Some((number, unit))
-    })
+/// A tuple with the period, the metric and the minimum calls, None when not given
+fn parse_leaderboard_args(text: &str) -> (Option<CallPeriod>, Option<LeaderboardMetric>, Option<usize>) {
+    let mut period = None;
+    let mut metric = None;
+    let mut min_calls = None;
+    for arg in text.split_whitespace().skip(1) {
+        if let Some(arg_min_calls) = arg.to_lowercase().strip_prefix("min=").and_then(|min_calls| min_calls.parse::<usize>().ok()) {
+            min_calls = Some(arg_min_calls);
+        } else if let Some(arg_period) = parse_period(arg) {
+            period = Some(arg_period);
+        } else if let Some(arg_metric) = LeaderboardMetric::parse(arg) {
+            metric = Some(arg_metric);
+        }
+    }
+    (period, metric, min_calls)
 }
 
-/// Check if the message is a lb command
+/// Parse the arguments of a lb command
+/// 
+/// # Description
+/// 
+/// The period and the metric can be given in any order, eg "/lb 7d avg" or "/lb hits all".
+/// The leaderboard defaults to the best calls of the default period.
 /// 
 /// # Arguments
 /// 
-/// * `message` - The message to check
+/// * `text` - The command text
+/// * `default_period` - The period without one in the command, the last day if it isn't valid
 /// 
 /// # Returns
 /// 
-/// A boolean indicating if the message is a ranking command
-pub fn is_lb_command(message: &str) -> bool {
-    message.starts_with("/lb")
+/// A tuple with the period and the metric
+pub fn parse_lb_command(text: &str, default_period: &str) -> (CallPeriod, LeaderboardMetric) {
+    let (period, metric, _) = parse_leaderboard_args(text);
+    let period = period.or_else(|| parse_period(default_period)).unwrap_or_else(|| parse_period("1d").unwrap());
+    (period, metric.unwrap_or(LeaderboardMetric::BestMultiplier))
 }
 
+/// Minimum number of calls to appear on the callers ranking, unless the command sets one
+pub const DEFAULT_CALLERS_MIN_CALLS: usize = 3;
 
-/// Extract the days from a lb command
+/// Parse the arguments of a callers command
 /// 
-/// # Arguments
-/// 
-/// * `command` - The command to extract the days from
-/// 
-/// # Returns
+/// # Description
 /// 
-/// An Option containing the days or None
-pub fn extract_days(command: &str) -> Option<u32> {
-    let re = regex::Regex::new(r"/lb (\d+)d").unwrap();
-    re.captures(command)
-        .and_then(|cap| cap.get(1).map(|m| m.as_str().parse::<u32>().ok()))
-        .flatten()
-}
-
-/// Extract the hours from a lb command
+/// Takes a period, a metric and a minimum number of calls as "min=<calls>" in any order, eg
+/// "/callers 30d median min=5". The ranking defaults to the average multiplier over 30 days.
 /// 
 /// # Arguments
 /// 
-/// * `command` - The command to extract the hours from
+/// * `text` - The command text
 /// 
 /// # Returns
 /// 
-/// An Option containing the hours or None
-pub fn extract_hours(command: &str) -> Option<u32> {
-    let re = regex::Regex::new(r"/lb (\d+)h").unwrap();
-    re.captures(command)
-        .and_then(|cap| cap.get(1).map(|m| m.as_str().parse::<u32>().ok()))
-        .flatten()
+/// A tuple with the period, the metric and the minimum number of calls
+pub fn parse_callers_command(text: &str) -> (CallPeriod, LeaderboardMetric, usize) {
+    let (period, metric, min_calls) = parse_leaderboard_args(text);
+    let period = period.unwrap_or_else(|| parse_period("30d").unwrap());
+    (period, metric.unwrap_or(LeaderboardMetric::AverageMultiplier), min_calls.unwrap_or(DEFAULT_CALLERS_MIN_CALLS))
 }
 
-/// Extract the months from a lb command
+/// Check if the message is a lb command
 /// 
 /// # Arguments
 /// 
-/// * `command` - The command to extract the months from
+/// * `message` - The message to check
 /// 
 /// # Returns
 /// 
-/// An Option containing the months or None
-pub fn extract_months(command: &str) -> Option<u32> {
-    let re = regex::Regex::new(r"/lb (\d+)m").unwrap();
-    re.captures(command)
-        .and_then(|cap| cap.get(1).map(|m| m.as_str().parse::<u32>().ok()))
-        .flatten()
+/// A boolean indicating if the message is a ranking command
+pub fn is_lb_command(message: &str) -> bool {
+    message.starts_with("/lb")
 }
 
-/// Extract the years from a lb command
+/// Check if the message is a callers command
 /// 
 /// # Arguments
 /// 
-/// * `command` - The command to extract the years from
+/// * `message` - The message to check
 /// 
 /// # Returns
 /// 
-/// An Option containing the years or None
-pub fn extract_years(command: &str) -> Option<u32> {
-    let re = regex::Regex::new(r"/lb (\d+)y").unwrap();
-    re.captures(command)
-        .and_then(|cap| cap.get(1).map(|m| m.as_str().parse::<u32>().ok()))
-        .flatten()
+/// A boolean indicating if the message is a callers ranking command
+pub fn is_callers_command(message: &str) -> bool {
+    message.starts_with("/callers")
 }
 
+
 /// Convert a time string to a timestamp
 /// 
 /// # Arguments
@@ -194,8 +271,7 @@ pub async fn get_call_info(address: &String, pool: &PgPool, msg: &Message) -> Re
     // First call info
     let mut call_info_str = String::new();
     let is_first_call = crate::db::is_first_call(&pool,address.as_ref(), msg.chat.id.to_string().as_str()).await?;
-    let scanner_response = get_scanner_search(address).await?;
-    let mkt_cap = scanner_response["pair"]["token1TotalSupplyFormatted"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0) * scanner_response["pair"]["pairPrice1Usd"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
+    let mkt_cap = market_data().pair_stats(address).await?.market_cap();
     if !is_first_call {
         let chat_id_str = msg.chat.id.to_string();
         let first_call = {
@@ -265,16 +341,19 @@ pub fn create_main_menu_keyboard() -> InlineKeyboardMarkup {
     ]);
     buttons.push(vec![
         InlineKeyboardButton::callback("Limit orders", "limit_orders"), 
+        InlineKeyboardButton::callback("🔁 DCA / TWAP", "recurring_orders"),
         InlineKeyboardButton::callback("Auto sell", "auto_sell")
     ]);
     buttons.push(vec![
         InlineKeyboardButton::callback("Positions", "positions"),
+        InlineKeyboardButton::callback("📊 Portfolio", "portfolio"),
         InlineKeyboardButton::callback("Wallet", "wallet"),
         InlineKeyboardButton::callback("Help", "help"),
     ]);
 
     buttons.push(vec![
         InlineKeyboardButton::callback("Settings", "settings"),
+        InlineKeyboardButton::callback("📒 Trades", "trades_journal"),
         InlineKeyboardButton::callback("💰 Referrals", "referrals"),
     ]);
 
@@ -342,6 +421,7 @@ pub async fn create_sol_sell_swap_keyboard(pool: &PgPool, user_tg_id: &str, toke
 
     buttons.push(vec![
         InlineKeyboardButton::callback("Sell", format!("sell:{}", token_address)),
+        InlineKeyboardButton::callback("🔁 TWAP Sell", format!("twap_sell:{}", token_address)),
     ]);
 
     Ok(InlineKeyboardMarkup::new(buttons))
@@ -408,10 +488,29 @@ pub async fn create_sol_buy_swap_keyboard(pool: &PgPool, user_tg_id: &str) -> In
         InlineKeyboardButton::callback(if slippage == 0.18 { "✅ 18% Slippage" } else { "18% Slippage" }, "_"),
         InlineKeyboardButton::callback(if slippage != 0.18 { format!("✅ {}% Slippage 📝", slippage * 100.0) } else { "X Slippage 🖌".to_string() }, "set_custom_slippage")
     ]);
+    // Strategy presets, picking one sets the amount and the ladders of the next buy of the token
+    let presets = get_or_create_strategy_presets(&pool, user_tg_id).await.unwrap_or_default();
+    let last_sent_token = get_user_last_sent_token(&pool, user_tg_id).await.unwrap_or_default();
+    let pending_preset_id = get_pending_strategy_preset(&pool, user_tg_id, &last_sent_token).await.unwrap_or_default().map(|preset| preset.id);
+    for presets_row in presets.chunks(3) {
+        buttons.push(presets_row.iter().map(|preset| {
+            InlineKeyboardButton::callback(
+                if pending_preset_id == Some(preset.id) { format!("✅ {} · {} SOL", preset.name, preset.buy_amount) } else { format!("🎯 {}", preset.name) },
+                format!("preset:{}", preset.id)
+            )
+        }).collect());
+    }
+    buttons.push(vec![
+        InlineKeyboardButton::callback("💾 Save Preset", "save_preset"),
+        InlineKeyboardButton::callback("⚙️ Presets", "strategy_presets"),
+    ]);
     buttons.push(vec![
         InlineKeyboardButton::callback("Add Take Profit", "add_take_profit"),
         InlineKeyboardButton::callback("Add Stop Loss", "add_stop_loss"),
     ]);
+    buttons.push(vec![
+        InlineKeyboardButton::callback("Add Trailing Stop", "add_trailing_stop"),
+    ]);
 
     // Add a row for take profits
     let take_profits = user_settings.take_profits.clone();
@@ -440,9 +539,41 @@ pub async fn create_sol_buy_swap_keyboard(pool: &PgPool, user_tg_id: &str) -> In
         }
     }
 
+    for mc_target in user_settings.mc_targets.clone() {
+        let row: Vec<InlineKeyboardButton> = vec![
+            InlineKeyboardButton::callback(format!("${} MC {}", format_number(mc_target.market_cap), if mc_target.is_take_profit() { "📈" } else { "📉" }), "_"),
+            InlineKeyboardButton::callback(format!("sell {}% 💰", mc_target.sell_percentage), "_"),
+            InlineKeyboardButton::callback("❌", format!("delete_mc_target:{}_{}_{}", mc_target.kind, mc_target.market_cap, mc_target.sell_percentage)),
+        ];
+        buttons.push(row);
+    }
+
+    for trailing_stop in user_settings.trailing_stops.clone() {
+        let row: Vec<InlineKeyboardButton> = vec![
+            InlineKeyboardButton::callback(format!("-{}% from high 📉", trailing_stop.0), "_"),
+            InlineKeyboardButton::callback(format!("sell {}% 💰", trailing_stop.1), "_"),
+            InlineKeyboardButton::callback("❌", format!("delete_trailing_stop:{}_{}", trailing_stop.0, trailing_stop.1)),
+        ];
+        buttons.push(row);
+    }
+
+    let is_limit = user_settings.swap_or_limit == "limit";
     buttons.push(vec![
-        InlineKeyboardButton::callback("Buy", format!("buy:{}", buy_amount)),
+        InlineKeyboardButton::callback(if is_limit { "Swap" } else { "✅ Swap" }, "toggle_swap_limit:swap"),
+        InlineKeyboardButton::callback(if is_limit { "✅ Limit" } else { "Limit" }, "toggle_swap_limit:limit"),
     ]);
+
+    if is_limit {
+        buttons.push(vec![
+            InlineKeyboardButton::callback(format!("Limit Buy {} SOL", buy_amount), "limit_buy"),
+            InlineKeyboardButton::callback("🔁 DCA Buy", "dca_buy"),
+        ]);
+    } else {
+        buttons.push(vec![
+            InlineKeyboardButton::callback("Buy", format!("buy:{}", buy_amount)),
+            InlineKeyboardButton::callback("🔁 DCA Buy", "dca_buy"),
+        ]);
+    }
     println!("@create_sol_buy_swap_keyboard/ buttons created");
     InlineKeyboardMarkup::new(buttons)
 }
@@ -522,44 +653,17 @@ pub fn is_pnl_command(message: &str) -> bool {
     message.starts_with("/pnl")
 }
 
-/// Get the pair address and token address
-/// 
-/// # Arguments
-/// 
-/// * `address` - The address to get the pair address and token address for
-/// 
-/// # Returns
-/// 
-/// A JSON object containing the pair address and token address
-pub async fn get_pair_token_pair_and_token_address(address: &str) -> Result<Value> {
-    let client = Client::new();
-    let response = client.get(format!("https://api-rs.dexcelerate.com/pair/{}/pair-and-token", address))
-        .send()
-        .await?;
-    let json: serde_json::Value = response.json().await?;
-    Ok(json)
-}
-
-
-/// Get the ATH of a token
+/// Check if the message is a portfolio command
 /// 
 /// # Arguments
 /// 
-/// * `timestamp` - The timestamp to get the ATH for
-/// * `token_address` - The address of the token
+/// * `message` - The message to check
 /// 
 /// # Returns
 /// 
-/// A JSON object containing the ATH
-pub async fn get_ath(timestamp: i64, token_address: &str, chain: &str) -> Result<Value> {
-    let url = format!("https://api-rs.dexcelerate.com/token/{}/{}/ath?timestamp={}", chain, token_address, timestamp);
-    let client = Client::new();
-    let response = client.get(url)
-        .send()
-        .await?;
-    let json: serde_json::Value = response.json().await?;
-
-    Ok(json)
+/// A boolean indicating if the message is a portfolio command
+pub fn is_portfolio_command(message: &str) -> bool {
+    message.starts_with("/portfolio")
 }
 
 /// Format the number to a more readable format
@@ -581,19 +685,6 @@ pub fn format_number(num: f64) -> String {
     }
 }
 
-/// Calculate the liquidity of a pair
-/// 
-/// # Arguments
-/// 
-/// * `pair0_reserve_usd` - The reserve of the first token in USD
-/// * `pair1_reserve_usd` - The reserve of the second token in USD
-/// 
-/// # Returns
-/// 
-pub fn calculate_liquidity(pair0_reserve_usd: f64, pair1_reserve_usd: f64) -> f64 {
-    pair0_reserve_usd + pair1_reserve_usd
-}
-
 /// 
 /// # Arguments
 /// 
@@ -696,37 +787,38 @@ pub fn format_age(duration: Duration) -> String {
 /// 
 /// # Arguments
 /// 
-/// * `ath_response` - The response from the API call to get the ATH
-/// * `holders_response` - The response from the API call to get the holders
-/// * `data` - The response from the API call to get the data
-/// * `username` - The username of the user who made the call
+/// * `pool` - The database pool
+/// * `ath` - The ATH of the token since its pair was created
+/// * `holders` - The holders of the token
+/// * `safety` - The safety scan of the token
+/// * `pair_stats` - The market stats of the main pair of the token
+/// * `call_info_str` - The first call info, empty for the first call
+/// * `user` - The user who made the call
 /// 
 /// # Returns
 /// 
 /// A string containing the formatted message
-pub async fn call_message(pool: &SafePool, ath_response: &Value, holders_response: &Value, scanner_response: &Value,  mut call_info_str: String, user: User, chain: &str) -> String {
+pub async fn call_message(pool: &SafePool, ath: &Ath, holders: &Holders, safety: &TokenSafety, pair_stats: &PairStats, mut call_info_str: String, user: User) -> String {
     // Main info
-    let pair_address = scanner_response["pair"]["pairAddress"].as_str().unwrap_or("");
-    let token_symbol = scanner_response["pair"]["token1Symbol"].as_str().unwrap_or("N/A").to_uppercase();
-    let token_name = scanner_response["pair"]["token1Name"].as_str().unwrap_or("N/A");
-    let token_usd_price = format!("{:.8}", scanner_response["pair"]["pairPrice1Usd"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0)).parse::<f64>().unwrap_or(0.0);
-    let age = age_token(scanner_response["pair"]["pairCreatedAt"].as_str().unwrap_or(""));
-    let circulating_supply = scanner_response["pair"]["token1TotalSupplyFormatted"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
+    let pair_address = pair_stats.pair.pair_address.as_str();
+    let chain = pair_stats.pair.chain.as_str();
+    let token_symbol = pair_stats.symbol.to_uppercase();
+    let token_name = pair_stats.name.as_str();
+    let token_usd_price = format!("{:.8}", pair_stats.price_usd).parse::<f64>().unwrap_or(0.0);
+    let age = pair_stats.created_at.map(|created_at| age_token(&created_at.to_rfc3339())).unwrap_or("N/A".to_string());
     
     // Stats
-    let fdv = format_number(scanner_response["pair"]["fdv"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0));
+    let fdv = format_number(pair_stats.fdv);
 
     // Ath 
-    let ath = format_number(ath_response["athTokenPrice"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0) * circulating_supply);
-    let ath_date = time_ago(ath_response["athTimestamp"].as_str().unwrap_or(""));
+    let ath_date = ath.timestamp.map(|timestamp| time_ago(&timestamp.to_rfc3339())).unwrap_or("N/A".to_string());
+    let ath = format_number(ath.price * pair_stats.total_supply);
     
     // Liq
-    let pair_reserves0 = scanner_response["pair"]["pairReserves0Usd"].as_str().unwrap_or("0");
-    let pair_reserves1 = scanner_response["pair"]["pairReserves1Usd"].as_str().unwrap_or("0");
-    let liquidity: String = format_number(calculate_liquidity(pair_reserves0.parse::<f64>().unwrap_or(0.0), pair_reserves1.parse::<f64>().unwrap_or(0.0)));
+    let liquidity: String = format_number(pair_stats.liquidity_usd);
     
-    let volume = format_number(scanner_response["pairStats"]["twentyFourHour"]["volume"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0));
-    let mkt_cap: String = format_number(scanner_response["pair"]["token1TotalSupplyFormatted"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0) * scanner_response["pair"]["pairPrice1Usd"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0));
+    let volume = format_number(pair_stats.volume_24h);
+    let mkt_cap: String = format_number(pair_stats.market_cap());
     log::info!("mkt_cap: {}", mkt_cap);
 
    //  If is first call, call_info_str com empty from @call function, so we need to add the first call info
@@ -734,55 +826,27 @@ pub async fn call_message(pool: &SafePool, ath_response: &Value, holders_respons
         call_info_str = format!("🔥 First Call <a href=\"https://t.me/sj_copyTradebot?start=user_{}\"><i><b>{}</b></i></a> @ {}\n",user.id,  user.username.unwrap_or("N/A".to_string()), mkt_cap);
         call_info_str.push_str(&format!("└ Calls today: {} 🎉", crate::db::get_qtd_calls_user_made_in_24hrs(&pool, user.tg_id.as_str()).await.unwrap_or(0)));
     }   
-    // One hour change
-    let one_hour_first = scanner_response["pairStats"]["oneHour"]["first"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
-    let one_hour_last = scanner_response["pairStats"]["oneHour"]["last"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
-    let one_hour_change = if one_hour_first != 0.0 {
-        ((one_hour_last / one_hour_first) - 1.0) * 100.0
-    } else {
-        0.0
-    };
-    let one_hour_change_str = format!("{:.2}", one_hour_change);
-    // 24 hour change
-    let twenty_four_hour_first = scanner_response["pairStats"]["twentyFourHour"]["first"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
-    let twenty_four_hour_last = scanner_response["pairStats"]["twentyFourHour"]["last"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
-    let twenty_four_hour_change = if twenty_four_hour_first != 0.0 {
-        ((twenty_four_hour_last / twenty_four_hour_first) - 1.0) * 100.0
-    } else {
-        0.0
-    };
-    let twenty_four_hour_change_str = format_number(twenty_four_hour_change);
+    let one_hour_change_str = format!("{:.2}", pair_stats.price_change_1h);
+    let twenty_four_hour_change_str = format_number(pair_stats.price_change_24h);
     
     // Info
-    let buy_volume = format_number(scanner_response["pairStats"]["oneHour"]["buyVolume"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0));
-    let buys = format_number(scanner_response["pairStats"]["oneHour"]["buys"].as_i64().unwrap_or(0) as f64);
-    let sells = format_number(scanner_response["pairStats"]["oneHour"]["sells"].as_i64().unwrap_or(0) as f64);
+    let buy_volume = format_number(pair_stats.buy_volume_1h);
+    let buys = format_number(pair_stats.buys_1h as f64);
+    let sells = format_number(pair_stats.sells_1h as f64);
 
-    let lp = if scanner_response["pair"]["totalLockedRatio"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0) > 0.0 { "🔥" } else { "🔴" };
+    let token_address = pair_stats.pair.token_address.as_str();
+    let safety_str = safety_message(safety);
 
-    let token_address = scanner_response["pair"]["token1Address"].as_str().unwrap_or("");
-    let verified = if scanner_response["pair"]["isVerified"].as_bool().unwrap_or(false) { "🟢" } else { "🔴" };
+    let top_10_holders_percentage = format_number(holders.top_percent(10));
 
-    let top_10_holders_percentage = format_number(holders_response["holders"]
-    .as_array()
-    .unwrap_or(&Vec::new())
-    .iter()
-    .skip(1)
-    .take(10)  // Take only the first 10 elements
-    .map(|h| h["percent"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0) * 100.0) // Multiply by 100 to convert to percentage
-    .sum::<f64>());
-
-    let holders_str = holders_response["holders"]
-    .as_array()
-    .unwrap_or(&Vec::new())
+    let holders_str = holders.holders
     .iter()
     .skip(1)
     .take(5)
     .enumerate()
     .map(|(i, holder)| {
-        let holder_address = holder["holderAddress"].as_str().unwrap_or("");
-        let percent = holder["percent"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0) * 100.0;
-        let percent_str = format!("{:.1}", percent);
+        let holder_address = holder.address.as_str();
+        let percent_str = format!("{:.1}", holder.percent);
         
         match i {
             0 => format!("👥 TH: <a href=\"https://solscan.io/account/{holder_address}\">{percent_str}</a>⋅"),
@@ -795,9 +859,9 @@ pub async fn call_message(pool: &SafePool, ath_response: &Value, holders_respons
     .join("");
 
     // links management
-    let twitter = scanner_response["pair"]["linkTwitter"].as_str().unwrap_or("");
-    let website = scanner_response["pair"]["linkWebsite"].as_str().unwrap_or("");   
-    let telegram = scanner_response["pair"]["linkTelegram"].as_str().unwrap_or("");
+    let twitter = pair_stats.twitter.as_deref().unwrap_or("");
+    let website = pair_stats.website.as_deref().unwrap_or("");   
+    let telegram = pair_stats.telegram.as_deref().unwrap_or("");
 
     let mut links = String::new();
     let mut link_added = false;
@@ -844,7 +908,7 @@ pub async fn call_message(pool: &SafePool, ath_response: &Value, holders_respons
         ⛰️ ATH: <code>${ath}</code> <code>[{ath_date}]</code>\n\
         🚀 1H: <code>{one_hour_change_str}%</code> . <code>${buy_volume}</code> 🅑 {buys} 🅢 {sells}\n\
         {holders_str}\n\
-        LP: {lp} Mint:{verified}\n\
+        {safety_str}\n\
         {links_section}\
         <code>{token_address}</code>\n\n\
         {call_info_str}\n\n\
@@ -918,98 +982,436 @@ pub fn is_ranking_command(message: &str) -> bool {
     message.starts_with("/ranking")
 }
 
-/// Get the user calls average multiplier
+/// Build a call with its cached ATH
 /// 
 /// # Arguments
 /// 
-/// * `lb` - The leaderboard
-/// * `user_tg_id` - The user Telegram ID
+/// * `call` - The call
+/// * `call_aths` - The cached ATHs, by call id
+/// 
+/// # Returns
+/// 
+/// The call with its ATH price and multiplier, both 0 while the ATH isn't computed yet
+pub fn call_with_cached_ath(call: Call, call_aths: &HashMap<i32, CallAth>) -> CallWithAth {
+    match call_aths.get(&call.id) {
+        Some(call_ath) => {
+            let call_price = call.price.parse::<f64>().unwrap_or(0.0);
+            let multiplier = if call_price > 0.0 { call_ath.ath_price / call_price } else { 0.0 };
+            CallWithAth {
+                call,
+                ath_after_call: call_ath.ath_price,
+                multiplier,
+                ath_updated_at: Some(call_ath.updated_at),
+            }
+        }
+        None => CallWithAth {
+            call,
+            ath_after_call: 0.0,
+            multiplier: 0.0,
+            ath_updated_at: None,
+        },
+    }
+}
+
+/// Describe how fresh the cached ATHs of a list of calls are
+/// 
+/// # Arguments
+/// 
+/// * `calls` - The calls with their cached ATH
 /// 
 /// # Returns
 /// 
-/// A f64 representing the user calls average multiplier
-fn get_user_average_multiplier(lb: &[CallWithAth], user_tg_id: String) -> f64 {
-    let mut count = 0;
-    for call in lb {
-        if call.call.user_tg_id == user_tg_id {
-            count += 1;
+/// A line with the age of the oldest ATH and the calls still waiting for theirs, empty without calls
+pub fn format_ath_staleness(calls: &[CallWithAth]) -> String {
+    let pending = calls.iter().filter(|call| call.ath_updated_at.is_none()).count();
+    let oldest_update = calls.iter().filter_map(|call| call.ath_updated_at).min();
+    let mut staleness = match oldest_update {
+        Some(updated_at) => format!("📡 ATHs updated {} ago", format_age(Utc::now() - updated_at)),
+        None => String::new(),
+    };
+    if pending > 0 {
+        if !staleness.is_empty() {
+            staleness.push_str(" · ");
         }
+        staleness.push_str(&format!("⏳ {} call{} waiting for {} ATH", pending, if pending == 1 { "" } else { "s" }, if pending == 1 { "its" } else { "their" }));
     }
+    staleness
+}
+
+/// Struct to hold the stats of a caller over a list of calls
+/// 
+/// # Fields
+/// 
+/// * `user_tg_id` - The caller Telegram ID
+/// * `calls` - The number of calls
+/// * `rated_calls` - The number of calls whose ATH is computed
+/// * `hits` - The number of calls that reached 2x
+/// * `best_multiplier` - The best multiplier
+/// * `average_multiplier` - The average multiplier of the rated calls
+/// * `median_multiplier` - The median multiplier of the rated calls
+/// * `best_call` - The call with the best multiplier
+#[derive(Debug, Clone, Serialize)]
+pub struct CallerStats {
+    pub user_tg_id: String,
+    pub calls: usize,
+    pub rated_calls: usize,
+    pub hits: usize,
+    pub best_multiplier: f64,
+    pub average_multiplier: f64,
+    pub median_multiplier: f64,
+    pub best_call: Option<CallWithAth>,
+}
+
+impl CallerStats {
+    /// Share of the rated calls that reached 2x, as a percentage
+    pub fn hit_rate(&self) -> f64 {
+        if self.rated_calls == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.rated_calls as f64 * 100.0
+        }
+    }
+}
 
-    if count == 0 {
-        return 0.0;
+/// Get the stats of each caller
+/// 
+/// # Description
+/// 
+/// Calls still waiting for their ATH count in the number of calls but not in the multipliers
+/// and the hit rate.
+/// 
+/// # Arguments
+/// 
+/// * `calls` - The calls with their ATH
+/// 
+/// # Returns
+/// 
+/// A Vec<CallerStats> with one entry per caller, in order of first call
+pub fn get_caller_stats(calls: &[CallWithAth]) -> Vec<CallerStats> {
+    let mut callers: Vec<CallerStats> = Vec::new();
+    let mut multipliers: Vec<Vec<f64>> = Vec::new();
+    for call in calls {
+        let index = match callers.iter().position(|caller| caller.user_tg_id == call.call.user_tg_id) {
+            Some(index) => index,
+            None => {
+                callers.push(CallerStats {
+                    user_tg_id: call.call.user_tg_id.clone(),
+                    calls: 0,
+                    rated_calls: 0,
+                    hits: 0,
+                    best_multiplier: 0.0,
+                    average_multiplier: 0.0,
+                    median_multiplier: 0.0,
+                    best_call: None,
+                });
+                multipliers.push(Vec::new());
+                callers.len() - 1
+            }
+        };
+        let caller = &mut callers[index];
+        caller.calls += 1;
+        if call.ath_updated_at.is_some() {
+            multipliers[index].push(call.multiplier);
+            caller.rated_calls += 1;
+            if call.multiplier >= 2.0 {
+                caller.hits += 1;
+            }
+            if caller.best_call.is_none() || call.multiplier > caller.best_multiplier {
+                caller.best_multiplier = call.multiplier;
+                caller.best_call = Some(call.clone());
+            }
+        }
+    }
+    for (caller, mut multipliers) in callers.iter_mut().zip(multipliers) {
+        if multipliers.is_empty() {
+            continue;
+        }
+        caller.average_multiplier = multipliers.iter().sum::<f64>() / multipliers.len() as f64;
+        multipliers.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let middle = multipliers.len() / 2;
+        caller.median_multiplier = if multipliers.len() % 2 == 0 {
+            (multipliers[middle - 1] + multipliers[middle]) / 2.0
+        } else {
+            multipliers[middle]
+        };
     }
+    callers
+}
 
-    let total_multiplier: f64 = lb.iter()
-        .map(|call| call.ath_after_call / call.call.price.parse::<f64>().unwrap_or(0.0))
-        .sum();
-    total_multiplier / count as f64
+/// Rank callers by a leaderboard metric, best first
+/// 
+/// # Arguments
+/// 
+/// * `callers` - The callers stats
+/// * `metric` - The metric to rank by, the best calls rank the callers by best multiplier
+/// 
+/// # Returns
+/// 
+/// The ranked callers
+pub fn rank_callers(mut callers: Vec<CallerStats>, metric: LeaderboardMetric) -> Vec<CallerStats> {
+    let key = |caller: &CallerStats| match metric {
+        LeaderboardMetric::BestMultiplier => (caller.best_multiplier, caller.calls as f64),
+        LeaderboardMetric::AverageMultiplier => (caller.average_multiplier, caller.rated_calls as f64),
+        LeaderboardMetric::MedianMultiplier => (caller.median_multiplier, caller.rated_calls as f64),
+        LeaderboardMetric::HitRate => (caller.hit_rate(), caller.rated_calls as f64),
+        LeaderboardMetric::TotalCalls => (caller.calls as f64, caller.average_multiplier),
+    };
+    callers.sort_by(|a, b| key(b).partial_cmp(&key(a)).unwrap_or(std::cmp::Ordering::Equal));
+    callers
 }
 
 /// Create the leaderboard message
 /// 
+/// # Description
+/// 
+/// The best calls metric lists the top 10 calls, the other metrics the top 10 callers.
+/// 
 /// # Arguments
 /// 
-/// * `lb` - The leaderboard, as a vector of CallWithAth structs
-/// * `days` - The number of days
+/// * `lb` - The first call of each token in the period, as CallWithAth structs
+/// * `calls_count` - The number of calls in the period
+/// * `period` - The period of the leaderboard
+/// * `metric` - The metric to rank by
 /// * `channel_name` - The channel name
+/// * `start_param` - The deep link the callers link to, "user" for the stats or "profile" for the public profile
+/// * `pool` - The database pool
 /// 
 /// # Returns
 /// 
 /// A String representing the leaderboard message
-pub async fn leaderboard_message(lb: Vec<CallWithAth>, period_str: String, channel_name: &str, pool: &SafePool) -> Result<String> {
+pub async fn leaderboard_message(mut lb: Vec<CallWithAth>, calls_count: usize, period: &CallPeriod, metric: LeaderboardMetric, channel_name: &str, start_param: &str, pool: &SafePool) -> Result<String> {
+    let callers = get_caller_stats(&lb);
+    let rated_calls: usize = callers.iter().map(|caller| caller.rated_calls).sum();
+    let hits: usize = callers.iter().map(|caller| caller.hits).sum();
+    let hit_rate = if rated_calls == 0 { 0.0 } else { hits as f64 / rated_calls as f64 * 100.0 };
+    let staleness = format_ath_staleness(&lb);
+
     let mut learderboard_string = String::new();
-    let mut count = 1;
-    let mut hits = 0;
-    let mut mvp_string = String::new();
-    let mut mvp_average_multiplier = 0.0;
-    for call in &lb {
-        let multiplier = call.ath_after_call / call.call.price.parse::<f64>().unwrap_or(0.0);
-        let user = crate::db::get_user(&pool, call.call.user_tg_id.as_str()).await?;
-        let user_tg_id = user.tg_id;
-        let username = user.username.unwrap_or("Unknown".to_string());
-        let calls_count_user = crate::db::get_user_call_count_for_user_chat_with_period(&pool, call.call.user_tg_id.as_str(), call.call.chat_id.as_str(), period_str.as_str()).await?;
-        let calls_count_chat = crate::db::get_chat_call_count_with_period(&pool, call.call.chat_id.as_str(), period_str.as_str()).await?;
-        if multiplier > 2.0 {
-            hits += 1;
-        }
-        if count == 1 {
-            mvp_average_multiplier = get_user_average_multiplier(&lb, call.call.user_tg_id.to_string());
-            mvp_string.push_str(&format!("👑 {}\n", channel_name));
-            mvp_string.push_str(&format!("├ <code>MVP:</code>               <b>@{}</b>\n", username));
-            mvp_string.push_str(&format!("├ <code>Period:</code>         <b>{}</b>\n", period_str));
-            mvp_string.push_str(&format!("├ <code>Calls:</code>           <b>{}</b>\n", calls_count_chat));
-            
-            learderboard_string.push_str(&format!("👑🟣 <b>{}</b>:<a href=\"https://t.me/sj_copyTradebot?start=user_{user_tg_id}\"><i><b>{username}</b></i></a> ({calls_count_user}): ${} [<b>{:.1}x</b>]\n", count, call.call.token_symbol, multiplier));
-        }
-        if count == 2 {
-            learderboard_string.push_str(&format!("🥈🟣 <b>{}</b>:<a href=\"https://t.me/sj_copyTradebot?start=user_{user_tg_id}\"><i><b>{username}</b></i></a> ({calls_count_user}): ${} [<b>{:.1}x</b>]\n", count, call.call.token_symbol, multiplier));
-        } else if count == 3 {
-            learderboard_string.push_str(&format!("🥉🟣 <b>{}</b>:<a href=\"https://t.me/sj_copyTradebot?start=user_{user_tg_id}\"><i><b>{username}</b></i></a> ({calls_count_user}): ${} [<b>{:.1}x</b>]\n", count, call.call.token_symbol, multiplier));
-        } else if multiplier < 1.5  && count > 3{
-            learderboard_string.push_str(&format!("😭🟣 <b>{}</b>:<a href=\"https://t.me/sj_copyTradebot?start=user_{user_tg_id}\"><i><b>{username}</b></i></a> ({calls_count_user}): ${}\n", count, call.call.token_symbol));
-        } else if count > 3 && multiplier > 2.0 {
-            learderboard_string.push_str(&format!("😎 🟣 <b>{}</b>:<a href=\"https://t.me/sj_copyTradebot?start=user_{user_tg_id}\"><i><b>{username}</b></i></a> ({calls_count_user}): ${} [<b>{:.1}x</b>]\n", count, call.call.token_symbol, multiplier));
+    let mvp_tg_id;
+    if metric == LeaderboardMetric::BestMultiplier {
+        lb.sort_by(|a, b| b.multiplier.partial_cmp(&a.multiplier).unwrap_or(std::cmp::Ordering::Equal));
+        mvp_tg_id = lb.first().map(|call| call.call.user_tg_id.clone());
+        for (index, call) in lb.iter().take(10).enumerate() {
+            let count = index + 1;
+            let multiplier = call.multiplier;
+            let user = crate::db::get_user(&pool, call.call.user_tg_id.as_str()).await?;
+            let user_tg_id = user.tg_id;
+            let username = html::escape(&user.username.unwrap_or("Unknown".to_string()));
+            let calls_count_user = callers.iter().find(|caller| caller.user_tg_id == call.call.user_tg_id).map(|caller| caller.calls).unwrap_or(0);
+            let badge = match count {
+                1 => "👑🟣",
+                2 => "🥈🟣",
+                3 => "🥉🟣",
+                _ if multiplier >= 2.0 => "😎 🟣",
+                _ if multiplier < 1.5 => "😭🟣",
+                _ => "🟣",
+            };
+            if count > 3 && multiplier < 1.5 {
+                learderboard_string.push_str(&format!("{badge} <b>{}</b>:<a href=\"https://t.me/sj_copyTradebot?start={start_param}_{user_tg_id}\"><i><b>{username}</b></i></a> ({calls_count_user}): ${}\n", count, html::escape(&call.call.token_symbol)));
+            } else {
+                learderboard_string.push_str(&format!("{badge} <b>{}</b>:<a href=\"https://t.me/sj_copyTradebot?start={start_param}_{user_tg_id}\"><i><b>{username}</b></i></a> ({calls_count_user}): ${} [<b>{:.1}x</b>]\n", count, html::escape(&call.call.token_symbol), multiplier));
+            }
         }
-        count += 1;
-        if count == 10 {
-            break;
+    } else {
+        let ranked = rank_callers(callers.clone(), metric);
+        mvp_tg_id = ranked.first().map(|caller| caller.user_tg_id.clone());
+        for (index, caller) in ranked.iter().take(10).enumerate() {
+            let count = index + 1;
+            let user = crate::db::get_user(&pool, caller.user_tg_id.as_str()).await?;
+            let user_tg_id = user.tg_id;
+            let username = html::escape(&user.username.unwrap_or("Unknown".to_string()));
+            let badge = match count {
+                1 => "👑🟣",
+                2 => "🥈🟣",
+                3 => "🥉🟣",
+                _ => "🟣",
+            };
+            let value = match metric {
+                LeaderboardMetric::AverageMultiplier => format!("avg <b>{:.1}x</b>", caller.average_multiplier),
+                LeaderboardMetric::MedianMultiplier => format!("median <b>{:.1}x</b>", caller.median_multiplier),
+                LeaderboardMetric::HitRate => format!("<b>{:.0}%</b> hits ({}/{})", caller.hit_rate(), caller.hits, caller.rated_calls),
+                _ => format!("<b>{}</b> calls", caller.calls),
+            };
+            learderboard_string.push_str(&format!("{badge} <b>{}</b>:<a href=\"https://t.me/sj_copyTradebot?start={start_param}_{user_tg_id}\"><i><b>{username}</b></i></a> ({}): {value} [best <b>{:.1}x</b>]\n", count, caller.calls, caller.best_multiplier));
         }
     }
-    let hit_rate = hits as f64 / count as f64 * 100.0;
-    mvp_string.push_str(&format!("├ <code>Hit rate:</code>      <b>{:.2}%</b>\n", hit_rate));
-    mvp_string.push_str(&format!("└ <code>Return:</code>         <b>{:.2}x</b>\n", mvp_average_multiplier));
+
+    let mut mvp_string = String::new();
+    mvp_string.push_str(&format!("👑 {}\n", html::escape(channel_name)));
+    if let Some(mvp) = mvp_tg_id.and_then(|mvp_tg_id| callers.into_iter().find(|caller| caller.user_tg_id == mvp_tg_id)) {
+        let username = html::escape(&crate::db::get_user(&pool, mvp.user_tg_id.as_str()).await?.username.unwrap_or("Unknown".to_string()));
+        mvp_string.push_str(&format!("├ <code>MVP:</code>               <b>@{}</b>\n", username));
+        mvp_string.push_str(&format!("├ <code>Period:</code>         <b>{}</b>\n", period.label));
+        mvp_string.push_str(&format!("├ <code>Ranking:</code>       <b>{}</b>\n", metric.label()));
+        mvp_string.push_str(&format!("├ <code>Calls:</code>           <b>{}</b>\n", calls_count));
+        mvp_string.push_str(&format!("├ <code>Hit rate:</code>      <b>{:.2}%</b>\n", hit_rate));
+        mvp_string.push_str(&format!("└ <code>Return:</code>         <b>{:.2}x</b>\n", mvp.average_multiplier));
+    } else {
+        mvp_string.push_str(&format!("├ <code>Period:</code>         <b>{}</b>\n", period.label));
+        mvp_string.push_str("└ <code>Calls:</code>           <b>0</b>\n");
+    }
     Ok(format!("
     {mvp_string}\n\
     <blockquote>\
     {learderboard_string}\
-    </blockquote>\n\n\
+    </blockquote>\n\
+    <i>{staleness}</i>\n\n\
     • TOKEN PNL » /pnl <i>token_address</i>\n\
-    • LEADERBOARD » /lb <i>Period</i>\n\n\
+    • LEADERBOARD » /lb <i>7d | 12h | 3m | all | 2024-10-01..2024-10-31</i> <i>best | avg | median | hits | calls</i>\n\n\
     🏆 <a href=\"https://app.dexcelerate.com/scanner\">Watch and trade automatically with #1 dex</a>\n
     "))
 }
 
+/// Struct to hold a caller of the callers ranking
+/// 
+/// # Fields
+/// 
+/// * `rank` - The rank of the caller, from 1
+/// * `username` - The caller username
+/// * `stats` - The caller stats over the period
+#[derive(Debug, Clone, Serialize)]
+pub struct RankedCaller {
+    pub rank: usize,
+    pub username: String,
+    #[serde(flatten)]
+    pub stats: CallerStats,
+}
+
+/// Struct to hold the callers ranking of a group, as served to the mini app
+/// 
+/// # Fields
+/// 
+/// * `chat_id` - The group chat ID, None for the global ranking
+/// * `period` - The period of the ranking
+/// * `metric` - The metric the callers are ranked by
+/// * `min_calls` - The minimum number of calls to be ranked
+/// * `callers` - The ranked callers, best first
+#[derive(Debug, Clone, Serialize)]
+pub struct CallersRanking {
+    pub chat_id: Option<String>,
+    pub period: CallPeriod,
+    pub metric: String,
+    pub min_calls: usize,
+    pub callers: Vec<RankedCaller>,
+}
+
+/// Create the callers ranking message
+/// 
+/// # Arguments
+/// 
+/// * `ranking` - The callers ranking
+/// * `channel_name` - The channel name
+/// 
+/// # Returns
+/// 
+/// A String representing the callers ranking message
+pub fn callers_message(ranking: &CallersRanking, channel_name: &str) -> String {
+    let mut callers_string = String::new();
+    for caller in ranking.callers.iter().take(10) {
+        let badge = match caller.rank {
+            1 => "👑",
+            2 => "🥈",
+            3 => "🥉",
+            _ => "🟣",
+        };
+        let best_call = match &caller.stats.best_call {
+            Some(best_call) => format!("${} {:.1}x", html::escape(&best_call.call.token_symbol), best_call.multiplier),
+            None => "-".to_string(),
+        };
+        callers_string.push_str(&format!(
+            "{badge} <b>{}</b>: <a href=\"https://t.me/sj_copyTradebot?start=user_{}\"><i><b>{}</b></i></a> ({} calls)\n\
+            ├ avg <b>{:.1}x</b> · median <b>{:.1}x</b> · hits <b>{:.0}%</b>\n\
+            └ best {}\n",
+            caller.rank,
+            caller.stats.user_tg_id,
+            html::escape(&caller.username),
+            caller.stats.calls,
+            caller.stats.average_multiplier,
+            caller.stats.median_multiplier,
+            caller.stats.hit_rate(),
+            best_call,
+        ));
+    }
+    if callers_string.is_empty() {
+        callers_string = format!("No caller with {} calls or more yet\n", ranking.min_calls);
+    }
+    format!("
+    🏅 {} callers\n\
+    ├ <code>Period:</code>         <b>{}</b>\n\
+    ├ <code>Ranking:</code>       <b>{}</b>\n\
+    └ <code>Min calls:</code>   <b>{}</b>\n\n\
+    <blockquote>\
+    {callers_string}\
+    </blockquote>\n\
+    • CALLERS » /callers <i>30d | all</i> <i>avg | median | hits | calls | best</i> <i>min=3</i>\n\
+    • LEADERBOARD » /lb <i>Period</i>\n
+    ", html::escape(channel_name), ranking.period.label, ranking.metric, ranking.min_calls)
+}
+
+/// Struct to hold the public profile of a caller, built from the groups in the global leaderboard
+/// 
+/// # Fields
+/// 
+/// * `username` - The caller username
+/// * `stats` - The caller stats over all time
+/// * `best_calls` - The best calls of the caller, best first
+/// * `groups` - The groups the caller is active in
+#[derive(Debug, Clone, Serialize)]
+pub struct CallerProfile {
+    pub username: String,
+    pub stats: CallerStats,
+    pub best_calls: Vec<CallWithAth>,
+    pub groups: Vec<GroupActivity>,
+}
+
+/// Create the caller profile message
+/// 
+/// # Arguments
+/// 
+/// * `profile` - The caller profile
+/// 
+/// # Returns
+/// 
+/// A String representing the caller profile message
+pub fn caller_profile_message(profile: &CallerProfile) -> String {
+    let mut best_calls_string = String::new();
+    for (index, call) in profile.best_calls.iter().enumerate() {
+        let badge = match index {
+            0 => "👑",
+            1 => "🥈",
+            2 => "🥉",
+            _ => "🟣",
+        };
+        best_calls_string.push_str(&format!("{badge} ${} [<b>{:.2}x</b>] · {}\n", html::escape(&call.call.token_symbol), call.multiplier, call.call.time.format("%Y-%m-%d")));
+    }
+    if best_calls_string.is_empty() {
+        best_calls_string = "No call in public groups yet\n".to_string();
+    }
+    let groups_string = profile.groups.iter()
+        .map(|group| format!("{} ({})", html::escape(group.title.as_deref().unwrap_or("Unknown group")), group.calls))
+        .collect::<Vec<String>>()
+        .join(", ");
+    format!("
+    🌍 @{}\n\
+    ├ Calls: <code>{}</code>\n\
+    ├ Hit rate: <code>{:.2}%</code>\n\
+    ├ Multiplier: <code>{:.2}x</code> avg · <code>{:.2}x</code> median\n\
+    └ Groups: {}\n\n\
+    <blockquote>\
+    {best_calls_string}\
+    </blockquote>\n\
+    <i>Profiles only count calls made in groups that joined the global leaderboard</i>\n\
+    • GLOBAL LEADERBOARD » /glb <i>Period</i>\n
+    ",
+        html::escape(&profile.username),
+        profile.stats.calls,
+        profile.stats.hit_rate(),
+        profile.stats.average_multiplier,
+        profile.stats.median_multiplier,
+        if groups_string.is_empty() { "-".to_string() } else { groups_string },
+    )
+}
+
 /// Create the user stats message
 /// 
 /// # Arguments
@@ -1018,11 +1420,12 @@ pub async fn leaderboard_message(lb: Vec<CallWithAth>, period_str: String, chann
 /// * `calls_count` - The number of calls
 /// * `best_call_multiplier` - The best call multiplier
 /// * `learderboard_string` - The leaderboard string
+/// * `staleness` - How fresh the ATHs of the calls are
 /// 
 /// # Returns
 /// 
 /// A String representing the user stats message
-pub fn user_stats_message(username: String, calls_count: usize, multipliers_sum: f64, multipliers_avg: f64, learderboard_string: String, hit_rate: f64) -> String {
+pub fn user_stats_message(username: String, calls_count: usize, multipliers_sum: f64, multipliers_avg: f64, learderboard_string: String, hit_rate: f64, staleness: String) -> String {
     format!("
     🥷 @{username}\n\
     ├ Calls: <code>{calls_count}</code>\n\
@@ -1031,6 +1434,7 @@ pub fn user_stats_message(username: String, calls_count: usize, multipliers_sum:
     <blockquote>\
     {learderboard_string}
     </blockquote>\n\
+    <i>{staleness}</i>\n\
     ")
 }
 
@@ -1167,12 +1571,8 @@ pub async fn create_positions_message(user_tg_id: &str, pool: &SafePool) -> Resu
                 positions_str.push_str("Open positions:\n");
                 for position in positions {
                     let mint = position.token_address;
-                    let scanner_response = get_scanner_search(&mint).await?;
-                    let price = scanner_response["pair"]["pairPrice1Usd"]
-                        .as_str()
-                        .unwrap_or("0")
-                        .parse::<f64>()
-                        .unwrap_or(0.0);
+                    let pair_stats = market_data().pair_stats(&mint).await?;
+                    let price = pair_stats.price_usd;
                     
                     let entry_value = position.entry_price * position.amount;
                     let current_value = price * position.amount;
@@ -1185,7 +1585,7 @@ pub async fn create_positions_message(user_tg_id: &str, pool: &SafePool) -> Resu
                         0.0
                     };
 
-                    let symbol = scanner_response["pair"]["token1Symbol"].as_str().unwrap_or("N/A");
+                    let symbol = pair_stats.symbol.as_str();
                     let token_ui_amount = position.ui_amount;
                     let position_age = Utc::now().signed_duration_since(DateTime::<Utc>::from_utc(position.created_at, Utc));
                     
@@ -1208,12 +1608,8 @@ pub async fn create_positions_message(user_tg_id: &str, pool: &SafePool) -> Resu
                 positions_str.push_str("Closed positions:\n");
                 for position in positions {
                     let mint = position.token_address;
-                    let scanner_response = get_scanner_search(&mint).await?;
-                    let price = scanner_response["pair"]["pairPrice1Usd"]
-                        .as_str()
-                        .unwrap_or("0")
-                        .parse::<f64>()
-                        .unwrap_or(0.0);
+                    let pair_stats = market_data().pair_stats(&mint).await?;
+                    let price = pair_stats.price_usd;
                     
                     let entry_value = position.entry_price * position.amount;
                     let current_value = price * position.amount;
@@ -1226,7 +1622,7 @@ pub async fn create_positions_message(user_tg_id: &str, pool: &SafePool) -> Resu
                         0.0
                     };
 
-                    let symbol = scanner_response["pair"]["token1Symbol"].as_str().unwrap_or("N/A");
+                    let symbol = pair_stats.symbol.as_str();
                     let token_ui_amount = position.ui_amount;
                     let position_age = Utc::now().signed_duration_since(DateTime::<Utc>::from_utc(position.created_at, Utc));
                     
@@ -1283,6 +1679,24 @@ pub async fn create_positions_keyboard(user_tg_id: &str, pool: &SafePool) -> Res
             )
         ]
     );
+    // A PnL card button for each listed position
+    let positions = if user_settings.active_complete_positions == "active" {
+        get_active_positions(pool, user_tg_id).await?
+    } else {
+        crate::db::get_complete_positions(pool, user_tg_id).await?
+    };
+    for position in positions {
+        let address = &position.token_address;
+        let mut row = vec![InlineKeyboardButton::callback(
+            format!("🖼 PnL card {}…{}", &address[..4.min(address.len())], &address[address.len().saturating_sub(4)..]),
+            format!("pnl_card:{}", position.id)
+        )];
+        // The take profits and stop losses of an open position can still be edited
+        if !position.completed {
+            row.push(InlineKeyboardButton::callback("🎯 Targets", format!("position_targets:{}", position.id)));
+        }
+        buttons.push(row);
+    }
     Ok(InlineKeyboardMarkup::new(buttons))
 }
 
@@ -1391,103 +1805,196 @@ pub fn parse_stop_loss_message(text: &str) -> Result<(f64, f64)> {
     Ok((down, percentage))
 }
 
-
-/// Check if a token is a token with liquidity on Raydium
+/// Parse a market cap or price value
 /// 
 /// # Arguments
 /// 
-/// * `token_address` - The token address
+/// * `text` - The value, with an optional leading '$' and K, M or B suffix (eg: '$1.5M')
 /// 
 /// # Returns
 /// 
-/// A Vec<String> representing the Raydium tokens
-pub async fn check_raydiums_tokens(token_address: Vec<String>) -> Result<Vec<String>> {
-    let tokens_str = token_address.join(",");
-    let client = reqwest::Client::new();
-    let response = client.get(
-        format!("https://api-v3.raydium.io/mint/price?mints={tokens_str}")
-    )
-    .send()
-    .await?;
+/// The value as a number
+pub fn parse_market_cap_value(text: &str) -> Result<f64> {
+    let text = text.trim().trim_start_matches('$').to_uppercase();
+    let (number, multiplier) = match text.chars().last() {
+        Some('K') => (&text[..text.len() - 1], 1_000.0),
+        Some('M') => (&text[..text.len() - 1], 1_000_000.0),
+        Some('B') => (&text[..text.len() - 1], 1_000_000_000.0),
+        _ => (text.as_str(), 1.0),
+    };
+    Ok(number.trim().parse::<f64>()? * multiplier)
+}
 
-    let response_json = response.json::<serde_json::Value>().await?;
-    let mut tokens: Vec<String> = Vec::new();
-    if let Some(data) = response_json["data"].as_object() {
-        for (key, value) in data.iter() {
-            if value.is_string() {
-                tokens.push(key.to_string());
-            }
-        }
+/// Parse the market cap target message
+/// 
+/// # Arguments
+/// 
+/// * `text` - The text to parse, '$<market_cap>,<% to sell>' (eg: '$1M,50')
+/// * `kind` - take_profit or stop_loss
+/// 
+/// # Returns
+/// 
+/// The market cap target
+pub fn parse_market_cap_target_message(text: &str, kind: &str) -> Result<MarketCapTarget> {
+    let parts: Vec<&str> = text.split(',').collect();
+    if parts.len() != 2 {
+        return Err(anyhow::anyhow!("Invalid format"));
+    }
+    let market_cap = parse_market_cap_value(parts[0])?;
+    if market_cap <= 0.0 {
+        return Err(anyhow::anyhow!("Market cap must be positive"));
     }
-    Ok(tokens)
+    let sell_percentage = parts[1].trim().parse::<f64>()?;
+    if sell_percentage <= 0.0 || sell_percentage > 100.0 {
+        return Err(anyhow::anyhow!("Percentage to sell must be between 0 and 100"));
+    }
+    Ok(MarketCapTarget { kind: kind.to_string(), market_cap, sell_percentage })
 }
 
-
-/// Check the Raydium tokens prices
+/// Parse the limit buy message
 /// 
 /// # Arguments
 /// 
-/// * `token_addresses` - The token addresses
+/// * `text` - The text to parse, '<mc|price>,<value>' with an optional ',<hours to expire>'
 /// 
 /// # Returns
 /// 
-/// A HashMap<String, String> representing the token prices
-pub async fn check_raydium_tokens_prices(token_addresses: Vec<String>) -> Result<HashMap<String, String>, reqwest::Error> {
-    let client = Client::new();
-    let mut url = String::from("https://api-v3.raydium.io/mint/price");
-    let tokens_str = token_addresses.join(",");
-
-    url = format!("{url}?mints={tokens_str}");
-
-    // Send the request
-    let response = client.get(url)
-        .send()
-        .await?;
-
-    // Parse the response
-    let response_body: Value = response.json().await?;
-    let data = response_body["data"].as_object().unwrap();
-
-    // Convert the response data to a HashMap, filtering out null values
-    let prices = data.iter()
-        .filter_map(|(key, value)| {
-            value.as_str().map(|v| (key.clone(), v.to_string()))
-        })
-        .collect::<HashMap<String, String>>();
-
-    Ok(prices)
+/// A tuple with the trigger kind ("mc" or "price"), the trigger value and the hours to expire
+pub fn parse_limit_buy_message(text: &str) -> Result<(String, f64, f64)> {
+    let parts: Vec<&str> = text.split(',').map(|part| part.trim()).collect();
+    if parts.len() != 2 && parts.len() != 3 {
+        return Err(anyhow::anyhow!("Invalid format"));
+    }
+    let kind = parts[0].to_lowercase();
+    if kind != "mc" && kind != "price" {
+        return Err(anyhow::anyhow!("Trigger must be mc or price"));
+    }
+    let value = parse_market_cap_value(parts[1])?;
+    if value <= 0.0 {
+        return Err(anyhow::anyhow!("Trigger must be positive"));
+    }
+    // Orders expire after a day unless the user says otherwise
+    let hours = match parts.get(2) {
+        Some(hours) => hours.parse::<f64>()?,
+        None => 24.0,
+    };
+    if hours <= 0.0 {
+        return Err(anyhow::anyhow!("Expiry must be positive"));
+    }
+    Ok((kind, value, hours))
 }
 
+/// Parse the DCA message
+/// 
+/// # Arguments
+/// 
+/// * `text` - The text to parse, '<sol_per_buy>,<buys>,<interval_minutes>'
+/// 
+/// # Returns
+/// 
+/// A tuple with the SOL per buy, the number of buys and the minutes between buys
+pub fn parse_dca_message(text: &str) -> Result<(f64, i32, i32)> {
+    let parts: Vec<&str> = text.split(',').map(|part| part.trim()).collect();
+    if parts.len() != 3 {
+        return Err(anyhow::anyhow!("Invalid format"));
+    }
+    let sol_per_buy = parts[0].parse::<f64>()?;
+    let buys = parts[1].parse::<i32>()?;
+    let interval_minutes = parts[2].parse::<i32>()?;
+    if sol_per_buy <= 0.0 || buys <= 0 || interval_minutes <= 0 {
+        return Err(anyhow::anyhow!("Values must be positive"));
+    }
+    Ok((sol_per_buy, buys, interval_minutes))
+}
 
-/// Check the tokens prices using dexcelerate APIS
+/// Parse the TWAP message
 /// 
 /// # Arguments
 /// 
-/// * `token_addresses` - The token addresses
+/// * `text` - The text to parse, '<slices>,<window_minutes>'
 /// 
 /// # Returns
 /// 
-/// A HashMap<String, String> representing the token prices
-pub async fn check_tokens_prices(token_addresses: Vec<String>) -> Result<HashMap<String, String>> {
-    let mut prices = HashMap::new();
-    for token in token_addresses {
-        let scanner_response = get_scanner_search(&token).await?;
-        let price = scanner_response["pair"]["pairPrice1Usd"].to_string();
-        prices.insert(token, price);
+/// A tuple with the number of slices and the minutes to spread them over
+pub fn parse_twap_message(text: &str) -> Result<(i32, i32)> {
+    let parts: Vec<&str> = text.split(',').map(|part| part.trim()).collect();
+    if parts.len() != 2 {
+        return Err(anyhow::anyhow!("Invalid format"));
+    }
+    let slices = parts[0].parse::<i32>()?;
+    let window_minutes = parts[1].parse::<i32>()?;
+    if slices <= 0 || window_minutes <= 0 {
+        return Err(anyhow::anyhow!("Values must be positive"));
     }
-    Ok(prices)
+    Ok((slices, window_minutes))
 }
 
-/// Get the token amount in a wallet
+/// Parse the trailing stop message
 /// 
 /// # Arguments
 /// 
-/// * `solana_wallet_address` - The Solana wallet address
-/// * `token_address` - The token address
+/// * `text` - The text to parse, '<% down from the highest price>,<% to sell>'
 /// 
 /// # Returns
 /// 
-/// A tuple representing the token amount and the UI amount
+/// A tuple representing the trailing stop
+pub fn parse_trailing_stop_message(text: &str) -> Result<(f64, f64)> {
+    let parts: Vec<&str> = text.split(',').collect();
+    if parts.len() != 2 {
+        return Err(anyhow::anyhow!("Invalid format"));
+    }
+    // Trail stays in percentage, the trigger moves with the highest price
+    let trail = parts[0].trim().parse::<f64>()?;
+    if trail <= 0.0 || trail >= 100.0 {
+        return Err(anyhow::anyhow!("Trail must be between 0 and 100"));
+    }
+
+    let percentage = parts[1].trim().parse::<f64>()?;
+    if percentage <= 0.0 || percentage > 100.0 {
+        return Err(anyhow::anyhow!("Percentage to sell must be between 0 and 100"));
+    }
+    Ok((trail, percentage))
+}
+
+
+/// Parse a trade message from the pumpportal stream
+///
+/// # Description
+///
+/// The bonding curve price is derived from the SOL and token amounts of the trade
+///
+/// # Arguments
+///
+/// * `text` - The websocket message
+///
+/// # Returns
+///
+/// The token mint and its price in SOL, or None if the message is not a trade
+pub fn parse_pump_trade(text: &str) -> Option<(String, f64)> {
+    let trade: Value = serde_json::from_str(text).ok()?;
+    let mint = trade["mint"].as_str()?;
+    let tx_type = trade["txType"].as_str()?;
+    if tx_type != "buy" && tx_type != "sell" {
+        return None;
+    }
+    let sol_amount = trade["solAmount"].as_f64()?;
+    let token_amount = trade["tokenAmount"].as_f64()?;
+    if token_amount <= 0.0 {
+        return None;
+    }
+    Some((mint.to_string(), sol_amount / token_amount))
+}
+
+/// Get the token amount in a wallet
+/// 
+/// # Arguments
+/// 
+/// * `solana_wallet_address` - The Solana wallet address
+/// * `token_address` - The token address
+/// 
+/// # Returns
+/// 
+/// A tuple representing the token amount and the UI amount
 pub async fn get_token_amount_in_wallet(solana_wallet_address: &str, token_address: &str) -> Result<(f64, String)> {
     let positions = get_positions_balance(solana_wallet_address).await?;
     let empty_vec = Vec::new(); // Create a binding for the empty vector
@@ -1656,7 +2163,8 @@ pub async fn create_open_withdraw_sol_message(tg_id: &str, pool: &SafePool) -> R
 /// 
 /// # Description
 /// 
-/// Fetch all the active positions and create a message with all TPs and SLs
+/// Fetch all the active positions and create a message with all TPs, SLs and trailing stops with
+/// their current trigger price, followed by the open limit buy orders
 /// 
 /// # Arguments
 /// 
@@ -1670,10 +2178,10 @@ pub async fn create_limit_orders_message(pool: &SafePool, tg_id: &str) -> Result
     let active_positions = get_active_positions(pool, tg_id).await?;
     let mut limit_orders_str = String::new();
     for position in active_positions {
-        let scanner_response = get_scanner_search(&position.token_address).await?;
-        let token_name = scanner_response["pair"]["tokenName"].to_string();
-        let token_price = scanner_response["pair"]["pairPrice1Usd"].to_string().trim_matches('"').to_string();
-        let token_symbol = scanner_response["pair"]["token1Symbol"].to_string().trim_matches('"').to_string();
+        let pair_stats = market_data().pair_stats(&position.token_address).await?;
+        let token_name = pair_stats.name;
+        let token_price = pair_stats.price_usd.to_string();
+        let token_symbol = pair_stats.symbol;
         let mut tps_str = String::new();
         if !position.take_profits.is_empty()  {
             tps_str.push_str("Take profits:\n");
@@ -1688,13 +2196,45 @@ pub async fn create_limit_orders_message(pool: &SafePool, tg_id: &str) -> Result
                 sls_str.push_str(&format!("{}x📉 - SELL {}%\n", sl.0, sl.1));
             }
         }
+        let mut mcs_str = String::new();
+        if !position.mc_targets.is_empty() {
+            mcs_str.push_str("Market cap targets:\n");
+            for mc_target in &position.mc_targets {
+                mcs_str.push_str(&format!("${} MC{} - SELL {}%\n", format_number(mc_target.market_cap), if mc_target.is_take_profit() { "📈" } else { "📉" }, mc_target.sell_percentage));
+            }
+        }
+        let mut tss_str = String::new();
+        if !position.trailing_stops.is_empty() {
+            // Until the watcher sees a price the trail starts from the entry price
+            let highest_price = position.highest_price.unwrap_or(position.entry_price);
+            tss_str.push_str(&format!("Trailing stops (high <code>${}</code>):\n", highest_price));
+            for ts in position.trailing_stops {
+                tss_str.push_str(&format!("-{}%📉 - SELL {}% - trigger <code>${:.10}</code>\n", ts.0, ts.1, trailing_stop_trigger_price(ts.0, highest_price)));
+            }
+        }
         limit_orders_str.push_str(&format!(
             "{token_name} <code>${token_symbol}</code> <code>${token_price}</code>\n\
             {tps_str}\n\
-            {sls_str}
+            {sls_str}\n\
+            {mcs_str}\n\
+            {tss_str}
             ",
         ));
     }
+    let limit_buys = get_user_open_limit_orders(pool, tg_id).await?;
+    if !limit_buys.is_empty() {
+        limit_orders_str.push_str("Limit buys:\n");
+        for order in limit_buys {
+            let trigger_str = match order.trigger_mc {
+                Some(mc) => format!("MC ≤ ${}", mc),
+                None => format!("price ≤ ${}", order.trigger_price),
+            };
+            limit_orders_str.push_str(&format!(
+                "#{} <code>{}</code>\nBUY {} SOL when {} - expires {} UTC\n",
+                order.id, order.token_address, order.sol_amount, trigger_str, order.expires_at.format("%Y-%m-%d %H:%M")
+            ));
+        }
+    }
     Ok(format!("Limit orders:\n\
     {limit_orders_str}
     "))
@@ -1716,9 +2256,795 @@ pub async fn limit_orders_keyboard(pool: &SafePool, tg_id: &str) -> Result<Inlin
     let open_positions = get_active_positions(pool, tg_id).await?;
     buttons.push(vec![InlineKeyboardButton::callback("← Back", "back")]);
     for position in open_positions {
-        let scanner_response = get_scanner_search(&position.token_address).await?;
-        let token_name = scanner_response["pair"]["token1Name"].to_string().trim_matches('"').to_string();
+        let token_name = market_data().pair_stats(&position.token_address).await?.name;
         buttons.push(vec![InlineKeyboardButton::callback(format!("{}", token_name), format!("open_position:{}", position.id))]);
     }
+    for order in get_user_open_limit_orders(pool, tg_id).await? {
+        buttons.push(vec![InlineKeyboardButton::callback(format!("❌ Cancel limit buy #{}", order.id), format!("cancel_limit_order:{}", order.id))]);
+    }
     Ok(InlineKeyboardMarkup::new(buttons))
-}
\ No newline at end of file
+}
+
+/// Requests the suggested wallets to copy from the latest wallet discovery job in the solana_app
+/// 
+/// # Returns
+/// 
+/// A Value with the discovery report (`updated_at`, `tokens` and ranked `wallets`)
+pub async fn get_suggested_wallets() -> Result<Value> {
+    let client = reqwest::Client::new();
+    let response = client.get("http://solana_app:3030/suggested_wallets")
+    .send()
+    .await?;
+    let response_json = response.json::<Value>().await?;
+    println!("@get_suggested_wallets/ response_json: {:?}", response_json);
+    Ok(response_json)
+}
+
+/// Starts a wallet discovery job in the solana_app
+/// 
+/// # Arguments
+/// 
+/// * `tokens` - The token mints to analyse
+/// 
+/// # Returns
+/// 
+/// True if the job was started, false if the solana_app refused it because a job is already
+/// running or the last one finished too recently
+pub async fn request_wallet_discovery(tokens: Vec<String>) -> Result<bool> {
+    let client = reqwest::Client::new();
+    let response = client.post("http://solana_app:3030/discover_wallets")
+    .json(&serde_json::json!({ "tokens": tokens }))
+    .send()
+    .await?;
+    println!("@request_wallet_discovery/ response: {:?}", response);
+    if response.status() == reqwest::StatusCode::CONFLICT || response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Ok(false);
+    }
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!("Failed to start wallet discovery: {}", response.text().await?));
+    }
+    Ok(true)
+}
+
+/// Create the copy trade message
+/// 
+/// # Description
+/// 
+/// List the suggested wallets to copy ranked by the last wallet discovery job
+/// 
+/// # Arguments
+/// 
+/// * `report` - The discovery report returned by the solana_app
+/// 
+/// # Returns
+/// 
+/// A String representing the copy trade message
+pub fn create_copy_trade_message(report: &Value) -> String {
+    let wallets = report["wallets"].as_array().cloned().unwrap_or_default();
+    if wallets.is_empty() {
+        return "Copy Trade:\n\
+        No suggested wallets yet, check again after the next wallet discovery job.".to_string();
+    }
+    let updated_at = Utc.timestamp_opt(report["updated_at"].as_i64().unwrap_or(0), 0).single().unwrap_or_default();
+    let mut wallets_str = String::new();
+    for (i, wallet) in wallets.iter().enumerate() {
+        wallets_str.push_str(&format!(
+            "{}. <code>{}</code>\n\
+            🎯 Hit rate: {:.0}% ({}/{}) | 💰 PnL: {:.2} SOL | ⏱ Early entry: {:.0}%\n",
+            i + 1,
+            wallet["wallet"].as_str().unwrap_or(""),
+            wallet["hit_rate"].as_f64().unwrap_or(0.0) * 100.0,
+            wallet["wins"].as_u64().unwrap_or(0),
+            wallet["closed_trades"].as_u64().unwrap_or(0),
+            wallet["realised_pnl_sol"].as_f64().unwrap_or(0.0),
+            wallet["early_entry"].as_f64().unwrap_or(0.0) * 100.0,
+        ));
+    }
+    format!("Copy Trade:\n\
+    Suggested wallets to copy ({} tokens analysed, {})\n\n\
+    {wallets_str}",
+    report["tokens"].as_array().map(|t| t.len()).unwrap_or(0),
+    time_ago(&updated_at.to_rfc3339()))
+}
+
+/// Create the copy trade keyboard
+/// 
+/// # Arguments
+/// 
+/// * `is_admin` - Whether the user is a bot admin, only they can start a wallet discovery job
+/// 
+/// # Returns
+/// 
+/// An InlineKeyboardMarkup object
+pub fn create_copy_trade_keyboard(is_admin: bool) -> InlineKeyboardMarkup {
+    let mut buttons: Vec<Vec<InlineKeyboardButton>> = vec![];
+    if is_admin {
+        buttons.push(vec![InlineKeyboardButton::callback("🔎 Discover wallets", "discover_wallets"), InlineKeyboardButton::callback("🔄 Refresh", "copy_trade")]);
+    } else {
+        buttons.push(vec![InlineKeyboardButton::callback("🔄 Refresh", "copy_trade")]);
+    }
+    buttons.push(vec![InlineKeyboardButton::callback("← Back", "back")]);
+    InlineKeyboardMarkup::new(buttons)
+}
+
+/// Create the recurring orders message
+/// 
+/// # Description
+/// 
+/// Lists the active and paused DCA and TWAP orders of the user with their progress
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `tg_id` - The Telegram ID
+/// 
+/// # Returns
+/// 
+/// A String representing the recurring orders message
+pub async fn create_recurring_orders_message(pool: &SafePool, tg_id: &str) -> Result<String> {
+    let orders = get_user_recurring_orders(pool, tg_id).await?;
+    if orders.is_empty() {
+        return Ok("No DCA or TWAP orders running.\nStart one from the buy or sell menu of a token.".to_string());
+    }
+    let mut orders_str = String::new();
+    for order in orders {
+        let action_str = if order.kind == "dca" {
+            format!("🟢 DCA buy {} SOL every {} min", order.sol_per_slice.unwrap_or(0.0), order.interval_secs / 60)
+        } else {
+            format!("🔴 TWAP sell every {} min", order.interval_secs / 60)
+        };
+        let progress = "▰".repeat(order.executed_slices as usize) + &"▱".repeat((order.total_slices - order.executed_slices).max(0) as usize);
+        let status_str = if order.status == "paused" {
+            "⏸ paused".to_string()
+        } else {
+            format!("next {} UTC", order.next_run_at.format("%H:%M"))
+        };
+        let error_str = match order.last_error {
+            Some(error) => format!("\n⚠️ Last slice failed: {}", error),
+            None => String::new(),
+        };
+        orders_str.push_str(&format!(
+            "#{} <code>{}</code>\n{}\n{} {}/{} - {}{}\n\n",
+            order.id, order.token_address, action_str, progress, order.executed_slices, order.total_slices, status_str, error_str
+        ));
+    }
+    Ok(format!("DCA / TWAP orders:\n\n{orders_str}"))
+}
+
+/// Create the recurring orders keyboard
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `tg_id` - The Telegram ID
+/// 
+/// # Returns
+/// 
+/// An InlineKeyboardMarkup with pause/resume and cancel buttons for each order
+pub async fn create_recurring_orders_keyboard(pool: &SafePool, tg_id: &str) -> Result<InlineKeyboardMarkup> {
+    let mut buttons: Vec<Vec<InlineKeyboardButton>> = vec![];
+    buttons.push(vec![
+        InlineKeyboardButton::callback("← Back", "back"),
+        InlineKeyboardButton::callback("↻ Refresh", "recurring_orders"),
+    ]);
+    for order in get_user_recurring_orders(pool, tg_id).await? {
+        let pause_or_resume = if order.status == "paused" {
+            InlineKeyboardButton::callback(format!("▶️ Resume #{}", order.id), format!("resume_recurring_order:{}", order.id))
+        } else {
+            InlineKeyboardButton::callback(format!("⏸ Pause #{}", order.id), format!("pause_recurring_order:{}", order.id))
+        };
+        buttons.push(vec![
+            pause_or_resume,
+            InlineKeyboardButton::callback(format!("❌ Cancel #{}", order.id), format!("cancel_recurring_order:{}", order.id)),
+        ]);
+    }
+    Ok(InlineKeyboardMarkup::new(buttons))
+}
+
+/// Create the trade journal message
+/// 
+/// # Description
+/// 
+/// Lists the latest buy and sell fills of the user with their trigger, amounts, fees and realised PnL
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `tg_id` - The Telegram ID
+/// 
+/// # Returns
+/// 
+/// A String representing the trade journal message
+pub async fn create_trades_journal_message(pool: &SafePool, tg_id: &str) -> Result<String> {
+    let trades = get_user_trades(pool, tg_id, 20).await?;
+    if trades.is_empty() {
+        return Ok("No trades yet.\nYour buys and sells will show up here.".to_string());
+    }
+    let mut trades_str = String::new();
+    for trade in trades {
+        let side_str = if trade.side == "buy" { "🟢 BUY" } else { "🔴 SELL" };
+        let fill_str = match (trade.token_ui_amount, trade.sol_amount) {
+            (Some(token_ui_amount), Some(sol_amount)) => format!(
+                "{} tokens for {:.4} SOL @ ${:.8}",
+                format_number(token_ui_amount), sol_amount, trade.price_usd.unwrap_or(0.0)
+            ),
+            _ => "Fill not available".to_string(),
+        };
+        let fees_str = match trade.fees_sol {
+            Some(fees_sol) => format!(" · fees {:.5} SOL", fees_sol),
+            None => String::new(),
+        };
+        let pnl_str = match trade.realised_pnl_sol {
+            Some(realised_pnl_sol) => format!("\nRealised PnL: {:+.4} SOL", realised_pnl_sol),
+            None => String::new(),
+        };
+        trades_str.push_str(&format!(
+            "{} <code>{}</code> · {}\n{}{}{}\n{} UTC · <a href=\"https://solscan.io/tx/{}\">tx</a>\n\n",
+            side_str, trade.token_address, trade.source.replace('_', " "), fill_str, fees_str, pnl_str, trade.created_at.format("%Y-%m-%d %H:%M"), trade.signature
+        ));
+    }
+    Ok(format!("📒 Trade journal (last 20):\n\n{trades_str}"))
+}
+
+/// Create the trade journal keyboard
+/// 
+/// # Returns
+/// 
+/// An InlineKeyboardMarkup with the back and refresh buttons
+pub fn create_trades_journal_keyboard() -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("← Back", "back"),
+        InlineKeyboardButton::callback("↻ Refresh", "trades_journal"),
+    ]])
+}
+
+/// Format a signed SOL amount with its USD value
+fn format_sol_usd(sol: f64, sol_price: f64) -> String {
+    format!("{:+.4} SOL (${:+.2})", sol, sol * sol_price)
+}
+
+/// Create the portfolio message
+/// 
+/// # Description
+/// 
+/// Aggregates the positions of the user: SOL invested in the open positions and their current value,
+/// unrealised and realised PnL in SOL and USD, the best and worst open positions and the PnL realised
+/// over 24h, 7d and 30d from the trade history
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `tg_id` - The Telegram ID
+/// 
+/// # Returns
+/// 
+/// A String representing the portfolio message
+pub async fn create_portfolio_message(pool: &SafePool, tg_id: &str) -> Result<String> {
+    let sol_price = sol_to_usd(1.0).await?;
+    let positions = get_positions_by_user_tg_id(pool, tg_id).await?;
+    let realised_pnl_sol: f64 = positions.iter().map(|position| position.realised_pnl_sol).sum();
+
+    let mut invested_sol = 0.0;
+    let mut value_sol = 0.0;
+    // (symbol, unrealised PnL %) of the open positions
+    let mut performances: Vec<(String, f64)> = vec![];
+    for position in positions.iter().filter(|position| !position.completed) {
+        let pair_stats = market_data().pair_stats(&position.token_address).await?;
+        let price = pair_stats.price_usd;
+        let symbol = pair_stats.symbol;
+        let position_value_sol = if sol_price > 0.0 {
+            price * position.ui_amount.parse::<f64>().unwrap_or(0.0) / sol_price
+        } else {
+            0.0
+        };
+        invested_sol += position.cost_basis_sol;
+        value_sol += position_value_sol;
+        if position.cost_basis_sol > 0.0 {
+            performances.push((symbol, (position_value_sol - position.cost_basis_sol) / position.cost_basis_sol * 100.0));
+        }
+    }
+    let unrealised_pnl_sol = value_sol - invested_sol;
+    let unrealised_pnl_percent = if invested_sol > 0.0 { unrealised_pnl_sol / invested_sol * 100.0 } else { 0.0 };
+    let (pnl_24h, pnl_7d, pnl_30d) = get_user_realised_pnl_by_period(pool, tg_id).await?;
+
+    let mut message = format!(
+        "📊 <b>Portfolio</b>\n\n\
+        Invested: <b>{:.4} SOL (${:.2})</b>\n\
+        Value: <b>{:.4} SOL (${:.2})</b>\n\
+        Unrealised PnL: <b>{}</b> [{:+.2}%]\n\
+        Realised PnL: <b>{}</b>\n\n\
+        Realised 24h: {}\n\
+        Realised 7d: {}\n\
+        Realised 30d: {}\n",
+        invested_sol, invested_sol * sol_price,
+        value_sol, value_sol * sol_price,
+        format_sol_usd(unrealised_pnl_sol, sol_price), unrealised_pnl_percent,
+        format_sol_usd(realised_pnl_sol, sol_price),
+        format_sol_usd(pnl_24h, sol_price),
+        format_sol_usd(pnl_7d, sol_price),
+        format_sol_usd(pnl_30d, sol_price),
+    );
+    let best = performances.iter().max_by(|a, b| a.1.total_cmp(&b.1));
+    let worst = performances.iter().min_by(|a, b| a.1.total_cmp(&b.1));
+    if let (Some(best), Some(worst)) = (best, worst) {
+        message.push_str(&format!("\n🏆 Best: <code>${}</code> {:+.2}%\n💀 Worst: <code>${}</code> {:+.2}%\n", html::escape(&best.0), best.1, html::escape(&worst.0), worst.1));
+    }
+    Ok(message)
+}
+
+/// Create the portfolio keyboard
+/// 
+/// # Returns
+/// 
+/// An InlineKeyboardMarkup with the back and refresh buttons
+pub fn create_portfolio_keyboard() -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("← Back", "back"),
+        InlineKeyboardButton::callback("🔄 Refresh", "refresh_portfolio"),
+    ]])
+}
+
+/// Create the PnL card of a position
+/// 
+/// # Description
+/// 
+/// An open position compares the current price to the average entry price. A completed position
+/// shows the average exit price of its sells and the PnL realised on the SOL invested.
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `position` - The position
+/// * `username` - The username shown on the card
+/// 
+/// # Returns
+/// 
+/// The PnlCard of the position
+pub async fn create_position_pnl_card(pool: &SafePool, position: &Position, username: &str) -> Result<PnlCard> {
+    let pair_stats = market_data().pair_stats(&position.token_address).await?;
+    let symbol = pair_stats.symbol;
+    let (exit_label, exit_price, percent) = if position.completed {
+        let sells: Vec<Trade> = get_position_trades(pool, position.id).await?
+            .into_iter()
+            .filter(|trade| trade.side == "sell" && trade.price_usd.is_some() && trade.token_ui_amount.is_some())
+            .collect();
+        let sold: f64 = sells.iter().map(|trade| trade.token_ui_amount.unwrap_or(0.0)).sum();
+        let exit_price = if sold > 0.0 {
+            sells.iter().map(|trade| trade.price_usd.unwrap_or(0.0) * trade.token_ui_amount.unwrap_or(0.0)).sum::<f64>() / sold
+        } else {
+            0.0
+        };
+        let percent = if position.sol_entry > 0.0 {
+            position.realised_pnl_sol / position.sol_entry * 100.0
+        } else if position.entry_price > 0.0 && exit_price > 0.0 {
+            (exit_price / position.entry_price - 1.0) * 100.0
+        } else {
+            0.0
+        };
+        ("Avg exit", exit_price, percent)
+    } else {
+        let price = pair_stats.price_usd;
+        let percent = if position.entry_price > 0.0 { (price / position.entry_price - 1.0) * 100.0 } else { 0.0 };
+        ("Current", price, percent)
+    };
+    let referral_link = get_refferal(pool, &position.tg_user_id).await?
+        .map(|refferal| format!("t.me/sj_copyTradebot?start=r-{}", refferal.uuid));
+    Ok(PnlCard {
+        symbol,
+        title: "Position".to_string(),
+        entry_label: "Avg entry".to_string(),
+        entry: format!("${:.8}", position.entry_price),
+        exit_label: exit_label.to_string(),
+        exit: format!("${:.8}", exit_price),
+        percent,
+        username: username.to_string(),
+        referral_link,
+    })
+}
+
+/// Create the PnL card of a call
+/// 
+/// # Arguments
+/// 
+/// * `pool` - The database pool
+/// * `pnl_call` - The PnL of the call
+/// * `symbol` - The token symbol
+/// 
+/// # Returns
+/// 
+/// The PnlCard of the call, with the market cap at the call and now
+pub async fn create_call_pnl_card(pool: &SafePool, pnl_call: &PnlCall, symbol: &str) -> Result<PnlCard> {
+    let call = get_call_by_id(pool, pnl_call.call_id).await?;
+    let user = get_user(pool, &call.user_tg_id).await?;
+    let referral_link = get_refferal(pool, &call.user_tg_id).await?
+        .map(|refferal| format!("t.me/sj_copyTradebot?start=r-{}", refferal.uuid));
+    Ok(PnlCard {
+        symbol: symbol.to_string(),
+        title: "Call".to_string(),
+        entry_label: "Called at".to_string(),
+        entry: format!("${}", format_number(call.mkt_cap.parse::<f64>().unwrap_or(0.0))),
+        exit_label: "Now".to_string(),
+        exit: format!("${}", format_number(pnl_call.mkt_cap.parse::<f64>().unwrap_or(0.0))),
+        percent: pnl_call.percent.parse::<f64>().unwrap_or(0.0),
+        username: user.username.unwrap_or("Unknown".to_string()),
+        referral_link,
+    })
+}
+
+/// Format take profit, stop loss, trailing stop and market cap ladders on one line each
+/// 
+/// # Arguments
+/// 
+/// * `take_profits` - The take profits, [ (multiplier, % to sell), ... ]
+/// * `stop_losses` - The stop losses, [ (multiplier, % to sell), ... ]
+/// * `trailing_stops` - The trailing stops, [ (% down from the highest price, % to sell), ... ]
+/// * `mc_targets` - The market cap targets
+/// 
+/// # Returns
+/// 
+/// A String with the ladders, "No take profits or stop losses" when they are all empty
+pub fn format_ladders(take_profits: &[(f64, f64)], stop_losses: &[(f64, f64)], trailing_stops: &[(f64, f64)], mc_targets: &[MarketCapTarget]) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    if !take_profits.is_empty() {
+        lines.push(format!("📈 TP: {}", take_profits.iter().map(|tp| format!("{}x sell {}%", tp.0, tp.1)).collect::<Vec<_>>().join(", ")));
+    }
+    if !stop_losses.is_empty() {
+        lines.push(format!("📉 SL: {}", stop_losses.iter().map(|sl| format!("-{:.0}% sell {}%", (1.0 - sl.0) * 100.0, sl.1)).collect::<Vec<_>>().join(", ")));
+    }
+    if !trailing_stops.is_empty() {
+        lines.push(format!("🪜 Trailing: {}", trailing_stops.iter().map(|ts| format!("-{}% from high sell {}%", ts.0, ts.1)).collect::<Vec<_>>().join(", ")));
+    }
+    if !mc_targets.is_empty() {
+        lines.push(format!("🏦 MC: {}", mc_targets.iter().map(|target| format!("${} {} sell {}%", format_number(target.market_cap), if target.is_take_profit() { "TP" } else { "SL" }, target.sell_percentage)).collect::<Vec<_>>().join(", ")));
+    }
+    if lines.is_empty() {
+        return "No take profits or stop losses".to_string();
+    }
+    lines.join("\n")
+}
+
+/// Create the strategy presets message
+/// 
+/// # Arguments
+/// 
+/// * `presets` - The user presets
+/// 
+/// # Returns
+/// 
+/// A String listing the presets with their ladders and buy amount
+pub fn create_strategy_presets_message(presets: &[StrategyPreset]) -> String {
+    let mut presets_str = String::new();
+    for preset in presets {
+        presets_str.push_str(&format!(
+            "🎯 <b>{}</b> · buy {} SOL\n{}\n\n",
+            html::escape(&preset.name), preset.buy_amount, format_ladders(&preset.take_profits, &preset.stop_losses, &preset.trailing_stops, &preset.mc_targets)
+        ));
+    }
+    if presets_str.is_empty() {
+        presets_str.push_str("No presets yet.\n\n");
+    }
+    format!("⚙️ Strategy presets\n\n{presets_str}Pick a preset on the buy menu to use its buy amount, take profits and stop losses on the next buy of the token. Save the current ones as a preset with 💾 Save Preset, saving under an existing name replaces it.")
+}
+
+/// Create the strategy presets keyboard
+/// 
+/// # Arguments
+/// 
+/// * `presets` - The user presets
+/// 
+/// # Returns
+/// 
+/// An InlineKeyboardMarkup with a delete button per preset
+pub fn create_strategy_presets_keyboard(presets: &[StrategyPreset]) -> InlineKeyboardMarkup {
+    let mut buttons: Vec<Vec<InlineKeyboardButton>> = vec![vec![InlineKeyboardButton::callback("← Back", "back")]];
+    for preset in presets {
+        buttons.push(vec![InlineKeyboardButton::callback(format!("❌ Delete {}", preset.name), format!("delete_preset:{}", preset.id))]);
+    }
+    InlineKeyboardMarkup::new(buttons)
+}
+
+/// Create the position targets message
+/// 
+/// # Arguments
+/// 
+/// * `position` - The position
+/// 
+/// # Returns
+/// 
+/// A String with the take profits, stop losses, trailing stops and market cap targets of the position
+pub fn create_position_targets_message(position: &Position) -> String {
+    format!(
+        "🎯 Targets of <code>{}</code>\n\n{}\n\nApply a preset to replace them, or add and remove levels one by one.",
+        position.token_address, format_ladders(&position.take_profits, &position.stop_losses, &position.trailing_stops, &position.mc_targets)
+    )
+}
+
+/// Create the position targets keyboard
+/// 
+/// # Arguments
+/// 
+/// * `position` - The position
+/// * `presets` - The user presets
+/// 
+/// # Returns
+/// 
+/// An InlineKeyboardMarkup with a delete button per level, a button per preset and the add buttons
+pub fn create_position_targets_keyboard(position: &Position, presets: &[StrategyPreset]) -> InlineKeyboardMarkup {
+    let mut buttons: Vec<Vec<InlineKeyboardButton>> = vec![vec![InlineKeyboardButton::callback("← Back", "positions")]];
+    for take_profit in &position.take_profits {
+        buttons.push(vec![InlineKeyboardButton::callback(
+            format!("❌ {}x 📈 sell {}%", take_profit.0, take_profit.1),
+            format!("del_pos_level:{}:tp:{}_{}", position.id, take_profit.0, take_profit.1)
+        )]);
+    }
+    for stop_loss in &position.stop_losses {
+        buttons.push(vec![InlineKeyboardButton::callback(
+            format!("❌ {}x 📉 sell {}%", stop_loss.0, stop_loss.1),
+            format!("del_pos_level:{}:sl:{}_{}", position.id, stop_loss.0, stop_loss.1)
+        )]);
+    }
+    for trailing_stop in &position.trailing_stops {
+        buttons.push(vec![InlineKeyboardButton::callback(
+            format!("❌ -{}% from high sell {}%", trailing_stop.0, trailing_stop.1),
+            format!("del_pos_level:{}:ts:{}_{}", position.id, trailing_stop.0, trailing_stop.1)
+        )]);
+    }
+    for mc_target in &position.mc_targets {
+        buttons.push(vec![InlineKeyboardButton::callback(
+            format!("❌ ${} MC {} sell {}%", format_number(mc_target.market_cap), if mc_target.is_take_profit() { "📈" } else { "📉" }, mc_target.sell_percentage),
+            format!("del_pos_level:{}:{}:{}_{}", position.id, if mc_target.is_take_profit() { "mctp" } else { "mcsl" }, mc_target.market_cap, mc_target.sell_percentage)
+        )]);
+    }
+    buttons.push(vec![
+        InlineKeyboardButton::callback("Add Take Profit", format!("pos_add_take_profit:{}", position.id)),
+        InlineKeyboardButton::callback("Add Stop Loss", format!("pos_add_stop_loss:{}", position.id)),
+    ]);
+    for presets_row in presets.chunks(3) {
+        buttons.push(presets_row.iter().map(|preset| {
+            InlineKeyboardButton::callback(format!("Apply {}", preset.name), format!("apply_preset:{}:{}", position.id, preset.id))
+        }).collect());
+    }
+    InlineKeyboardMarkup::new(buttons)
+}
+
+/// Check a call against the call tracking settings of its group
+/// 
+/// # Arguments
+/// 
+/// * `settings` - The group settings
+/// * `pair_stats` - The market stats of the main pair of the token
+/// 
+/// # Returns
+/// 
+/// None if the call counts, otherwise the reason it doesn't
+pub fn check_group_call_rules(settings: &GroupSettings, pair_stats: &PairStats) -> Option<String> {
+    let chain = pair_stats.pair.chain.as_str();
+    if !settings.allowed_chains.is_empty() && !settings.allowed_chains.iter().any(|allowed_chain| allowed_chain.eq_ignore_ascii_case(chain)) {
+        return Some(format!("{} calls aren't tracked in this group", chain));
+    }
+    let liquidity = pair_stats.liquidity_usd;
+    if liquidity < settings.min_liquidity {
+        return Some(format!("Liquidity ${} is below the ${} minimum of this group", format_number(liquidity), format_number(settings.min_liquidity)));
+    }
+    let mkt_cap = pair_stats.market_cap();
+    if mkt_cap < settings.min_mkt_cap {
+        return Some(format!("Market cap ${} is below the ${} minimum of this group", format_number(mkt_cap), format_number(settings.min_mkt_cap)));
+    }
+    None
+}
+
+/// Create the group settings message
+/// 
+/// # Arguments
+/// 
+/// * `settings` - The group settings
+/// 
+/// # Returns
+/// 
+/// A String with the call tracking settings of the group
+pub fn create_group_settings_message(settings: &GroupSettings) -> String {
+    let on_off = |enabled: bool| if enabled { "✅ On" } else { "❌ Off" };
+    let chains = if settings.allowed_chains.is_empty() { "All".to_string() } else { settings.allowed_chains.join(", ") };
+    format!(
+        "⚙️ <b>Group settings</b>\n\n\
+        ├ Auto calls: <b>{}</b>\n\
+        ├ Require /call: <b>{}</b>\n\
+        ├ Chains: <b>{}</b>\n\
+        ├ Min liquidity: <b>${}</b>\n\
+        ├ Min market cap: <b>${}</b>\n\
+        ├ One call per user and token: <b>{}</b>\n\
+        ├ Leaderboard period: <b>{}</b>\n\
+        ├ Digest: <b>{}</b>\n\
+        ├ Milestones: <b>{}</b>\n\
+        └ Global leaderboard: <b>{}</b>\n\n\
+        <i>Only the group admins can change the settings</i>",
+        on_off(settings.auto_calls),
+        on_off(settings.require_call_prefix),
+        chains,
+        format_number(settings.min_liquidity),
+        format_number(settings.min_mkt_cap),
+        on_off(settings.one_call_per_user_token),
+        settings.lb_default_period,
+        format_digest_schedule(settings),
+        format_milestones(&settings.milestones),
+        on_off(settings.global_leaderboard),
+    )
+}
+
+/// Create the group settings keyboard
+/// 
+/// # Arguments
+/// 
+/// * `settings` - The group settings
+/// 
+/// # Returns
+/// 
+/// An InlineKeyboardMarkup with a toggle per switch and a button per value
+pub fn create_group_settings_keyboard(settings: &GroupSettings) -> InlineKeyboardMarkup {
+    let toggle = |label: &str, enabled: bool, setting: &str| {
+        InlineKeyboardButton::callback(format!("{} {}", if enabled { "✅" } else { "❌" }, label), format!("group_toggle:{}", setting))
+    };
+    InlineKeyboardMarkup::new(vec![
+        vec![toggle("Auto calls", settings.auto_calls, "auto_calls"), toggle("Require /call", settings.require_call_prefix, "require_call_prefix")],
+        vec![toggle("One call per token", settings.one_call_per_user_token, "one_call_per_user_token"), toggle("Global", settings.global_leaderboard, "global_leaderboard")],
+        vec![InlineKeyboardButton::callback("⛓ Chains", "group_set:allowed_chains"), InlineKeyboardButton::callback("📅 Leaderboard period", "group_set:lb_default_period")],
+        vec![InlineKeyboardButton::callback("💦 Min liquidity", "group_set:min_liquidity"), InlineKeyboardButton::callback("📊 Min market cap", "group_set:min_mkt_cap")],
+        vec![InlineKeyboardButton::callback("🚀 Milestones", "group_set:milestones")],
+        vec![
+            InlineKeyboardButton::callback(format!("📬 Digest: {}", capitalize(&settings.digest_frequency)), "group_toggle:digest_frequency"),
+            InlineKeyboardButton::callback("🕘 Digest time", "group_set:digest_time"),
+            toggle("Pin digest", settings.digest_pin, "digest_pin"),
+        ],
+    ])
+}
+
+/// Capitalize the first letter of a word
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Describe when the leaderboard digest of a group is posted, eg "Daily at 09:00 Europe/Paris, pinned"
+pub fn format_digest_schedule(settings: &GroupSettings) -> String {
+    let day = match settings.digest_frequency.as_str() {
+        "daily" => "Daily",
+        "weekly" => "Mondays",
+        _ => return "Off".to_string(),
+    };
+    format!(
+        "{} at {:02}:00 {}{}",
+        day,
+        settings.digest_hour,
+        settings.digest_timezone,
+        if settings.digest_pin { ", pinned" } else { "" }
+    )
+}
+
+/// Parse the time of a leaderboard digest
+/// 
+/// # Arguments
+/// 
+/// * `text` - The hour, optionally followed by an IANA timezone, eg "9", "18 Europe/Paris" or "8 America/New_York"
+/// 
+/// # Returns
+/// 
+/// An Option containing the hour and the timezone, UTC by default, or None if the time isn't valid
+pub fn parse_digest_time(text: &str) -> Option<(i32, Tz)> {
+    let re = Regex::new(r"^(\d{1,2})(?::00)?h?(?:\s+(\S+))?$").unwrap();
+    let cap = re.captures(text.trim())?;
+    let hour = cap[1].parse::<i32>().ok()?;
+    if hour > 23 {
+        return None;
+    }
+    let timezone = match cap.get(2) {
+        Some(timezone) if timezone.as_str().eq_ignore_ascii_case("utc") => Tz::UTC,
+        Some(timezone) => timezone.as_str().parse::<Tz>().ok()?,
+        None => Tz::UTC,
+    };
+    Some((hour, timezone))
+}
+
+/// Check if the leaderboard digest of a group is due
+/// 
+/// # Description
+/// 
+/// A digest is due once per local day, daily or on mondays for weekly digests, from the digest
+/// hour in the group timezone. An unknown timezone is read as UTC.
+/// 
+/// # Arguments
+/// 
+/// * `settings` - The group settings
+/// * `now` - The current time
+/// 
+/// # Returns
+/// 
+/// A boolean indicating if the digest should be posted
+pub fn is_digest_due(settings: &GroupSettings, now: DateTime<Utc>) -> bool {
+    use chrono::{Datelike, Timelike};
+    let timezone = settings.digest_timezone.parse::<Tz>().unwrap_or(Tz::UTC);
+    let local_now = now.with_timezone(&timezone);
+    let due_today = match settings.digest_frequency.as_str() {
+        "daily" => true,
+        "weekly" => local_now.weekday() == chrono::Weekday::Mon,
+        _ => false,
+    };
+    if !due_today || (local_now.hour() as i32) < settings.digest_hour {
+        return false;
+    }
+    match settings.digest_last_sent_at {
+        Some(last_sent_at) => last_sent_at.with_timezone(&timezone).date_naive() < local_now.date_naive(),
+        None => true,
+    }
+}
+
+/// How many days the calls are watched for their milestones
+pub const CALL_MILESTONES_DAYS: i32 = 3;
+/// How many called tokens are watched for their milestones at most, the most recently called first
+pub const MAX_CALL_MILESTONES_TOKENS: i64 = 200;
+
+/// Format the call milestones of a group, eg "2x, 5x, 10x"
+pub fn format_milestones(milestones: &[f64]) -> String {
+    if milestones.is_empty() {
+        return "Off".to_string();
+    }
+    milestones.iter().map(|milestone| format!("{}x", milestone)).collect::<Vec<String>>().join(", ")
+}
+
+/// Parse the call milestones of a group
+/// 
+/// # Arguments
+/// 
+/// * `text` - Comma separated multipliers above 1, eg "2,5,10", or "off"
+/// 
+/// # Returns
+/// 
+/// An Option containing the sorted milestones, empty for "off", or None if they aren't valid
+pub fn parse_milestones(text: &str) -> Option<Vec<f64>> {
+    let text = text.trim().to_lowercase();
+    if text == "off" {
+        return Some(Vec::new());
+    }
+    let mut milestones = Vec::new();
+    for milestone in text.split(",") {
+        let milestone = milestone.trim().trim_end_matches("x").parse::<f64>().ok()?;
+        if milestone <= 1.0 || !milestone.is_finite() {
+            return None;
+        }
+        if !milestones.contains(&milestone) {
+            milestones.push(milestone);
+        }
+    }
+    milestones.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    Some(milestones)
+}
+
+/// Create the message announcing a call milestone
+/// 
+/// # Arguments
+/// 
+/// * `call` - The call
+/// * `milestone` - The milestone reached
+/// * `multiplier` - The current multiplier of the call
+/// * `username` - The username of the caller
+/// 
+/// # Returns
+/// 
+/// A String with the milestone, the market cap at the call and now
+pub fn call_milestone_message(call: &Call, milestone: f64, multiplier: f64, username: &str) -> String {
+    let call_mkt_cap = call.mkt_cap.parse::<f64>().unwrap_or(0.0);
+    let badge = if milestone >= 10.0 { "🌕" } else if milestone >= 5.0 { "🔥" } else { "🚀" };
+    format!(
+        "{badge} <b>${}</b> hit <b>{}x</b> from <a href=\"https://t.me/sj_copyTradebot?start=user_{}\"><i><b>{}</b></i></a>'s call\n\
+        └ ${} → <b>${}</b> ({:.2}x)",
+        html::escape(&call.token_symbol.to_uppercase()),
+        milestone,
+        call.user_tg_id,
+        html::escape(username),
+        format_number(call_mkt_cap),
+        format_number(call_mkt_cap * multiplier),
+        multiplier,
+    )
+}
@@ -0,0 +1,304 @@
+use serde::{Deserialize, Serialize};
+
+/// Kind of a position order level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrderKind {
+    TakeProfit,
+    StopLoss,
+    TrailingStop,
+    MarketCapTakeProfit,
+    MarketCapStopLoss,
+}
+
+impl OrderKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderKind::TakeProfit => "take_profit",
+            OrderKind::StopLoss => "stop_loss",
+            OrderKind::TrailingStop => "trailing_stop",
+            OrderKind::MarketCapTakeProfit => "mc_take_profit",
+            OrderKind::MarketCapStopLoss => "mc_stop_loss",
+        }
+    }
+}
+
+/// Attempts after which a failed level isn't retried anymore
+pub const MAX_ORDER_ATTEMPTS: i32 = 3;
+/// Minutes after which a level still executing is considered abandoned and can be claimed again
+pub const STALE_ORDER_MINUTES: i32 = 5;
+
+/// Final state of a position order level
+///
+/// A level without a row in `position_orders` is pending. It moves to executing when the
+/// watcher claims it and then to filled or failed. Failed levels are claimed again on the next
+/// price ticks until `MAX_ORDER_ATTEMPTS`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrderStatus {
+    Filled,
+    Failed,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Filled => "filled",
+            OrderStatus::Failed => "failed",
+        }
+    }
+}
+
+/// A take profit, stop loss or trailing stop level of a position
+///
+/// For trailing stops `multiplier` holds the % down from the highest price instead, and for
+/// market cap levels the target market cap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LadderLevel {
+    pub kind: OrderKind,
+    pub multiplier: f64,
+    pub sell_percentage: f64,
+}
+
+/// Get the levels of a position ladder reached at a price, in execution order
+///
+/// # Description
+///
+/// Take profits are reached when the price is at or above `multiplier * entry_price` and run
+/// from the lowest multiplier up. Stop losses are reached when the price is at or below
+/// `multiplier * entry_price` and run from the highest multiplier (the closest to the entry) down.
+/// Take profits go before stop losses.
+///
+/// # Arguments
+///
+/// * `take_profits` - The position take profits as (multiplier, % to sell)
+/// * `stop_losses` - The position stop losses as (multiplier, % to sell)
+/// * `entry_price` - The position entry price
+/// * `price` - The current price
+///
+/// # Returns
+///
+/// The reached levels
+pub fn triggered_levels(take_profits: &[(f64, f64)], stop_losses: &[(f64, f64)], entry_price: f64, price: f64) -> Vec<LadderLevel> {
+    if entry_price <= 0.0 || price <= 0.0 {
+        return Vec::new();
+    }
+
+    let mut reached_take_profits: Vec<LadderLevel> = take_profits.iter()
+        .filter(|tp| price >= tp.0 * entry_price)
+        .map(|tp| LadderLevel { kind: OrderKind::TakeProfit, multiplier: tp.0, sell_percentage: tp.1 })
+        .collect();
+    reached_take_profits.sort_by(|a, b| a.multiplier.partial_cmp(&b.multiplier).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut reached_stop_losses: Vec<LadderLevel> = stop_losses.iter()
+        .filter(|sl| price <= sl.0 * entry_price)
+        .map(|sl| LadderLevel { kind: OrderKind::StopLoss, multiplier: sl.0, sell_percentage: sl.1 })
+        .collect();
+    reached_stop_losses.sort_by(|a, b| b.multiplier.partial_cmp(&a.multiplier).unwrap_or(std::cmp::Ordering::Equal));
+
+    reached_take_profits.extend(reached_stop_losses);
+    reached_take_profits
+}
+
+/// Get the price a trailing stop fires at
+///
+/// # Arguments
+///
+/// * `trail_percentage` - The % down from the highest price
+/// * `highest_price` - The highest price since entry
+///
+/// # Returns
+///
+/// The trigger price
+pub fn trailing_stop_trigger_price(trail_percentage: f64, highest_price: f64) -> f64 {
+    highest_price * (1.0 - trail_percentage / 100.0)
+}
+
+/// Get the trailing stops of a position reached at a price, in execution order
+///
+/// # Description
+///
+/// A trailing stop is reached when the price is at or below its trigger price, computed from
+/// the highest price since entry. They run from the tightest trail (the highest trigger) down.
+///
+/// # Arguments
+///
+/// * `trailing_stops` - The position trailing stops as (% down from the highest price, % to sell)
+/// * `highest_price` - The highest price since entry
+/// * `price` - The current price
+///
+/// # Returns
+///
+/// The reached levels
+pub fn triggered_trailing_stops(trailing_stops: &[(f64, f64)], highest_price: f64, price: f64) -> Vec<LadderLevel> {
+    if highest_price <= 0.0 || price <= 0.0 {
+        return Vec::new();
+    }
+
+    let mut reached_trailing_stops: Vec<LadderLevel> = trailing_stops.iter()
+        .filter(|ts| price <= trailing_stop_trigger_price(ts.0, highest_price))
+        .map(|ts| LadderLevel { kind: OrderKind::TrailingStop, multiplier: ts.0, sell_percentage: ts.1 })
+        .collect();
+    reached_trailing_stops.sort_by(|a, b| a.multiplier.partial_cmp(&b.multiplier).unwrap_or(std::cmp::Ordering::Equal));
+    reached_trailing_stops
+}
+
+/// A take profit or stop loss at an absolute market cap
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketCapTarget {
+    pub kind: String, // take_profit or stop_loss
+    pub market_cap: f64,
+    pub sell_percentage: f64,
+}
+
+impl MarketCapTarget {
+    pub fn is_take_profit(&self) -> bool {
+        self.kind == "take_profit"
+    }
+}
+
+/// Get the market cap targets of a position reached at a price, in execution order
+///
+/// # Description
+///
+/// The market cap is the price times the circulating supply. Take profits are reached when it
+/// is at or above the target and run from the lowest target up, stop losses when it is at or
+/// below the target and run from the highest target down.
+///
+/// # Arguments
+///
+/// * `targets` - The position market cap targets
+/// * `circulating_supply` - The token circulating supply, nothing is reached when it is unknown (0, inf or NaN)
+/// * `price` - The current price
+///
+/// # Returns
+///
+/// The reached levels
+pub fn triggered_market_cap_targets(targets: &[MarketCapTarget], circulating_supply: f64, price: f64) -> Vec<LadderLevel> {
+    if !circulating_supply.is_finite() || circulating_supply <= 0.0 || price <= 0.0 {
+        return Vec::new();
+    }
+    let market_cap = price * circulating_supply;
+
+    let mut reached_take_profits: Vec<LadderLevel> = targets.iter()
+        .filter(|target| target.is_take_profit() && market_cap >= target.market_cap)
+        .map(|target| LadderLevel { kind: OrderKind::MarketCapTakeProfit, multiplier: target.market_cap, sell_percentage: target.sell_percentage })
+        .collect();
+    reached_take_profits.sort_by(|a, b| a.multiplier.partial_cmp(&b.multiplier).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut reached_stop_losses: Vec<LadderLevel> = targets.iter()
+        .filter(|target| !target.is_take_profit() && market_cap <= target.market_cap)
+        .map(|target| LadderLevel { kind: OrderKind::MarketCapStopLoss, multiplier: target.market_cap, sell_percentage: target.sell_percentage })
+        .collect();
+    reached_stop_losses.sort_by(|a, b| b.multiplier.partial_cmp(&a.multiplier).unwrap_or(std::cmp::Ordering::Equal));
+
+    reached_take_profits.extend(reached_stop_losses);
+    reached_take_profits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run a ladder over a price path like the watcher does, a level leaves the ladder once fired
+    fn run_price_path(take_profits: &[(f64, f64)], stop_losses: &[(f64, f64)], entry_price: f64, path: &[f64]) -> Vec<(usize, OrderKind, f64)> {
+        let mut take_profits = take_profits.to_vec();
+        let mut stop_losses = stop_losses.to_vec();
+        let mut fired = Vec::new();
+        for (tick, price) in path.iter().enumerate() {
+            for level in triggered_levels(&take_profits, &stop_losses, entry_price, *price) {
+                let executed = (level.multiplier, level.sell_percentage);
+                match level.kind {
+                    OrderKind::TakeProfit => take_profits.retain(|tp| *tp != executed),
+                    _ => stop_losses.retain(|sl| *sl != executed),
+                }
+                fired.push((tick, level.kind, level.multiplier));
+            }
+        }
+        fired
+    }
+
+    #[test]
+    fn take_profits_fire_once_each_as_the_price_climbs() {
+        let fired = run_price_path(&[(3.0, 50.0), (2.0, 25.0)], &[(0.5, 100.0)], 1.0, &[1.0, 1.9, 2.0, 2.5, 2.1, 3.2, 4.0]);
+        assert_eq!(fired, vec![(2, OrderKind::TakeProfit, 2.0), (5, OrderKind::TakeProfit, 3.0)]);
+    }
+
+    #[test]
+    fn a_gap_up_fires_every_reached_take_profit_lowest_first() {
+        let fired = run_price_path(&[(5.0, 50.0), (2.0, 25.0), (3.0, 25.0), (10.0, 100.0)], &[], 0.001, &[0.001, 0.006]);
+        assert_eq!(fired, vec![(1, OrderKind::TakeProfit, 2.0), (1, OrderKind::TakeProfit, 3.0), (1, OrderKind::TakeProfit, 5.0)]);
+    }
+
+    #[test]
+    fn a_crash_fires_the_stop_losses_closest_to_the_entry_first() {
+        let fired = run_price_path(&[(2.0, 50.0)], &[(0.5, 100.0), (0.8, 50.0)], 2.0, &[2.0, 1.7, 0.9, 0.5]);
+        assert_eq!(fired, vec![(2, OrderKind::StopLoss, 0.8), (2, OrderKind::StopLoss, 0.5)]);
+    }
+
+    #[test]
+    fn take_profits_go_before_stop_losses_on_the_same_tick() {
+        // A stop loss above the entry locks profits, it can be reached together with a take profit
+        let levels = triggered_levels(&[(1.5, 50.0)], &[(2.0, 100.0)], 1.0, 1.6);
+        assert_eq!(levels.iter().map(|level| level.kind).collect::<Vec<_>>(), vec![OrderKind::TakeProfit, OrderKind::StopLoss]);
+    }
+
+    #[test]
+    fn a_round_trip_fires_take_profits_then_stop_losses() {
+        let fired = run_price_path(&[(2.0, 50.0)], &[(0.7, 100.0)], 1.0, &[1.0, 2.4, 1.5, 0.9, 0.6, 0.5]);
+        assert_eq!(fired, vec![(1, OrderKind::TakeProfit, 2.0), (4, OrderKind::StopLoss, 0.7)]);
+    }
+
+    #[test]
+    fn nothing_fires_without_an_entry_price_or_a_price() {
+        assert!(triggered_levels(&[(2.0, 50.0)], &[(0.5, 100.0)], 0.0, 1.0).is_empty());
+        assert!(triggered_levels(&[(2.0, 50.0)], &[(0.5, 100.0)], 1.0, 0.0).is_empty());
+    }
+
+    #[test]
+    fn trailing_stops_follow_the_highest_price() {
+        let trailing_stops = [(10.0, 50.0), (25.0, 100.0)];
+        let mut highest_price: f64 = 0.0;
+        let mut fired = Vec::new();
+        for (tick, price) in [1.0, 1.5, 2.0, 1.85, 1.79, 1.6, 1.5].iter().enumerate() {
+            highest_price = highest_price.max(*price);
+            for level in triggered_trailing_stops(&trailing_stops, highest_price, *price) {
+                if !fired.iter().any(|(_, trail)| *trail == level.multiplier) {
+                    fired.push((tick, level.multiplier));
+                }
+            }
+        }
+        assert_eq!(fired, vec![(4, 10.0), (6, 25.0)]);
+    }
+
+    fn mc_target(kind: &str, market_cap: f64, sell_percentage: f64) -> MarketCapTarget {
+        MarketCapTarget { kind: kind.to_string(), market_cap, sell_percentage }
+    }
+
+    #[test]
+    fn market_cap_targets_fire_in_order_as_the_market_cap_moves() {
+        let targets = [
+            mc_target("take_profit", 2_000_000.0, 50.0),
+            mc_target("take_profit", 1_000_000.0, 25.0),
+            mc_target("stop_loss", 200_000.0, 100.0),
+            mc_target("stop_loss", 400_000.0, 50.0),
+        ];
+        let circulating_supply = 1_000_000_000.0;
+        let market_caps = |price: f64| triggered_market_cap_targets(&targets, circulating_supply, price)
+            .iter()
+            .map(|level| (level.kind, level.multiplier))
+            .collect::<Vec<_>>();
+
+        assert!(market_caps(0.0005).is_empty());
+        assert_eq!(market_caps(0.001), vec![(OrderKind::MarketCapTakeProfit, 1_000_000.0)]);
+        assert_eq!(market_caps(0.0025), vec![(OrderKind::MarketCapTakeProfit, 1_000_000.0), (OrderKind::MarketCapTakeProfit, 2_000_000.0)]);
+        assert_eq!(market_caps(0.0001), vec![(OrderKind::MarketCapStopLoss, 400_000.0), (OrderKind::MarketCapStopLoss, 200_000.0)]);
+    }
+
+    #[test]
+    fn market_cap_targets_need_a_known_supply() {
+        let targets = [mc_target("take_profit", 1_000_000.0, 50.0), mc_target("stop_loss", 200_000.0, 100.0)];
+        assert!(triggered_market_cap_targets(&targets, f64::INFINITY, 0.001).is_empty());
+        assert!(triggered_market_cap_targets(&targets, f64::NAN, 0.001).is_empty());
+        assert!(triggered_market_cap_targets(&targets, 0.0, 0.001).is_empty());
+        assert!(triggered_market_cap_targets(&targets, 1_000_000_000.0, 0.0).is_empty());
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use anyhow::Result;
+
+/// Entries kept before the expired ones are swept
+const SWEEP_THRESHOLD: usize = 1024;
+
+/// The last answer of a key and when it came, locked while it is being fetched. Errors are kept
+/// as their message, only to be shared with the requests that waited for them.
+type Slot<V> = Arc<tokio::sync::Mutex<Option<(Instant, std::result::Result<V, String>)>>>;
+
+tokio::task_local! {
+    /// Set while a background job runs, its requests give way to the ones of the users
+    static BACKGROUND: ();
+}
+
+/// Run a background job, its upstream requests only take the rate limit slots the users leave free
+pub async fn in_background<F: Future>(job: F) -> F::Output {
+    BACKGROUND.scope((), job).await
+}
+
+/// Cache of upstream responses, each kept for a fixed time
+///
+/// Concurrent requests of the same key are coalesced: the first one fetches while the others
+/// wait for its answer, error included, instead of hitting the upstream API too. Errors aren't
+/// cached, the next request fetches again.
+pub struct TtlCache<V> {
+    ttl: Duration,
+    slots: Mutex<HashMap<String, Slot<V>>>,
+}
+
+impl<V: Clone> TtlCache<V> {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, slots: Mutex::new(HashMap::new()) }
+    }
+
+    /// Get the cached value of a key, or fetch it when missing or expired
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key
+    /// * `fetch` - Fetches the value from upstream
+    ///
+    /// # Returns
+    ///
+    /// The cached or fetched value, or the fetch error
+    pub async fn get_or_fetch<F, Fut>(&self, key: &str, fetch: F) -> Result<V>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V>>,
+    {
+        let requested_at = Instant::now();
+        let slot = self.slot(key);
+        let mut cached = slot.lock().await;
+        match cached.as_ref() {
+            Some((fetched_at, Ok(value))) if fetched_at.elapsed() < self.ttl => return Ok(value.clone()),
+            // The fetch this request waited for failed
+            Some((fetched_at, Err(e))) if *fetched_at >= requested_at => return Err(anyhow::anyhow!("{}", e)),
+            _ => {}
+        }
+        match fetch().await {
+            Ok(value) => {
+                *cached = Some((Instant::now(), Ok(value.clone())));
+                Ok(value)
+            }
+            Err(e) => {
+                *cached = Some((Instant::now(), Err(format!("{:#}", e))));
+                Err(e)
+            }
+        }
+    }
+
+    /// Get the slot of a key, sweeping the expired slots once the cache grows
+    fn slot(&self, key: &str) -> Slot<V> {
+        let mut slots = self.slots.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if slots.len() >= SWEEP_THRESHOLD && !slots.contains_key(key) {
+            let ttl = self.ttl;
+            // A slot being fetched is locked and kept, its waiters need it
+            slots.retain(|_, slot| match slot.try_lock() {
+                Ok(cached) => matches!(cached.as_ref(), Some((fetched_at, Ok(_))) if fetched_at.elapsed() < ttl),
+                Err(_) => true,
+            });
+        }
+        slots.entry(key.to_string()).or_default().clone()
+    }
+}
+
+/// Spaces out the requests to an upstream API to stay under its rate limit
+///
+/// The users requests queue for the next slots in turn. The requests of background jobs, see
+/// [`in_background`], never queue: they only take a slot that is free right away, so a trade
+/// being booked waits at most one slot behind them.
+pub struct RateLimiter {
+    interval: Duration,
+    next_slot: tokio::sync::Mutex<tokio::time::Instant>,
+}
+
+impl RateLimiter {
+    pub fn per_second(requests: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / requests.max(1),
+            next_slot: tokio::sync::Mutex::new(tokio::time::Instant::now()),
+        }
+    }
+
+    /// Wait for the next free request slot
+    pub async fn acquire(&self) {
+        if BACKGROUND.try_with(|_| ()).is_ok() {
+            loop {
+                let wait = {
+                    let mut next_slot = self.next_slot.lock().await;
+                    let now = tokio::time::Instant::now();
+                    if *next_slot <= now {
+                        *next_slot = now + self.interval;
+                        return;
+                    }
+                    *next_slot - now
+                };
+                tokio::time::sleep(wait).await;
+            }
+        }
+        let wait = {
+            let mut next_slot = self.next_slot.lock().await;
+            let now = tokio::time::Instant::now();
+            let slot = (*next_slot).max(now);
+            *next_slot = slot + self.interval;
+            slot - now
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Fetch a key of the cache, counting the upstream requests
+    async fn fetch(cache: &TtlCache<u32>, fetches: &AtomicUsize, answer: Result<u32, &str>) -> Result<u32> {
+        cache.get_or_fetch("key", || async {
+            fetches.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            answer.map_err(|e| anyhow::anyhow!("{}", e))
+        }).await
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_share_one_fetch() {
+        let cache = TtlCache::new(Duration::from_secs(60));
+        let fetches = AtomicUsize::new(0);
+        let (first, second) = tokio::join!(fetch(&cache, &fetches, Ok(1)), fetch(&cache, &fetches, Ok(2)));
+        assert_eq!((first.unwrap(), second.unwrap()), (1, 1));
+        assert_eq!(fetch(&cache, &fetches, Ok(3)).await.unwrap(), 1);
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn waiters_share_the_error_but_the_next_request_fetches_again() {
+        let cache = TtlCache::new(Duration::from_secs(60));
+        let fetches = AtomicUsize::new(0);
+        let (first, second) = tokio::join!(fetch(&cache, &fetches, Err("HTTP 429")), fetch(&cache, &fetches, Ok(2)));
+        assert_eq!(first.unwrap_err().to_string(), "HTTP 429");
+        assert_eq!(second.unwrap_err().to_string(), "HTTP 429");
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert_eq!(fetch(&cache, &fetches, Ok(3)).await.unwrap(), 3);
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn expired_values_are_fetched_again() {
+        let cache = TtlCache::new(Duration::from_millis(10));
+        let fetches = AtomicUsize::new(0);
+        assert_eq!(fetch(&cache, &fetches, Ok(1)).await.unwrap(), 1);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(fetch(&cache, &fetches, Ok(2)).await.unwrap(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn background_requests_do_not_queue_ahead_of_the_users() {
+        let limiter = RateLimiter::per_second(10);
+        let start = tokio::time::Instant::now();
+        // The background job takes the free slot, then the users queue up
+        in_background(limiter.acquire()).await;
+        let user = async {
+            limiter.acquire().await;
+            limiter.acquire().await;
+            tokio::time::Instant::now()
+        };
+        let background = in_background(async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            limiter.acquire().await;
+            tokio::time::Instant::now()
+        });
+        // The users take the 100ms and 200ms slots, the background job waits for the free one after
+        let (user_done, background_done) = tokio::join!(user, background);
+        assert_eq!(user_done - start, Duration::from_millis(200));
+        assert_eq!(background_done - start, Duration::from_millis(300));
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::time::Duration;
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use reqwest::Client;
+use super::cache::{RateLimiter, TtlCache};
+use super::raydium::check_raydium_tokens_prices;
+use super::{parse_f64, Ath, Holder, Holders, MarketData, PairStats, TokenPair};
+
+/// Requests per second allowed to the dexcelerate API
+static RATE_LIMIT: LazyLock<RateLimiter> = LazyLock::new(|| RateLimiter::per_second(5));
+/// The main pair moves when a token graduates from pump.fun, it is kept a minute
+static PAIR_CACHE: LazyLock<TtlCache<Value>> = LazyLock::new(|| TtlCache::new(Duration::from_secs(60)));
+static PAIR_STATS_CACHE: LazyLock<TtlCache<Value>> = LazyLock::new(|| TtlCache::new(Duration::from_secs(10)));
+static ATH_CACHE: LazyLock<TtlCache<Value>> = LazyLock::new(|| TtlCache::new(Duration::from_secs(60)));
+static HOLDERS_CACHE: LazyLock<TtlCache<Value>> = LazyLock::new(|| TtlCache::new(Duration::from_secs(60)));
+
+/// Market data from the dexcelerate API, with the prices from the Raydium API
+///
+/// The scanner has the total supply only, the circulating supply is left to the next provider.
+/// The responses are cached briefly and the requests rate limited, so the users looking at the
+/// same token share one upstream request.
+pub struct Dexcelerate;
+
+#[async_trait]
+impl MarketData for Dexcelerate {
+    fn name(&self) -> &'static str {
+        "dexcelerate"
+    }
+
+    async fn token_pair(&self, address: &str) -> Result<TokenPair> {
+        let pair_and_token = get_pair_token_pair_and_token_address(address).await?;
+        let token_pair = TokenPair {
+            pair_address: pair_and_token["pairAddress"].as_str().unwrap_or_default().to_string(),
+            token_address: pair_and_token["tokenAddress"].as_str().unwrap_or_default().to_string(),
+            chain: pair_and_token["chainName"].as_str().unwrap_or_default().to_string(),
+        };
+        if token_pair.pair_address.is_empty() || token_pair.token_address.is_empty() {
+            return Err(anyhow::anyhow!("No pair found for {}", address));
+        }
+        Ok(token_pair)
+    }
+
+    async fn pair_stats(&self, address: &str) -> Result<PairStats> {
+        let token_pair = self.token_pair(address).await?;
+        let scanner_response = get_pair_stats_response(&token_pair).await?;
+        Ok(pair_stats_from_scanner(token_pair, &scanner_response))
+    }
+
+    async fn ath(&self, token_address: &str, chain: &str, since: DateTime<Utc>) -> Result<Ath> {
+        let ath_response = get_ath(since.timestamp_millis(), token_address, chain).await?;
+        Ok(Ath {
+            price: parse_f64(&ath_response["athTokenPrice"]),
+            timestamp: parse_datetime(&ath_response["athTimestamp"]),
+        })
+    }
+
+    async fn holders(&self, token_address: &str) -> Result<Holders> {
+        let holders_response = get_holders(token_address).await?;
+        holders_from_response(&holders_response)
+    }
+
+    async fn prices(&self, token_addresses: &[String]) -> Result<HashMap<String, f64>> {
+        let prices = check_raydium_tokens_prices(token_addresses.to_vec()).await?;
+        Ok(prices.into_iter()
+            .filter_map(|(token, price)| price.parse::<f64>().ok().map(|price| (token, price)))
+            .collect())
+    }
+}
+
+/// Get the pair address and token address
+/// 
+/// # Arguments
+/// 
+/// * `address` - The address to get the pair address and token address for
+/// 
+/// # Returns
+/// 
+/// A JSON object containing the pair address and token address
+pub async fn get_pair_token_pair_and_token_address(address: &str) -> Result<Value> {
+    PAIR_CACHE.get_or_fetch(address, || async {
+        RATE_LIMIT.acquire().await;
+        let response = Client::new().get(format!("https://api-rs.dexcelerate.com/pair/{}/pair-and-token", address))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Failed to fetch data: HTTP {}", response.status()));
+        }
+        let json: Value = response.json().await?;
+        Ok(json)
+    }).await
+}
+
+/// Get the scanner stats of a pair
+///
+/// # Arguments
+///
+/// * `token_pair` - The pair
+///
+/// # Returns
+///
+/// A JSON object containing the scanner search
+pub async fn get_pair_stats_response(token_pair: &TokenPair) -> Result<Value> {
+    let url = format!("https://api-rs.dexcelerate.com/scanner/{}/{}/{}/pair-stats", token_pair.chain, token_pair.pair_address, token_pair.token_address);
+    PAIR_STATS_CACHE.get_or_fetch(&url, || async {
+        RATE_LIMIT.acquire().await;
+        println!("@get_pair_stats_response/ url: {:?}", url);
+        let response = Client::new().get(url.as_str())
+            .send()
+            .await?;
+
+        // Check if the response status is success
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Failed to fetch data: HTTP {}", response.status()));
+        }
+
+        // Read the response body as a string
+        let body = response.text().await?;
+        if body.is_empty() {
+            log::error!("Received empty response body");
+            return Err(anyhow::anyhow!("Received empty response body"));
+        }
+        Ok(serde_json::from_str(&body)?)
+    }).await
+}
+
+/// Get the ATH of a token
+/// 
+/// # Arguments
+/// 
+/// * `timestamp` - The timestamp to get the ATH for
+/// * `token_address` - The address of the token
+/// * `chain` - The chain of the token
+/// 
+/// # Returns
+/// 
+/// A JSON object containing the ATH
+pub async fn get_ath(timestamp: i64, token_address: &str, chain: &str) -> Result<Value> {
+    let url = format!("https://api-rs.dexcelerate.com/token/{}/{}/ath?timestamp={}", chain, token_address, timestamp);
+    ATH_CACHE.get_or_fetch(&url, || async {
+        RATE_LIMIT.acquire().await;
+        let response = Client::new().get(url.as_str())
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Failed to fetch data: HTTP {}", response.status()));
+        }
+        let json: Value = response.json().await?;
+        Ok(json)
+    }).await
+}
+
+/// Get the holders of a token
+/// 
+/// # Arguments
+/// 
+/// * `address` - The address of the token
+/// 
+/// # Returns
+///
+/// A Result containing the holders or an error
+pub async fn get_holders(address: &str) -> Result<Value> {
+    HOLDERS_CACHE.get_or_fetch(address, || async {
+        RATE_LIMIT.acquire().await;
+        let url = format!("https://api-rs.dexcelerate.com/token/SOL/{}/holders", address);
+        let response = Client::new().get(url)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Failed to fetch data: HTTP {}", response.status()));
+        }
+        let json: Value = response.json().await?;
+        Ok(json)
+    }).await
+}
+
+/// Parse the response of the dexcelerate scanner
+///
+/// # Arguments
+///
+/// * `token_pair` - The scanned pair
+/// * `scanner_response` - The response from the API call to get the scanner search
+///
+/// # Returns
+///
+/// The stats of the pair
+pub fn pair_stats_from_scanner(token_pair: TokenPair, scanner_response: &Value) -> PairStats {
+    let pair = &scanner_response["pair"];
+    let change = |period: &str| {
+        let first = parse_f64(&scanner_response["pairStats"][period]["first"]);
+        let last = parse_f64(&scanner_response["pairStats"][period]["last"]);
+        if first != 0.0 { (last / first - 1.0) * 100.0 } else { 0.0 }
+    };
+    let link = |key: &str| pair[key].as_str().filter(|link| !link.is_empty()).map(|link| link.to_string());
+    let burned_supply = parse_f64(&pair["burnedSupply"]);
+    PairStats {
+        pair: token_pair,
+        name: pair["token1Name"].as_str().unwrap_or("N/A").to_string(),
+        symbol: pair["token1Symbol"].as_str().unwrap_or("N/A").to_string(),
+        price_usd: parse_f64(&pair["pairPrice1Usd"]),
+        total_supply: parse_f64(&pair["token1TotalSupplyFormatted"]),
+        circulating_supply: None,
+        fdv: parse_f64(&pair["fdv"]),
+        liquidity_usd: parse_f64(&pair["pairReserves0Usd"]) + parse_f64(&pair["pairReserves1Usd"]),
+        volume_24h: parse_f64(&scanner_response["pairStats"]["twentyFourHour"]["volume"]),
+        buy_volume_1h: parse_f64(&scanner_response["pairStats"]["oneHour"]["buyVolume"]),
+        price_change_1h: change("oneHour"),
+        price_change_24h: change("twentyFourHour"),
+        buys_1h: scanner_response["pairStats"]["oneHour"]["buys"].as_i64().unwrap_or(0),
+        sells_1h: scanner_response["pairStats"]["oneHour"]["sells"].as_i64().unwrap_or(0),
+        created_at: parse_datetime(&pair["pairCreatedAt"]),
+        twitter: link("linkTwitter"),
+        website: link("linkWebsite"),
+        telegram: link("linkTelegram"),
+        lp_burned_percent: (burned_supply > 0.0).then(|| parse_f64(&pair["burnedAmount"]) / burned_supply * 100.0),
+        lp_locked_percent: Some(parse_f64(&pair["totalLockedRatio"]) * 100.0),
+    }
+}
+
+/// Parse the response of the dexcelerate holders
+///
+/// # Arguments
+///
+/// * `holders_response` - The response from the API call to get the holders
+///
+/// # Returns
+///
+/// The holders, largest first
+pub fn holders_from_response(holders_response: &Value) -> Result<Holders> {
+    let holders = holders_response["holders"].as_array()
+        .ok_or(anyhow::anyhow!("No holders in the response"))?
+        .iter()
+        .map(|holder| Holder {
+            address: holder["holderAddress"].as_str().unwrap_or_default().to_string(),
+            percent: parse_f64(&holder["percent"]) * 100.0,
+        })
+        .collect();
+    Ok(Holders { holders })
+}
+
+/// Parse an RFC 3339 date the API sends as a string
+fn parse_datetime(value: &Value) -> Option<DateTime<Utc>> {
+    value.as_str()
+        .and_then(|datetime| DateTime::parse_from_rfc3339(datetime).ok())
+        .map(|datetime| datetime.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn scanner_response() -> Value {
+        json!({
+            "pair": {
+                "token1Name": "Bonk",
+                "token1Symbol": "Bonk",
+                "pairPrice1Usd": "0.00002",
+                "token1TotalSupplyFormatted": "1000000",
+                "fdv": "20",
+                "pairReserves0Usd": "100.5",
+                "pairReserves1Usd": "99.5",
+                "pairCreatedAt": "2024-11-01T12:00:00Z",
+                "linkTwitter": "https://x.com/bonk_inu",
+                "linkWebsite": "",
+                "burnedSupply": "200",
+                "burnedAmount": "150",
+                "totalLockedRatio": "0.5"
+            },
+            "pairStats": {
+                "oneHour": { "first": "0.00001", "last": "0.00002", "buys": 12, "sells": 7, "buyVolume": "1500.25" },
+                "twentyFourHour": { "first": "0.00004", "last": "0.00002", "volume": "98000" }
+            }
+        })
+    }
+
+    #[test]
+    fn the_scanner_response_is_parsed_into_pair_stats() {
+        let token_pair = TokenPair { pair_address: "Pair111".to_string(), token_address: "Token111".to_string(), chain: "solana".to_string() };
+        let pair_stats = pair_stats_from_scanner(token_pair.clone(), &scanner_response());
+        assert_eq!(pair_stats.pair, token_pair);
+        assert_eq!(pair_stats.symbol, "Bonk");
+        assert_eq!(pair_stats.price_usd, 0.00002);
+        assert_eq!(pair_stats.total_supply, 1000000.0);
+        // The scanner only has the total supply
+        assert_eq!(pair_stats.circulating_supply, None);
+        assert_eq!(pair_stats.market_cap(), 20.0);
+        assert_eq!(pair_stats.liquidity_usd, 200.0);
+        assert_eq!(pair_stats.volume_24h, 98000.0);
+        assert_eq!(pair_stats.buy_volume_1h, 1500.25);
+        assert_eq!(pair_stats.price_change_1h, 100.0);
+        assert_eq!(pair_stats.price_change_24h, -50.0);
+        assert_eq!((pair_stats.buys_1h, pair_stats.sells_1h), (12, 7));
+        assert_eq!(pair_stats.created_at.map(|created_at| created_at.to_rfc3339()), Some("2024-11-01T12:00:00+00:00".to_string()));
+        assert_eq!(pair_stats.twitter.as_deref(), Some("https://x.com/bonk_inu"));
+        // Empty links are no links
+        assert_eq!(pair_stats.website, None);
+        assert_eq!(pair_stats.telegram, None);
+        assert_eq!(pair_stats.lp_burned_percent, Some(75.0));
+        assert_eq!(pair_stats.lp_locked_percent, Some(50.0));
+    }
+
+    #[test]
+    fn a_missing_scanner_response_gives_empty_stats() {
+        let pair_stats = pair_stats_from_scanner(TokenPair::default(), &Value::Null);
+        assert_eq!(pair_stats.name, "N/A");
+        assert_eq!(pair_stats.price_usd, 0.0);
+        assert_eq!(pair_stats.price_change_1h, 0.0);
+        assert_eq!(pair_stats.created_at, None);
+        // Without an LP supply nothing can be burned
+        assert_eq!(pair_stats.lp_burned_percent, None);
+    }
+
+    #[test]
+    fn the_holders_percents_are_turned_into_percentages() {
+        let holders_response = json!({
+            "holders": [
+                { "holderAddress": "Pool111", "percent": "0.5" },
+                { "holderAddress": "Whale111", "percent": "0.125" },
+                { "holderAddress": "Holder111", "percent": 0.05 }
+            ]
+        });
+        let holders = holders_from_response(&holders_response).unwrap();
+        assert_eq!(holders.holders[0], Holder { address: "Pool111".to_string(), percent: 50.0 });
+        assert_eq!(holders.holders[1].percent, 12.5);
+        assert_eq!(holders.top_percent(10), 17.5);
+    }
+
+    #[test]
+    fn a_response_without_holders_is_an_error() {
+        assert!(holders_from_response(&json!({ "error": "Not found" })).is_err());
+    }
+}
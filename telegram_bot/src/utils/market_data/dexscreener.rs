@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::time::Duration;
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::Client;
+use serde_json::Value;
+use super::cache::{RateLimiter, TtlCache};
+use super::{parse_f64, Ath, Holders, MarketData, PairStats, TokenPair};
+
+/// Tokens the DexScreener API accepts per request
+const TOKENS_PER_REQUEST: usize = 30;
+
+/// The DexScreener API allows 300 requests per minute
+static RATE_LIMIT: LazyLock<RateLimiter> = LazyLock::new(|| RateLimiter::per_second(5));
+static PAIRS_CACHE: LazyLock<TtlCache<Vec<Value>>> = LazyLock::new(|| TtlCache::new(Duration::from_secs(10)));
+
+/// Market data from the public DexScreener API
+///
+/// DexScreener has no ATH nor holders, only the pairs and their prices.
+pub struct DexScreener;
+
+#[async_trait]
+impl MarketData for DexScreener {
+    fn name(&self) -> &'static str {
+        "dexscreener"
+    }
+
+    async fn token_pair(&self, address: &str) -> Result<TokenPair> {
+        Ok(self.pair_stats(address).await?.pair)
+    }
+
+    async fn pair_stats(&self, address: &str) -> Result<PairStats> {
+        let mut pairs = get_token_pairs(&[address.to_string()]).await?;
+        // The address may be a pair address rather than a token address
+        if pairs.is_empty() {
+            pairs = search_pairs(address).await?;
+        }
+        let pair = pairs.iter()
+            .filter(|pair| pair["baseToken"]["address"].as_str() == Some(address) || pair["pairAddress"].as_str() == Some(address))
+            .max_by(|a, b| parse_f64(&a["liquidity"]["usd"]).partial_cmp(&parse_f64(&b["liquidity"]["usd"])).unwrap_or(std::cmp::Ordering::Equal))
+            .ok_or(anyhow::anyhow!("No pair found for {}", address))?;
+        Ok(pair_stats_from_pair(pair))
+    }
+
+    async fn ath(&self, _token_address: &str, _chain: &str, _since: DateTime<Utc>) -> Result<Ath> {
+        Err(anyhow::anyhow!("DexScreener has no ATH"))
+    }
+
+    async fn holders(&self, _token_address: &str) -> Result<Holders> {
+        Err(anyhow::anyhow!("DexScreener has no holders"))
+    }
+
+    async fn prices(&self, token_addresses: &[String]) -> Result<HashMap<String, f64>> {
+        let mut prices: HashMap<String, (f64, f64)> = HashMap::new();
+        for tokens in token_addresses.chunks(TOKENS_PER_REQUEST) {
+            for pair in get_token_pairs(tokens).await? {
+                let token = pair["baseToken"]["address"].as_str().unwrap_or_default().to_string();
+                let liquidity = parse_f64(&pair["liquidity"]["usd"]);
+                let price = parse_f64(&pair["priceUsd"]);
+                // A token trades in several pairs, the most liquid one has the price that matters
+                if tokens.contains(&token) && price > 0.0 && prices.get(&token).map(|(_, best)| liquidity > *best).unwrap_or(true) {
+                    prices.insert(token, (price, liquidity));
+                }
+            }
+        }
+        Ok(prices.into_iter().map(|(token, (price, _))| (token, price)).collect())
+    }
+}
+
+/// Get the pairs of tokens
+///
+/// # Arguments
+///
+/// * `token_addresses` - The token addresses, at most 30
+///
+/// # Returns
+///
+/// The pairs of the tokens
+async fn get_token_pairs(token_addresses: &[String]) -> Result<Vec<Value>> {
+    let url = format!("https://api.dexscreener.com/latest/dex/tokens/{}", token_addresses.join(","));
+    get_pairs(url).await
+}
+
+/// Search the pairs matching a pair address, a token address or a name
+async fn search_pairs(query: &str) -> Result<Vec<Value>> {
+    let url = format!("https://api.dexscreener.com/latest/dex/search?q={}", query);
+    get_pairs(url).await
+}
+
+async fn get_pairs(url: String) -> Result<Vec<Value>> {
+    PAIRS_CACHE.get_or_fetch(&url, || async {
+        RATE_LIMIT.acquire().await;
+        let response = Client::new().get(url.as_str())
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Failed to fetch data: HTTP {}", response.status()));
+        }
+        let json: Value = response.json().await?;
+        Ok(json["pairs"].as_array().cloned().unwrap_or_default())
+    }).await
+}
+
+/// Parse a DexScreener pair
+///
+/// # Description
+///
+/// DexScreener has no supply, the total supply is derived from the FDV and the circulating one
+/// from the market cap. It has neither the buy volume nor the LP burns and locks.
+///
+/// # Arguments
+///
+/// * `pair` - The pair
+///
+/// # Returns
+///
+/// The stats of the pair
+fn pair_stats_from_pair(pair: &Value) -> PairStats {
+    let price_usd = parse_f64(&pair["priceUsd"]);
+    let fdv = parse_f64(&pair["fdv"]);
+    let market_cap = parse_f64(&pair["marketCap"]);
+    let social = |kind: &str| pair["info"]["socials"].as_array()
+        .and_then(|socials| socials.iter().find(|social| social["type"].as_str() == Some(kind)))
+        .and_then(|social| social["url"].as_str())
+        .map(|url| url.to_string());
+    PairStats {
+        pair: TokenPair {
+            pair_address: pair["pairAddress"].as_str().unwrap_or_default().to_string(),
+            token_address: pair["baseToken"]["address"].as_str().unwrap_or_default().to_string(),
+            chain: pair["chainId"].as_str().unwrap_or_default().to_string(),
+        },
+        name: pair["baseToken"]["name"].as_str().unwrap_or("N/A").to_string(),
+        symbol: pair["baseToken"]["symbol"].as_str().unwrap_or("N/A").to_string(),
+        price_usd,
+        total_supply: if price_usd > 0.0 { fdv / price_usd } else { 0.0 },
+        circulating_supply: Some(market_cap / price_usd).filter(|circulating_supply| price_usd > 0.0 && *circulating_supply > 0.0),
+        fdv,
+        liquidity_usd: parse_f64(&pair["liquidity"]["usd"]),
+        volume_24h: parse_f64(&pair["volume"]["h24"]),
+        buy_volume_1h: 0.0,
+        price_change_1h: parse_f64(&pair["priceChange"]["h1"]),
+        price_change_24h: parse_f64(&pair["priceChange"]["h24"]),
+        buys_1h: pair["txns"]["h1"]["buys"].as_i64().unwrap_or(0),
+        sells_1h: pair["txns"]["h1"]["sells"].as_i64().unwrap_or(0),
+        created_at: pair["pairCreatedAt"].as_i64().and_then(|millis| Utc.timestamp_millis_opt(millis).single()),
+        twitter: social("twitter"),
+        website: pair["info"]["websites"].as_array()
+            .and_then(|websites| websites.first())
+            .and_then(|website| website["url"].as_str())
+            .map(|url| url.to_string()),
+        telegram: social("telegram"),
+        lp_burned_percent: None,
+        lp_locked_percent: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn the_pair_is_parsed_into_pair_stats() {
+        let pair = json!({
+            "chainId": "solana",
+            "pairAddress": "Pair111",
+            "baseToken": { "address": "Token111", "name": "Bonk", "symbol": "Bonk" },
+            "priceUsd": "0.5",
+            "fdv": 20,
+            "marketCap": 10,
+            "liquidity": { "usd": 200.5 },
+            "volume": { "h24": 98000 },
+            "priceChange": { "h1": 12.5, "h24": -50 },
+            "txns": { "h1": { "buys": 12, "sells": 7 } },
+            "pairCreatedAt": 1730462400000i64,
+            "info": {
+                "websites": [{ "label": "Website", "url": "https://bonkcoin.com" }],
+                "socials": [{ "type": "twitter", "url": "https://x.com/bonk_inu" }]
+            }
+        });
+        let pair_stats = pair_stats_from_pair(&pair);
+        assert_eq!(pair_stats.pair, TokenPair { pair_address: "Pair111".to_string(), token_address: "Token111".to_string(), chain: "solana".to_string() });
+        assert_eq!(pair_stats.symbol, "Bonk");
+        assert_eq!(pair_stats.price_usd, 0.5);
+        // The supply is derived from the FDV
+        assert_eq!(pair_stats.total_supply, 40.0);
+        assert_eq!(pair_stats.circulating_supply, Some(20.0));
+        assert_eq!(pair_stats.liquidity_usd, 200.5);
+        assert_eq!(pair_stats.volume_24h, 98000.0);
+        assert_eq!((pair_stats.price_change_1h, pair_stats.price_change_24h), (12.5, -50.0));
+        assert_eq!((pair_stats.buys_1h, pair_stats.sells_1h), (12, 7));
+        assert_eq!(pair_stats.created_at.map(|created_at| created_at.to_rfc3339()), Some("2024-11-01T12:00:00+00:00".to_string()));
+        assert_eq!(pair_stats.website.as_deref(), Some("https://bonkcoin.com"));
+        assert_eq!(pair_stats.twitter.as_deref(), Some("https://x.com/bonk_inu"));
+        assert_eq!(pair_stats.telegram, None);
+        assert_eq!((pair_stats.lp_burned_percent, pair_stats.lp_locked_percent), (None, None));
+    }
+
+    #[test]
+    fn a_pair_without_a_price_has_no_supply() {
+        let pair_stats = pair_stats_from_pair(&json!({ "fdv": 20 }));
+        assert_eq!(pair_stats.price_usd, 0.0);
+        assert_eq!(pair_stats.total_supply, 0.0);
+        assert_eq!(pair_stats.circulating_supply, None);
+        assert_eq!(pair_stats.name, "N/A");
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use super::{Ath, Holders, MarketData, PairStats, TokenPair};
+
+/// Market data of a token, as stored in a fixture file
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenFixture {
+    pub pair_stats: PairStats,
+    pub ath: Option<Ath>,
+    pub holders: Option<Holders>,
+}
+
+/// Fake market data served from JSON fixtures, without calling any API
+///
+/// Each token has a `<token address>.json` file in the directory holding a [`TokenFixture`],
+/// the tokens without a file are unknown.
+pub struct Fixtures {
+    dir: PathBuf,
+}
+
+impl Fixtures {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Load the fixture of a token, or of the token of a pair
+    fn load(&self, address: &str) -> Result<TokenFixture> {
+        let path = self.dir.join(format!("{}.json", address));
+        if path.exists() {
+            return Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?);
+        }
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+                continue;
+            }
+            let fixture: TokenFixture = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+            if fixture.pair_stats.pair.pair_address == address {
+                return Ok(fixture);
+            }
+        }
+        Err(anyhow::anyhow!("No fixture for {} in {}", address, self.dir.display()))
+    }
+}
+
+#[async_trait]
+impl MarketData for Fixtures {
+    fn name(&self) -> &'static str {
+        "fixtures"
+    }
+
+    async fn token_pair(&self, address: &str) -> Result<TokenPair> {
+        Ok(self.load(address)?.pair_stats.pair)
+    }
+
+    async fn pair_stats(&self, address: &str) -> Result<PairStats> {
+        Ok(self.load(address)?.pair_stats)
+    }
+
+    async fn ath(&self, token_address: &str, _chain: &str, _since: DateTime<Utc>) -> Result<Ath> {
+        self.load(token_address)?.ath.ok_or(anyhow::anyhow!("No ATH fixture for {}", token_address))
+    }
+
+    async fn holders(&self, token_address: &str) -> Result<Holders> {
+        self.load(token_address)?.holders.ok_or(anyhow::anyhow!("No holders fixture for {}", token_address))
+    }
+
+    async fn prices(&self, token_addresses: &[String]) -> Result<HashMap<String, f64>> {
+        Ok(token_addresses.iter()
+            .filter_map(|token| self.load(token).ok().map(|fixture| (token.clone(), fixture.pair_stats.price_usd)))
+            .collect())
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+pub mod cache;
+pub mod dexcelerate;
+pub mod dexscreener;
+pub mod fixtures;
+pub mod raydium;
+
+pub use dexcelerate::Dexcelerate;
+pub use dexscreener::DexScreener;
+pub use fixtures::Fixtures;
+
+/// The main pair of a token
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenPair {
+    pub pair_address: String,
+    pub token_address: String,
+    pub chain: String, // eg "solana" or "ethereum"
+}
+
+/// Market stats of the main pair of a token
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PairStats {
+    pub pair: TokenPair,
+    pub name: String,
+    pub symbol: String,
+    pub price_usd: f64,
+    pub total_supply: f64, // In ui amount
+    pub circulating_supply: Option<f64>, // In ui amount, None when the provider doesn't know it
+    pub fdv: f64,
+    pub liquidity_usd: f64,
+    pub volume_24h: f64,
+    #[serde(default)]
+    pub buy_volume_1h: f64,
+    pub price_change_1h: f64, // In percent
+    pub price_change_24h: f64, // In percent
+    pub buys_1h: i64,
+    pub sells_1h: i64,
+    pub created_at: Option<DateTime<Utc>>,
+    pub twitter: Option<String>,
+    pub website: Option<String>,
+    pub telegram: Option<String>,
+    pub lp_burned_percent: Option<f64>, // None when the pair has no LP supply or it is unknown
+    pub lp_locked_percent: Option<f64>, // None when unknown
+}
+
+impl PairStats {
+    /// Market cap of the token, the supply at the current price
+    pub fn market_cap(&self) -> f64 {
+        self.total_supply * self.price_usd
+    }
+}
+
+/// All time high price of a token
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Ath {
+    pub price: f64,
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+/// A holder of a token
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Holder {
+    pub address: String,
+    pub percent: f64, // Share of the supply, in percent
+}
+
+/// Holders of a token, largest first
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Holders {
+    pub holders: Vec<Holder>,
+}
+
+impl Holders {
+    /// Share of the supply held by the top holders, the first holder is the pool and is skipped
+    pub fn top_percent(&self, count: usize) -> f64 {
+        self.holders.iter().skip(1).take(count).map(|holder| holder.percent).sum()
+    }
+}
+
+/// A source of market data
+///
+/// Every provider doesn't have every endpoint, the ones it lacks return an error so the
+/// [`Failover`] moves on to the next provider.
+#[async_trait]
+pub trait MarketData: Send + Sync {
+    /// Name of the provider, for the logs
+    fn name(&self) -> &'static str;
+
+    /// Get the main pair of a token from a token or pair address
+    async fn token_pair(&self, address: &str) -> Result<TokenPair>;
+
+    /// Get the market stats of the main pair of a token
+    async fn pair_stats(&self, address: &str) -> Result<PairStats>;
+
+    /// Get the all time high price of a token since a date
+    async fn ath(&self, token_address: &str, chain: &str, since: DateTime<Utc>) -> Result<Ath>;
+
+    /// Get the holders of a token
+    async fn holders(&self, token_address: &str) -> Result<Holders>;
+
+    /// Get the USD prices of tokens, the tokens without a price are left out
+    async fn prices(&self, token_addresses: &[String]) -> Result<HashMap<String, f64>>;
+
+    /// Get the circulating supply of a token, in ui amount
+    async fn circulating_supply(&self, token_address: &str) -> Result<f64> {
+        self.pair_stats(token_address).await?.circulating_supply
+            .filter(|circulating_supply| *circulating_supply > 0.0)
+            .ok_or(anyhow::anyhow!("No circulating supply for {}", token_address))
+    }
+}
+
+/// Market data asking each provider in turn until one answers
+pub struct Failover {
+    providers: Vec<Box<dyn MarketData>>,
+}
+
+impl Failover {
+    pub fn new(providers: Vec<Box<dyn MarketData>>) -> Self {
+        Self { providers }
+    }
+}
+
+/// Ask each provider of a failover in turn and return the first answer
+macro_rules! first_answer {
+    ($failover:expr, $method:literal, |$provider:ident| $call:expr) => {{
+        let mut last_error = anyhow::anyhow!("No market data provider");
+        for $provider in $failover.providers.iter() {
+            match $call.await {
+                Ok(answer) => return Ok(answer),
+                Err(e) => {
+                    eprintln!("@market_data/ {} {} failed: {:?}", $provider.name(), $method, e);
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }};
+}
+
+#[async_trait]
+impl MarketData for Failover {
+    fn name(&self) -> &'static str {
+        "failover"
+    }
+
+    async fn token_pair(&self, address: &str) -> Result<TokenPair> {
+        first_answer!(self, "token_pair", |provider| provider.token_pair(address))
+    }
+
+    async fn pair_stats(&self, address: &str) -> Result<PairStats> {
+        first_answer!(self, "pair_stats", |provider| provider.pair_stats(address))
+    }
+
+    async fn ath(&self, token_address: &str, chain: &str, since: DateTime<Utc>) -> Result<Ath> {
+        first_answer!(self, "ath", |provider| provider.ath(token_address, chain, since))
+    }
+
+    async fn holders(&self, token_address: &str) -> Result<Holders> {
+        first_answer!(self, "holders", |provider| provider.holders(token_address))
+    }
+
+    async fn circulating_supply(&self, token_address: &str) -> Result<f64> {
+        first_answer!(self, "circulating_supply", |provider| provider.circulating_supply(token_address))
+    }
+
+    /// The tokens a provider has no price for are asked to the next one
+    async fn prices(&self, token_addresses: &[String]) -> Result<HashMap<String, f64>> {
+        let mut prices = HashMap::new();
+        let mut missing: Vec<String> = token_addresses.to_vec();
+        for provider in self.providers.iter() {
+            if missing.is_empty() {
+                break;
+            }
+            match provider.prices(&missing).await {
+                Ok(provider_prices) => prices.extend(provider_prices),
+                Err(e) => eprintln!("@market_data/ {} prices failed: {:?}", provider.name(), e),
+            }
+            missing.retain(|token| !prices.contains_key(token));
+        }
+        Ok(prices)
+    }
+}
+
+static MARKET_DATA: OnceLock<Box<dyn MarketData>> = OnceLock::new();
+
+/// Get the market data of the bot
+///
+/// # Description
+///
+/// Built once from the MARKET_DATA env var, a comma separated list of providers tried in order.
+/// The default is "dexcelerate,dexscreener", "fixtures:<dir>" serves the JSON fixtures of a
+/// directory instead of calling any API.
+///
+/// # Returns
+///
+/// The market data
+pub fn market_data() -> &'static dyn MarketData {
+    MARKET_DATA.get_or_init(|| {
+        let config = std::env::var("MARKET_DATA").unwrap_or("dexcelerate,dexscreener".to_string());
+        println!("@market_data/ providers: {}", config);
+        let providers: Vec<Box<dyn MarketData>> = config.split(",")
+            .map(|provider| provider.trim())
+            .filter_map(|provider| -> Option<Box<dyn MarketData>> {
+                match provider {
+                    "dexcelerate" => Some(Box::new(Dexcelerate)),
+                    "dexscreener" => Some(Box::new(DexScreener)),
+                    _ if provider.starts_with("fixtures:") => Some(Box::new(Fixtures::new(provider.trim_start_matches("fixtures:")))),
+                    _ => {
+                        eprintln!("@market_data/ unknown provider {}", provider);
+                        None
+                    }
+                }
+            })
+            .collect();
+        Box::new(Failover::new(providers))
+    }).as_ref()
+}
+
+/// Parse a number the APIs send as a string, 0 when missing
+pub(crate) fn parse_f64(value: &serde_json::Value) -> f64 {
+    match value {
+        serde_json::Value::String(value) => value.parse::<f64>().unwrap_or(0.0),
+        value => value.as_f64().unwrap_or(0.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const BONK: &str = "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263";
+
+    /// The fixtures shipped with the bot
+    fn shipped_fixtures() -> Fixtures {
+        Fixtures::new(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/market_data"))
+    }
+
+    /// A fixtures directory of its own for a test, with one token at each price
+    fn fixtures_with_prices(name: &str, prices: &[(&str, f64)]) -> Fixtures {
+        let dir = std::env::temp_dir().join(format!("market_data_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (token, price) in prices {
+            let mut pair_stats = PairStats { price_usd: *price, ..Default::default() };
+            pair_stats.pair.token_address = token.to_string();
+            pair_stats.pair.pair_address = format!("{}-pair", token);
+            let fixture = serde_json::json!({ "pair_stats": pair_stats, "ath": null, "holders": null });
+            std::fs::write(dir.join(format!("{}.json", token)), fixture.to_string()).unwrap();
+        }
+        Fixtures::new(dir)
+    }
+
+    #[tokio::test]
+    async fn the_first_provider_that_answers_wins() {
+        let failover = Failover::new(vec![
+            Box::new(Fixtures::new("/nonexistent")),
+            Box::new(fixtures_with_prices("first_answer_a", &[("TokenA", 1.0)])),
+            Box::new(fixtures_with_prices("first_answer_b", &[("TokenA", 2.0)])),
+        ]);
+        assert_eq!(failover.pair_stats("TokenA").await.unwrap().price_usd, 1.0);
+        // A pair address finds its token too
+        assert_eq!(failover.token_pair("TokenA-pair").await.unwrap().token_address, "TokenA");
+    }
+
+    #[tokio::test]
+    async fn the_last_error_is_returned_when_no_provider_answers() {
+        let failover = Failover::new(vec![Box::new(shipped_fixtures())]);
+        assert!(failover.pair_stats("Unknown111").await.is_err());
+        assert!(Failover::new(vec![]).holders(BONK).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn missing_prices_are_asked_to_the_next_provider() {
+        let failover = Failover::new(vec![
+            Box::new(fixtures_with_prices("prices_a", &[("TokenA", 1.0)])),
+            Box::new(fixtures_with_prices("prices_b", &[("TokenA", 5.0), ("TokenB", 2.0)])),
+        ]);
+        let prices = failover.prices(&["TokenA".to_string(), "TokenB".to_string(), "TokenC".to_string()]).await.unwrap();
+        assert_eq!(prices, HashMap::from([("TokenA".to_string(), 1.0), ("TokenB".to_string(), 2.0)]));
+    }
+
+    #[tokio::test]
+    async fn the_circulating_supply_comes_from_the_first_provider_that_knows_it() {
+        let without_supply = fixtures_with_prices("supply_a", &[("TokenA", 1.0)]);
+        let failover = Failover::new(vec![Box::new(without_supply), Box::new(shipped_fixtures())]);
+        assert!(failover.circulating_supply("TokenA").await.is_err());
+        let bonk = shipped_fixtures().pair_stats(BONK).await.unwrap();
+        assert_eq!(failover.circulating_supply(BONK).await.unwrap(), bonk.circulating_supply.unwrap());
+        assert!(bonk.circulating_supply.unwrap() < bonk.total_supply);
+    }
+
+    #[tokio::test]
+    async fn the_shipped_fixture_serves_every_endpoint() {
+        let fixtures = shipped_fixtures();
+        let pair_stats = fixtures.pair_stats(BONK).await.unwrap();
+        assert_eq!(pair_stats.symbol, "Bonk");
+        assert_eq!(pair_stats.market_cap(), pair_stats.total_supply * pair_stats.price_usd);
+        assert_eq!(fixtures.token_pair(&pair_stats.pair.pair_address).await.unwrap(), pair_stats.pair);
+        assert!(fixtures.ath(BONK, "solana", DateTime::<Utc>::default()).await.unwrap().price > pair_stats.price_usd);
+        let holders = fixtures.holders(BONK).await.unwrap();
+        assert_eq!(holders.top_percent(10), 6.0);
+    }
+
+    #[test]
+    fn numbers_are_parsed_from_strings_and_numbers() {
+        assert_eq!(parse_f64(&serde_json::json!("1.5")), 1.5);
+        assert_eq!(parse_f64(&serde_json::json!(2.5)), 2.5);
+        assert_eq!(parse_f64(&serde_json::json!("N/A")), 0.0);
+        assert_eq!(parse_f64(&serde_json::Value::Null), 0.0);
+    }
+}
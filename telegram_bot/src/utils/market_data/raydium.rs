@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use anyhow::Result;
+use reqwest::Client;
+use serde_json::Value;
+use super::cache::RateLimiter;
+
+/// Requests per second allowed to the Raydium API
+static RATE_LIMIT: LazyLock<RateLimiter> = LazyLock::new(|| RateLimiter::per_second(5));
+/// Mints asked to the Raydium API per request
+const MINTS_PER_REQUEST: usize = 50;
+
+/// Check if a token is a token with liquidity on Raydium
+/// 
+/// # Arguments
+/// 
+/// * `token_address` - The token address
+/// 
+/// # Returns
+/// 
+/// A Vec<String> representing the Raydium tokens
+pub async fn check_raydiums_tokens(token_address: Vec<String>) -> Result<Vec<String>> {
+    let prices = check_raydium_tokens_prices(token_address).await?;
+    Ok(prices.into_keys().collect())
+}
+
+/// Check the Raydium tokens prices
+/// 
+/// # Description
+/// 
+/// The tokens are asked in batches of 50 mints per request.
+/// 
+/// # Arguments
+/// 
+/// * `token_addresses` - The token addresses
+/// 
+/// # Returns
+/// 
+/// A HashMap<String, String> representing the token prices
+pub async fn check_raydium_tokens_prices(token_addresses: Vec<String>) -> Result<HashMap<String, String>> {
+    let mut prices = HashMap::new();
+    for tokens in token_addresses.chunks(MINTS_PER_REQUEST) {
+        RATE_LIMIT.acquire().await;
+        let tokens_str = tokens.join(",");
+        let url = format!("https://api-v3.raydium.io/mint/price?mints={tokens_str}");
+
+        // Send the request
+        let response = Client::new().get(url)
+            .send()
+            .await?;
+
+        // Check if the response status is success
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Failed to fetch data: HTTP {}", response.status()));
+        }
+
+        // Parse the response
+        let response_body: Value = response.json().await?;
+        let data = response_body["data"].as_object().ok_or(anyhow::anyhow!("No prices in the Raydium response"))?;
+
+        // Add the response data to the prices, filtering out null values
+        prices.extend(data.iter()
+            .filter_map(|(key, value)| {
+                value.as_str().map(|v| (key.clone(), v.to_string()))
+            }));
+    }
+
+    Ok(prices)
+}
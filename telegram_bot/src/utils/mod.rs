@@ -1 +1,6 @@
-pub mod helpers;
\ No newline at end of file
+pub mod helpers;
+pub mod ladder;
+pub mod market_data;
+pub mod pnl_card;
+pub mod price_service;
+pub mod safety;
\ No newline at end of file
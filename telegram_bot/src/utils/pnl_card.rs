@@ -0,0 +1,101 @@
+use ab_glyph::{FontRef, PxScale};
+use anyhow::Result;
+use image::{ImageFormat, Rgba, RgbaImage};
+use imageproc::drawing::{draw_filled_rect_mut, draw_text_mut, text_size};
+use imageproc::rect::Rect;
+use std::io::Cursor;
+
+const CARD_WIDTH: u32 = 1200;
+const CARD_HEIGHT: u32 = 630;
+const MARGIN: i32 = 64;
+
+const FONT_BOLD: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans-Bold.ttf");
+const FONT_REGULAR: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+
+const BACKGROUND_TOP: [u8; 3] = [16, 14, 32];
+const BACKGROUND_BOTTOM: [u8; 3] = [40, 20, 64];
+const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const GREY: Rgba<u8> = Rgba([160, 160, 185, 255]);
+const PURPLE: Rgba<u8> = Rgba([153, 69, 255, 255]);
+const GREEN: Rgba<u8> = Rgba([20, 241, 149, 255]);
+const RED: Rgba<u8> = Rgba([255, 77, 109, 255]);
+
+/// Data shown on a PnL card, for a position or a call
+#[derive(Debug, Clone)]
+pub struct PnlCard {
+    pub symbol: String,
+    pub title: String, // What the card is about, eg "Position" or "Call"
+    pub entry_label: String, // eg "Entry" or "Called at"
+    pub entry: String,
+    pub exit_label: String, // eg "Exit", "Current" or "Now"
+    pub exit: String,
+    pub percent: f64,
+    pub username: String,
+    pub referral_link: Option<String>,
+}
+
+impl PnlCard {
+    /// Multiplier matching the percentage, 1 + percent / 100
+    pub fn multiplier(&self) -> f64 {
+        (1.0 + self.percent / 100.0).max(0.0)
+    }
+}
+
+/// Render a PnL card as a PNG
+///
+/// The card is a 1200x630 image, the size Telegram previews without cropping, with the token
+/// symbol, the percentage and multiplier in green or red, the entry and exit and the user
+/// referral link at the bottom.
+pub fn render_pnl_card(card: &PnlCard) -> Result<Vec<u8>> {
+    let bold = FontRef::try_from_slice(FONT_BOLD)?;
+    let regular = FontRef::try_from_slice(FONT_REGULAR)?;
+    let mut image = RgbaImage::new(CARD_WIDTH, CARD_HEIGHT);
+
+    // Vertical gradient background
+    for (_, y, pixel) in image.enumerate_pixels_mut() {
+        let t = y as f32 / CARD_HEIGHT as f32;
+        let channel = |i: usize| (BACKGROUND_TOP[i] as f32 * (1.0 - t) + BACKGROUND_BOTTOM[i] as f32 * t) as u8;
+        *pixel = Rgba([channel(0), channel(1), channel(2), 255]);
+    }
+    let accent = if card.percent >= 0.0 { GREEN } else { RED };
+    draw_filled_rect_mut(&mut image, Rect::at(0, 0).of_size(CARD_WIDTH, 10), PURPLE);
+    draw_filled_rect_mut(&mut image, Rect::at(MARGIN, 250).of_size(8, 150), accent);
+
+    // Header
+    draw_text_mut(&mut image, PURPLE, MARGIN, 48, PxScale::from(40.0), &bold, "DEXCELERATE");
+    let title_scale = PxScale::from(32.0);
+    let (title_width, _) = text_size(title_scale, &regular, &card.title);
+    draw_text_mut(&mut image, GREY, CARD_WIDTH as i32 - MARGIN - title_width as i32, 54, title_scale, &regular, &card.title);
+
+    // Token and result
+    draw_text_mut(&mut image, WHITE, MARGIN, 130, PxScale::from(80.0), &bold, &format!("${}", card.symbol.to_uppercase()));
+    // The percentage shrinks to stay clear of the entry / exit column
+    let column = CARD_WIDTH as i32 / 2 + 80;
+    let percent_text = format!("{:+.2}%", card.percent);
+    let max_percent_width = (column - MARGIN - 36 - 24) as f32;
+    let (percent_width, _) = text_size(PxScale::from(110.0), &bold, &percent_text);
+    let percent_size = 110.0 * (max_percent_width / percent_width.max(1) as f32).min(1.0);
+    draw_text_mut(&mut image, accent, MARGIN + 36, 250 + (110.0 - percent_size) as i32 / 2, PxScale::from(percent_size), &bold, &percent_text);
+    draw_text_mut(&mut image, WHITE, MARGIN + 36, 370, PxScale::from(44.0), &regular, &format!("{:.2}x", card.multiplier()));
+
+    // Entry and exit
+    let label_scale = PxScale::from(28.0);
+    let value_scale = PxScale::from(40.0);
+    draw_text_mut(&mut image, GREY, column, 260, label_scale, &regular, &card.entry_label);
+    draw_text_mut(&mut image, WHITE, column, 295, value_scale, &bold, &card.entry);
+    draw_text_mut(&mut image, GREY, column, 360, label_scale, &regular, &card.exit_label);
+    draw_text_mut(&mut image, WHITE, column, 395, value_scale, &bold, &card.exit);
+
+    // Footer
+    draw_filled_rect_mut(&mut image, Rect::at(MARGIN, 500).of_size(CARD_WIDTH - 2 * MARGIN as u32, 2), GREY);
+    draw_text_mut(&mut image, WHITE, MARGIN, 530, PxScale::from(34.0), &bold, &format!("@{}", card.username));
+    if let Some(referral_link) = &card.referral_link {
+        let link_scale = PxScale::from(26.0);
+        let (link_width, _) = text_size(link_scale, &regular, referral_link);
+        draw_text_mut(&mut image, GREY, CARD_WIDTH as i32 - MARGIN - link_width as i32, 536, link_scale, &regular, referral_link);
+    }
+
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
@@ -0,0 +1,308 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use futures_util::stream::StreamExt;
+use futures_util::SinkExt;
+use tokio::sync::{mpsc, RwLock};
+use tokio_tungstenite::connect_async;
+use tungstenite::Message as WsMessage;
+use crate::db::{self, SafePool};
+use crate::utils::market_data::market_data;
+use crate::utils::helpers::{check_raydiums_tokens, parse_pump_trade, sol_to_usd, CALL_MILESTONES_DAYS, MAX_CALL_MILESTONES_TOKENS};
+
+const PUMPPORTAL_URL: &str = "wss://pumpportal.fun/api/data";
+/// Delay before reconnecting to pumpportal after the connection failed or closed
+const PUMPPORTAL_RECONNECT_DELAY: tokio::time::Duration = tokio::time::Duration::from_secs(5);
+
+#[derive(Debug, serde::Serialize)]
+pub struct PumpPayload {
+    method: String,
+    keys: Vec<String>,
+}
+
+/// A token price change pushed by the price service
+#[derive(Debug, Clone)]
+pub struct PriceUpdate {
+    pub token_address: String,
+    pub price: f64,
+}
+
+/// Price service
+///
+/// # Description
+///
+/// Tracks the prices of the tokens the bot cares about. Pump.fun tokens are priced from the
+/// pumpportal trade stream, tokens with Raydium liquidity are polled in a single batch request.
+/// Subscriptions are only sent for tokens that start or stop being tracked, prices are cached
+/// and every price change is pushed through a channel.
+#[derive(Clone)]
+pub struct PriceService {
+    prices: Arc<RwLock<HashMap<String, f64>>>,
+    pump_tokens: Arc<RwLock<HashSet<String>>>,
+    raydium_tokens: Arc<RwLock<HashSet<String>>>,
+    sol_price: Arc<RwLock<f64>>,
+    updates: mpsc::UnboundedSender<PriceUpdate>,
+}
+
+impl PriceService {
+    /// Start the price service
+    ///
+    /// # Arguments
+    ///
+    /// * `pool` - The database pool
+    ///
+    /// # Returns
+    ///
+    /// The price service and the receiver of the price changes
+    pub async fn start(pool: SafePool) -> (PriceService, mpsc::UnboundedReceiver<PriceUpdate>) {
+        let (updates, updates_rx) = mpsc::unbounded_channel();
+        let service = PriceService {
+            prices: Arc::new(RwLock::new(HashMap::new())),
+            pump_tokens: Arc::new(RwLock::new(HashSet::new())),
+            raydium_tokens: Arc::new(RwLock::new(HashSet::new())),
+            sol_price: Arc::new(RwLock::new(0.0)),
+            updates,
+        };
+        let (subscriptions, subscriptions_rx) = mpsc::unbounded_channel::<PumpPayload>();
+
+        // SOL price, needed to convert the bonding curve prices to USD
+        let sol_price_service = service.clone();
+        tokio::spawn(async move {
+            loop {
+                match sol_to_usd(1.0).await {
+                    Ok(price) if price > 0.0 => *sol_price_service.sol_price.write().await = price,
+                    Ok(_) => eprintln!("@price_service/ SOL price not found"),
+                    Err(e) => eprintln!("@price_service/ error fetching SOL price: {:?}", e),
+                }
+                tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+            }
+        });
+
+        // Pump.fun trade stream
+        let pump_service = service.clone();
+        tokio::spawn(async move {
+            pump_service.run_pumpportal(subscriptions_rx).await;
+        });
+
+        // Tracked tokens and pumpportal subscriptions
+        let tracker_service = service.clone();
+        tokio::spawn(async move {
+            loop {
+                match tracker_service.refresh_tracked_tokens(&pool).await {
+                    Ok((subscribe, unsubscribe)) => {
+                        for (method, keys) in [("subscribeTokenTrade", subscribe), ("unsubscribeTokenTrade", unsubscribe)] {
+                            if keys.is_empty() {
+                                continue;
+                            }
+                            println!("@price_service/ {method}: {:?}", keys);
+                            let pump_payload = PumpPayload {
+                                method: method.to_string(),
+                                keys,
+                            };
+                            if let Err(e) = subscriptions.send(pump_payload) {
+                                eprintln!("@price_service/ error queuing {method}: {:?}", e);
+                            }
+                        }
+                    }
+                    Err(e) => eprintln!("@price_service/ error refreshing tracked tokens: {:?}", e),
+                }
+                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+            }
+        });
+
+        // Raydium prices
+        let raydium_service = service.clone();
+        tokio::spawn(async move {
+            loop {
+                let raydium_tokens: Vec<String> = raydium_service.raydium_tokens.read().await.iter().cloned().collect();
+                if !raydium_tokens.is_empty() {
+                    match market_data().prices(&raydium_tokens).await {
+                        Ok(prices) => {
+                            for (token, price) in prices {
+                                raydium_service.publish(token, price).await;
+                            }
+                        }
+                        Err(e) => eprintln!("@price_service/ error fetching Raydium prices: {:?}", e),
+                    }
+                }
+                tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+            }
+        });
+
+        (service, updates_rx)
+    }
+
+    /// Price the Pump.fun tokens from the pumpportal trade stream
+    ///
+    /// # Description
+    ///
+    /// The subscriptions die with the connection, so whenever it fails or closes the service
+    /// reconnects and subscribes again to every tracked Pump.fun token.
+    ///
+    /// # Arguments
+    ///
+    /// * `subscriptions` - The subscribe and unsubscribe payloads of the tracker
+    async fn run_pumpportal(&self, mut subscriptions: mpsc::UnboundedReceiver<PumpPayload>) {
+        loop {
+            let ws_stream = match connect_async(PUMPPORTAL_URL).await {
+                Ok((ws_stream, _)) => ws_stream,
+                Err(e) => {
+                    eprintln!("@price_service/ error connecting to pumpportal: {:?}", e);
+                    tokio::time::sleep(PUMPPORTAL_RECONNECT_DELAY).await;
+                    continue;
+                }
+            };
+            println!("@price_service/ connected to pumpportal");
+            let (mut pump_write, mut pump_read) = ws_stream.split();
+
+            let pump_tokens: Vec<String> = self.pump_tokens.read().await.iter().cloned().collect();
+            let mut pending = if pump_tokens.is_empty() {
+                None
+            } else {
+                Some(PumpPayload {
+                    method: "subscribeTokenTrade".to_string(),
+                    keys: pump_tokens,
+                })
+            };
+
+            loop {
+                if let Some(pump_payload) = pending.take() {
+                    let payload_json = match serde_json::to_string(&pump_payload) {
+                        Ok(payload_json) => payload_json,
+                        Err(e) => {
+                            eprintln!("@price_service/ error serializing {}: {:?}", pump_payload.method, e);
+                            continue;
+                        }
+                    };
+                    if let Err(e) = pump_write.send(WsMessage::Text(payload_json)).await {
+                        eprintln!("@price_service/ error sending {}: {:?}", pump_payload.method, e);
+                        break;
+                    }
+                }
+                tokio::select! {
+                    msg = pump_read.next() => match msg {
+                        Some(Ok(WsMessage::Text(text))) => {
+                            if let Some((mint, price_sol)) = parse_pump_trade(&text) {
+                                if !self.pump_tokens.read().await.contains(&mint) {
+                                    continue;
+                                }
+                                let sol_price = *self.sol_price.read().await;
+                                if sol_price > 0.0 {
+                                    self.publish(mint, price_sol * sol_price).await;
+                                }
+                            }
+                        }
+                        Some(Ok(_)) => {}
+                        Some(Err(e)) => {
+                            eprintln!("@price_service/ error receiving message: {:?}", e);
+                            break;
+                        }
+                        None => break,
+                    },
+                    pump_payload = subscriptions.recv() => match pump_payload {
+                        Some(pump_payload) => pending = Some(pump_payload),
+                        None => return,
+                    },
+                }
+            }
+            eprintln!("@price_service/ pumpportal connection closed, reconnecting");
+            tokio::time::sleep(PUMPPORTAL_RECONNECT_DELAY).await;
+        }
+    }
+
+    /// Cache a price and push it if it changed
+    ///
+    /// # Arguments
+    ///
+    /// * `token_address` - The token address
+    /// * `price` - The price in USD
+    async fn publish(&self, token_address: String, price: f64) {
+        let changed = {
+            let mut prices = self.prices.write().await;
+            let changed = prices.get(&token_address).map(|cached| *cached != price).unwrap_or(true);
+            prices.insert(token_address.clone(), price);
+            changed
+        };
+        if changed {
+            if let Err(e) = self.updates.send(PriceUpdate { token_address, price }) {
+                eprintln!("@price_service/ error pushing price update: {:?}", e);
+            }
+        }
+    }
+
+    /// Refresh the tracked tokens from the database
+    ///
+    /// # Description
+    ///
+    /// Splits the tracked tokens between Raydium and Pump.fun (tokens graduate from the bonding
+    /// curve to Raydium) and drops the cached prices of the tokens that are no longer tracked.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool` - The database pool
+    ///
+    /// # Returns
+    ///
+    /// The Pump.fun tokens to subscribe and to unsubscribe
+    async fn refresh_tracked_tokens(&self, pool: &SafePool) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+        let tokens: HashSet<String> = get_tracked_tokens(pool).await?.into_iter().collect();
+        let known_raydium_tokens = self.raydium_tokens.read().await.clone();
+
+        // Once a token has Raydium liquidity it stays there, only the others need to be checked
+        let unknown_tokens: Vec<String> = tokens.iter()
+            .filter(|token| !known_raydium_tokens.contains(*token))
+            .cloned()
+            .collect();
+        let new_raydium_tokens: HashSet<String> = if unknown_tokens.is_empty() {
+            HashSet::new()
+        } else {
+            check_raydiums_tokens(unknown_tokens.clone()).await?.into_iter().collect()
+        };
+
+        let raydium_tokens: HashSet<String> = tokens.iter()
+            .filter(|token| known_raydium_tokens.contains(*token) || new_raydium_tokens.contains(*token))
+            .cloned()
+            .collect();
+        let pump_tokens: HashSet<String> = unknown_tokens.into_iter()
+            .filter(|token| !new_raydium_tokens.contains(token))
+            .collect();
+
+        let old_pump_tokens = self.pump_tokens.read().await.clone();
+        let subscribe: Vec<String> = pump_tokens.difference(&old_pump_tokens).cloned().collect();
+        let unsubscribe: Vec<String> = old_pump_tokens.difference(&pump_tokens).cloned().collect();
+
+        *self.pump_tokens.write().await = pump_tokens;
+        *self.raydium_tokens.write().await = raydium_tokens;
+        self.prices.write().await.retain(|token, _| tokens.contains(token));
+
+        Ok((subscribe, unsubscribe))
+    }
+}
+
+/// Get the tokens whose price has to be tracked
+///
+/// # Description
+///
+/// Tokens of open positions, for their take profits and stop losses, tokens with open
+/// limit buy orders and the most recently called tokens, for their milestones.
+///
+/// # Arguments
+///
+/// * `pool` - The database pool
+///
+/// # Returns
+///
+/// A Vec<String> with the token addresses
+async fn get_tracked_tokens(pool: &SafePool) -> anyhow::Result<Vec<String>> {
+    let mut tokens = db::get_open_positions_tokens(pool).await?;
+    for token in db::get_open_limit_orders_tokens(pool).await? {
+        if !tokens.contains(&token) {
+            tokens.push(token);
+        }
+    }
+    for token in db::get_recent_calls_tokens(pool, CALL_MILESTONES_DAYS, MAX_CALL_MILESTONES_TOKENS).await? {
+        if !tokens.contains(&token) {
+            tokens.push(token);
+        }
+    }
+    Ok(tokens)
+}
@@ -0,0 +1,393 @@
+use std::sync::LazyLock;
+use std::time::Duration;
+use anyhow::Result;
+use serde_json::Value;
+use crate::utils::market_data::cache::TtlCache;
+use crate::utils::market_data::{Holders, PairStats};
+
+/// Risk points added for each finding, the total is capped at 100
+const MINT_AUTHORITY_RISK: u32 = 30;
+const FREEZE_AUTHORITY_RISK: u32 = 20;
+const TRANSFER_FEE_RISK: u32 = 20;
+const RISKY_EXTENSION_RISK: u32 = 25;
+const LP_NOT_SECURED_RISK: u32 = 20;
+const TOP_HOLDERS_RISK: u32 = 15;
+const WHALE_HOLDER_RISK: u32 = 10;
+const DEV_HOLDINGS_RISK: u32 = 15;
+
+/// Token-2022 extensions that let the issuer move, freeze or lock the holders tokens
+const RISKY_EXTENSIONS: [(&str, &str); 4] = [
+    ("permanentDelegate", "Permanent delegate can move any holder's tokens"),
+    ("transferHook", "Transfers run a custom program"),
+    ("nonTransferable", "Token can't be transferred"),
+    ("defaultAccountState", "New token accounts can start frozen"),
+];
+
+/// The mint authorities rarely change, a failed read is kept as well so it isn't retried on every scan
+static MINT_INFO_CACHE: LazyLock<TtlCache<Value>> = LazyLock::new(|| TtlCache::new(Duration::from_secs(300)));
+/// The scan runs before the token messages are sent, a slow solana app must not hold them back
+const MINT_INFO_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Share of the LP that must be burned or locked to be considered safe
+const LP_SECURED_THRESHOLD: f64 = 90.0;
+/// Share of the supply held by the top 10 holders above which it is flagged
+const TOP_HOLDERS_THRESHOLD: f64 = 30.0;
+/// Share of the supply held by a single holder above which it is flagged
+const WHALE_HOLDER_THRESHOLD: f64 = 10.0;
+/// Share of the supply held by the dev wallet above which it is flagged
+const DEV_HOLDINGS_THRESHOLD: f64 = 5.0;
+
+/// Result of the safety scan of a token
+///
+/// The facts are None when they couldn't be read, eg when the mint account is unavailable
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct TokenSafety {
+    pub risk_score: u32, // 0 is safe, 100 is the riskiest
+    pub warnings: Vec<String>,
+    pub mint_renounced: Option<bool>,
+    pub freeze_renounced: Option<bool>,
+    pub transfer_fee_percent: Option<f64>,
+    pub lp_secured_percent: Option<f64>, // Share of the LP burned or locked
+    pub top_10_holders_percent: f64,
+    pub dev_holdings_percent: Option<f64>,
+}
+
+impl TokenSafety {
+    /// Emoji and label of the risk level
+    pub fn level(&self) -> (&'static str, &'static str) {
+        if self.risk_score < 20 {
+            ("🟢", "Low")
+        } else if self.risk_score < 50 {
+            ("🟡", "Medium")
+        } else {
+            ("🔴", "High")
+        }
+    }
+
+    fn add_risk(&mut self, risk: u32, warning: String) {
+        self.risk_score = (self.risk_score + risk).min(100);
+        self.warnings.push(warning);
+    }
+}
+
+/// Get the on-chain safety facts of a token mint from the solana app
+///
+/// # Description
+///
+/// Failures are cached too, as a null value: a mint the solana app couldn't read in time isn't
+/// asked again until the cache entry expires.
+///
+/// # Arguments
+///
+/// * `token_address` - The token mint
+///
+/// # Returns
+///
+/// A Result containing the mint authorities, Token-2022 extensions and dev wallet holdings, an
+/// error if the solana app didn't answer within 3 seconds the last time it was asked
+pub async fn get_token_mint_info(token_address: &str) -> Result<Value> {
+    let mint_info = MINT_INFO_CACHE.get_or_fetch(token_address, || async {
+        match fetch_token_mint_info(token_address).await {
+            Ok(mint_info) => Ok(mint_info),
+            Err(e) => {
+                eprintln!("@get_token_mint_info/ error fetching the mint info of {}: {:?}", token_address, e);
+                Ok(Value::Null)
+            }
+        }
+    }).await?;
+    if mint_info.is_null() {
+        return Err(anyhow::anyhow!("The mint info of {} is unavailable", token_address));
+    }
+    Ok(mint_info)
+}
+
+/// Request the on-chain safety facts of a token mint to the solana app
+///
+/// # Arguments
+///
+/// * `token_address` - The token mint
+///
+/// # Returns
+///
+/// A Result containing the mint info, an error if the solana app didn't answer within 3 seconds
+async fn fetch_token_mint_info(token_address: &str) -> Result<Value> {
+    let client = reqwest::Client::new();
+    let response = client.get(
+        format!("http://solana_app:3030/token_safety/{token_address}")
+    )
+    .timeout(MINT_INFO_TIMEOUT)
+    .send()
+    .await?;
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!("Failed to get the mint info of {}: {}", token_address, response.text().await?));
+    }
+    Ok(response.json::<Value>().await?)
+}
+
+/// Scan a token for rug risks
+///
+/// # Description
+///
+/// The mint account is only read for Solana tokens. When it can't be read in time the scan still
+/// runs on the holders and the LP, with a warning saying the mint wasn't checked.
+///
+/// # Arguments
+///
+/// * `pair_stats` - The market stats of the main pair of the token
+/// * `holders` - The holders of the token
+///
+/// # Returns
+///
+/// The safety of the token
+pub async fn get_token_safety(pair_stats: &PairStats, holders: &Holders) -> TokenSafety {
+    let token_address = pair_stats.pair.token_address.as_str();
+    let is_solana = pair_stats.pair.chain == "solana";
+    let mint_info = if is_solana {
+        match get_token_mint_info(token_address).await {
+            Ok(mint_info) => Some(mint_info),
+            Err(e) => {
+                eprintln!("@get_token_safety/ error fetching the mint info of {}: {:?}", token_address, e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let mut safety = check_token_safety(mint_info.as_ref(), holders, pair_stats);
+    if is_solana && mint_info.is_none() {
+        safety.warnings.push("Mint account not checked".to_string());
+    }
+    safety
+}
+
+/// Score the rug risks of a token
+///
+/// # Arguments
+///
+/// * `mint_info` - The mint info from the solana app, None if it couldn't be read
+/// * `holders` - The holders of the token
+/// * `pair_stats` - The market stats of the main pair of the token
+///
+/// # Returns
+///
+/// The safety of the token
+pub fn check_token_safety(mint_info: Option<&Value>, holders: &Holders, pair_stats: &PairStats) -> TokenSafety {
+    let mut safety = TokenSafety::default();
+
+    // Mint account
+    if let Some(mint_info) = mint_info {
+        let mint_renounced = mint_info["mint_authority"].is_null();
+        let freeze_renounced = mint_info["freeze_authority"].is_null();
+        safety.mint_renounced = Some(mint_renounced);
+        safety.freeze_renounced = Some(freeze_renounced);
+        if !mint_renounced {
+            safety.add_risk(MINT_AUTHORITY_RISK, "Mint authority not renounced, supply can be inflated".to_string());
+        }
+        if !freeze_renounced {
+            safety.add_risk(FREEZE_AUTHORITY_RISK, "Freeze authority not renounced, wallets can be frozen".to_string());
+        }
+        let transfer_fee_percent = mint_info["transfer_fee_bps"].as_u64().unwrap_or(0) as f64 / 100.0;
+        safety.transfer_fee_percent = Some(transfer_fee_percent);
+        if transfer_fee_percent > 0.0 {
+            safety.add_risk(TRANSFER_FEE_RISK, format!("Transfer fee of {:.2}%", transfer_fee_percent));
+        }
+        let extensions: Vec<&str> = mint_info["extensions"].as_array()
+            .map(|extensions| extensions.iter().filter_map(|extension| extension.as_str()).collect())
+            .unwrap_or_default();
+        for (extension, warning) in RISKY_EXTENSIONS.iter() {
+            if extensions.contains(extension) {
+                safety.add_risk(RISKY_EXTENSION_RISK, warning.to_string());
+            }
+        }
+        // Dev wallet
+        let supply = mint_info["supply"].as_f64().unwrap_or(0.0);
+        if let (Some(dev_balance), true) = (mint_info["dev_balance"].as_f64(), supply > 0.0) {
+            let dev_holdings_percent = dev_balance / supply * 100.0;
+            safety.dev_holdings_percent = Some(dev_holdings_percent);
+            if dev_holdings_percent > DEV_HOLDINGS_THRESHOLD {
+                safety.add_risk(DEV_HOLDINGS_RISK, format!("Dev wallet holds {:.1}%", dev_holdings_percent));
+            }
+        }
+    }
+
+    // Holders, the first one is the pool
+    safety.top_10_holders_percent = holders.top_percent(10);
+    if safety.top_10_holders_percent > TOP_HOLDERS_THRESHOLD {
+        safety.add_risk(TOP_HOLDERS_RISK, format!("Top 10 holders own {:.1}%", safety.top_10_holders_percent));
+    }
+    let top_holder_percent = holders.top_percent(1);
+    if top_holder_percent > WHALE_HOLDER_THRESHOLD {
+        safety.add_risk(WHALE_HOLDER_RISK, format!("A single holder owns {:.1}%", top_holder_percent));
+    }
+
+    // LP, pump.fun tokens on their bonding curve have no LP yet and some providers don't know it
+    let lp_known = pair_stats.lp_burned_percent.is_some() || pair_stats.lp_locked_percent.is_some();
+    let locked_percent = pair_stats.lp_locked_percent.unwrap_or(0.0);
+    if lp_known && (pair_stats.lp_burned_percent.is_some() || locked_percent > 0.0 || !pair_stats.pair.token_address.ends_with("pump")) {
+        let lp_secured_percent = pair_stats.lp_burned_percent.unwrap_or(0.0).max(locked_percent).min(100.0);
+        safety.lp_secured_percent = Some(lp_secured_percent);
+        if lp_secured_percent < LP_SECURED_THRESHOLD {
+            safety.add_risk(LP_NOT_SECURED_RISK, format!("Only {:.0}% of the LP is burned or locked", lp_secured_percent));
+        }
+    }
+
+    safety
+}
+
+/// Create the safety section of the token messages
+///
+/// # Arguments
+///
+/// * `safety` - The safety of the token
+///
+/// # Returns
+///
+/// A String with the risk level, the checks and one line per warning
+pub fn safety_message(safety: &TokenSafety) -> String {
+    let check = |passed: Option<bool>| match passed {
+        Some(true) => "🟢",
+        Some(false) => "🔴",
+        None => "⚪️",
+    };
+    let (level_emoji, level) = safety.level();
+    let mut message = format!(
+        "🛡 Risk: {level_emoji} <b>{level}</b> ({}/100) ⋅ Mint: {} ⋅ Freeze: {} ⋅ LP: {}",
+        safety.risk_score,
+        check(safety.mint_renounced),
+        check(safety.freeze_renounced),
+        check(safety.lp_secured_percent.map(|lp_secured_percent| lp_secured_percent >= LP_SECURED_THRESHOLD)),
+    );
+    for warning in safety.warnings.iter() {
+        message.push_str(&format!("\n⚠️ {}", warning));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::utils::market_data::Holder;
+
+    /// Holders with the pool first, like the holders endpoint returns them
+    fn holders(percents: &[f64]) -> Holders {
+        Holders {
+            holders: percents.iter()
+                .enumerate()
+                .map(|(index, percent)| Holder { address: format!("holder{index}"), percent: *percent })
+                .collect(),
+        }
+    }
+
+    fn renounced_mint() -> Value {
+        json!({ "mint_authority": null, "freeze_authority": null, "transfer_fee_bps": 0, "extensions": [], "supply": 1000.0, "dev_balance": 0.0 })
+    }
+
+    /// A pair of a token with the given LP shares burned and locked
+    fn pair_stats(token_address: &str, lp_burned_percent: Option<f64>, lp_locked_percent: Option<f64>) -> PairStats {
+        let mut pair_stats = PairStats { lp_burned_percent, lp_locked_percent, ..Default::default() };
+        pair_stats.pair.token_address = token_address.to_string();
+        pair_stats
+    }
+
+    fn burned_lp() -> PairStats {
+        pair_stats("Token111", Some(100.0), Some(0.0))
+    }
+
+    #[test]
+    fn a_renounced_token_with_a_burned_lp_and_spread_holders_is_safe() {
+        let safety = check_token_safety(Some(&renounced_mint()), &holders(&[40.0, 2.0, 2.0, 1.0]), &burned_lp());
+        assert_eq!(safety.risk_score, 0);
+        assert!(safety.warnings.is_empty());
+        assert_eq!(safety.mint_renounced, Some(true));
+        assert_eq!(safety.freeze_renounced, Some(true));
+        assert_eq!(safety.lp_secured_percent, Some(100.0));
+        assert_eq!(safety.dev_holdings_percent, Some(0.0));
+        assert_eq!(safety.level().1, "Low");
+    }
+
+    #[test]
+    fn the_pool_is_not_counted_in_the_top_holders() {
+        let safety = check_token_safety(None, &holders(&[80.0, 5.0, 5.0]), &burned_lp());
+        assert_eq!(safety.top_10_holders_percent, 10.0);
+        assert_eq!(safety.risk_score, 0);
+    }
+
+    #[test]
+    fn the_mint_authorities_extensions_and_fees_add_their_risks() {
+        let mint_info = json!({
+            "mint_authority": "Authority111",
+            "freeze_authority": "Authority111",
+            "transfer_fee_bps": 250,
+            "extensions": ["transferFeeConfig", "permanentDelegate"],
+            "supply": 1000.0,
+            "dev_balance": 0.0,
+        });
+        let safety = check_token_safety(Some(&mint_info), &holders(&[]), &burned_lp());
+        assert_eq!(safety.mint_renounced, Some(false));
+        assert_eq!(safety.freeze_renounced, Some(false));
+        assert_eq!(safety.transfer_fee_percent, Some(2.5));
+        assert_eq!(safety.risk_score, MINT_AUTHORITY_RISK + FREEZE_AUTHORITY_RISK + TRANSFER_FEE_RISK + RISKY_EXTENSION_RISK);
+        assert_eq!(safety.warnings.len(), 4);
+        assert_eq!(safety.level().1, "High");
+    }
+
+    #[test]
+    fn the_risk_score_is_capped_at_100() {
+        let mint_info = json!({
+            "mint_authority": "Authority111",
+            "freeze_authority": "Authority111",
+            "transfer_fee_bps": 1000,
+            "extensions": ["permanentDelegate", "transferHook", "nonTransferable", "defaultAccountState"],
+            "supply": 1000.0,
+            "dev_balance": 500.0,
+        });
+        let safety = check_token_safety(Some(&mint_info), &holders(&[10.0, 50.0, 20.0]), &pair_stats("Token111", None, Some(0.0)));
+        assert_eq!(safety.risk_score, 100);
+    }
+
+    #[test]
+    fn whales_and_the_dev_wallet_are_flagged() {
+        let mut mint_info = renounced_mint();
+        mint_info["dev_balance"] = json!(80.0);
+        let safety = check_token_safety(Some(&mint_info), &holders(&[50.0, 25.0, 10.0]), &burned_lp());
+        assert_eq!(safety.dev_holdings_percent, Some(8.0));
+        assert_eq!(safety.top_10_holders_percent, 35.0);
+        assert_eq!(safety.risk_score, DEV_HOLDINGS_RISK + TOP_HOLDERS_RISK + WHALE_HOLDER_RISK);
+    }
+
+    #[test]
+    fn the_lp_is_secured_by_the_larger_of_the_burned_and_locked_shares() {
+        let safety = check_token_safety(None, &holders(&[]), &pair_stats("Token111", Some(50.0), Some(95.0)));
+        assert_eq!(safety.lp_secured_percent, Some(95.0));
+        assert_eq!(safety.risk_score, 0);
+
+        let safety = check_token_safety(None, &holders(&[]), &pair_stats("Token111", Some(50.0), Some(0.0)));
+        assert_eq!(safety.lp_secured_percent, Some(50.0));
+        assert_eq!(safety.risk_score, LP_NOT_SECURED_RISK);
+    }
+
+    #[test]
+    fn pump_fun_tokens_on_their_bonding_curve_have_no_lp() {
+        let safety = check_token_safety(None, &holders(&[]), &pair_stats("Token111pump", None, Some(0.0)));
+        assert_eq!(safety.lp_secured_percent, None);
+        assert_eq!(safety.risk_score, 0);
+    }
+
+    #[test]
+    fn an_unknown_lp_is_not_flagged() {
+        let safety = check_token_safety(None, &holders(&[]), &pair_stats("Token111", None, None));
+        assert_eq!(safety.lp_secured_percent, None);
+        assert_eq!(safety.risk_score, 0);
+    }
+
+    #[test]
+    fn an_unread_mint_leaves_the_mint_checks_unknown() {
+        let safety = check_token_safety(None, &holders(&[]), &burned_lp());
+        assert_eq!(safety.mint_renounced, None);
+        assert_eq!(safety.freeze_renounced, None);
+        assert_eq!(safety.transfer_fee_percent, None);
+        assert_eq!(safety.dev_holdings_percent, None);
+        assert!(safety_message(&safety).contains("Mint: ⚪️"));
+    }
+}